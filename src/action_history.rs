@@ -0,0 +1,68 @@
+use crate::AgentId;
+use rocketsim_rs::sim::CarControls;
+use std::collections::VecDeque;
+
+/// The controls every car stepped with in the last few steps of the episode,
+/// kept by `Env::with_action_history` and given to components in `StepContext::action_history`
+#[derive(Clone, Default)]
+pub struct ActionHistory {
+    len: usize,
+    cars: Vec<(AgentId, VecDeque<CarControls>)>,
+}
+
+impl ActionHistory {
+    /// Keep the controls of the last `len` steps
+    pub fn new(len: usize) -> Self {
+        Self {
+            len,
+            cars: Vec::new(),
+        }
+    }
+
+    /// How many steps of controls are kept
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The controls of `agent_id`, newest first.
+    ///
+    /// There are fewer than `len` of them early in an episode, or none for a car that just spawned
+    pub fn of(&self, agent_id: AgentId) -> impl Iterator<Item = &CarControls> {
+        self.cars
+            .iter()
+            .find(|(id, _)| *id == agent_id)
+            .into_iter()
+            .flat_map(|(_, controls)| controls)
+    }
+
+    pub(crate) fn push(&mut self, actions: &[(AgentId, CarControls)]) {
+        // forget cars that left the arena
+        self.cars
+            .retain(|(id, _)| actions.iter().any(|(agent_id, _)| agent_id == id));
+
+        for &(agent_id, controls) in actions {
+            let index = match self.cars.iter().position(|(id, _)| *id == agent_id) {
+                Some(index) => index,
+                None => {
+                    self.cars
+                        .push((agent_id, VecDeque::with_capacity(self.len)));
+                    self.cars.len() - 1
+                }
+            };
+            let history = &mut self.cars[index].1;
+
+            history.truncate(self.len.saturating_sub(1));
+            if self.len != 0 {
+                history.push_front(controls);
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.cars.clear();
+    }
+}
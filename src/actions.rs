@@ -0,0 +1,258 @@
+use crate::{Action, AgentId, SpaceType};
+use rocketsim_rs::{glam_ext::GameStateA, sim::CarControls};
+
+fn button(value: f32) -> bool {
+    value > 0.5
+}
+
+/// The standard 90 action table: 24 ground actions and 66 aerial ones,
+/// each agent's input is the index of an action.
+///
+/// Indices outside of the table leave the car without input.
+/// `TICK_SKIP` is the default tick skip, which `Env::with_tick_skip` can override
+#[derive(Clone, Debug)]
+pub struct LookupTableAction<const TICK_SKIP: u32 = 8> {
+    table: Vec<CarControls>,
+}
+
+impl<const TICK_SKIP: u32> Default for LookupTableAction<TICK_SKIP> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const TICK_SKIP: u32> LookupTableAction<TICK_SKIP> {
+    pub fn new() -> Self {
+        let mut table = Vec::with_capacity(90);
+        let axis = [-1., 0., 1.];
+
+        for throttle in axis {
+            for steer in axis {
+                for boost in [false, true] {
+                    for handbrake in [false, true] {
+                        if boost && throttle != 1. {
+                            continue;
+                        }
+
+                        table.push(CarControls {
+                            throttle,
+                            steer,
+                            pitch: 0.,
+                            yaw: steer,
+                            roll: 0.,
+                            jump: false,
+                            boost,
+                            handbrake,
+                        });
+                    }
+                }
+            }
+        }
+
+        for pitch in axis {
+            for yaw in axis {
+                for roll in axis {
+                    for jump in [false, true] {
+                        for boost in [false, true] {
+                            // only roll is needed for side flips
+                            if jump && yaw != 0. {
+                                continue;
+                            }
+                            // already a ground action
+                            if pitch == 0. && roll == 0. && !jump {
+                                continue;
+                            }
+
+                            table.push(CarControls {
+                                throttle: f32::from(u8::from(boost)),
+                                steer: yaw,
+                                pitch,
+                                yaw,
+                                roll,
+                                jump,
+                                boost,
+                                // to not be stuck in a wavedash or flip
+                                handbrake: jump && (pitch != 0. || yaw != 0. || roll != 0.),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        Self { table }
+    }
+
+    /// Use a custom table instead of the standard one
+    pub fn from_table(table: Vec<CarControls>) -> Self {
+        Self { table }
+    }
+
+    pub fn table(&self) -> &[CarControls] {
+        &self.table
+    }
+}
+
+impl<SI, const TICK_SKIP: u32> Action<SI> for LookupTableAction<TICK_SKIP> {
+    type Input = Vec<i32>;
+
+    fn get_tick_skip() -> u32 {
+        TICK_SKIP
+    }
+
+    fn get_action_space(&self, _agent_id: AgentId, _shared_info: &SI) -> usize {
+        self.table.len()
+    }
+
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    /// Masks boosting without boost, and jumping in the air once the flip or double jump is used up
+    fn get_action_mask(
+        &self,
+        agent_id: AgentId,
+        state: &GameStateA,
+        _shared_info: &SI,
+    ) -> Vec<bool> {
+        let Some(car) = state.cars.iter().find(|car| AgentId::of(car) == agent_id) else {
+            return vec![true; self.table.len()];
+        };
+        let car = &car.state;
+        let can_boost = car.boost > 0.;
+        let can_jump = car.is_on_ground || !(car.has_flipped || car.has_double_jumped);
+
+        self.table
+            .iter()
+            .map(|controls| (can_boost || !controls.boost) && (can_jump || !controls.jump))
+            .collect()
+    }
+
+    fn parse_actions(
+        &mut self,
+        actions: Vec<i32>,
+        _state: &GameStateA,
+        _shared_info: &mut SI,
+    ) -> Vec<CarControls> {
+        actions
+            .into_iter()
+            .map(|action| {
+                let controls = usize::try_from(action)
+                    .ok()
+                    .and_then(|index| self.table.get(index));
+
+                match controls {
+                    Some(controls) => *controls,
+                    None => {
+                        log::warn!("Action {action} isn't in the lookup table");
+                        CarControls::default()
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// Eight floats per agent in the order throttle, steer, pitch, yaw, roll, jump, boost, handbrake.
+///
+/// The axes are clamped to -1 and 1, and buttons are pressed if their value is above 0.5
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ContinuousAction<const TICK_SKIP: u32 = 8>;
+
+impl<SI, const TICK_SKIP: u32> Action<SI> for ContinuousAction<TICK_SKIP> {
+    type Input = Vec<Vec<f32>>;
+
+    fn get_tick_skip() -> u32 {
+        TICK_SKIP
+    }
+
+    fn get_action_space(&self, _agent_id: AgentId, _shared_info: &SI) -> usize {
+        8
+    }
+
+    fn get_action_space_type(&self, _agent_id: AgentId, _shared_info: &SI) -> SpaceType {
+        SpaceType::Box {
+            low: -1.,
+            high: 1.,
+            shape: vec![8],
+        }
+    }
+
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn parse_actions(
+        &mut self,
+        actions: Vec<Vec<f32>>,
+        _state: &GameStateA,
+        _shared_info: &mut SI,
+    ) -> Vec<CarControls> {
+        actions
+            .into_iter()
+            .map(|action| {
+                let value = |i: usize| action.get(i).copied().unwrap_or_default();
+                let axis = |i: usize| value(i).clamp(-1., 1.);
+
+                CarControls {
+                    throttle: axis(0),
+                    steer: axis(1),
+                    pitch: axis(2),
+                    yaw: axis(3),
+                    roll: axis(4),
+                    jump: button(value(5)),
+                    boost: button(value(6)),
+                    handbrake: button(value(7)),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Eight choices per agent in the same order as `ContinuousAction`:
+/// the five axes pick from 0, 1 or 2 for -1, 0 or 1, and the three buttons from 0 or 1.
+///
+/// The choices are sent as floats so trainers can use the same input as `ContinuousAction`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MultiDiscreteAction<const TICK_SKIP: u32 = 8>;
+
+impl<SI, const TICK_SKIP: u32> Action<SI> for MultiDiscreteAction<TICK_SKIP> {
+    type Input = Vec<Vec<f32>>;
+
+    fn get_tick_skip() -> u32 {
+        TICK_SKIP
+    }
+
+    fn get_action_space(&self, _agent_id: AgentId, _shared_info: &SI) -> usize {
+        8
+    }
+
+    fn get_action_space_type(&self, _agent_id: AgentId, _shared_info: &SI) -> SpaceType {
+        SpaceType::MultiDiscrete(vec![3, 3, 3, 3, 3, 2, 2, 2])
+    }
+
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn parse_actions(
+        &mut self,
+        actions: Vec<Vec<f32>>,
+        _state: &GameStateA,
+        _shared_info: &mut SI,
+    ) -> Vec<CarControls> {
+        actions
+            .into_iter()
+            .map(|action| {
+                let value = |i: usize| action.get(i).copied().unwrap_or_default().round();
+                // missing axes are centered
+                let axis = |i: usize| action.get(i).map_or(0., |_| (value(i) - 1.).clamp(-1., 1.));
+
+                CarControls {
+                    throttle: axis(0),
+                    steer: axis(1),
+                    pitch: axis(2),
+                    yaw: axis(3),
+                    roll: axis(4),
+                    jump: button(value(5)),
+                    boost: button(value(6)),
+                    handbrake: button(value(7)),
+                }
+            })
+            .collect()
+    }
+}
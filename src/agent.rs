@@ -0,0 +1,129 @@
+use rocketsim_rs::{
+    glam_ext::{CarInfoA, GameStateA},
+    sim::Team,
+};
+use std::fmt;
+
+/// Identifies one car for as long as it's in the arena, the same value as RocketSim's car id.
+///
+/// Cars keep their id when others are added or removed, unlike their index in `GameStateA::cars`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AgentId(pub u32);
+
+impl AgentId {
+    pub fn of(car: &CarInfoA) -> Self {
+        Self(car.id)
+    }
+}
+
+impl From<u32> for AgentId {
+    fn from(id: u32) -> Self {
+        Self(id)
+    }
+}
+
+impl From<AgentId> for u32 {
+    fn from(id: AgentId) -> Self {
+        id.0
+    }
+}
+
+impl fmt::Display for AgentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Where one agent is in a game state
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct AgentSlot {
+    pub id: AgentId,
+    /// Index in `GameStateA::cars`, which is the order the cars were spawned in
+    pub index: usize,
+    pub team: Team,
+    /// Index among the cars of the same team, also in spawn order
+    pub slot: usize,
+}
+
+/// Maps between agent ids, indices in `GameStateA::cars` and team slots,
+/// so code that lines up per-agent lists doesn't have to assume they're all in the same order
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct AgentMap {
+    agents: Vec<AgentSlot>,
+}
+
+impl AgentMap {
+    pub fn new(state: &GameStateA) -> Self {
+        let mut team_sizes = [0; 2];
+        let agents = state
+            .cars
+            .iter()
+            .enumerate()
+            .map(|(index, car)| {
+                let team_size = &mut team_sizes[usize::from(car.team == Team::Orange)];
+                let slot = *team_size;
+                *team_size += 1;
+
+                AgentSlot {
+                    id: AgentId::of(car),
+                    index,
+                    team: car.team,
+                    slot,
+                }
+            })
+            .collect();
+
+        Self { agents }
+    }
+
+    pub fn len(&self) -> usize {
+        self.agents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.agents.is_empty()
+    }
+
+    /// Every agent in spawn order
+    pub fn iter(&self) -> impl Iterator<Item = &AgentSlot> {
+        self.agents.iter()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = AgentId> + '_ {
+        self.agents.iter().map(|agent| agent.id)
+    }
+
+    pub fn get(&self, id: AgentId) -> Option<&AgentSlot> {
+        self.agents.iter().find(|agent| agent.id == id)
+    }
+
+    /// Index of the agent in `GameStateA::cars`
+    pub fn index_of(&self, id: AgentId) -> Option<usize> {
+        self.get(id).map(|agent| agent.index)
+    }
+
+    /// The agent at `index` in `GameStateA::cars`
+    pub fn id_at(&self, index: usize) -> Option<AgentId> {
+        self.agents.get(index).map(|agent| agent.id)
+    }
+
+    pub fn team_slot(&self, id: AgentId) -> Option<(Team, usize)> {
+        self.get(id).map(|agent| (agent.team, agent.slot))
+    }
+
+    /// The `slot`th agent on `team`
+    pub fn id_in_slot(&self, team: Team, slot: usize) -> Option<AgentId> {
+        self.agents
+            .iter()
+            .find(|agent| agent.team == team && agent.slot == slot)
+            .map(|agent| agent.id)
+    }
+
+    /// Every agent on `team` in slot order
+    pub fn team(&self, team: Team) -> impl Iterator<Item = AgentId> + '_ {
+        self.agents
+            .iter()
+            .filter(move |agent| agent.team == team)
+            .map(|agent| agent.id)
+    }
+}
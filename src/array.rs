@@ -0,0 +1,19 @@
+use crate::{Error, FullObs, Result};
+use ndarray::Array2;
+
+/// The observations as one agents × obs array, ready to hand to tch, candle, burn or numpy.
+///
+/// Fails if the agents' observations aren't all the same size
+pub fn obs_array(obs: &FullObs) -> Result<Array2<f32>> {
+    let obs_size = obs.first().map_or(0, Vec::len);
+
+    if let Some(row) = obs.iter().find(|row| row.len() != obs_size) {
+        return Err(Error::Invalid(format!(
+            "Can't make an array from observations of size {obs_size} and {}",
+            row.len()
+        )));
+    }
+
+    Array2::from_shape_vec((obs.len(), obs_size), obs.concat())
+        .map_err(|e| Error::Invalid(e.to_string()))
+}
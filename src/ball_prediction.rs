@@ -0,0 +1,105 @@
+use crate::common_values::BALL_RADIUS;
+use glam::Vec3A;
+use rocketsim_rs::{
+    cxx::UniquePtr,
+    glam_ext::BallA,
+    math::Vec3,
+    sim::{Arena, ArenaConfig, GameMode},
+};
+
+/// Where the ball is at one point of a prediction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BallSlice {
+    /// Seconds after the predicted state
+    pub time: f32,
+    pub pos: Vec3A,
+    pub vel: Vec3A,
+    pub ang_vel: Vec3A,
+}
+
+fn to_raw(v: Vec3A) -> Vec3 {
+    Vec3::new(v.x, v.y, v.z)
+}
+
+fn to_glam(v: Vec3) -> Vec3A {
+    Vec3A::new(v.x, v.y, v.z)
+}
+
+/// Predicts where the ball goes if no car touches it, by simulating it in an arena of its own.
+///
+/// Keep one around and call `predict` from an obs builder or reward, e.g. for the time until the ball lands.
+/// `rocketsim_rs::init` must have been called first
+pub struct BallPredictor {
+    arena: UniquePtr<Arena>,
+    num_slices: usize,
+    ticks_per_slice: u32,
+    slices: Vec<BallSlice>,
+}
+
+impl BallPredictor {
+    /// Predict `seconds` ahead on the field of `game_mode`, with a slice for every tick
+    pub fn new(game_mode: GameMode, seconds: f32) -> Self {
+        let arena = Arena::new(game_mode, ArenaConfig::default(), 120);
+        let num_slices = (seconds.max(0.) * arena.get_tick_rate()).round() as usize;
+
+        Self {
+            arena,
+            num_slices,
+            ticks_per_slice: 1,
+            slices: Vec::with_capacity(num_slices),
+        }
+    }
+
+    /// Only keep a slice every `ticks_per_slice` ticks, the prediction still covers the same time
+    pub fn with_ticks_per_slice(mut self, ticks_per_slice: u32) -> Self {
+        let ticks_per_slice = ticks_per_slice.max(1);
+        let num_ticks = self.num_slices * self.ticks_per_slice as usize;
+
+        self.num_slices = num_ticks / ticks_per_slice as usize;
+        self.ticks_per_slice = ticks_per_slice;
+        self
+    }
+
+    /// Simulate the ball from `ball` and return the predicted slices, the first one being a step after `ball`
+    pub fn predict(&mut self, ball: &BallA) -> &[BallSlice] {
+        let mut state = self.arena.pin_mut().get_ball();
+        state.pos = to_raw(ball.pos);
+        state.vel = to_raw(ball.vel);
+        state.ang_vel = to_raw(ball.ang_vel);
+        self.arena.pin_mut().set_ball(state);
+
+        let seconds_per_slice = self.ticks_per_slice as f32 / self.arena.get_tick_rate();
+        self.slices.clear();
+        for i in 1..=self.num_slices {
+            self.arena.pin_mut().step(self.ticks_per_slice);
+
+            let ball = self.arena.pin_mut().get_ball();
+            self.slices.push(BallSlice {
+                time: i as f32 * seconds_per_slice,
+                pos: to_glam(ball.pos),
+                vel: to_glam(ball.vel),
+                ang_vel: to_glam(ball.ang_vel),
+            });
+        }
+
+        &self.slices
+    }
+
+    /// The slices of the last prediction
+    pub fn slices(&self) -> &[BallSlice] {
+        &self.slices
+    }
+
+    /// The first slice at least `time` seconds after the predicted state
+    pub fn at(&self, time: f32) -> Option<&BallSlice> {
+        self.slices.iter().find(|slice| slice.time >= time)
+    }
+
+    /// Seconds until the ball next rests on the ground, `None` if it stays in the air for the whole prediction
+    pub fn time_until_landing(&self) -> Option<f32> {
+        self.slices
+            .iter()
+            .find(|slice| slice.pos.z <= BALL_RADIUS * 1.05 && slice.vel.z <= 0.)
+            .map(|slice| slice.time)
+    }
+}
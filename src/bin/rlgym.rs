@@ -0,0 +1,263 @@
+use rlgym_rs::{
+    bots::{Atba, BallChaser, Goalie},
+    config::{ConfigEnv, ExperimentConfig, Registry},
+    rating::MatchOutcome,
+    recording::{read_recording, replay, Recorder},
+    remote::RemoteEnv,
+    rocketsim_rs::{glam_ext::GameStateA, init, sim::CarControls},
+    shutdown::ShutdownSignal,
+    tournament::play_match,
+    Action, AgentId, FullObs, Obs, Policy, SharedInfoProvider, StepContext,
+};
+use std::{
+    collections::HashMap, env, error::Error, fs::File, process, str::FromStr, time::Instant,
+};
+
+const USAGE: &str = "Usage:
+  rlgym bench <config> [--steps N] [--record FILE]   step random agents and report steps per second
+  rlgym render <recording> [--tick-skip N]           replay a recording in RLViser
+  rlgym validate <config>                            build the env and print the effective config
+  rlgym eval <config> <blue> <orange> [--matches N] [--max-steps N]
+                                                     play bots against each other, bots are atba, ballchaser and goalie";
+
+type CliResult<T = ()> = Result<T, Box<dyn Error>>;
+
+struct NoSharedInfo;
+
+impl SharedInfoProvider<()> for NoSharedInfo {
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut ()) {}
+    fn apply(&mut self, _game_state: &GameStateA, _shared_info: &mut ()) {}
+}
+
+/// The CLI only drives cars with random controls or bots, neither of which need observations
+struct NoObs;
+
+impl Obs<()> for NoObs {
+    fn get_obs_space(&self, _agent_id: AgentId, _shared_info: &()) -> usize {
+        0
+    }
+
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut ()) {}
+
+    fn build_obs(&mut self, ctx: &StepContext, _shared_info: &mut ()) -> FullObs {
+        vec![Vec::new(); ctx.state.cars.len()]
+    }
+}
+
+struct ControlsAction;
+
+impl Action<()> for ControlsAction {
+    type Input = Vec<CarControls>;
+
+    fn get_tick_skip() -> u32 {
+        // replaced by the config's tick skip
+        8
+    }
+
+    fn get_action_space(&self, _agent_id: AgentId, _shared_info: &()) -> usize {
+        8
+    }
+
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut ()) {}
+
+    fn parse_actions(
+        &mut self,
+        actions: Self::Input,
+        _state: &GameStateA,
+        _shared_info: &mut (),
+    ) -> Vec<CarControls> {
+        actions
+    }
+}
+
+type CliEnv = ConfigEnv<NoSharedInfo, NoObs, ControlsAction, ()>;
+
+fn build_env(config: &ExperimentConfig) -> CliResult<CliEnv> {
+    init(None, true);
+    Ok(config.build_env(&Registry::new(), NoSharedInfo, NoObs, ControlsAction, ())?)
+}
+
+fn random_controls(rng: &mut fastrand::Rng) -> CarControls {
+    let mut axis = || rng.f32() * 2. - 1.;
+
+    CarControls {
+        throttle: axis(),
+        steer: axis(),
+        pitch: axis(),
+        yaw: axis(),
+        roll: axis(),
+        jump: rng.bool(),
+        boost: rng.bool(),
+        handbrake: rng.bool(),
+    }
+}
+
+fn bot(name: &str) -> CliResult<Box<dyn Policy<()>>> {
+    match name {
+        "atba" => Ok(Box::new(Atba)),
+        "ballchaser" => Ok(Box::new(BallChaser::default())),
+        "goalie" => Ok(Box::new(Goalie::default())),
+        _ => Err(format!("Unknown bot {name:?}, expected atba, ballchaser or goalie").into()),
+    }
+}
+
+/// Positional arguments and `--name value` options
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> CliResult<Self> {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+
+        while let Some(arg) = args.next() {
+            if let Some(name) = arg.strip_prefix("--") {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("--{name} needs a value"))?;
+                options.insert(name.to_string(), value);
+            } else {
+                positional.push(arg);
+            }
+        }
+
+        Ok(Self {
+            positional,
+            options,
+        })
+    }
+
+    fn positional(&self, index: usize, name: &str) -> CliResult<&str> {
+        self.positional
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| format!("Missing <{name}>").into())
+    }
+
+    fn option<T: FromStr>(&self, name: &str, default: T) -> CliResult<T> {
+        match self.options.get(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| format!("Invalid value for --{name}: {value}").into()),
+            None => Ok(default),
+        }
+    }
+}
+
+fn bench(args: &Args) -> CliResult {
+    let config = ExperimentConfig::load(args.positional(1, "config")?)?;
+    let steps = args.option("steps", 10_000usize)?;
+    let mut recorder = match args.options.get("record") {
+        Some(path) => Some(Recorder::create(path)?),
+        None => None,
+    };
+
+    let mut env = build_env(&config)?;
+    let mut rng = fastrand::Rng::new();
+    let num_agents = env.external_agent_ids().len();
+    let shutdown = ShutdownSignal::ctrl_c()?;
+
+    env.reset()?;
+    let start = Instant::now();
+
+    for _ in 0..steps {
+        if shutdown.is_requested() {
+            break;
+        }
+
+        if let Some(recorder) = &mut recorder {
+            recorder.record(&RemoteEnv::snapshot(&mut env))?;
+        }
+
+        let actions = (0..num_agents).map(|_| random_controls(&mut rng)).collect();
+        let result = env.step(actions)?;
+        if result.is_terminal || result.truncated {
+            env.reset()?;
+        }
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+    let steps = env.total_step_count();
+    println!(
+        "{steps} steps and {} finished episodes in {elapsed:.2}s, {:.0} steps/s, {:.0} ticks/s",
+        env.episodes_completed(),
+        steps as f64 / elapsed,
+        (steps * u64::from(env.tick_skip())) as f64 / elapsed
+    );
+
+    if let Some(recorder) = recorder {
+        let frames = recorder.frames();
+        recorder.finish()?;
+        println!("Recorded {frames} frames");
+    }
+
+    env.shutdown()?;
+    Ok(())
+}
+
+fn render(args: &Args) -> CliResult {
+    let states = read_recording(File::open(args.positional(1, "recording")?)?)?;
+    let tick_skip = args.option("tick-skip", 8)?;
+
+    println!("Replaying {} frames", states.len());
+    replay(&states, tick_skip)?;
+
+    Ok(())
+}
+
+fn validate(args: &Args) -> CliResult {
+    let config = ExperimentConfig::load(args.positional(1, "config")?)?;
+    let env = build_env(&config)?;
+
+    print!("{}", config.effective(env.arena()).to_toml()?);
+    Ok(())
+}
+
+fn eval(args: &Args) -> CliResult {
+    let config = ExperimentConfig::load(args.positional(1, "config")?)?;
+    let blue = args.positional(2, "blue")?;
+    let orange = args.positional(3, "orange")?;
+    let matches = args.option("matches", 10usize)?;
+    let max_steps = args.option(
+        "max-steps",
+        15 * 60 * 120 / config.tick_skip.max(1) as usize,
+    )?;
+
+    let mut env = build_env(&config)?;
+    let game_mode = config.game_mode.into();
+    let (mut wins, mut draws, mut losses) = (0, 0, 0);
+
+    for _ in 0..matches {
+        match play_match(&mut env, bot(blue)?, bot(orange)?, game_mode, max_steps)? {
+            MatchOutcome::Win => wins += 1,
+            MatchOutcome::Draw => draws += 1,
+            MatchOutcome::Loss => losses += 1,
+        }
+    }
+
+    println!("{blue} (blue) vs {orange} (orange): {wins} wins, {draws} draws, {losses} losses");
+    Ok(())
+}
+
+fn exit_with(error: &dyn std::fmt::Display) -> ! {
+    eprintln!("{error}");
+    process::exit(1);
+}
+
+fn main() {
+    let args = Args::parse(env::args().skip(1)).unwrap_or_else(|e| exit_with(&e));
+
+    let result = match args.positional.first().map(String::as_str) {
+        Some("bench") => bench(&args),
+        Some("render") => render(&args),
+        Some("validate") => validate(&args),
+        Some("eval") => eval(&args),
+        _ => exit_with(&USAGE),
+    };
+
+    if let Err(e) = result {
+        exit_with(&e);
+    }
+}
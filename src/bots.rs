@@ -0,0 +1,168 @@
+use crate::{common_values::own_goal_center, AgentId, Policy};
+use glam::Vec3A;
+use rocketsim_rs::{
+    glam_ext::{CarInfoA, CarStateA, GameStateA},
+    sim::CarControls,
+};
+
+/// The car of `agent_id`, `None` if it's not in `state`, e.g. after it was removed from the arena
+fn find_car(state: &GameStateA, agent_id: AgentId) -> Option<&CarInfoA> {
+    let car = state.cars.iter().find(|car| AgentId::of(car) == agent_id);
+    if car.is_none() {
+        log::warn!("Bot was asked to control car {agent_id:?}, which isn't in the state");
+    }
+
+    car
+}
+
+/// Returns the angle between the car's forward direction and `target`, positive if `target` is to the right
+fn angle_to(car: &CarStateA, target: Vec3A) -> f32 {
+    let local = car.rot_mat.transpose() * (target - car.pos);
+    local.y.atan2(local.x)
+}
+
+const fn controls(throttle: f32, steer: f32, boost: bool, handbrake: bool) -> CarControls {
+    CarControls {
+        throttle,
+        steer,
+        pitch: 0.,
+        yaw: 0.,
+        roll: 0.,
+        boost,
+        jump: false,
+        handbrake,
+    }
+}
+
+/// Drive at full throttle towards `target`, powersliding on sharp turns
+fn drive_towards(car: &CarStateA, target: Vec3A, boost: bool) -> CarControls {
+    let angle = angle_to(car, target);
+    let steer = (angle * 3.).clamp(-1., 1.);
+    let handbrake = angle.abs() > 1.8 && car.is_on_ground;
+
+    controls(1., steer, boost && angle.abs() < 0.3, handbrake)
+}
+
+/// Always-Towards-Ball-Agent, drives straight at the ball without ever boosting
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Atba;
+
+impl<SI> Policy<SI> for Atba {
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn get_controls(
+        &mut self,
+        agent_id: AgentId,
+        _obs: &[f32],
+        state: &GameStateA,
+        _shared_info: &mut SI,
+    ) -> CarControls {
+        let Some(car) = find_car(state, agent_id) else {
+            return CarControls::default();
+        };
+
+        drive_towards(&car.state, state.ball.pos, false)
+    }
+}
+
+/// Chases the ball while boosting, detouring to the closest active big boost pad when running low
+#[derive(Clone, Copy, Debug)]
+pub struct BallChaser {
+    /// Below this amount of boost the chaser goes to collect more
+    pub min_boost: f32,
+}
+
+impl Default for BallChaser {
+    fn default() -> Self {
+        Self { min_boost: 20. }
+    }
+}
+
+impl<SI> Policy<SI> for BallChaser {
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn get_controls(
+        &mut self,
+        agent_id: AgentId,
+        _obs: &[f32],
+        state: &GameStateA,
+        _shared_info: &mut SI,
+    ) -> CarControls {
+        let Some(car_info) = find_car(state, agent_id) else {
+            return CarControls::default();
+        };
+        let car = &car_info.state;
+
+        let target = if car.boost < self.min_boost {
+            state
+                .pads
+                .iter()
+                .filter(|pad| pad.is_big && pad.state.is_active)
+                .map(|pad| pad.position)
+                .min_by(|a, b| {
+                    a.distance_squared(car.pos)
+                        .total_cmp(&b.distance_squared(car.pos))
+                })
+                .unwrap_or(state.ball.pos)
+        } else {
+            state.ball.pos
+        };
+
+        drive_towards(car, target, car.boost > 0.)
+    }
+}
+
+/// Shadows the ball from between it and its own goal, only challenging when the ball gets close
+#[derive(Clone, Copy, Debug)]
+pub struct Goalie {
+    /// How far along the line from the goal to the ball to position, from 0 (goal line) to 1 (ball)
+    pub shadow_fraction: f32,
+    /// If the ball is closer than this to the goal, drive straight at it
+    pub challenge_distance: f32,
+}
+
+impl Default for Goalie {
+    fn default() -> Self {
+        Self {
+            shadow_fraction: 0.2,
+            challenge_distance: 2000.,
+        }
+    }
+}
+
+impl<SI> Policy<SI> for Goalie {
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn get_controls(
+        &mut self,
+        agent_id: AgentId,
+        _obs: &[f32],
+        state: &GameStateA,
+        _shared_info: &mut SI,
+    ) -> CarControls {
+        let Some(car_info) = find_car(state, agent_id) else {
+            return CarControls::default();
+        };
+        let car = &car_info.state;
+        let goal = own_goal_center(car_info.team);
+        let ball = state.ball.pos;
+
+        if ball.distance(goal) < self.challenge_distance {
+            return drive_towards(car, ball, true);
+        }
+
+        let mut target = goal + (ball - goal) * self.shadow_fraction;
+        target.z = 0.;
+
+        let distance = car.pos.distance(target);
+        if distance < 300. {
+            // in position, slowly turn to face the ball
+            let steer = (angle_to(car, ball) * 3.).clamp(-1., 1.);
+            return controls(0.1, steer, false, false);
+        }
+
+        let mut controls = drive_towards(car, target, distance > 2500.);
+        controls.throttle = (distance / 1000.).min(1.);
+        controls
+    }
+}
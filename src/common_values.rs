@@ -0,0 +1,97 @@
+use glam::Vec3A;
+use rocketsim_rs::{
+    glam_ext::{BoostPadA, GameStateA},
+    sim::Team,
+};
+
+pub const SIDE_WALL_X: f32 = 4096.;
+pub const BACK_WALL_Y: f32 = 5120.;
+/// The corner walls are where `|x| + |y|` reaches this
+pub const CORNER_WALL_XY: f32 = 8064.;
+pub const CEILING_Z: f32 = 2044.;
+pub const BACK_NET_Y: f32 = 6000.;
+pub const GOAL_HEIGHT: f32 = 642.775;
+/// Once the ball's y coordinate is past this, a goal has been scored
+pub const GOAL_THRESHOLD: f32 = 5215.5;
+
+pub const BALL_RADIUS: f32 = 92.75;
+pub const BALL_MAX_SPEED: f32 = 6000.;
+pub const CAR_MAX_SPEED: f32 = 2300.;
+pub const SUPERSONIC_THRESHOLD: f32 = 2200.;
+pub const CAR_MAX_ANG_VEL: f32 = 5.5;
+
+/// Seconds until a pad is active again after it's picked up
+pub const BIG_PAD_COOLDOWN: f32 = 10.;
+pub const SMALL_PAD_COOLDOWN: f32 = 4.;
+
+pub const ORANGE_GOAL_CENTER: Vec3A = Vec3A::new(0., BACK_WALL_Y, GOAL_HEIGHT / 2.);
+pub const BLUE_GOAL_CENTER: Vec3A = Vec3A::new(0., -BACK_WALL_Y, GOAL_HEIGHT / 2.);
+pub const ORANGE_GOAL_BACK: Vec3A = Vec3A::new(0., BACK_NET_Y, GOAL_HEIGHT / 2.);
+pub const BLUE_GOAL_BACK: Vec3A = Vec3A::new(0., -BACK_NET_Y, GOAL_HEIGHT / 2.);
+
+/// The center of the goal that `team` defends
+pub fn own_goal_center(team: Team) -> Vec3A {
+    if team == Team::Blue {
+        BLUE_GOAL_CENTER
+    } else {
+        ORANGE_GOAL_CENTER
+    }
+}
+
+/// The center of the goal that `team` attacks
+pub fn opponent_goal_center(team: Team) -> Vec3A {
+    if team == Team::Blue {
+        ORANGE_GOAL_CENTER
+    } else {
+        BLUE_GOAL_CENTER
+    }
+}
+
+/// The back of the net of the goal that `team` defends, at half the goal's height
+pub fn own_goal_back(team: Team) -> Vec3A {
+    if team == Team::Blue {
+        BLUE_GOAL_BACK
+    } else {
+        ORANGE_GOAL_BACK
+    }
+}
+
+/// The back of the net of the goal that `team` attacks, at half the goal's height
+pub fn opponent_goal_back(team: Team) -> Vec3A {
+    if team == Team::Blue {
+        ORANGE_GOAL_BACK
+    } else {
+        BLUE_GOAL_BACK
+    }
+}
+
+/// Seconds until `pad` is active again, 0 when it's active
+pub fn pad_timer(pad: &BoostPadA) -> f32 {
+    if pad.state.is_active {
+        0.
+    } else {
+        pad.state.cooldown
+    }
+}
+
+/// How long `pad` stays inactive after it's picked up
+pub fn pad_cooldown(pad: &BoostPadA) -> f32 {
+    if pad.is_big {
+        BIG_PAD_COOLDOWN
+    } else {
+        SMALL_PAD_COOLDOWN
+    }
+}
+
+/// The active pad closest to `pos`, only counting big pads with `big_only`
+pub fn nearest_active_pad(state: &GameStateA, pos: Vec3A, big_only: bool) -> Option<&BoostPadA> {
+    state
+        .pads
+        .iter()
+        .filter(|pad| pad.state.is_active && (pad.is_big || !big_only))
+        .min_by(|a, b| {
+            a.position
+                .distance_squared(pos)
+                .total_cmp(&b.position.distance_squared(pos))
+        })
+}
@@ -0,0 +1,337 @@
+use crate::{game_modes::scoring_team, StepContext, Terminal, Truncate};
+use rocketsim_rs::{glam_ext::GameStateA, sim::GameMode};
+
+/// Ends the episode once the ball is in either goal: past a goal line in soccar,
+/// through a hoop in hoops, or through the floor in dropshot
+#[derive(Clone, Copy)]
+pub struct GoalScoredCondition {
+    pub game_mode: GameMode,
+}
+
+impl Default for GoalScoredCondition {
+    fn default() -> Self {
+        Self::new(GameMode::SOCCAR)
+    }
+}
+
+impl GoalScoredCondition {
+    pub fn new(game_mode: GameMode) -> Self {
+        Self { game_mode }
+    }
+}
+
+impl<SI> Terminal<SI> for GoalScoredCondition {
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn is_terminal(&mut self, ctx: &StepContext, _shared_info: &mut SI) -> bool {
+        scoring_team(self.game_mode, ctx.state).is_some()
+    }
+}
+
+impl<SI> Truncate<SI> for GoalScoredCondition {
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn should_truncate(&mut self, ctx: &StepContext, _shared_info: &mut SI) -> bool {
+        scoring_team(self.game_mode, ctx.state).is_some()
+    }
+}
+
+/// Seconds of game time between two states, from their tick counts
+fn seconds_between(start_tick: u64, state: &GameStateA) -> f32 {
+    state.tick_count.saturating_sub(start_tick) as f32 / state.tick_rate
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Limit {
+    Steps(u32),
+    Seconds(f32),
+}
+
+/// Ends the episode after a fixed amount of game time or a fixed number of steps
+#[derive(Clone, Copy, Debug)]
+pub struct TimeoutCondition {
+    limit: Limit,
+    steps: u32,
+    start_tick: u64,
+}
+
+impl TimeoutCondition {
+    /// Counted in game time from the tick counts, so it doesn't depend on the tick skip
+    pub fn seconds(seconds: f32) -> Self {
+        Self::new(Limit::Seconds(seconds))
+    }
+
+    pub fn steps(max_steps: u32) -> Self {
+        Self::new(Limit::Steps(max_steps))
+    }
+
+    fn new(limit: Limit) -> Self {
+        Self {
+            limit,
+            steps: 0,
+            start_tick: 0,
+        }
+    }
+
+    fn start(&mut self, initial_state: &GameStateA) {
+        self.steps = 0;
+        self.start_tick = initial_state.tick_count;
+    }
+
+    fn timed_out(&mut self, state: &GameStateA) -> bool {
+        self.steps += 1;
+
+        match self.limit {
+            Limit::Steps(max_steps) => self.steps >= max_steps,
+            Limit::Seconds(seconds) => seconds_between(self.start_tick, state) >= seconds,
+        }
+    }
+}
+
+impl<SI> Terminal<SI> for TimeoutCondition {
+    fn reset(&mut self, initial_state: &GameStateA, _shared_info: &mut SI) {
+        self.start(initial_state);
+    }
+
+    fn is_terminal(&mut self, ctx: &StepContext, _shared_info: &mut SI) -> bool {
+        self.timed_out(ctx.state)
+    }
+}
+
+impl<SI> Truncate<SI> for TimeoutCondition {
+    fn reset(&mut self, initial_state: &GameStateA, _shared_info: &mut SI) {
+        self.start(initial_state);
+    }
+
+    fn should_truncate(&mut self, ctx: &StepContext, _shared_info: &mut SI) -> bool {
+        self.timed_out(ctx.state)
+    }
+}
+
+/// Ends the episode when no car has touched the ball for `seconds` of game time
+#[derive(Clone, Copy, Debug)]
+pub struct NoTouchTimeoutCondition {
+    pub seconds: f32,
+    last_touch_tick: u64,
+}
+
+impl NoTouchTimeoutCondition {
+    pub fn new(seconds: f32) -> Self {
+        Self {
+            seconds,
+            last_touch_tick: 0,
+        }
+    }
+
+    fn timed_out(&mut self, state: &GameStateA) -> bool {
+        let last_hit = state
+            .cars
+            .iter()
+            .map(|car| &car.state.ball_hit_info)
+            // hits from before a reset of the tick count are in the future
+            .filter(|hit| hit.is_valid && hit.tick_count_when_hit <= state.tick_count)
+            .map(|hit| hit.tick_count_when_hit)
+            .max();
+
+        if let Some(last_hit) = last_hit {
+            self.last_touch_tick = self.last_touch_tick.max(last_hit);
+        }
+
+        seconds_between(self.last_touch_tick, state) >= self.seconds
+    }
+}
+
+impl<SI> Terminal<SI> for NoTouchTimeoutCondition {
+    fn reset(&mut self, initial_state: &GameStateA, _shared_info: &mut SI) {
+        self.last_touch_tick = initial_state.tick_count;
+    }
+
+    fn is_terminal(&mut self, ctx: &StepContext, _shared_info: &mut SI) -> bool {
+        self.timed_out(ctx.state)
+    }
+}
+
+impl<SI> Truncate<SI> for NoTouchTimeoutCondition {
+    fn reset(&mut self, initial_state: &GameStateA, _shared_info: &mut SI) {
+        self.last_touch_tick = initial_state.tick_count;
+    }
+
+    fn should_truncate(&mut self, ctx: &StepContext, _shared_info: &mut SI) -> bool {
+        self.timed_out(ctx.state)
+    }
+}
+
+/// Met if any of the conditions is, e.g. `AnyCondition(vec![Box::new(GoalScoredCondition::default()) as Box<dyn Terminal<_>>, ...])`.
+///
+/// Every condition is checked every step, even after one is met, since some of them count steps
+pub struct AnyCondition<C>(pub Vec<C>);
+
+/// Met if all of the conditions are, every condition is checked every step
+pub struct AllCondition<C>(pub Vec<C>);
+
+impl<SI, C: Terminal<SI>> Terminal<SI> for AnyCondition<C> {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        for condition in &mut self.0 {
+            condition.reset(initial_state, shared_info);
+        }
+    }
+
+    fn is_terminal(&mut self, ctx: &StepContext, shared_info: &mut SI) -> bool {
+        self.0.iter_mut().fold(false, |any, condition| {
+            condition.is_terminal(ctx, shared_info) || any
+        })
+    }
+}
+
+impl<SI, C: Truncate<SI>> Truncate<SI> for AnyCondition<C> {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        for condition in &mut self.0 {
+            condition.reset(initial_state, shared_info);
+        }
+    }
+
+    fn should_truncate(&mut self, ctx: &StepContext, shared_info: &mut SI) -> bool {
+        self.0.iter_mut().fold(false, |any, condition| {
+            condition.should_truncate(ctx, shared_info) || any
+        })
+    }
+}
+
+impl<SI, C: Terminal<SI>> Terminal<SI> for AllCondition<C> {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        for condition in &mut self.0 {
+            condition.reset(initial_state, shared_info);
+        }
+    }
+
+    fn is_terminal(&mut self, ctx: &StepContext, shared_info: &mut SI) -> bool {
+        !self.0.is_empty()
+            && self.0.iter_mut().fold(true, |all, condition| {
+                condition.is_terminal(ctx, shared_info) && all
+            })
+    }
+}
+
+impl<SI, C: Truncate<SI>> Truncate<SI> for AllCondition<C> {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        for condition in &mut self.0 {
+            condition.reset(initial_state, shared_info);
+        }
+    }
+
+    fn should_truncate(&mut self, ctx: &StepContext, shared_info: &mut SI) -> bool {
+        !self.0.is_empty()
+            && self.0.iter_mut().fold(true, |all, condition| {
+                condition.should_truncate(ctx, shared_info) && all
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{NoSharedInfo, ScriptedEpisode, StateBuilder};
+    use glam::Vec3A;
+    use rocketsim_rs::sim::Team;
+
+    /// A state `ticks` into a game at 120 ticks per second with the ball at `ball_pos`
+    fn state_at(ticks: u64, ball_pos: Vec3A) -> GameStateA {
+        StateBuilder::new()
+            .with_tick_rate(120.)
+            .with_tick_count(ticks)
+            .with_ball(ball_pos, Vec3A::ZERO)
+            .with_car(Team::Blue, Vec3A::ZERO)
+            .build()
+    }
+
+    fn episode(states: Vec<GameStateA>) -> ScriptedEpisode<NoSharedInfo, ()> {
+        ScriptedEpisode::new(states, NoSharedInfo, ())
+    }
+
+    #[test]
+    fn goal_scored() {
+        let kickoff = state_at(0, Vec3A::new(0., 0., 93.));
+        let states = vec![
+            kickoff.clone(),
+            state_at(8, Vec3A::new(0., 4000., 93.)),
+            state_at(16, Vec3A::new(0., 5300., 93.)),
+        ];
+        let mut condition = GoalScoredCondition::default();
+        assert_eq!(
+            episode(states.clone()).terminals(&mut condition),
+            [false, true]
+        );
+        assert_eq!(episode(states).truncations(&mut condition), [false, true]);
+
+        // through an orange dropshot tile, nowhere near a soccar goal
+        let states = vec![kickoff, state_at(8, Vec3A::new(0., 2000., -200.))];
+        assert_eq!(
+            episode(states.clone()).terminals(&mut GoalScoredCondition::default()),
+            [false]
+        );
+        assert_eq!(
+            episode(states).terminals(&mut GoalScoredCondition::new(GameMode::DROPSHOT)),
+            [true]
+        );
+    }
+
+    #[test]
+    fn timeout_in_steps() {
+        let states = (0..4).map(|step| state_at(step * 8, Vec3A::ZERO)).collect();
+        let mut condition = TimeoutCondition::steps(2);
+        assert_eq!(
+            episode(states).terminals(&mut condition),
+            [false, true, true]
+        );
+
+        // a reset starts the count over
+        let states = (0..3).map(|step| state_at(step * 8, Vec3A::ZERO)).collect();
+        assert_eq!(episode(states).truncations(&mut condition), [false, true]);
+    }
+
+    #[test]
+    fn timeout_in_seconds() {
+        // counted from the initial state, not from the start of the game
+        let states = [1000, 1060, 1119, 1120]
+            .into_iter()
+            .map(|ticks| state_at(ticks, Vec3A::ZERO))
+            .collect();
+        let mut condition = TimeoutCondition::seconds(1.);
+        assert_eq!(
+            episode(states).terminals(&mut condition),
+            [false, false, true]
+        );
+    }
+
+    #[test]
+    fn no_touch_timeout() {
+        let touched_at = |ticks, hit_tick| {
+            let mut state = state_at(ticks, Vec3A::ZERO);
+            let hit = &mut state.cars[0].state.ball_hit_info;
+            hit.is_valid = true;
+            hit.tick_count_when_hit = hit_tick;
+            state
+        };
+
+        let untouched = vec![
+            state_at(0, Vec3A::ZERO),
+            state_at(60, Vec3A::ZERO),
+            state_at(120, Vec3A::ZERO),
+        ];
+        let mut condition = NoTouchTimeoutCondition::new(1.);
+        assert_eq!(episode(untouched).terminals(&mut condition), [false, true]);
+
+        let touched = vec![
+            state_at(0, Vec3A::ZERO),
+            touched_at(120, 100),
+            touched_at(180, 100),
+            touched_at(220, 100),
+            // a hit from the future is left over from before the tick count was reset
+            touched_at(240, 1000),
+        ];
+        assert_eq!(
+            episode(touched).truncations(&mut condition),
+            [false, false, true, true]
+        );
+    }
+}
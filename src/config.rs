@@ -0,0 +1,826 @@
+use crate::{
+    conditions::{AnyCondition, GoalScoredCondition, NoTouchTimeoutCondition, TimeoutCondition},
+    game_modes,
+    rewards::{
+        AlignBallGoalReward, CombinedReward, EventReward, FaceBallReward,
+        LiuDistanceBallToGoalReward, LiuDistanceReward, VelocityBallToGoalReward,
+        VelocityTowardBallReward,
+    },
+    state_setters::{KickoffStateSetter, RandomStateSetter},
+    Action, Env, Error, Mutators, Obs, Result, Reward, SharedInfoProvider, StateSetter, Terminal,
+    Truncate,
+};
+#[cfg(feature = "rlviser")]
+use crate::{LaunchMode, RenderConfig};
+use rocketsim_rs::{
+    cxx::UniquePtr,
+    sim::{Arena, DemoMode, GameMode},
+};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "rlviser")]
+use std::time::Duration;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Numeric parameters of a component, e.g. `{ max_steps = 300 }`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Params(pub BTreeMap<String, f32>);
+
+impl Params {
+    /// The value of `key`, or `default` if it isn't set
+    pub fn get(&self, key: &str, default: f32) -> f32 {
+        self.0.get(key).copied().unwrap_or(default)
+    }
+}
+
+/// A state setter, terminal or truncate condition, looked up by `name` in a `Registry`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ComponentConfig {
+    pub name: String,
+    #[serde(default)]
+    pub params: Params,
+}
+
+impl ComponentConfig {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            params: Params::default(),
+        }
+    }
+}
+
+/// A reward looked up by `name` in a `Registry`, scaled by `weight`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RewardConfig {
+    pub name: String,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    #[serde(default)]
+    pub params: Params,
+}
+
+fn default_weight() -> f32 {
+    1.
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameModeConfig {
+    #[default]
+    Soccar,
+    Hoops,
+    Heatseeker,
+    Snowday,
+    Dropshot,
+    TheVoid,
+}
+
+impl From<GameModeConfig> for GameMode {
+    fn from(game_mode: GameModeConfig) -> Self {
+        match game_mode {
+            GameModeConfig::Soccar => Self::SOCCAR,
+            GameModeConfig::Hoops => Self::HOOPS,
+            GameModeConfig::Heatseeker => Self::HEATSEEKER,
+            GameModeConfig::Snowday => Self::SNOWDAY,
+            GameModeConfig::Dropshot => Self::DROPSHOT,
+            GameModeConfig::TheVoid => Self::THE_VOID,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DemoModeConfig {
+    Normal,
+    OnContact,
+    Disabled,
+}
+
+impl From<DemoModeConfig> for DemoMode {
+    fn from(demo_mode: DemoModeConfig) -> Self {
+        match demo_mode {
+            DemoModeConfig::Normal => Self::NORMAL,
+            DemoModeConfig::OnContact => Self::ON_CONTACT,
+            DemoModeConfig::Disabled => Self::DISABLED,
+        }
+    }
+}
+
+impl From<DemoMode> for DemoModeConfig {
+    fn from(demo_mode: DemoMode) -> Self {
+        if demo_mode == DemoMode::ON_CONTACT {
+            Self::OnContact
+        } else if demo_mode == DemoMode::DISABLED {
+            Self::Disabled
+        } else {
+            Self::Normal
+        }
+    }
+}
+
+/// Overrides for RocketSim's mutators, anything left out keeps the game mode's default
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MutatorsConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gravity_z: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boost_accel_ground: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boost_accel_air: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ball_radius: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub respawn_delay: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub demo_mode: Option<DemoModeConfig>,
+}
+
+impl From<&MutatorsConfig> for Mutators {
+    fn from(config: &MutatorsConfig) -> Self {
+        Self {
+            gravity_z: config.gravity_z,
+            boost_accel_ground: config.boost_accel_ground,
+            boost_accel_air: config.boost_accel_air,
+            ball_radius: config.ball_radius,
+            respawn_delay: config.respawn_delay,
+            demo_mode: config.demo_mode.map(DemoMode::from),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TeamSizeConfig {
+    pub blue: usize,
+    pub orange: usize,
+}
+
+impl Default for TeamSizeConfig {
+    fn default() -> Self {
+        Self { blue: 1, orange: 1 }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings {
+    /// Open RLViser as soon as the env is built
+    pub enabled: bool,
+    /// The RLViser binary, `./rlviser` if not set
+    pub path: Option<PathBuf>,
+    /// Connect to an RLViser that's already running instead of launching one
+    pub no_spawn: bool,
+    /// Only launch RLViser if none is running yet
+    pub spawn_if_missing: bool,
+    /// Extra arguments for RLViser when it's launched
+    pub args: Vec<String>,
+    /// Seconds to wait for RLViser to answer before failing, rendering starts right away if not set
+    pub connect_timeout: Option<u64>,
+    /// RLViser's port and the env's port, RLViser's defaults if not set
+    pub ports: Option<(u16, u16)>,
+    /// Only render every this many steps, see `Env::with_render_every`
+    pub every: Option<u32>,
+    /// Send states to RLViser from their own thread through a queue of this many packets
+    pub render_thread: Option<usize>,
+}
+
+#[cfg(feature = "rlviser")]
+impl RenderSettings {
+    pub fn render_config(&self) -> RenderConfig {
+        let mut config = RenderConfig::new()
+            .with_spawn(!self.no_spawn)
+            .with_args(&self.args);
+
+        if self.spawn_if_missing {
+            config = config.with_launch(LaunchMode::SpawnIfMissing);
+        }
+
+        if let Some(seconds) = self.connect_timeout {
+            config = config.with_connect_timeout(Duration::from_secs(seconds));
+        }
+
+        if let Some(path) = &self.path {
+            config = config.with_path(path);
+        }
+
+        if let Some((rlviser_port, rocketsim_port)) = self.ports {
+            config = config.with_ports(rlviser_port, rocketsim_port);
+        }
+
+        if let Some(capacity) = self.render_thread {
+            config = config.with_render_thread(capacity);
+        }
+
+        config
+    }
+}
+
+/// Everything needed to build an env apart from the observation builder and action parser,
+/// which usually depend on the model being trained and stay in code.
+///
+/// Loaded from TOML or YAML, with every field optional:
+///
+/// ```toml
+/// game_mode = "soccar"
+/// tick_skip = 8
+/// team_size = { blue = 2, orange = 2 }
+/// mutators = { gravity_z = -325.0 }
+/// state_setter = { name = "kickoff" }
+/// rewards = [{ name = "touch_ball", weight = 0.5 }]
+/// terminals = [{ name = "goal_scored" }]
+/// truncates = [{ name = "timeout", params = { max_steps = 300 } }]
+/// ```
+///
+/// Here `touch_ball` is a reward that was added to the `Registry` with `with_reward`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExperimentConfig {
+    pub game_mode: GameModeConfig,
+    pub mutators: MutatorsConfig,
+    pub team_size: TeamSizeConfig,
+    pub tick_skip: u32,
+    pub state_setter: ComponentConfig,
+    pub rewards: Vec<RewardConfig>,
+    pub terminals: Vec<ComponentConfig>,
+    pub truncates: Vec<ComponentConfig>,
+    pub render: RenderSettings,
+}
+
+impl Default for ExperimentConfig {
+    fn default() -> Self {
+        Self {
+            game_mode: GameModeConfig::default(),
+            mutators: MutatorsConfig::default(),
+            team_size: TeamSizeConfig::default(),
+            tick_skip: 8,
+            state_setter: ComponentConfig::new("kickoff"),
+            rewards: Vec::new(),
+            terminals: vec![ComponentConfig::new("goal_scored")],
+            truncates: Vec::new(),
+            render: RenderSettings::default(),
+        }
+    }
+}
+
+enum Format {
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    fn of(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Ok(Self::Toml),
+            Some("yaml" | "yml") => Ok(Self::Yaml),
+            _ => Err(Error::Config(format!(
+                "Can't tell the format of {}, use a .toml, .yaml or .yml extension",
+                path.display()
+            ))),
+        }
+    }
+}
+
+impl ExperimentConfig {
+    pub fn from_toml(text: &str) -> Result<Self> {
+        toml::from_str(text).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    pub fn from_yaml(text: &str) -> Result<Self> {
+        serde_yaml::from_str(text).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    pub fn to_yaml(&self) -> Result<String> {
+        serde_yaml::to_string(self).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    /// Read a config, the format is picked from the file extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+
+        match Format::of(path)? {
+            Format::Toml => Self::from_toml(&text),
+            Format::Yaml => Self::from_yaml(&text),
+        }
+    }
+
+    /// Write this config, the format is picked from the file extension
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let text = match Format::of(path)? {
+            Format::Toml => self.to_toml()?,
+            Format::Yaml => self.to_yaml()?,
+        };
+
+        Ok(fs::write(path, text)?)
+    }
+
+    /// This config with every mutator filled in from `arena`, e.g. `Env::arena` of an env built from it.
+    ///
+    /// Save this next to checkpoints so the run can be reproduced even if RocketSim's defaults change
+    pub fn effective(&self, arena: &Arena) -> Self {
+        let mutators = arena.get_mutator_config();
+
+        Self {
+            mutators: MutatorsConfig {
+                gravity_z: Some(mutators.gravity.z),
+                boost_accel_ground: Some(mutators.boost_accel_ground),
+                boost_accel_air: Some(mutators.boost_accel_air),
+                ball_radius: Some(mutators.ball_radius),
+                respawn_delay: Some(mutators.respawn_delay),
+                demo_mode: Some(mutators.demo_mode.into()),
+            },
+            ..self.clone()
+        }
+    }
+
+    /// Create an arena with the game mode, mutators and cars from this config.
+    ///
+    /// `rocketsim_rs::init` must have been called first
+    pub fn build_arena(&self) -> UniquePtr<Arena> {
+        let mut arena = game_modes::build_arena(
+            self.game_mode.into(),
+            self.team_size.blue,
+            self.team_size.orange,
+        );
+        self.apply_mutators(&mut arena);
+
+        arena
+    }
+
+    /// Set the mutators that this config overrides, leaving the others as they are
+    fn apply_mutators(&self, arena: &mut UniquePtr<Arena>) {
+        Mutators::from(&self.mutators).apply(arena);
+    }
+
+    /// Look up the state setter, rewards, terminals and truncates in `registry`
+    fn build_components<SI>(&self, registry: &Registry<SI>) -> Result<ConfigComponents<SI>> {
+        if self.tick_skip == 0 {
+            return Err(Error::Config(String::from("tick_skip must be at least 1")));
+        }
+
+        let state_setter = registry.state_setter(&self.state_setter)?;
+        let mut reward = WeightedReward::new();
+        for config in &self.rewards {
+            reward.push(
+                config.name.as_str(),
+                config.weight,
+                registry.reward(config)?,
+            );
+        }
+        let terminal = AnyCondition(
+            self.terminals
+                .iter()
+                .map(|config| registry.terminal(config))
+                .collect::<Result<_>>()?,
+        );
+        let truncate = AnyCondition(
+            self.truncates
+                .iter()
+                .map(|config| registry.truncate(config))
+                .collect::<Result<_>>()?,
+        );
+
+        Ok((state_setter, reward, terminal, truncate))
+    }
+
+    /// Build an env from this config, with the components looked up in `registry`.
+    ///
+    /// `rocketsim_rs::init` must have been called first
+    pub fn build_env<SIP, OBS, ACT, SI>(
+        &self,
+        registry: &Registry<SI>,
+        shared_info_provider: SIP,
+        observations: OBS,
+        action: ACT,
+        shared_info: SI,
+    ) -> Result<ConfigEnv<SIP, OBS, ACT, SI>>
+    where
+        SIP: SharedInfoProvider<SI>,
+        OBS: Obs<SI>,
+        ACT: Action<SI>,
+    {
+        let (state_setter, reward, terminal, truncate) = self.build_components(registry)?;
+
+        let mut env = Env::new(
+            self.build_arena(),
+            state_setter,
+            shared_info_provider,
+            observations,
+            action,
+            reward,
+            terminal,
+            truncate,
+            shared_info,
+        )
+        .with_tick_skip(self.tick_skip)
+        .with_render_every(self.render.every.unwrap_or(1));
+        env.validate()?;
+
+        if self.render.enabled {
+            #[cfg(feature = "rlviser")]
+            env.enable_rlviser(&self.render.render_config())?;
+            #[cfg(not(feature = "rlviser"))]
+            return Err(Error::Config(String::from(
+                "Rendering needs the rlviser feature",
+            )));
+        }
+
+        Ok(env)
+    }
+}
+
+/// An env built by `ExperimentConfig::build_env`
+pub type ConfigEnv<SIP, OBS, ACT, SI> = Env<
+    Box<dyn StateSetter<SI>>,
+    SIP,
+    OBS,
+    ACT,
+    WeightedReward<SI>,
+    AnyTerminal<SI>,
+    AnyTruncate<SI>,
+    SI,
+>;
+
+type ConfigComponents<SI> = (
+    Box<dyn StateSetter<SI>>,
+    WeightedReward<SI>,
+    AnyTerminal<SI>,
+    AnyTruncate<SI>,
+);
+
+impl<SIP, OBS, ACT, SI> ConfigEnv<SIP, OBS, ACT, SI>
+where
+    SIP: SharedInfoProvider<SI>,
+    OBS: Obs<SI>,
+    ACT: Action<SI>,
+{
+    /// Rebuild the state setter, rewards, terminals and truncates from `config`
+    /// and apply its tick skip and mutators, without losing anything else about the env.
+    ///
+    /// Call it between episodes, the new components are reset by the next `reset`.
+    /// The game mode, team sizes and render setting need a new env and are ignored.
+    /// If anything in `config` is invalid, nothing is changed
+    pub fn apply_config_update(
+        &mut self,
+        registry: &Registry<SI>,
+        config: &ExperimentConfig,
+    ) -> Result<()> {
+        let (state_setter, reward, terminal, truncate) = config.build_components(registry)?;
+
+        self.state_setter = state_setter;
+        self.reward = reward;
+        self.terminal = terminal;
+        self.truncate = truncate;
+        self.tick_skip = config.tick_skip;
+        config.apply_mutators(&mut self.arena);
+
+        Ok(())
+    }
+}
+
+/// Notices when a config file changes, for tuning a running env with `Env::apply_config_update`
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    /// Changes are counted from now, so the first `poll` only returns a config if the file changed since
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let modified = modified(&path);
+
+        Self { path, modified }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The new config if the file was modified since the last call,
+    /// a file that fails to load is reported once and then waits for the next change
+    pub fn poll(&mut self) -> Result<Option<ExperimentConfig>> {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return Ok(None);
+        }
+
+        self.modified = modified;
+        ExperimentConfig::load(&self.path).map(Some)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+type Factory<T> = Box<dyn Fn(&Params) -> Result<T>>;
+
+/// Maps the component names used in configs to functions that build them from their params.
+///
+/// `new` comes with the built-in `kickoff` and `random` (with a `cars_in_air` param) state setters, `goal_scored` and
+/// `no_touch_timeout` (with a `seconds` param) terminals, `timeout` truncate (with a `seconds` or `max_steps` param) and the rewards in `rewards`:
+/// `velocity_toward_ball`, `velocity_ball_to_goal`, `face_ball`, `align_ball_goal`,
+/// `liu_distance`, `liu_distance_ball_to_goal` and `event`, with their fields as params
+pub struct Registry<SI> {
+    state_setters: HashMap<String, Factory<Box<dyn StateSetter<SI>>>>,
+    rewards: HashMap<String, Factory<Box<dyn Reward<SI>>>>,
+    terminals: HashMap<String, Factory<Box<dyn Terminal<SI>>>>,
+    truncates: HashMap<String, Factory<Box<dyn Truncate<SI>>>>,
+}
+
+impl<SI: 'static> Default for Registry<SI> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<SI: 'static> Registry<SI> {
+    pub fn new() -> Self {
+        Self::empty()
+            .with_state_setter("kickoff", |_| Ok(Box::new(KickoffStateSetter::new())))
+            .with_state_setter("random", |params| {
+                Ok(Box::new(
+                    RandomStateSetter::new().with_cars_in_air(params.get("cars_in_air", 0.) > 0.5),
+                ))
+            })
+            .with_reward("velocity_toward_ball", |params| {
+                Ok(Box::new(VelocityTowardBallReward {
+                    use_scalar_projection: params.get("use_scalar_projection", 0.) > 0.5,
+                }))
+            })
+            .with_reward("velocity_ball_to_goal", |params| {
+                Ok(Box::new(VelocityBallToGoalReward {
+                    own_goal: params.get("own_goal", 0.) > 0.5,
+                }))
+            })
+            .with_reward("face_ball", |_| Ok(Box::new(FaceBallReward)))
+            .with_reward("align_ball_goal", |params| {
+                Ok(Box::new(AlignBallGoalReward {
+                    defense: params.get("defense", 1.),
+                    offense: params.get("offense", 1.),
+                }))
+            })
+            .with_reward("liu_distance", |_| Ok(Box::new(LiuDistanceReward)))
+            .with_reward("liu_distance_ball_to_goal", |params| {
+                Ok(Box::new(LiuDistanceBallToGoalReward {
+                    own_goal: params.get("own_goal", 0.) > 0.5,
+                }))
+            })
+            .with_reward("event", |params| {
+                Ok(Box::new(
+                    EventReward::new()
+                        .with_goal(params.get("goal", 0.))
+                        .with_concede(params.get("concede", 0.))
+                        .with_touch(params.get("touch", 0.))
+                        .with_save(params.get("save", 0.))
+                        .with_demoed(params.get("demoed", 0.))
+                        .with_boost_pickup(params.get("boost_pickup", 0.)),
+                ))
+            })
+            .with_terminal("goal_scored", |_| Ok(Box::<GoalScoredCondition>::default()))
+            .with_terminal("hoops_goal_scored", |_| {
+                Ok(Box::new(GoalScoredCondition::new(GameMode::HOOPS)))
+            })
+            .with_terminal("dropshot_goal_scored", |_| {
+                Ok(Box::new(GoalScoredCondition::new(GameMode::DROPSHOT)))
+            })
+            .with_terminal("no_touch_timeout", |params| {
+                let seconds = params.get("seconds", 0.);
+                if seconds <= 0. {
+                    return Err(Error::Config(String::from(
+                        "no_touch_timeout needs a positive number of seconds",
+                    )));
+                }
+
+                Ok(Box::new(NoTouchTimeoutCondition::new(seconds)))
+            })
+            .with_truncate("timeout", |params| {
+                let seconds = params.get("seconds", 0.);
+                if seconds > 0. {
+                    return Ok(Box::new(TimeoutCondition::seconds(seconds)));
+                }
+
+                let max_steps = params.get("max_steps", 0.);
+                if max_steps < 1. {
+                    return Err(Error::Config(String::from(
+                        "timeout needs a max_steps of at least 1 or a positive number of seconds",
+                    )));
+                }
+
+                Ok(Box::new(TimeoutCondition::steps(max_steps as u32)))
+            })
+    }
+
+    /// A registry without the built-in components
+    pub fn empty() -> Self {
+        Self {
+            state_setters: HashMap::new(),
+            rewards: HashMap::new(),
+            terminals: HashMap::new(),
+            truncates: HashMap::new(),
+        }
+    }
+
+    pub fn with_state_setter(
+        mut self,
+        name: impl Into<String>,
+        factory: impl Fn(&Params) -> Result<Box<dyn StateSetter<SI>>> + 'static,
+    ) -> Self {
+        self.state_setters.insert(name.into(), Box::new(factory));
+        self
+    }
+
+    pub fn with_reward(
+        mut self,
+        name: impl Into<String>,
+        factory: impl Fn(&Params) -> Result<Box<dyn Reward<SI>>> + 'static,
+    ) -> Self {
+        self.rewards.insert(name.into(), Box::new(factory));
+        self
+    }
+
+    pub fn with_terminal(
+        mut self,
+        name: impl Into<String>,
+        factory: impl Fn(&Params) -> Result<Box<dyn Terminal<SI>>> + 'static,
+    ) -> Self {
+        self.terminals.insert(name.into(), Box::new(factory));
+        self
+    }
+
+    pub fn with_truncate(
+        mut self,
+        name: impl Into<String>,
+        factory: impl Fn(&Params) -> Result<Box<dyn Truncate<SI>>> + 'static,
+    ) -> Self {
+        self.truncates.insert(name.into(), Box::new(factory));
+        self
+    }
+}
+
+impl<SI> Registry<SI> {
+    pub fn state_setter(&self, config: &ComponentConfig) -> Result<Box<dyn StateSetter<SI>>> {
+        build(
+            &self.state_setters,
+            "state setter",
+            &config.name,
+            &config.params,
+        )
+    }
+
+    pub fn reward(&self, config: &RewardConfig) -> Result<Box<dyn Reward<SI>>> {
+        build(&self.rewards, "reward", &config.name, &config.params)
+    }
+
+    pub fn terminal(&self, config: &ComponentConfig) -> Result<Box<dyn Terminal<SI>>> {
+        build(&self.terminals, "terminal", &config.name, &config.params)
+    }
+
+    pub fn truncate(&self, config: &ComponentConfig) -> Result<Box<dyn Truncate<SI>>> {
+        build(&self.truncates, "truncate", &config.name, &config.params)
+    }
+}
+
+fn build<T>(
+    factories: &HashMap<String, Factory<T>>,
+    kind: &str,
+    name: &str,
+    params: &Params,
+) -> Result<T> {
+    let factory = factories
+        .get(name)
+        .ok_or_else(|| Error::Config(format!("No {kind} named {name:?} is registered")))?;
+
+    factory(params).map_err(|e| Error::Config(format!("Failed to build {kind} {name:?}: {e}")))
+}
+
+/// The rewards of a config, named after their entry
+pub type WeightedReward<SI, R = f32> = CombinedReward<SI, R>;
+
+/// Terminal if any of the conditions is
+pub type AnyTerminal<SI> = AnyCondition<Box<dyn Terminal<SI>>>;
+
+/// Truncated if any of the conditions is
+pub type AnyTruncate<SI> = AnyCondition<Box<dyn Truncate<SI>>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tuned() -> ExperimentConfig {
+        let mut config = ExperimentConfig {
+            game_mode: GameModeConfig::Hoops,
+            tick_skip: 4,
+            ..Default::default()
+        };
+        config.mutators.gravity_z = Some(-325.);
+        config.mutators.demo_mode = Some(DemoModeConfig::Disabled);
+        config.team_size = TeamSizeConfig { blue: 3, orange: 2 };
+        config.rewards.push(RewardConfig {
+            name: String::from("event"),
+            weight: 2.,
+            params: Params(BTreeMap::from([(String::from("goal"), 10.)])),
+        });
+        let mut timeout = ComponentConfig::new("timeout");
+        timeout.params.0.insert(String::from("max_steps"), 300.);
+        config.truncates.push(timeout);
+        config.render.ports = Some((45243, 34254));
+        config
+    }
+
+    #[test]
+    fn round_trips() {
+        let config = tuned();
+        let toml = config.to_toml().unwrap();
+        assert_eq!(ExperimentConfig::from_toml(&toml).unwrap(), config);
+        let yaml = config.to_yaml().unwrap();
+        assert_eq!(ExperimentConfig::from_yaml(&yaml).unwrap(), config);
+
+        // every field is optional
+        assert_eq!(
+            ExperimentConfig::from_toml("").unwrap(),
+            ExperimentConfig::default()
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(matches!(
+            ExperimentConfig::from_toml("tick_skp = 4"),
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(
+            ExperimentConfig::from_yaml("rewards:\n  - name: event\n    wieght: 2.0\n"),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn registry_errors() {
+        let registry = Registry::<()>::new();
+
+        let unknown = RewardConfig {
+            name: String::from("touch_ball"),
+            weight: 1.,
+            params: Params::default(),
+        };
+        assert!(matches!(registry.reward(&unknown), Err(Error::Config(_))));
+
+        let mut timeout = ComponentConfig::new("timeout");
+        assert!(matches!(registry.truncate(&timeout), Err(Error::Config(_))));
+        timeout.params.0.insert(String::from("seconds"), 10.);
+        assert!(registry.truncate(&timeout).is_ok());
+
+        let config = ExperimentConfig {
+            tick_skip: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.build_components(&registry),
+            Err(Error::Config(_))
+        ));
+        assert!(ExperimentConfig::default()
+            .build_components(&registry)
+            .is_ok());
+    }
+
+    #[test]
+    fn watcher_polls_changes() {
+        let path = std::env::temp_dir().join(format!(
+            "rlgym_rs_config_watcher_{}.toml",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+
+        let mut watcher = ConfigWatcher::new(&path);
+        assert!(watcher.poll().unwrap().is_none());
+
+        tuned().save(&path).unwrap();
+        assert_eq!(watcher.poll().unwrap(), Some(tuned()));
+        assert!(watcher.poll().unwrap().is_none());
+
+        // a file that's gone fails to load once, then waits for the next change
+        fs::remove_file(&path).unwrap();
+        assert!(watcher.poll().is_err());
+        assert!(watcher.poll().unwrap().is_none());
+
+        ExperimentConfig::default().save(&path).unwrap();
+        assert_eq!(watcher.poll().unwrap(), Some(ExperimentConfig::default()));
+        fs::remove_file(&path).unwrap();
+    }
+}
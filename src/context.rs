@@ -0,0 +1,67 @@
+use crate::{ActionHistory, AgentId, GameEvent};
+use rocketsim_rs::{glam_ext::GameStateA, sim::CarControls};
+
+/// Everything observation builders, rewards, terminals and truncates get to see about a step,
+/// or about the reset that started the episode
+#[derive(Clone, Copy)]
+pub struct StepContext<'a> {
+    /// The state after the step
+    pub state: &'a GameStateA,
+    /// The state before the step, `None` right after a reset
+    pub previous_state: Option<&'a GameStateA>,
+    /// The controls every car stepped with, empty right after a reset
+    pub actions: &'a [(AgentId, CarControls)],
+    /// Everything that happened during the step's ticks, empty unless `Env::with_event_capture` is on
+    pub events: &'a [GameEvent],
+    /// Ticks since the episode started
+    pub episode_ticks: u64,
+    /// The controls of the last few steps, `None` unless `Env::with_action_history` is on
+    pub action_history: Option<&'a ActionHistory>,
+}
+
+impl<'a> StepContext<'a> {
+    /// The context of a reset to `state`, e.g. to call components by hand
+    pub fn new(state: &'a GameStateA) -> Self {
+        Self {
+            state,
+            previous_state: None,
+            actions: &[],
+            events: &[],
+            episode_ticks: 0,
+            action_history: None,
+        }
+    }
+
+    pub fn with_previous_state(mut self, previous_state: &'a GameStateA) -> Self {
+        self.previous_state = Some(previous_state);
+        self
+    }
+
+    pub fn with_actions(mut self, actions: &'a [(AgentId, CarControls)]) -> Self {
+        self.actions = actions;
+        self
+    }
+
+    pub fn with_events(mut self, events: &'a [GameEvent]) -> Self {
+        self.events = events;
+        self
+    }
+
+    pub fn with_episode_ticks(mut self, episode_ticks: u64) -> Self {
+        self.episode_ticks = episode_ticks;
+        self
+    }
+
+    pub fn with_action_history(mut self, action_history: &'a ActionHistory) -> Self {
+        self.action_history = Some(action_history);
+        self
+    }
+
+    /// The controls `agent_id` stepped with
+    pub fn action_of(&self, agent_id: AgentId) -> Option<&'a CarControls> {
+        self.actions
+            .iter()
+            .find(|(id, _)| *id == agent_id)
+            .map(|(_, controls)| controls)
+    }
+}
@@ -0,0 +1,241 @@
+use crate::MaybeSend;
+
+/// What a curriculum decides on, as of the reset it's called for
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CurriculumProgress {
+    /// Steps taken since the env was created
+    pub total_steps: u64,
+    /// Episodes that ended with a terminal or truncated step
+    pub episodes_completed: u64,
+    /// The last value given to `Env::set_curriculum_metric`, e.g. a win rate from an evaluation
+    pub metric: Option<f32>,
+}
+
+/// Changes an env's state setter, reward and terminal condition as training goes on.
+///
+/// Register one with `Env::with_curriculum`, it's updated before every reset that uses the state setter
+pub trait Curriculum<SS, REW, TERM, SI>: MaybeSend {
+    fn update(
+        &mut self,
+        progress: &CurriculumProgress,
+        state_setter: &mut SS,
+        reward: &mut REW,
+        terminal: &mut TERM,
+        shared_info: &mut SI,
+    );
+    /// Name of the current stage, for logging
+    fn stage(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// When a stage of a `StagedCurriculum` is done
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Advance {
+    /// Once this many steps were taken in total
+    Steps(u64),
+    /// Once this many episodes were completed in total
+    Episodes(u64),
+    /// Once the curriculum metric reaches this value
+    Metric(f32),
+    /// Never, for the last stage
+    Never,
+}
+
+impl Advance {
+    fn is_met(self, progress: &CurriculumProgress) -> bool {
+        match self {
+            Self::Steps(steps) => progress.total_steps >= steps,
+            Self::Episodes(episodes) => progress.episodes_completed >= episodes,
+            Self::Metric(value) => progress.metric.map_or(false, |metric| metric >= value),
+            Self::Never => false,
+        }
+    }
+}
+
+#[cfg(feature = "sync")]
+type Enter<SS, REW, TERM, SI> = Box<dyn FnMut(&mut SS, &mut REW, &mut TERM, &mut SI) + Send>;
+#[cfg(not(feature = "sync"))]
+type Enter<SS, REW, TERM, SI> = Box<dyn FnMut(&mut SS, &mut REW, &mut TERM, &mut SI)>;
+
+struct Stage<SS, REW, TERM, SI> {
+    name: String,
+    until: Advance,
+    enter: Enter<SS, REW, TERM, SI>,
+}
+
+/// Goes through its stages in order, entering the next one once the current one's `Advance` is met.
+///
+/// Entering a stage calls its closure with the components, e.g. to swap a boxed state setter
+/// or change weights with `CombinedReward::set_weight`. The first stage is entered on the first reset
+pub struct StagedCurriculum<SS, REW, TERM, SI> {
+    stages: Vec<Stage<SS, REW, TERM, SI>>,
+    current: Option<usize>,
+}
+
+impl<SS, REW, TERM, SI> Default for StagedCurriculum<SS, REW, TERM, SI> {
+    fn default() -> Self {
+        Self {
+            stages: Vec::new(),
+            current: None,
+        }
+    }
+}
+
+impl<SS, REW, TERM, SI> StagedCurriculum<SS, REW, TERM, SI> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a stage that lasts `until`, `enter` sets the components up for it
+    pub fn with_stage(
+        mut self,
+        name: impl Into<String>,
+        until: Advance,
+        enter: impl FnMut(&mut SS, &mut REW, &mut TERM, &mut SI) + MaybeSend + 'static,
+    ) -> Self {
+        self.stages.push(Stage {
+            name: name.into(),
+            until,
+            enter: Box::new(enter),
+        });
+        self
+    }
+
+    /// Index of the current stage, `None` before the first reset
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+}
+
+impl<SS, REW, TERM, SI> Curriculum<SS, REW, TERM, SI> for StagedCurriculum<SS, REW, TERM, SI>
+where
+    Self: MaybeSend,
+{
+    fn update(
+        &mut self,
+        progress: &CurriculumProgress,
+        state_setter: &mut SS,
+        reward: &mut REW,
+        terminal: &mut TERM,
+        shared_info: &mut SI,
+    ) {
+        let mut next = self.current.map_or(0, |current| current + 1);
+        if let Some(current) = self.current {
+            if !self.stages[current].until.is_met(progress) {
+                return;
+            }
+        }
+
+        // skip over stages that are already done, e.g. when resuming a run
+        while next + 1 < self.stages.len() && self.stages[next].until.is_met(progress) {
+            next += 1;
+        }
+
+        let Some(stage) = self.stages.get_mut(next) else {
+            return;
+        };
+        log::info!("Entering curriculum stage {next} ({})", stage.name);
+        (stage.enter)(state_setter, reward, terminal, shared_info);
+        self.current = Some(next);
+    }
+
+    fn stage(&self) -> Option<&str> {
+        self.current
+            .map(|current| self.stages[current].name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every stage records its name in the state setter when it's entered
+    type Recorded = StagedCurriculum<Vec<&'static str>, (), (), ()>;
+
+    fn recorded(stages: &[(&'static str, Advance)]) -> Recorded {
+        stages
+            .iter()
+            .fold(StagedCurriculum::new(), |curriculum, &(name, until)| {
+                curriculum.with_stage(name, until, move |entered: &mut Vec<_>, _, _, _| {
+                    entered.push(name);
+                })
+            })
+    }
+
+    fn update(curriculum: &mut Recorded, progress: CurriculumProgress) -> Vec<&'static str> {
+        let mut entered = Vec::new();
+        curriculum.update(&progress, &mut entered, &mut (), &mut (), &mut ());
+        entered
+    }
+
+    fn progress(
+        total_steps: u64,
+        episodes_completed: u64,
+        metric: Option<f32>,
+    ) -> CurriculumProgress {
+        CurriculumProgress {
+            total_steps,
+            episodes_completed,
+            metric,
+        }
+    }
+
+    const STAGES: [(&str, Advance); 4] = [
+        ("warmup", Advance::Steps(100)),
+        ("shaping", Advance::Episodes(20)),
+        ("evaluated", Advance::Metric(0.5)),
+        ("final", Advance::Never),
+    ];
+
+    #[test]
+    fn advances_in_order() {
+        let mut curriculum = recorded(&STAGES);
+        assert_eq!((curriculum.current(), curriculum.stage()), (None, None));
+
+        assert_eq!(update(&mut curriculum, progress(0, 0, None)), ["warmup"]);
+        assert_eq!(curriculum.current(), Some(0));
+        assert!(update(&mut curriculum, progress(50, 0, None)).is_empty());
+
+        assert_eq!(update(&mut curriculum, progress(100, 0, None)), ["shaping"]);
+        assert_eq!(curriculum.stage(), Some("shaping"));
+        assert!(update(&mut curriculum, progress(200, 19, Some(1.))).is_empty());
+    }
+
+    #[test]
+    fn skips_stages_that_are_done() {
+        // resuming a run that's past the first two stages only enters the third
+        let mut curriculum = recorded(&STAGES);
+        assert_eq!(
+            update(&mut curriculum, progress(1000, 30, Some(0.2))),
+            ["evaluated"]
+        );
+        assert_eq!(curriculum.current(), Some(2));
+
+        // the last stage is entered even though skipping never goes past it
+        let mut curriculum = recorded(&STAGES);
+        assert_eq!(
+            update(&mut curriculum, progress(1000, 30, Some(0.9))),
+            ["final"]
+        );
+        assert_eq!(curriculum.stage(), Some("final"));
+    }
+
+    #[test]
+    fn stays_in_the_last_stage() {
+        let mut curriculum = recorded(&STAGES);
+        update(&mut curriculum, progress(1000, 30, Some(0.9)));
+        assert!(update(&mut curriculum, progress(2000, 60, Some(1.))).is_empty());
+        assert_eq!(curriculum.current(), Some(3));
+
+        // even once its own advance is met
+        let mut curriculum = recorded(&[("only", Advance::Steps(10))]);
+        assert_eq!(update(&mut curriculum, progress(0, 0, None)), ["only"]);
+        assert!(update(&mut curriculum, progress(100, 0, None)).is_empty());
+        assert_eq!(curriculum.current(), Some(0));
+
+        let mut empty = recorded(&[]);
+        assert!(update(&mut empty, progress(0, 0, None)).is_empty());
+        assert_eq!(empty.current(), None);
+    }
+}
@@ -0,0 +1,74 @@
+use glam::Vec3A;
+use rocketsim_rs::{
+    math::Vec3,
+    render::{Color, Render, RenderMessage},
+};
+use std::sync::{Arc, Mutex};
+
+fn to_raw(v: Vec3A) -> Vec3 {
+    Vec3::new(v.x, v.y, v.z)
+}
+
+/// Debug shapes for the renderer, e.g. a reward's target or a predicted ball path drawn in RLViser.
+///
+/// Clones share the same queue, so components can keep one from `Env::debug_draw` and draw from their own code.
+/// Shapes are grouped by an id, drawing a group again replaces what it showed before.
+/// The env sends what was drawn at the end of every step, and drops it while nothing is rendering
+#[derive(Clone, Default)]
+pub struct DebugDraw {
+    pending: Arc<Mutex<Vec<RenderMessage>>>,
+}
+
+impl DebugDraw {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the shapes of `group` with `renders`
+    pub fn draw(&self, group: i32, renders: Vec<Render>) {
+        self.push(RenderMessage::AddRender(group, renders));
+    }
+
+    /// Replace the shapes of `group` with a line from `start` to `end` for each pair
+    pub fn lines(&self, group: i32, lines: impl IntoIterator<Item = (Vec3A, Vec3A)>, color: Color) {
+        let renders = lines
+            .into_iter()
+            .map(|(start, end)| Render::Line {
+                start: to_raw(start),
+                end: to_raw(end),
+                color,
+            })
+            .collect();
+
+        self.draw(group, renders);
+    }
+
+    /// Replace the shapes of `group` with a line through every point, e.g. a ball prediction's positions
+    pub fn line_strip(&self, group: i32, points: impl IntoIterator<Item = Vec3A>, color: Color) {
+        let positions = points.into_iter().map(to_raw).collect();
+        self.draw(group, vec![Render::LineStrip { positions, color }]);
+    }
+
+    /// Stop showing the shapes of `group`
+    pub fn clear(&self, group: i32) {
+        self.push(RenderMessage::RemoveRender(group));
+    }
+
+    fn push(&self, message: RenderMessage) {
+        // a component that panicked mid-draw can't leave the queue in a bad state
+        let mut pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        pending.push(message);
+    }
+
+    /// Everything drawn since the last call, in order
+    pub(crate) fn take(&self) -> Vec<RenderMessage> {
+        let mut pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        std::mem::take(&mut *pending)
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{remote::RemoteEnv, Error, Result};
+use rocketsim_rs::{
+    bytes::{FromBytes, FromBytesExact, ToBytes},
+    glam_ext::GameStateA,
+    sim::Team,
+    GameState,
+};
+use serde_json::{json, Value};
+use std::{io::Read, net::ToSocketAddrs, time::Instant};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+#[derive(Default)]
+struct Metrics {
+    steps: u64,
+    episodes: u64,
+    episode_steps: u64,
+    episode_rewards: Vec<f32>,
+}
+
+fn state_json(state: &GameStateA) -> Value {
+    let cars: Vec<_> = state
+        .cars
+        .iter()
+        .map(|car| {
+            json!({
+                "id": car.id,
+                "team": if car.team == Team::Blue { "blue" } else { "orange" },
+                "pos": car.state.pos.to_array(),
+                "vel": car.state.vel.to_array(),
+                "ang_vel": car.state.ang_vel.to_array(),
+                "forward": car.state.rot_mat.x_axis.to_array(),
+                "up": car.state.rot_mat.z_axis.to_array(),
+                "boost": car.state.boost,
+                "is_on_ground": car.state.is_on_ground,
+                "is_demoed": car.state.is_demoed,
+            })
+        })
+        .collect();
+
+    let pads: Vec<_> = state
+        .pads
+        .iter()
+        .map(|pad| {
+            json!({
+                "pos": pad.position.to_array(),
+                "is_big": pad.is_big,
+                "is_active": pad.state.is_active,
+                "cooldown": pad.state.cooldown,
+            })
+        })
+        .collect();
+
+    json!({
+        "tick_count": state.tick_count,
+        "ball": {
+            "pos": state.ball.pos.to_array(),
+            "vel": state.ball.vel.to_array(),
+            "ang_vel": state.ball.ang_vel.to_array(),
+        },
+        "cars": cars,
+        "pads": pads,
+    })
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_snapshot(hex: &str) -> Result<GameState, String> {
+    let bytes = from_hex(hex).ok_or("Snapshot isn't valid hex")?;
+    if bytes.len() < GameState::MIN_NUM_BYTES || GameState::get_num_bytes(&bytes) != bytes.len() {
+        return Err(String::from("Snapshot has the wrong length"));
+    }
+
+    Ok(GameState::from_bytes(&bytes))
+}
+
+fn parse_actions(body: &Value) -> Option<Vec<Vec<f32>>> {
+    body.get("actions")?
+        .as_array()?
+        .iter()
+        .map(|action| {
+            action
+                .as_array()?
+                .iter()
+                .map(|value| value.as_f64().map(|value| value as f32))
+                .collect()
+        })
+        .collect()
+}
+
+/// Serve a JSON API for poking at `env` over HTTP on `addr`, until the process exits.
+///
+/// - `GET /state` returns the last state, with a hex `snapshot` that can be passed back to `/reset`
+/// - `GET /spaces` returns the observation and action size of every agent
+/// - `GET /metrics` returns step and episode counters
+/// - `POST /reset` resets the env, to the hex `snapshot` in the body if there is one
+/// - `POST /step` steps the env with the `actions` in the body, one list of floats per agent
+///
+/// Requests are handled one at a time on the calling thread,
+/// this is meant for debugging and scripting rather than training
+pub fn serve(addr: impl ToSocketAddrs, env: &mut dyn RemoteEnv) -> Result<()> {
+    let server =
+        Server::http(addr).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+    let started = Instant::now();
+    let mut metrics = Metrics::default();
+
+    for mut request in server.incoming_requests() {
+        let mut body = String::new();
+        let body = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) if body.trim().is_empty() => Ok(Value::Null),
+            Ok(_) => serde_json::from_str(&body)
+                .map_err(|e| (400, format!("The body isn't valid JSON: {e}"))),
+            Err(e) => Err((400, format!("Couldn't read the body: {e}"))),
+        };
+
+        let result = body.and_then(|body| handle(&request, &body, env, &mut metrics, started));
+        let (status, value) = match result {
+            Ok(value) => (200, value),
+            Err((status, message)) => (status, json!({ "error": message })),
+        };
+
+        let response = Response::from_string(value.to_string())
+            .with_status_code(status)
+            .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+        // the client hanging up early isn't our problem
+        let _ = request.respond(response);
+    }
+
+    Ok(())
+}
+
+/// Blame the client for errors caused by what it sent
+fn env_error(error: Error) -> (u16, String) {
+    let status = match error {
+        Error::NotReset => 409,
+        Error::Arena(_) | Error::ActionCount { .. } => 400,
+        _ => 500,
+    };
+
+    (status, error.to_string())
+}
+
+fn handle(
+    request: &Request,
+    body: &Value,
+    env: &mut dyn RemoteEnv,
+    metrics: &mut Metrics,
+    started: Instant,
+) -> Result<Value, (u16, String)> {
+    let path = request.url().split('?').next().unwrap_or_default();
+
+    match (request.method(), path) {
+        (Method::Get, "/state") => {
+            let state = env
+                .state()
+                .ok_or((409, String::from("The env hasn't been reset yet")))?;
+            let mut value = state_json(&state);
+            value["snapshot"] = Value::String(to_hex(&env.snapshot().to_bytes()));
+            Ok(value)
+        }
+        (Method::Get, "/spaces") => Ok(json!({
+            "obs_spaces": env.obs_spaces(),
+            "action_spaces": env.action_spaces(),
+        })),
+        (Method::Get, "/metrics") => {
+            let uptime = started.elapsed().as_secs_f64();
+            Ok(json!({
+                "steps": metrics.steps,
+                "episodes": metrics.episodes,
+                "episode_steps": metrics.episode_steps,
+                "episode_rewards": metrics.episode_rewards,
+                "uptime_secs": uptime,
+                "steps_per_second": metrics.steps as f64 / uptime.max(f64::EPSILON),
+            }))
+        }
+        (Method::Post, "/reset") => {
+            let obs = match body.get("snapshot").and_then(Value::as_str) {
+                Some(hex) => {
+                    let snapshot = parse_snapshot(hex).map_err(|e| (400, e))?;
+                    env.reset_to(&snapshot).map_err(env_error)?
+                }
+                None => env.reset().map_err(env_error)?,
+            };
+
+            metrics.episode_steps = 0;
+            metrics.episode_rewards = vec![0.; obs.len()];
+            Ok(json!({ "obs": obs }))
+        }
+        (Method::Post, "/step") => {
+            if env.state().is_none() {
+                return Err((409, String::from("The env hasn't been reset yet")));
+            }
+
+            let actions = parse_actions(body).ok_or((
+                400,
+                String::from("Expected a body like {\"actions\": [[...], ...]}"),
+            ))?;
+            let step = env.step(actions).map_err(env_error)?;
+
+            metrics.steps += 1;
+            metrics.episode_steps += 1;
+            metrics.episode_rewards.resize(step.rewards.len(), 0.);
+            for (total, reward) in metrics.episode_rewards.iter_mut().zip(&step.rewards) {
+                *total += reward;
+            }
+            if step.is_terminal || step.truncated {
+                metrics.episodes += 1;
+            }
+
+            Ok(json!({
+                "obs": step.obs,
+                "rewards": step.rewards,
+                "is_terminal": step.is_terminal,
+                "truncated": step.truncated,
+            }))
+        }
+        _ => Err((404, format!("No endpoint for {} {path}", request.method()))),
+    }
+}
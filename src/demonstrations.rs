@@ -0,0 +1,117 @@
+use crate::{
+    recording::{TrajectoryFrame, TrajectoryReader},
+    Action, AgentId, Env, Error, Obs, Result, Reward, SharedInfoProvider, StateSetter, Terminal,
+    Truncate,
+};
+use rocketsim_rs::sim::CarControls;
+use std::io::Read;
+
+/// The observation a car saw and the controls its policy chose in response
+#[derive(Clone, Debug)]
+pub struct Demonstration {
+    pub agent_id: AgentId,
+    pub obs: Vec<f32>,
+    pub controls: CarControls,
+}
+
+/// Collects imitation learning datasets by letting policies drive every car.
+///
+/// Give every car a policy with `Env::set_policy` or `Env::set_team_policy` first,
+/// the env is then stepped with the default (empty) action input.
+/// To save a dataset, also give the env a `TrajectoryRecorder` and read it back with `read_demonstrations`
+#[derive(Default)]
+pub struct DemonstrationCollector {
+    samples: Vec<Demonstration>,
+}
+
+impl DemonstrationCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Step `env` `num_steps` times, resetting it whenever an episode ends,
+    /// and record an (obs, controls) pair for every car each step
+    pub fn collect<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI>(
+        &mut self,
+        env: &mut Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI>,
+        num_steps: usize,
+    ) -> Result<()>
+    where
+        SS: StateSetter<SI>,
+        SIP: SharedInfoProvider<SI>,
+        OBS: Obs<SI>,
+        ACT: Action<SI>,
+        ACT::Input: Default,
+        REW: Reward<SI>,
+        TERM: Terminal<SI>,
+        TRUNC: Truncate<SI>,
+    {
+        let mut needs_reset = env.last_state.is_none();
+
+        for _ in 0..num_steps {
+            if needs_reset {
+                env.reset()?;
+            }
+
+            let (Some(state), Some(obs)) = (env.last_state.clone(), env.last_obs.clone()) else {
+                return Err(Error::NotReset);
+            };
+            let result = env.step(ACT::Input::default())?;
+
+            for (car, obs) in state.cars.iter().zip(obs.iter()) {
+                let agent_id = AgentId::of(car);
+                let Some((_, controls)) = env.last_controls.iter().find(|(id, _)| *id == agent_id)
+                else {
+                    continue;
+                };
+
+                self.samples.push(Demonstration {
+                    agent_id,
+                    obs: obs.clone(),
+                    controls: *controls,
+                });
+            }
+
+            needs_reset = result.is_terminal || result.truncated;
+        }
+
+        Ok(())
+    }
+
+    pub fn samples(&self) -> &[Demonstration] {
+        &self.samples
+    }
+
+    pub fn into_samples(self) -> Vec<Demonstration> {
+        self.samples
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+}
+
+/// The (obs, controls) pairs of every step in a file written by a `TrajectoryRecorder`,
+/// e.g. one given to the env with `Env::with_trajectory_recorder` while collecting
+pub fn read_demonstrations<R: Read>(reader: TrajectoryReader<R>) -> Result<Vec<Demonstration>> {
+    let mut samples = Vec::new();
+    for frame in reader {
+        let TrajectoryFrame::Step(step) = frame? else {
+            continue;
+        };
+
+        for (agent_id, obs) in step.obs {
+            let Some((_, controls)) = step.controls.iter().find(|(id, _)| *id == agent_id) else {
+                continue;
+            };
+
+            samples.push(Demonstration {
+                agent_id,
+                obs,
+                controls: *controls,
+            });
+        }
+    }
+
+    Ok(samples)
+}
@@ -0,0 +1,270 @@
+use rocketsim_rs::{
+    glam_ext::{BallA, CarInfoA, GameStateA},
+    sim::Team,
+};
+use std::{borrow::Borrow, fmt};
+
+/// A single field that differs between two game states
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDiff {
+    /// Path to the field, e.g. `cars[1].state.vel.x` for the car with id 1
+    pub field: String,
+    pub left: f32,
+    pub right: f32,
+}
+
+impl fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} != {} (delta {})",
+            self.field,
+            self.left,
+            self.right,
+            self.right - self.left
+        )
+    }
+}
+
+/// All fields that differ at a given step
+#[derive(Debug, Clone, Default)]
+pub struct StepDiff {
+    pub step: usize,
+    pub fields: Vec<FieldDiff>,
+}
+
+/// Structured report of where two sequences of game states diverge
+#[derive(Debug, Clone, Default)]
+pub struct DiffReport {
+    pub steps_compared: usize,
+    /// Only steps with at least one differing field are included
+    pub divergences: Vec<StepDiff>,
+    /// Set to the lengths of the two sequences if they differ
+    pub length_mismatch: Option<(usize, usize)>,
+}
+
+impl DiffReport {
+    /// Compare two sequences of game states step by step.
+    ///
+    /// Floating point fields are considered equal if they are within `tolerance` of each other.
+    /// The sequences can come from `Env::rollout` or from a previously saved recording.
+    pub fn compare<L, R>(
+        left: impl IntoIterator<Item = L>,
+        right: impl IntoIterator<Item = R>,
+        tolerance: f32,
+    ) -> Self
+    where
+        L: Borrow<GameStateA>,
+        R: Borrow<GameStateA>,
+    {
+        let mut left = left.into_iter();
+        let mut right = right.into_iter();
+        let mut report = Self::default();
+
+        loop {
+            match (left.next(), right.next()) {
+                (Some(l), Some(r)) => {
+                    let fields = diff_states(l.borrow(), r.borrow(), tolerance);
+                    if !fields.is_empty() {
+                        report.divergences.push(StepDiff {
+                            step: report.steps_compared,
+                            fields,
+                        });
+                    }
+
+                    report.steps_compared += 1;
+                }
+                (None, None) => break,
+                (l, r) => {
+                    let left_len = report.steps_compared + usize::from(l.is_some()) + left.count();
+                    let right_len =
+                        report.steps_compared + usize::from(r.is_some()) + right.count();
+                    report.length_mismatch = Some((left_len, right_len));
+                    break;
+                }
+            }
+        }
+
+        report
+    }
+
+    pub fn is_identical(&self) -> bool {
+        self.divergences.is_empty() && self.length_mismatch.is_none()
+    }
+
+    /// The first step where the two sequences diverge
+    pub fn first_divergence(&self) -> Option<&StepDiff> {
+        self.divergences.first()
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Compared {} steps, {} diverged",
+            self.steps_compared,
+            self.divergences.len()
+        )?;
+
+        if let Some((left, right)) = self.length_mismatch {
+            writeln!(f, "Length mismatch: {left} != {right}")?;
+        }
+
+        for step in &self.divergences {
+            writeln!(f, "Step {}:", step.step)?;
+            for field in &step.fields {
+                writeln!(f, "  {field}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns every field that differs by more than `tolerance` between the two states
+pub fn diff_states(left: &GameStateA, right: &GameStateA, tolerance: f32) -> Vec<FieldDiff> {
+    let mut differ = Differ {
+        tolerance,
+        fields: Vec::new(),
+    };
+
+    differ.value(
+        "tick_count",
+        left.tick_count as f32,
+        right.tick_count as f32,
+    );
+    differ.ball("ball", &left.ball, &right.ball);
+    differ.value("cars.len", left.cars.len() as f32, right.cars.len() as f32);
+
+    // cars are matched by id, so a different car order or a car that switched teams still lines up
+    for l in &left.cars {
+        let field = format!("cars[{}]", l.id);
+        match right.cars.iter().find(|r| r.id == l.id) {
+            Some(r) => differ.car(&field, l, r),
+            None => differ.flag(&format!("{field}.exists"), true, false),
+        }
+    }
+
+    for r in &right.cars {
+        if !left.cars.iter().any(|l| l.id == r.id) {
+            differ.flag(&format!("cars[{}].exists", r.id), false, true);
+        }
+    }
+
+    differ.fields
+}
+
+struct Differ {
+    tolerance: f32,
+    fields: Vec<FieldDiff>,
+}
+
+impl Differ {
+    fn value(&mut self, field: &str, left: f32, right: f32) {
+        let differs = if left.is_nan() || right.is_nan() {
+            left.is_nan() != right.is_nan()
+        } else {
+            (left - right).abs() > self.tolerance
+        };
+
+        if differs {
+            self.fields.push(FieldDiff {
+                field: field.to_string(),
+                left,
+                right,
+            });
+        }
+    }
+
+    fn flag(&mut self, field: &str, left: bool, right: bool) {
+        if left != right {
+            self.fields.push(FieldDiff {
+                field: field.to_string(),
+                left: f32::from(u8::from(left)),
+                right: f32::from(u8::from(right)),
+            });
+        }
+    }
+
+    fn vec3(&mut self, field: &str, left: [f32; 3], right: [f32; 3]) {
+        for ((axis, l), r) in ["x", "y", "z"].into_iter().zip(left).zip(right) {
+            self.value(&format!("{field}.{axis}"), l, r);
+        }
+    }
+
+    fn ball(&mut self, field: &str, left: &BallA, right: &BallA) {
+        self.vec3(
+            &format!("{field}.pos"),
+            left.pos.to_array(),
+            right.pos.to_array(),
+        );
+        self.vec3(
+            &format!("{field}.vel"),
+            left.vel.to_array(),
+            right.vel.to_array(),
+        );
+        self.vec3(
+            &format!("{field}.ang_vel"),
+            left.ang_vel.to_array(),
+            right.ang_vel.to_array(),
+        );
+    }
+
+    fn car(&mut self, field: &str, left: &CarInfoA, right: &CarInfoA) {
+        // 0 for blue and 1 for orange
+        self.flag(
+            &format!("{field}.team"),
+            left.team == Team::Orange,
+            right.team == Team::Orange,
+        );
+
+        let (l, r) = (&left.state, &right.state);
+        self.vec3(
+            &format!("{field}.state.pos"),
+            l.pos.to_array(),
+            r.pos.to_array(),
+        );
+        self.vec3(
+            &format!("{field}.state.vel"),
+            l.vel.to_array(),
+            r.vel.to_array(),
+        );
+        self.vec3(
+            &format!("{field}.state.ang_vel"),
+            l.ang_vel.to_array(),
+            r.ang_vel.to_array(),
+        );
+        self.vec3(
+            &format!("{field}.state.forward"),
+            l.rot_mat.x_axis.to_array(),
+            r.rot_mat.x_axis.to_array(),
+        );
+        self.vec3(
+            &format!("{field}.state.up"),
+            l.rot_mat.z_axis.to_array(),
+            r.rot_mat.z_axis.to_array(),
+        );
+        self.value(&format!("{field}.state.boost"), l.boost, r.boost);
+        self.flag(
+            &format!("{field}.state.is_on_ground"),
+            l.is_on_ground,
+            r.is_on_ground,
+        );
+        self.flag(
+            &format!("{field}.state.has_jumped"),
+            l.has_jumped,
+            r.has_jumped,
+        );
+        self.flag(
+            &format!("{field}.state.has_flipped"),
+            l.has_flipped,
+            r.has_flipped,
+        );
+        self.flag(
+            &format!("{field}.state.is_demoed"),
+            l.is_demoed,
+            r.is_demoed,
+        );
+    }
+}
@@ -0,0 +1,423 @@
+use crate::{
+    handshake::Handshake,
+    remote::{read_transition, write_transition, RemoteEnv, Transition},
+    wire::{invalid_data, read_frame, write_frame, Reader, Writer, MAX_FRAME_LEN},
+    Error, FullObs, Result,
+};
+use std::{
+    io::{self, BufReader},
+    net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+/// Version of the messages exchanged by `Learner` and `RolloutWorker`
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Picks the actions of every agent from their observations inside a rollout worker
+pub trait RolloutPolicy {
+    fn act(&mut self, obs: &FullObs) -> Vec<Vec<f32>>;
+}
+
+/// Turns the model bytes published by the learner (e.g. an ONNX file) into a policy
+pub trait RolloutPolicyLoader {
+    fn load(&mut self, model: &[u8]) -> Result<Box<dyn RolloutPolicy>>;
+}
+
+/// Transitions collected by one worker with one version of the policy.
+///
+/// Encoded as the worker id, the policy version, the number of transitions, then each transition.
+/// Strings and lists are prefixed with their length as a `u32`, everything is little-endian
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Rollout {
+    pub worker_id: String,
+    pub policy_version: u64,
+    pub transitions: Vec<Transition>,
+}
+
+impl Rollout {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        writer
+            .str(&self.worker_id)
+            .u64(self.policy_version)
+            .u32(self.transitions.len() as u32);
+
+        for transition in &self.transitions {
+            write_transition(&mut writer, transition);
+        }
+
+        writer.finish()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        let worker_id = reader.str()?;
+        let policy_version = reader.u64()?;
+        let len = reader.u32()?;
+        let transitions = (0..len)
+            .map(|_| read_transition(&mut reader))
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self {
+            worker_id,
+            policy_version,
+            transitions,
+        })
+    }
+}
+
+fn encode_model(version: u64, model: &[u8]) -> Vec<u8> {
+    let mut writer = Writer::default();
+    writer.u64(version).u32(model.len() as u32);
+    writer.bytes.extend(model);
+    writer.finish()
+}
+
+fn decode_model(bytes: &[u8]) -> io::Result<(u64, Vec<u8>)> {
+    let mut reader = Reader::new(bytes);
+    let version = reader.u64()?;
+    let len = reader.u32()? as usize;
+    let model = reader.rest();
+    if model.len() != len {
+        return Err(invalid_data("Model length doesn't match its header"));
+    }
+
+    Ok((version, model.to_vec()))
+}
+
+#[derive(Default)]
+struct LearnerShared {
+    model: Option<(u64, Arc<Vec<u8>>)>,
+    workers: Vec<TcpStream>,
+}
+
+/// Receives rollouts from any number of `RolloutWorker`s and sends them new versions of the policy.
+///
+/// Every message is prefixed with its length as a little-endian `u32`.
+/// Both sides start with a `Handshake`, then a worker sends its id once after connecting, then a rollout whenever it has one.
+/// The learner sends the latest model to new workers and every published model to all workers
+pub struct Learner {
+    shared: Arc<Mutex<LearnerShared>>,
+    rollouts: mpsc::Receiver<Rollout>,
+    version: u64,
+    max_staleness: u64,
+    dropped: u64,
+}
+
+impl Learner {
+    /// Listen for workers on `addr`
+    pub fn bind(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let shared = Arc::new(Mutex::new(LearnerShared::default()));
+        let (sender, rollouts) = mpsc::channel();
+
+        let accept_shared = shared.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let shared = accept_shared.clone();
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    // a worker that misbehaves or disconnects only loses its own connection
+                    let _ = handle_worker(stream, &shared, &sender);
+                });
+            }
+        });
+
+        Ok(Self {
+            shared,
+            rollouts,
+            version: 0,
+            max_staleness: 0,
+            dropped: 0,
+        })
+    }
+
+    /// Accept rollouts collected with policies up to `max_staleness` versions older than the latest,
+    /// older rollouts are dropped. Defaults to 0, only accepting rollouts from the latest version
+    pub fn with_max_staleness(mut self, max_staleness: u64) -> Self {
+        self.max_staleness = max_staleness;
+        self
+    }
+
+    /// The latest published policy version
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// The number of rollouts that were dropped for being too stale
+    pub fn dropped_rollouts(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn num_workers(&self) -> usize {
+        self.shared.lock().unwrap().workers.len()
+    }
+
+    /// Send a new version of the policy to every worker, `version` must be higher than the previous one
+    pub fn publish(&mut self, version: u64, model: Vec<u8>) -> Result<()> {
+        if self.version != 0 && version <= self.version {
+            return Err(Error::Invalid(format!(
+                "Policy version {version} isn't higher than the last published version {}",
+                self.version
+            )));
+        }
+        self.version = version;
+
+        let message = encode_model(version, &model);
+        let mut shared = self.shared.lock().unwrap();
+        shared.model = Some((version, Arc::new(model)));
+        shared
+            .workers
+            .retain_mut(|stream| write_frame(stream, &message).is_ok());
+
+        Ok(())
+    }
+
+    fn accept(&mut self, rollout: &Rollout) -> bool {
+        let fresh = rollout.policy_version.saturating_add(self.max_staleness) >= self.version;
+        if !fresh {
+            self.dropped += 1;
+        }
+
+        fresh
+    }
+
+    /// Wait for the next rollout that isn't too stale,
+    /// `None` if `timeout` passes before one arrives
+    pub fn recv(&mut self, timeout: Duration) -> Option<Rollout> {
+        loop {
+            let rollout = self.rollouts.recv_timeout(timeout).ok()?;
+            if self.accept(&rollout) {
+                return Some(rollout);
+            }
+        }
+    }
+
+    /// All the rollouts that already arrived and aren't too stale
+    pub fn drain(&mut self) -> Vec<Rollout> {
+        let rollouts: Vec<_> = self.rollouts.try_iter().collect();
+        rollouts
+            .into_iter()
+            .filter(|rollout| self.accept(rollout))
+            .collect()
+    }
+}
+
+fn handle_worker(
+    mut stream: TcpStream,
+    shared: &Mutex<LearnerShared>,
+    sender: &mpsc::Sender<Rollout>,
+) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    Handshake::new(PROTOCOL_VERSION).exchange(&mut stream)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let worker_id = Reader::new(&read_frame(&mut reader, MAX_FRAME_LEN)?).str()?;
+
+    {
+        let mut shared = shared.lock().unwrap();
+        let mut writer = stream.try_clone()?;
+        if let Some((version, model)) = &shared.model {
+            write_frame(&mut writer, &encode_model(*version, model))?;
+        }
+        shared.workers.push(writer);
+    }
+
+    loop {
+        let rollout = Rollout::decode(&read_frame(&mut reader, MAX_FRAME_LEN)?)?;
+        if rollout.worker_id != worker_id {
+            return Err(invalid_data("Rollout is from a different worker"));
+        }
+
+        if sender.send(rollout).is_err() {
+            // the learner was dropped
+            return stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// What a `RolloutQueue` has for a worker when it's polled
+pub enum QueueUpdate {
+    /// No new policy since the last poll
+    Unchanged,
+    /// The newest policy published since the last poll
+    Policy { version: u64, model: Vec<u8> },
+    /// The learner is gone or asked the worker to stop
+    Stop,
+}
+
+/// How a `RolloutWorker` reaches its learner, e.g. a TCP connection to a `Learner` or a `RedisQueue`
+pub trait RolloutQueue {
+    /// Push the transitions collected with `policy_version` as one rollout
+    fn push_rollout(&mut self, policy_version: u64, transitions: Vec<Transition>) -> Result<()>;
+    fn poll(&mut self) -> Result<QueueUpdate>;
+}
+
+#[derive(Default)]
+struct Inbox {
+    model: Option<(u64, Vec<u8>)>,
+    closed: bool,
+}
+
+/// A TCP connection to a `Learner`
+struct TcpQueue {
+    worker_id: String,
+    stream: TcpStream,
+    inbox: Arc<Mutex<Inbox>>,
+}
+
+impl TcpQueue {
+    fn connect(addr: impl ToSocketAddrs, worker_id: String) -> Result<Self> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Handshake::new(PROTOCOL_VERSION).exchange(&mut stream)?;
+
+        let mut hello = Writer::default();
+        write_frame(&mut stream, &hello.str(&worker_id).finish())?;
+
+        let inbox = Arc::new(Mutex::new(Inbox::default()));
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let thread_inbox = inbox.clone();
+        thread::spawn(move || {
+            // only the latest model matters, so older ones that weren't picked up yet are overwritten
+            while let Ok(model) =
+                read_frame(&mut reader, MAX_FRAME_LEN).and_then(|bytes| decode_model(&bytes))
+            {
+                thread_inbox.lock().unwrap().model = Some(model);
+            }
+
+            thread_inbox.lock().unwrap().closed = true;
+        });
+
+        Ok(Self {
+            worker_id,
+            stream,
+            inbox,
+        })
+    }
+}
+
+impl RolloutQueue for TcpQueue {
+    fn push_rollout(&mut self, policy_version: u64, transitions: Vec<Transition>) -> Result<()> {
+        let rollout = Rollout {
+            worker_id: self.worker_id.clone(),
+            policy_version,
+            transitions,
+        };
+
+        Ok(write_frame(&mut self.stream, &rollout.encode())?)
+    }
+
+    fn poll(&mut self) -> Result<QueueUpdate> {
+        let mut inbox = self.inbox.lock().unwrap();
+        Ok(match inbox.model.take() {
+            Some((version, model)) => QueueUpdate::Policy { version, model },
+            None if inbox.closed => QueueUpdate::Stop,
+            None => QueueUpdate::Unchanged,
+        })
+    }
+}
+
+/// Steps an env with a locally cached copy of the learner's policy and pushes the rollouts to the learner.
+///
+/// New policy versions are picked up between rollouts,
+/// so every rollout is collected with exactly one version and tagged with it
+pub struct RolloutWorker {
+    queue: Box<dyn RolloutQueue>,
+    loader: Box<dyn RolloutPolicyLoader>,
+    policy: Option<(u64, Box<dyn RolloutPolicy>)>,
+    rollout_len: usize,
+}
+
+impl RolloutWorker {
+    pub fn new(queue: Box<dyn RolloutQueue>, loader: Box<dyn RolloutPolicyLoader>) -> Self {
+        Self {
+            queue,
+            loader,
+            policy: None,
+            rollout_len: 1024,
+        }
+    }
+
+    /// Connect to the `Learner` at `addr`
+    pub fn connect(
+        addr: impl ToSocketAddrs,
+        worker_id: impl Into<String>,
+        loader: Box<dyn RolloutPolicyLoader>,
+    ) -> Result<Self> {
+        let queue = TcpQueue::connect(addr, worker_id.into())?;
+        Ok(Self::new(Box::new(queue), loader))
+    }
+
+    /// The maximum number of transitions in a rollout, defaults to 1024.
+    /// Rollouts are also pushed at the end of every episode
+    pub fn with_rollout_len(mut self, rollout_len: usize) -> Self {
+        self.rollout_len = rollout_len.max(1);
+        self
+    }
+
+    /// The version of the policy currently in use, if one was received yet
+    pub fn policy_version(&self) -> Option<u64> {
+        self.policy.as_ref().map(|(version, _)| *version)
+    }
+
+    /// Load the newest model if there is one, returns false if the worker should stop
+    fn update_policy(&mut self) -> Result<bool> {
+        match self.queue.poll()? {
+            QueueUpdate::Unchanged => Ok(true),
+            QueueUpdate::Policy { version, model } => {
+                self.policy = Some((version, self.loader.load(&model)?));
+                Ok(true)
+            }
+            QueueUpdate::Stop => Ok(false),
+        }
+    }
+
+    /// Collect rollouts with `env` until the learner disconnects or asks the worker to stop
+    pub fn run(&mut self, env: &mut dyn RemoteEnv) -> Result<()> {
+        while self.policy.is_none() {
+            if !self.update_policy()? {
+                return Ok(());
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let mut transitions = Vec::with_capacity(self.rollout_len);
+        let mut obs = env.reset()?;
+
+        loop {
+            let (_, policy) = self.policy.as_mut().unwrap();
+            let actions = policy.act(&obs);
+            let step = env.step(actions.clone())?;
+
+            // the env is reset right away, but the last obs of the episode is kept for bootstrapping
+            let done = step.is_terminal || step.truncated;
+            let (next_obs, final_obs) = if done {
+                (env.reset()?, Some(step.obs))
+            } else {
+                (step.obs, None)
+            };
+            transitions.push(Transition {
+                obs,
+                actions,
+                rewards: step.rewards,
+                is_terminal: step.is_terminal,
+                truncated: step.truncated,
+                final_obs,
+            });
+            obs = next_obs;
+
+            if done || transitions.len() >= self.rollout_len {
+                let policy_version = self.policy_version().unwrap_or_default();
+                self.queue
+                    .push_rollout(policy_version, std::mem::take(&mut transitions))?;
+
+                if !self.update_policy()? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
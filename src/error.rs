@@ -0,0 +1,47 @@
+use std::io;
+use thiserror::Error;
+
+/// Everything that can go wrong in this crate
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Talking to RLViser failed
+    #[error("Render error: {0}")]
+    Render(#[source] io::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    /// RocketSim rejected a call, e.g. controls or a game state for a car that doesn't exist
+    #[error("Arena error: {0}")]
+    Arena(String),
+    /// `step` was called before the first `reset`
+    #[error("Must call reset() first")]
+    NotReset,
+    /// The action parser returned fewer controls than there are cars controlled by the trainer
+    #[error("Expected actions for {expected} cars, got {actual}")]
+    ActionCount { expected: usize, actual: usize },
+    /// The observation builder didn't return one observation per car
+    #[error("Expected observations for {expected} cars, got {actual}")]
+    ObsCount { expected: usize, actual: usize },
+    /// A config file couldn't be read or refers to something that doesn't exist
+    #[error("Config error: {0}")]
+    Config(String),
+    /// The components don't fit each other or the cars in the arena, found by `Env::validate`
+    #[error("Invalid env: {0}")]
+    Invalid(String),
+    /// The other side of a connection sent something unexpected or failed the handshake
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+}
+
+/// `Error` under a name that doesn't clash with other crates' errors in glob imports
+pub type RlgymError = Error;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Io(error) | Error::Render(error) => error,
+            error => io::Error::new(io::ErrorKind::Other, error),
+        }
+    }
+}
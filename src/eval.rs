@@ -0,0 +1,226 @@
+use crate::{
+    game_modes::scoring_team, rating::MatchOutcome, Action, AgentId, Env, FullObs, Obs, Result,
+    Reward, SharedInfoProvider, StateSetter, Terminal, Truncate,
+};
+use rocketsim_rs::sim::{GameMode, Team};
+use std::collections::BTreeMap;
+
+/// Mean, standard deviation and range of some values, all zero if there are none
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub mean: f32,
+    pub std: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Stats {
+    pub fn of(values: &[f32]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+
+        let len = values.len() as f32;
+        let mean = values.iter().sum::<f32>() / len;
+        let variance = values
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f32>()
+            / len;
+
+        Self {
+            mean,
+            std: variance.sqrt(),
+            min: values.iter().copied().fold(f32::INFINITY, f32::min),
+            max: values.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+        }
+    }
+}
+
+/// How an evaluation went, from the point of view of `EvalConfig::team`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EvalReport {
+    pub episodes: usize,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    pub goals_for: u32,
+    pub goals_against: u32,
+    /// Steps per episode
+    pub episode_length: Stats,
+    /// Every trainer-controlled agent's total reward per episode
+    pub episode_reward: Stats,
+    /// Like `episode_reward` for each component of a reward with a breakdown like `CombinedReward`,
+    /// in the order of `CombinedReward::names`
+    pub reward_components: Vec<Stats>,
+}
+
+impl EvalReport {
+    pub fn win_rate(&self) -> f32 {
+        if self.episodes == 0 {
+            return 0.;
+        }
+
+        self.wins as f32 / self.episodes as f32
+    }
+
+    pub fn log(&self) {
+        log::info!(
+            "{} episodes: {} wins, {} losses, {} draws ({:.1}% win rate), {} goals for, {} against, {:.1} steps and {:.3} reward per episode",
+            self.episodes,
+            self.wins,
+            self.losses,
+            self.draws,
+            self.win_rate() * 100.,
+            self.goals_for,
+            self.goals_against,
+            self.episode_length.mean,
+            self.episode_reward.mean,
+        );
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct EvalConfig {
+    pub episodes: usize,
+    /// Episodes that don't end by themselves are cut off after this many steps
+    pub max_steps: usize,
+    /// The team the report is from the point of view of
+    pub team: Team,
+    /// The game mode of the env, to know when a goal is scored
+    pub game_mode: GameMode,
+}
+
+impl EvalConfig {
+    /// Blue's point of view and at most 10 minutes per episode at 120 ticks per second and a tick skip of 8
+    pub fn new(episodes: usize) -> Self {
+        Self {
+            episodes,
+            max_steps: 9000,
+            team: Team::Blue,
+            game_mode: GameMode::SOCCAR,
+        }
+    }
+
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn with_team(mut self, team: Team) -> Self {
+        self.team = team;
+        self
+    }
+
+    /// Defaults to soccar
+    pub fn with_game_mode(mut self, game_mode: GameMode) -> Self {
+        self.game_mode = game_mode;
+        self
+    }
+}
+
+/// Play `config.episodes` episodes in `env` and report how they went.
+///
+/// `act` picks the actions of the cars controlled by the trainer from their observations,
+/// every other car is driven by the policies registered on the env, e.g. an `OnnxPolicy` opponent
+/// set with `Env::set_team_policy`. Rewards are only known for the cars driven by `act`.
+///
+/// Like `tournament::play_match`, goals are detected from the ball's position,
+/// so the terminal condition should end the episode on a goal
+pub fn evaluate<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI>(
+    env: &mut Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI>,
+    config: EvalConfig,
+    mut act: impl FnMut(&FullObs) -> ACT::Input,
+) -> Result<EvalReport>
+where
+    SS: StateSetter<SI>,
+    SIP: SharedInfoProvider<SI>,
+    OBS: Obs<SI>,
+    ACT: Action<SI>,
+    REW: Reward<SI>,
+    TERM: Terminal<SI>,
+    TRUNC: Truncate<SI>,
+{
+    let mut report = EvalReport {
+        episodes: config.episodes,
+        ..Default::default()
+    };
+    let mut lengths = Vec::with_capacity(config.episodes);
+    let mut returns = Vec::new();
+    let mut component_returns = Vec::<Vec<f32>>::new();
+
+    for _ in 0..config.episodes {
+        let mut obs = env.reset()?;
+        let mut goals_for = 0;
+        let mut goals_against = 0;
+        let mut was_scored = false;
+        let mut steps = 0;
+        let mut episode_returns = BTreeMap::<AgentId, (f32, Vec<f32>)>::new();
+
+        while steps < config.max_steps {
+            let result = env.step(act(&obs))?;
+            steps += 1;
+
+            for (index, (agent_id, reward)) in
+                result.agent_ids.iter().zip(&result.rewards).enumerate()
+            {
+                let (total, components) = episode_returns.entry(*agent_id).or_default();
+                *total += reward;
+
+                let breakdown = result
+                    .reward_breakdown
+                    .as_ref()
+                    .and_then(|breakdown| breakdown.get(index));
+                if let Some(breakdown) = breakdown {
+                    components.resize(breakdown.len(), 0.);
+                    for (component, value) in components.iter_mut().zip(breakdown) {
+                        *component += value;
+                    }
+                }
+            }
+
+            let scorer = scoring_team(config.game_mode, &result.state);
+            if let (Some(team), false) = (scorer, was_scored) {
+                if team == config.team {
+                    goals_for += 1;
+                } else {
+                    goals_against += 1;
+                }
+            }
+            was_scored = scorer.is_some();
+
+            if result.is_terminal || result.truncated {
+                break;
+            }
+            obs = result.obs;
+        }
+
+        match MatchOutcome::from_goals(goals_for, goals_against) {
+            MatchOutcome::Win => report.wins += 1,
+            MatchOutcome::Loss => report.losses += 1,
+            MatchOutcome::Draw => report.draws += 1,
+        }
+        report.goals_for += goals_for;
+        report.goals_against += goals_against;
+        lengths.push(steps as f32);
+
+        for (total, components) in episode_returns.into_values() {
+            returns.push(total);
+            if component_returns.len() < components.len() {
+                component_returns.resize_with(components.len(), Vec::new);
+            }
+            for (returns, value) in component_returns.iter_mut().zip(components) {
+                returns.push(value);
+            }
+        }
+    }
+
+    report.episode_length = Stats::of(&lengths);
+    report.episode_reward = Stats::of(&returns);
+    report.reward_components = component_returns
+        .iter()
+        .map(|returns| Stats::of(returns))
+        .collect();
+
+    Ok(report)
+}
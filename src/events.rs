@@ -0,0 +1,156 @@
+use crate::AgentId;
+use rocketsim_rs::{
+    cxx::UniquePtr,
+    glam_ext::GameStateA,
+    sim::{Arena, Team},
+};
+use std::{cell::RefCell, fmt, pin::Pin};
+
+/// Something that happened on one of the ticks of a step, including the ticks in between
+/// that rewards and terminals don't get to see the state of
+#[derive(Clone, Copy, PartialEq)]
+pub enum GameEvent {
+    Goal {
+        team: Team,
+        tick: u64,
+    },
+    /// Only the last touch of each car in the step is reported
+    Touch {
+        agent_id: AgentId,
+        tick: u64,
+    },
+    Bump {
+        bumper: AgentId,
+        victim: AgentId,
+        tick: u64,
+    },
+    Demo {
+        attacker: AgentId,
+        victim: AgentId,
+        tick: u64,
+    },
+}
+
+impl GameEvent {
+    pub fn tick(&self) -> u64 {
+        match self {
+            Self::Goal { tick, .. }
+            | Self::Touch { tick, .. }
+            | Self::Bump { tick, .. }
+            | Self::Demo { tick, .. } => *tick,
+        }
+    }
+}
+
+// written by hand since `Team` doesn't implement `Debug`
+impl fmt::Debug for GameEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Goal { team, tick } => f
+                .debug_struct("Goal")
+                .field(
+                    "team",
+                    &if *team == Team::Blue {
+                        "Blue"
+                    } else {
+                        "Orange"
+                    },
+                )
+                .field("tick", tick)
+                .finish(),
+            Self::Touch { agent_id, tick } => f
+                .debug_struct("Touch")
+                .field("agent_id", agent_id)
+                .field("tick", tick)
+                .finish(),
+            Self::Bump {
+                bumper,
+                victim,
+                tick,
+            } => f
+                .debug_struct("Bump")
+                .field("bumper", bumper)
+                .field("victim", victim)
+                .field("tick", tick)
+                .finish(),
+            Self::Demo {
+                attacker,
+                victim,
+                tick,
+            } => f
+                .debug_struct("Demo")
+                .field("attacker", attacker)
+                .field("victim", victim)
+                .field("tick", tick)
+                .finish(),
+        }
+    }
+}
+
+thread_local! {
+    // arenas step on the thread that called `Env::step`, which takes the events right after
+    static EVENTS: RefCell<Vec<GameEvent>> = RefCell::new(Vec::new());
+}
+
+fn push(event: GameEvent) {
+    EVENTS.with(|events| events.borrow_mut().push(event));
+}
+
+fn on_goal(arena: Pin<&mut Arena>, team: Team, _user_data: usize) {
+    let tick = arena.get_tick_count();
+    push(GameEvent::Goal { team, tick });
+}
+
+fn on_bump(arena: Pin<&mut Arena>, bumper: u32, victim: u32, is_demo: bool, _user_data: usize) {
+    let tick = arena.get_tick_count();
+    let (bumper, victim) = (AgentId(bumper), AgentId(victim));
+
+    push(if is_demo {
+        GameEvent::Demo {
+            attacker: bumper,
+            victim,
+            tick,
+        }
+    } else {
+        GameEvent::Bump {
+            bumper,
+            victim,
+            tick,
+        }
+    });
+}
+
+/// Replaces the arena's goal and bump callbacks with ones that record events
+pub(crate) fn install(arena: &mut UniquePtr<Arena>) {
+    arena.pin_mut().set_goal_scored_callback(on_goal, 0);
+    arena.pin_mut().set_car_bump_callback(on_bump, 0);
+}
+
+pub(crate) fn clear() {
+    EVENTS.with(|events| events.borrow_mut().clear());
+}
+
+/// The events recorded since `clear`, with the touches since `last_tick` found from the cars' ball hit info
+pub(crate) fn take(state: &GameStateA, last_tick: u64) -> Vec<GameEvent> {
+    let mut events = EVENTS.with(|events| events.take());
+
+    events.extend(
+        state
+            .cars
+            .iter()
+            .filter(|car| {
+                let hit = &car.state.ball_hit_info;
+                // hits from before a reset of the tick count are in the future
+                hit.is_valid
+                    && hit.tick_count_when_hit > last_tick
+                    && hit.tick_count_when_hit <= state.tick_count
+            })
+            .map(|car| GameEvent::Touch {
+                agent_id: AgentId::of(car),
+                tick: car.state.ball_hit_info.tick_count_when_hit,
+            }),
+    );
+    events.sort_by_key(GameEvent::tick);
+
+    events
+}
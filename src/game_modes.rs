@@ -0,0 +1,75 @@
+use crate::common_values::{BALL_RADIUS, GOAL_THRESHOLD};
+use rocketsim_rs::{
+    cxx::UniquePtr,
+    glam_ext::GameStateA,
+    sim::{Arena, ArenaConfig, CarConfig, GameMode, Team},
+};
+
+/// The ball scores in a hoop once its center drops below this height inside the rim
+pub const HOOPS_GOAL_SCORE_THRESHOLD_Z: f32 = 270.;
+/// How far the center of each hoop is from the center of the field
+pub const HOOPS_RIM_OFFSET_Y: f32 = 2770.;
+pub const HOOPS_RIM_RADIUS: f32 = 716.;
+/// The rims are ellipses, squashed along y by this much
+const HOOPS_RIM_SCALE_Y: f32 = 0.9;
+
+/// The ball has broken through the floor once its center is this far below it
+pub const DROPSHOT_GOAL_THRESHOLD_Z: f32 = -BALL_RADIUS * 1.75;
+
+pub const SOCCAR_BOOST_PADS: usize = 34;
+pub const HOOPS_BOOST_PADS: usize = 20;
+
+/// Create an arena for `game_mode` with `blue` and `orange` octanes.
+///
+/// `rocketsim_rs::init` must have been called first
+pub fn build_arena(game_mode: GameMode, blue: usize, orange: usize) -> UniquePtr<Arena> {
+    let mut arena = Arena::new(game_mode, ArenaConfig::default(), 120);
+
+    for (team, size) in [(Team::Blue, blue), (Team::Orange, orange)] {
+        for _ in 0..size {
+            let _ = arena.pin_mut().add_car(team, CarConfig::octane());
+        }
+    }
+
+    arena
+}
+
+/// How many boost pads an arena of `game_mode` has, e.g. for `DefaultObs::with_num_pads`
+pub fn num_boost_pads(game_mode: GameMode) -> usize {
+    if game_mode == GameMode::HOOPS {
+        HOOPS_BOOST_PADS
+    } else if game_mode == GameMode::SOCCAR
+        || game_mode == GameMode::HEATSEEKER
+        || game_mode == GameMode::SNOWDAY
+    {
+        SOCCAR_BOOST_PADS
+    } else {
+        0
+    }
+}
+
+/// The team that scored in a game of `game_mode`, if the ball is in either goal.
+///
+/// Game modes without goals never score
+pub fn scoring_team(game_mode: GameMode, state: &GameStateA) -> Option<Team> {
+    let ball = state.ball.pos;
+    // the ball is in orange's half when y is positive, so blue is the one scoring there
+    let scorer = if ball.y > 0. {
+        Team::Blue
+    } else {
+        Team::Orange
+    };
+
+    if game_mode == GameMode::HOOPS {
+        let rim_y = ball.y.abs() * HOOPS_RIM_SCALE_Y - HOOPS_RIM_OFFSET_Y;
+        let in_rim = ball.x * ball.x + rim_y * rim_y < HOOPS_RIM_RADIUS * HOOPS_RIM_RADIUS;
+
+        (ball.z < HOOPS_GOAL_SCORE_THRESHOLD_Z && in_rim).then_some(scorer)
+    } else if game_mode == GameMode::DROPSHOT {
+        (ball.z < DROPSHOT_GOAL_THRESHOLD_Z).then_some(scorer)
+    } else if game_mode == GameMode::THE_VOID {
+        None
+    } else {
+        (ball.y.abs() > GOAL_THRESHOLD).then_some(scorer)
+    }
+}
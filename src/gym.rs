@@ -0,0 +1,198 @@
+use crate::{
+    Action, Env, FullObs, Obs, Result, Reward, RewardValue, Shared, SharedInfoProvider,
+    StateSetter, Terminal, Truncate,
+};
+use std::collections::BTreeMap;
+
+/// A value in a Gymnasium `info` map
+#[derive(Clone, Debug, PartialEq)]
+pub enum InfoValue {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Str(String),
+    Ints(Vec<i64>),
+}
+
+impl From<bool> for InfoValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i64> for InfoValue {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<u64> for InfoValue {
+    fn from(value: u64) -> Self {
+        Self::UInt(value)
+    }
+}
+
+impl From<f64> for InfoValue {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+impl From<f32> for InfoValue {
+    fn from(value: f32) -> Self {
+        Self::Float(f64::from(value))
+    }
+}
+
+impl From<String> for InfoValue {
+    fn from(value: String) -> Self {
+        Self::Str(value)
+    }
+}
+
+impl From<&str> for InfoValue {
+    fn from(value: &str) -> Self {
+        Self::Str(value.to_string())
+    }
+}
+
+impl From<Vec<i64>> for InfoValue {
+    fn from(value: Vec<i64>) -> Self {
+        Self::Ints(value)
+    }
+}
+
+/// Extra information about a reset or step, like Gymnasium's `info` dict
+pub type Info = BTreeMap<String, InfoValue>;
+
+/// `(obs, rewards, terminated, truncated, info)`
+pub type GymStep<R = f32> = (Shared<FullObs>, Vec<R>, bool, bool, Info);
+
+type InfoFn<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R> =
+    Box<dyn FnMut(&Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R>, &mut Info)>;
+
+/// Wraps an `Env` with the Gymnasium API: `reset` returns `(obs, info)`
+/// and `step` returns `(obs, rewards, terminated, truncated, info)`.
+///
+/// The info always has `agent_ids`, `episode_steps`, `total_steps`, `episodes_completed`,
+/// and `seed` and `opponent_id` when they're known. Add more with `with_info`
+pub struct GymEnv<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R = f32>
+where
+    SS: StateSetter<SI>,
+    SIP: SharedInfoProvider<SI>,
+    OBS: Obs<SI>,
+    ACT: Action<SI>,
+    REW: Reward<SI, R>,
+    TERM: Terminal<SI>,
+    TRUNC: Truncate<SI>,
+{
+    env: Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R>,
+    info_fns: Vec<InfoFn<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R>>,
+}
+
+impl<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R> GymEnv<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R>
+where
+    SS: StateSetter<SI>,
+    SIP: SharedInfoProvider<SI>,
+    OBS: Obs<SI>,
+    ACT: Action<SI>,
+    REW: Reward<SI, R>,
+    TERM: Terminal<SI>,
+    TRUNC: Truncate<SI>,
+    R: RewardValue,
+{
+    pub fn new(env: Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R>) -> Self {
+        Self {
+            env,
+            info_fns: Vec::new(),
+        }
+    }
+
+    /// Add entries to the info after every reset and step, e.g. values kept in the shared info
+    pub fn with_info(
+        mut self,
+        info_fn: impl FnMut(&Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R>, &mut Info) + 'static,
+    ) -> Self {
+        self.info_fns.push(Box::new(info_fn));
+        self
+    }
+
+    pub fn env(&self) -> &Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R> {
+        &self.env
+    }
+
+    pub fn env_mut(&mut self) -> &mut Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R> {
+        &mut self.env
+    }
+
+    pub fn into_inner(self) -> Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R> {
+        self.env
+    }
+
+    /// Start a new episode, with a specific seed to replay an earlier one
+    pub fn reset(&mut self, seed: Option<u64>) -> Result<(Shared<FullObs>, Info)> {
+        let obs = match seed {
+            Some(seed) => self.env.reset_with(seed)?,
+            None => self.env.reset()?,
+        };
+
+        Ok((obs, self.info()))
+    }
+
+    pub fn step(&mut self, actions: ACT::Input) -> Result<GymStep<R>> {
+        let result = self.env.step(actions)?;
+
+        Ok((
+            result.obs,
+            result.rewards,
+            result.is_terminal,
+            result.truncated,
+            self.info(),
+        ))
+    }
+
+    fn info(&mut self) -> Info {
+        let mut info = Info::new();
+
+        let agent_ids = self.env.external_agent_ids();
+        info.insert(
+            String::from("agent_ids"),
+            InfoValue::from(
+                agent_ids
+                    .into_iter()
+                    .map(|id| i64::from(id.0))
+                    .collect::<Vec<_>>(),
+            ),
+        );
+        info.insert(
+            String::from("episode_steps"),
+            InfoValue::from(self.env.episode_step_count()),
+        );
+        info.insert(
+            String::from("total_steps"),
+            InfoValue::from(self.env.total_step_count()),
+        );
+        info.insert(
+            String::from("episodes_completed"),
+            InfoValue::from(self.env.episodes_completed()),
+        );
+
+        let metadata = self.env.episode_metadata();
+        if let Some(seed) = metadata.seed {
+            info.insert(String::from("seed"), InfoValue::from(seed));
+        }
+        if let Some(opponent_id) = &metadata.opponent_id {
+            info.insert(
+                String::from("opponent_id"),
+                InfoValue::from(opponent_id.as_str()),
+            );
+        }
+
+        for info_fn in &mut self.info_fns {
+            info_fn(&self.env, &mut info);
+        }
+
+        info
+    }
+}
@@ -0,0 +1,131 @@
+use crate::wire::{invalid_data, read_frame, write_frame, Reader, Writer};
+use std::io::{self, Read, Write};
+
+/// The version of rocketsim_rs this crate is built against, keep in sync with `Cargo.toml`
+pub const ROCKETSIM_VERSION: &str = "0.33";
+
+/// A handshake is a few short strings and numbers, anything longer isn't one
+const MAX_HANDSHAKE_LEN: usize = 4096;
+
+/// What each side of a connection tells the other before anything else is sent.
+///
+/// A different protocol version or config hash is rejected because the messages that follow would be misread,
+/// different crate or RocketSim versions only cause a warning.
+///
+/// The RLViser socket is the one protocol without it, since RLViser only speaks its own UDP packets.
+/// Its connection packets carry just a protocol version that older RLViser builds ignore,
+/// and game states of the wrong size are reported with the RocketSim version they're expected from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Handshake {
+    pub protocol_version: u32,
+    pub crate_version: String,
+    pub rocketsim_version: String,
+    /// Hash of the observation and action spaces, see `config_hash`.
+    /// `None` for sides that don't know them, like a learner before its first worker connects
+    pub config_hash: Option<u64>,
+}
+
+impl Handshake {
+    pub fn new(protocol_version: u32) -> Self {
+        Self {
+            protocol_version,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            rocketsim_version: ROCKETSIM_VERSION.to_string(),
+            config_hash: None,
+        }
+    }
+
+    pub fn with_config_hash(mut self, config_hash: u64) -> Self {
+        self.config_hash = Some(config_hash);
+        self
+    }
+
+    /// Compare with the other side's handshake, returning the warnings or why the connection should be rejected
+    pub fn check(&self, remote: &Self) -> Result<Vec<String>, String> {
+        if self.protocol_version != remote.protocol_version {
+            return Err(format!(
+                "Protocol version {} doesn't match the other side's {}",
+                self.protocol_version, remote.protocol_version
+            ));
+        }
+
+        if let (Some(local), Some(remote)) = (self.config_hash, remote.config_hash) {
+            if local != remote {
+                return Err(format!(
+                    "Observation and action config hash {local:016x} doesn't match the other side's {remote:016x}"
+                ));
+            }
+        }
+
+        let mut warnings = Vec::new();
+        if self.crate_version != remote.crate_version {
+            warnings.push(format!(
+                "rlgym_rs {} is talking to rlgym_rs {}",
+                self.crate_version, remote.crate_version
+            ));
+        }
+
+        if self.rocketsim_version != remote.rocketsim_version {
+            warnings.push(format!(
+                "RocketSim {} is talking to RocketSim {}, physics may differ",
+                self.rocketsim_version, remote.rocketsim_version
+            ));
+        }
+
+        Ok(warnings)
+    }
+
+    pub(crate) fn write(&self, writer: &mut Writer) {
+        writer
+            .u32(self.protocol_version)
+            .str(&self.crate_version)
+            .str(&self.rocketsim_version)
+            .bool(self.config_hash.is_some())
+            .u64(self.config_hash.unwrap_or_default());
+    }
+
+    pub(crate) fn read(reader: &mut Reader) -> io::Result<Self> {
+        let protocol_version = reader.u32()?;
+        let crate_version = reader.str()?;
+        let rocketsim_version = reader.str()?;
+        let has_config_hash = reader.bool()?;
+        let config_hash = reader.u64()?;
+
+        Ok(Self {
+            protocol_version,
+            crate_version,
+            rocketsim_version,
+            config_hash: has_config_hash.then_some(config_hash),
+        })
+    }
+
+    /// Send this handshake as a length-prefixed frame, then read and check the other side's
+    pub(crate) fn exchange(&self, stream: &mut (impl Read + Write)) -> io::Result<Self> {
+        let mut writer = Writer::default();
+        self.write(&mut writer);
+        write_frame(stream, &writer.finish())?;
+
+        let remote = Self::read(&mut Reader::new(&read_frame(stream, MAX_HANDSHAKE_LEN)?))?;
+        for warning in self.check(&remote).map_err(invalid_data)? {
+            log::warn!("{warning}");
+        }
+
+        Ok(remote)
+    }
+}
+
+/// A stable FNV-1a hash of the per-agent observation and action sizes,
+/// so that both sides of a connection can tell if they'd lay out their messages the same way
+pub fn config_hash(obs_spaces: &[usize], action_spaces: &[usize]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    let lens = [obs_spaces.len(), action_spaces.len()];
+
+    for value in lens.iter().chain(obs_spaces).chain(action_spaces) {
+        for byte in (*value as u64).to_le_bytes() {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    hash
+}
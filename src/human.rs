@@ -0,0 +1,99 @@
+use crate::{AgentId, MaybeSend, Policy, Result};
+use gilrs::{Axis, Button, GamepadId, Gilrs};
+use rocketsim_rs::{glam_ext::GameStateA, sim::CarControls};
+use std::io;
+
+/// Drives a car with a gamepad, e.g. to play against a bot with `play::PlayMode`. There's no keyboard support.
+///
+/// Uses Rocket League's default bindings: the triggers to drive, the left stick to steer and aim,
+/// A to jump, B to boost, X to powerslide and LB to air roll with the left stick.
+/// Releases every input while no gamepad is connected
+pub struct HumanAgent {
+    gilrs: Gilrs,
+    gamepad: Option<GamepadId>,
+    /// Stick values closer to zero than this are ignored
+    pub deadzone: f32,
+}
+
+impl HumanAgent {
+    /// Read the first gamepad that gets used, or the first connected one
+    pub fn new() -> Result<Self> {
+        let gilrs =
+            Gilrs::new().map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+
+        Ok(Self {
+            gilrs,
+            gamepad: None,
+            deadzone: 0.1,
+        })
+    }
+
+    /// Only read `gamepad`, e.g. one listed by `gilrs` for a second human
+    pub fn with_gamepad(mut self, gamepad: GamepadId) -> Self {
+        self.gamepad = Some(gamepad);
+        self
+    }
+
+    pub fn with_deadzone(mut self, deadzone: f32) -> Self {
+        self.deadzone = deadzone;
+        self
+    }
+
+    /// Take in every input since the last call and return what the gamepad is holding now
+    pub fn controls(&mut self) -> CarControls {
+        let mut last_used = None;
+        while let Some(event) = self.gilrs.next_event() {
+            last_used = Some(event.id);
+        }
+        if self.gamepad.is_none() {
+            self.gamepad = last_used.or_else(|| self.gilrs.gamepads().next().map(|(id, _)| id));
+        }
+
+        let Some(gamepad) = self.gamepad.and_then(|id| self.gilrs.connected_gamepad(id)) else {
+            return CarControls::default();
+        };
+
+        let stick = |axis| {
+            let value = gamepad.value(axis);
+            if value.abs() < self.deadzone {
+                0.
+            } else {
+                value.clamp(-1., 1.)
+            }
+        };
+        let trigger = |button| gamepad.button_data(button).map_or(0., |data| data.value());
+
+        let x = stick(Axis::LeftStickX);
+        let air_roll = gamepad.is_pressed(Button::LeftTrigger);
+
+        CarControls {
+            throttle: (trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2))
+                .clamp(-1., 1.),
+            steer: x,
+            // pushing the stick forward tips the nose down
+            pitch: -stick(Axis::LeftStickY),
+            yaw: if air_roll { 0. } else { x },
+            roll: if air_roll { x } else { 0. },
+            jump: gamepad.is_pressed(Button::South),
+            boost: gamepad.is_pressed(Button::East),
+            handbrake: gamepad.is_pressed(Button::West),
+        }
+    }
+}
+
+impl<SI> Policy<SI> for HumanAgent
+where
+    Self: MaybeSend,
+{
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn get_controls(
+        &mut self,
+        _agent_id: AgentId,
+        _obs: &[f32],
+        _state: &GameStateA,
+        _shared_info: &mut SI,
+    ) -> CarControls {
+        self.controls()
+    }
+}
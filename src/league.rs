@@ -0,0 +1,253 @@
+use crate::{
+    opponent_pool::{weighted_index, Checkpoint, OpponentPool},
+    rating::{Elo, MatchOutcome},
+    Error, Result,
+};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+/// A league shared by the envs of a `VecEnv` or `EnvPool`, see `PastSelfOpponents::with_league`
+pub type SharedLeague = Arc<Mutex<LeagueScheduler>>;
+
+/// Who the main agent plays against in one environment
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Matchup {
+    /// Against a copy of itself
+    SelfPlay,
+    /// Against the exploiter at this index
+    Exploiter(usize),
+    /// Against the past checkpoint with this id
+    PastSelf(String),
+}
+
+/// Fraction of environments given to each kind of matchup, they don't need to add up to 1
+#[derive(Clone, Copy, Debug)]
+pub struct LeagueProportions {
+    pub self_play: f32,
+    pub exploiters: f32,
+    pub past_selves: f32,
+}
+
+impl Default for LeagueProportions {
+    fn default() -> Self {
+        Self {
+            self_play: 0.35,
+            exploiters: 0.15,
+            past_selves: 0.5,
+        }
+    }
+}
+
+/// An agent trained specifically to find weaknesses in the main agent
+#[derive(Clone, Debug)]
+pub struct Exploiter {
+    /// Also the id of the exploiter's checkpoint, so it shouldn't match the id of a past self
+    pub name: String,
+    /// Where its weights are stored, loaded like a past self's checkpoint
+    pub path: PathBuf,
+    pub rating: f32,
+    pub games_played: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MatchupKind {
+    SelfPlay,
+    Exploiter,
+    PastSelf,
+}
+
+/// Assigns matchups across a set of environments in configurable proportions.
+///
+/// Each environment keeps its kind of matchup so the proportions stay stable,
+/// but a new opponent is picked every time a result is recorded
+/// so that stronger opponents are played more often as ratings change.
+///
+/// To run a league over the envs of a `VecEnv` or `EnvPool`, share it as a `SharedLeague`
+/// and give each env's `PastSelfOpponents` its index with `with_league`
+pub struct LeagueScheduler {
+    proportions: LeagueProportions,
+    pub past_selves: OpponentPool,
+    exploiters: Vec<Exploiter>,
+    kinds: Vec<MatchupKind>,
+    assignments: Vec<Matchup>,
+    rng: fastrand::Rng,
+}
+
+impl LeagueScheduler {
+    pub fn new(proportions: LeagueProportions, past_selves: OpponentPool) -> Self {
+        Self {
+            proportions,
+            past_selves,
+            exploiters: Vec::new(),
+            kinds: Vec::new(),
+            assignments: Vec::new(),
+            rng: fastrand::Rng::new(),
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = fastrand::Rng::with_seed(seed);
+        self
+    }
+
+    /// Returns the index of the new exploiter
+    pub fn add_exploiter(&mut self, name: impl Into<String>, path: impl Into<PathBuf>) -> usize {
+        self.exploiters.push(Exploiter {
+            name: name.into(),
+            path: path.into(),
+            rating: self.past_selves.main_rating(),
+            games_played: 0,
+        });
+
+        self.exploiters.len() - 1
+    }
+
+    pub fn exploiters(&self) -> &[Exploiter] {
+        &self.exploiters
+    }
+
+    pub fn assignments(&self) -> &[Matchup] {
+        &self.assignments
+    }
+
+    pub fn matchup(&self, env_index: usize) -> Option<&Matchup> {
+        self.assignments.get(env_index)
+    }
+
+    /// The checkpoint to load for the current matchup of `env_index`, `None` for self-play
+    pub fn opponent(&self, env_index: usize) -> Result<Option<Checkpoint>> {
+        match self.assigned(env_index)? {
+            Matchup::SelfPlay => Ok(None),
+            Matchup::Exploiter(index) => {
+                let exploiter = &self.exploiters[*index];
+                Ok(Some(Checkpoint {
+                    id: exploiter.name.clone(),
+                    path: exploiter.path.clone(),
+                    rating: exploiter.rating,
+                    games_played: exploiter.games_played,
+                    wins: 0,
+                    losses: 0,
+                    draws: 0,
+                }))
+            }
+            Matchup::PastSelf(id) => self.past_selves.get(id).cloned().map(Some).ok_or_else(|| {
+                Error::Invalid(format!("Checkpoint {id} was removed from the pool"))
+            }),
+        }
+    }
+
+    fn assigned(&self, env_index: usize) -> Result<&Matchup> {
+        self.assignments.get(env_index).ok_or_else(|| {
+            Error::Invalid(format!(
+                "Env {env_index} has no matchup, {} were assigned",
+                self.assignments.len()
+            ))
+        })
+    }
+
+    /// Split `num_envs` environments between the kinds of matchups and pick an opponent for each
+    pub fn assign(&mut self, num_envs: usize) -> &[Matchup] {
+        let mut weights = [
+            (MatchupKind::SelfPlay, self.proportions.self_play),
+            (MatchupKind::Exploiter, self.proportions.exploiters),
+            (MatchupKind::PastSelf, self.proportions.past_selves),
+        ];
+
+        // there's nobody to play against for these
+        for (kind, weight) in &mut weights {
+            if (*kind == MatchupKind::Exploiter && self.exploiters.is_empty())
+                || (*kind == MatchupKind::PastSelf && self.past_selves.is_empty())
+            {
+                *weight = 0.;
+            }
+        }
+
+        self.kinds = distribute(num_envs, &weights);
+        let assignments = (0..num_envs).map(|i| self.pick(self.kinds[i])).collect();
+        self.assignments = assignments;
+
+        &self.assignments
+    }
+
+    /// Re-split the environments, e.g. after adding exploiters or past selves
+    pub fn rebalance(&mut self) -> &[Matchup] {
+        self.assign(self.assignments.len())
+    }
+
+    /// Update ratings with the outcome of the episode in `env_index`,
+    /// `outcome` is from the point of view of the main agent.
+    ///
+    /// Returns the next matchup for that environment
+    pub fn record_result(&mut self, env_index: usize, outcome: MatchOutcome) -> Result<&Matchup> {
+        match self.assigned(env_index)?.clone() {
+            Matchup::SelfPlay => {}
+            Matchup::Exploiter(index) => {
+                let exploiter = &mut self.exploiters[index];
+                self.past_selves
+                    .rate_against_main(&mut exploiter.rating, outcome);
+                exploiter.games_played += 1;
+            }
+            Matchup::PastSelf(id) => {
+                self.past_selves.record_result(&id, outcome);
+            }
+        }
+
+        let next = self.pick(self.kinds[env_index]);
+        self.assignments[env_index] = next;
+        Ok(&self.assignments[env_index])
+    }
+
+    fn pick(&mut self, kind: MatchupKind) -> Matchup {
+        match kind {
+            MatchupKind::SelfPlay => Matchup::SelfPlay,
+            MatchupKind::Exploiter => {
+                let main_rating = self.past_selves.main_rating();
+                let weights = self
+                    .exploiters
+                    .iter()
+                    .map(|exploiter| Elo::expected_score(exploiter.rating, main_rating))
+                    .collect::<Vec<_>>();
+                Matchup::Exploiter(weighted_index(&mut self.rng, &weights))
+            }
+            MatchupKind::PastSelf => match self.past_selves.sample() {
+                Some(checkpoint) => Matchup::PastSelf(checkpoint.id.clone()),
+                None => Matchup::SelfPlay,
+            },
+        }
+    }
+}
+
+/// Split `total` into whole counts proportional to `weights` using the largest remainder method
+fn distribute(total: usize, weights: &[(MatchupKind, f32)]) -> Vec<MatchupKind> {
+    let weight_sum = weights.iter().map(|(_, weight)| weight).sum::<f32>();
+    if weight_sum <= 0. {
+        return vec![MatchupKind::SelfPlay; total];
+    }
+
+    let exact = weights
+        .iter()
+        .map(|(_, weight)| weight / weight_sum * total as f32)
+        .collect::<Vec<_>>();
+    let mut counts = exact.iter().map(|x| x.floor() as usize).collect::<Vec<_>>();
+
+    let mut by_remainder = (0..weights.len()).collect::<Vec<_>>();
+    by_remainder
+        .sort_by(|&a, &b| (exact[b] - exact[b].floor()).total_cmp(&(exact[a] - exact[a].floor())));
+
+    let assigned = counts.iter().sum::<usize>();
+    for &i in by_remainder
+        .iter()
+        .cycle()
+        .take(total.saturating_sub(assigned))
+    {
+        counts[i] += 1;
+    }
+
+    weights
+        .iter()
+        .zip(counts)
+        .flat_map(|((kind, _), count)| std::iter::repeat(*kind).take(count))
+        .collect()
+}
@@ -1,36 +1,214 @@
-use render::RLViserSocketHandler;
+pub use action_history::ActionHistory;
+pub use agent::{AgentId, AgentMap};
+pub use context::StepContext;
+use curriculum::{Curriculum, CurriculumProgress};
+pub use debug_draw::DebugDraw;
+use diff::DiffReport;
+pub use error::{Error, Result, RlgymError};
+pub use events::GameEvent;
+use metrics::Metrics;
+pub use mutators::Mutators;
+use opponent_pool::PastSelfOpponents;
+pub use policy::{share_policy, Policy, SharedPolicy};
+pub use realtime::RealtimeClock;
+use recording::TrajectoryRecorder;
+#[cfg(feature = "rlviser")]
+pub use render::{LaunchMode, RLViserSocketHandler, RenderConfig};
+pub use render_thread::ThreadedRenderer;
+pub use renderer::{RenderErrorPolicy, Renderer};
 pub use rocketsim_rs;
+pub use space::SpaceType;
+use watchdog::{Component, Watchdog};
 
+mod action_history;
+pub mod actions;
+pub mod agent;
+#[cfg(feature = "ndarray")]
+pub mod array;
+pub mod ball_prediction;
+pub mod bots;
+pub mod common_values;
+pub mod conditions;
+#[cfg(feature = "config")]
+pub mod config;
+mod context;
+pub mod curriculum;
+mod debug_draw;
+#[cfg(feature = "http")]
+pub mod debug_server;
+pub mod demonstrations;
+pub mod diff;
+pub mod distributed;
+mod error;
+pub mod eval;
+mod events;
+pub mod game_modes;
+pub mod gym;
+pub mod handshake;
+#[cfg(feature = "gilrs")]
+pub mod human;
+pub mod league;
+pub mod metrics;
+mod mutators;
+pub mod obs;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+pub mod opponent_pool;
+#[cfg(feature = "rlviser")]
+pub mod play;
+mod policy;
+/// Everything needed to write components and run an `Env`, `use rlgym_rs::prelude::*;`
+pub mod prelude;
+#[cfg(feature = "proptest")]
+pub mod properties;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod rating;
+mod realtime;
+pub mod recording;
+#[cfg(feature = "redis")]
+pub mod redis_queue;
+#[cfg(feature = "relay")]
+pub mod relay;
+pub mod remote;
+#[cfg(feature = "rlviser")]
 mod render;
+mod render_thread;
+mod renderer;
+pub mod rewards;
+#[cfg(feature = "rlbot")]
+pub mod rlbot;
+#[cfg(feature = "grpc")]
+pub mod serve;
+#[cfg(feature = "shm")]
+pub mod shm;
+pub mod shutdown;
+mod space;
+pub mod state_setters;
+pub mod supervisor;
+pub mod symmetry;
+pub mod tcp;
+pub mod teams;
+pub mod test_utils;
+pub mod tournament;
+pub mod vec_env;
+pub mod watchdog;
+mod wire;
+#[cfg(feature = "zmq")]
+pub mod zmq_worker;
 
+use glam::Vec3A;
 use rocketsim_rs::{
     cxx::UniquePtr,
-    glam_ext::GameStateA,
-    sim::{Arena, CarControls},
+    glam_ext::{CarInfoA, GameStateA},
+    render::Color,
+    sim::{Arena, CarConfig, CarControls, Team},
+    GameState,
+};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    time::{Duration, Instant},
 };
-use std::{io, rc::Rc, time::Duration};
 
 pub type FullObs = Vec<Vec<f32>>;
 
-pub struct StepResult {
-    pub obs: Rc<FullObs>,
-    pub rewards: Vec<f32>,
+/// How step results share states and observations: `Rc`, or `Arc` with the `sync` feature
+/// so results can be sent to other threads
+#[cfg(not(feature = "sync"))]
+pub type Shared<T> = std::rc::Rc<T>;
+#[cfg(feature = "sync")]
+pub type Shared<T> = std::sync::Arc<T>;
+
+/// `Send` with the `sync` feature and implemented by everything otherwise.
+///
+/// Policies and renderers need it so an `Env` holding them is `Send` when its components are
+#[cfg(feature = "sync")]
+pub trait MaybeSend: Send {}
+#[cfg(feature = "sync")]
+impl<T: Send + ?Sized> MaybeSend for T {}
+#[cfg(not(feature = "sync"))]
+pub trait MaybeSend {}
+#[cfg(not(feature = "sync"))]
+impl<T: ?Sized> MaybeSend for T {}
+
+/// Information about the current episode that isn't part of the game state
+#[derive(Clone, Debug, Default)]
+pub struct EpisodeMetadata {
+    /// Id of the checkpoint driving the opponents, if past-self opponents are enabled
+    pub opponent_id: Option<String>,
+    /// Seed the state setter was given for this episode, pass it to `Env::reset_with` to play the episode again.
+    ///
+    /// `None` if the episode started from `Env::reset_to`
+    pub seed: Option<u64>,
+}
+
+/// `obs` and `rewards` line up with `agent_ids`, use `obs_by_agent` and `rewards_by_agent`
+/// to look them up by agent instead of by position when cars can be added or removed
+pub struct StepResult<R = f32> {
+    pub obs: Shared<FullObs>,
+    pub rewards: Vec<R>,
     pub is_terminal: bool,
     pub truncated: bool,
-    pub state: Rc<GameStateA>,
+    pub state: Shared<GameStateA>,
+    /// The agents controlled by the trainer in the order of `obs` and `rewards`
+    pub agent_ids: Vec<AgentId>,
+    /// Everything that happened during the step's ticks in order, empty unless `Env::with_event_capture` was used
+    pub events: Vec<GameEvent>,
+    /// With `Env::with_auto_reset`, the last observation of the episode that just ended,
+    /// `obs` is then the first observation of the next one while `state` and `rewards` are still from the old one
+    pub final_obs: Option<Shared<FullObs>>,
+    /// With a reward made of several components like `CombinedReward`,
+    /// how much each of them contributed to every agent's reward, in the order of `rewards`
+    pub reward_breakdown: Option<Vec<Vec<R>>>,
 }
 
-pub struct Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI>
+impl<R: Copy> StepResult<R> {
+    /// Every agent's reward, iterating in order of agent id
+    pub fn rewards_by_agent(&self) -> BTreeMap<AgentId, R> {
+        self.agent_ids
+            .iter()
+            .copied()
+            .zip(self.rewards.iter().copied())
+            .collect()
+    }
+
+    /// The observations as one agents × obs array, see `array::obs_array`
+    #[cfg(feature = "ndarray")]
+    pub fn obs_array(&self) -> Result<ndarray::Array2<f32>> {
+        array::obs_array(&self.obs)
+    }
+
+    /// Every agent's observation, iterating in order of agent id
+    pub fn obs_by_agent(&self) -> BTreeMap<AgentId, &[f32]> {
+        self.agent_ids
+            .iter()
+            .copied()
+            .zip(self.obs.iter().map(Vec::as_slice))
+            .collect()
+    }
+}
+
+#[cfg(feature = "sync")]
+type StateSetHook = Box<dyn FnMut(&mut GameState) -> bool + Send>;
+#[cfg(not(feature = "sync"))]
+type StateSetHook = Box<dyn FnMut(&mut GameState) -> bool>;
+
+/// `R` is the reward of a single agent, see `RewardValue`
+pub struct Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R = f32>
 where
     SS: StateSetter<SI>,
     SIP: SharedInfoProvider<SI>,
     OBS: Obs<SI>,
     ACT: Action<SI>,
-    REW: Reward<SI>,
+    REW: Reward<SI, R>,
     TERM: Terminal<SI>,
     TRUNC: Truncate<SI>,
 {
-    arena: UniquePtr<Arena>,
+    arena: EnvArena,
     state_setter: SS,
     shared_info_provider: SIP,
     observations: OBS,
@@ -40,19 +218,50 @@ where
     truncate: TRUNC,
     shared_info: SI,
     tick_skip: u32,
-    last_state: Option<Rc<GameStateA>>,
-    renderer: Option<RLViserSocketHandler>,
+    last_state: Option<Shared<GameStateA>>,
+    last_obs: Option<Shared<FullObs>>,
+    obs_pool: Vec<Shared<FullObs>>,
+    last_controls: Vec<(AgentId, CarControls)>,
+    policies: Vec<(AgentId, Box<dyn Policy<SI>>)>,
+    team_policies: Vec<(Team, Box<dyn Policy<SI>>)>,
+    opponents: Option<PastSelfOpponents<SI>>,
+    episode_metadata: EpisodeMetadata,
+    episode_seeds: fastrand::Rng,
+    episode_steps: u64,
+    episode_start_tick: u64,
+    total_steps: u64,
+    episodes_completed: u64,
+    watchdog: Option<Watchdog>,
+    metrics: Option<Metrics>,
+    action_history: Option<ActionHistory>,
+    curriculum: Option<Box<dyn Curriculum<SS, REW, TERM, SI>>>,
+    curriculum_metric: Option<f32>,
+    capture_events: bool,
+    auto_reset: bool,
+    /// The arena was changed from outside, so `last_state` may not match its cars anymore
+    arena_changed: bool,
+    trajectory: Option<TrajectoryRecorder>,
+    renderer: Option<Box<dyn Renderer>>,
+    render_error_policy: RenderErrorPolicy,
+    render_errors: u64,
+    render_every: u32,
+    /// Tick count and time of the last state sent while sampling, to tell the renderer how fast they play out
+    last_render: Option<(u64, Instant)>,
+    state_set_hook: Option<StateSetHook>,
+    debug_draw: DebugDraw,
+    reward_value: PhantomData<R>,
 }
 
-impl<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI> Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI>
+impl<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R> Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R>
 where
     SS: StateSetter<SI>,
     SIP: SharedInfoProvider<SI>,
     OBS: Obs<SI>,
     ACT: Action<SI>,
-    REW: Reward<SI>,
+    REW: Reward<SI, R>,
     TERM: Terminal<SI>,
     TRUNC: Truncate<SI>,
+    R: RewardValue,
 {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
@@ -67,7 +276,7 @@ where
         shared_info: SI,
     ) -> Self {
         Self {
-            arena,
+            arena: EnvArena(arena),
             state_setter,
             shared_info_provider,
             observations,
@@ -78,49 +287,493 @@ where
             shared_info,
             tick_skip: ACT::get_tick_skip(),
             last_state: None,
+            last_obs: None,
+            obs_pool: Vec::new(),
+            last_controls: Vec::new(),
+            policies: Vec::new(),
+            team_policies: Vec::new(),
+            opponents: None,
+            episode_metadata: EpisodeMetadata::default(),
+            episode_seeds: fastrand::Rng::new(),
+            episode_steps: 0,
+            episode_start_tick: 0,
+            total_steps: 0,
+            episodes_completed: 0,
+            watchdog: None,
+            metrics: None,
+            action_history: None,
+            curriculum: None,
+            curriculum_metric: None,
+            capture_events: false,
+            auto_reset: false,
+            arena_changed: false,
+            trajectory: None,
             renderer: None,
+            render_error_policy: RenderErrorPolicy::default(),
+            render_errors: 0,
+            render_every: 1,
+            last_render: None,
+            state_set_hook: None,
+            debug_draw: DebugDraw::new(),
+            reward_value: PhantomData,
+        }
+    }
+
+    /// `new` followed by `validate`
+    #[allow(clippy::too_many_arguments)]
+    pub fn try_new(
+        arena: UniquePtr<Arena>,
+        state_setter: SS,
+        shared_info_provider: SIP,
+        observations: OBS,
+        action: ACT,
+        reward: REW,
+        terminal: TERM,
+        truncate: TRUNC,
+        shared_info: SI,
+    ) -> Result<Self> {
+        let mut env = Self::new(
+            arena,
+            state_setter,
+            shared_info_provider,
+            observations,
+            action,
+            reward,
+            terminal,
+            truncate,
+            shared_info,
+        );
+        env.validate()?;
+
+        Ok(env)
+    }
+
+    /// Check that the components fit each other and the cars currently in the arena,
+    /// so a misconfiguration is reported up front instead of from deep inside the first `step`.
+    ///
+    /// Call it again after changing the tick skip or the cars
+    pub fn validate(&mut self) -> Result<()> {
+        if self.tick_skip == 0 {
+            return Err(Error::Invalid(String::from("Tick skip must be at least 1")));
         }
+
+        let state = self.arena.pin_mut().get_game_state().to_glam();
+        let agents = AgentMap::new(&state);
+
+        self.observations.validate(&agents, &self.shared_info)?;
+        self.action.validate(&agents, &self.shared_info)?;
+
+        for id in agents.ids() {
+            if self.get_action_space(id) == 0 {
+                return Err(Error::Invalid(format!(
+                    "Agent {id} has an empty action space"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Step the arena this many ticks per step instead of the action parser's `get_tick_skip`
+    pub fn with_tick_skip(mut self, tick_skip: u32) -> Self {
+        self.tick_skip = tick_skip;
+        self
+    }
+
+    pub fn tick_skip(&self) -> u32 {
+        self.tick_skip
+    }
+
+    pub fn arena(&self) -> &Arena {
+        &self.arena
     }
 
-    /// Call at any time to open RLViser and start rendering the environment
-    pub fn enable_rendering(&mut self) {
+    /// Change the arena from outside the components, e.g. to add or remove cars mid-episode.
+    ///
+    /// The env's state and observations are out of date until `refresh`, which the next step calls first if needed
+    pub fn arena_mut(&mut self) -> Pin<&mut Arena> {
+        self.arena_changed = true;
+        self.arena.pin_mut()
+    }
+
+    /// Add a car mid-episode, `refresh` has the observations to pick its first action from.
+    ///
+    /// The number of observations and actions changes with the cars, and so does the length of each observation
+    /// unless the obs builder is padded for the new team size
+    pub fn add_car(&mut self, team: Team, config: &CarConfig) -> Result<AgentId> {
+        let id = self.arena.pin_mut().add_car(team, config);
+        self.arena_changed = true;
+
+        Ok(AgentId(id))
+    }
+
+    /// Remove a car mid-episode along with the policy driving it, if any
+    pub fn remove_car(&mut self, agent_id: AgentId) -> Result<()> {
+        self.arena
+            .pin_mut()
+            .remove_car(agent_id.0)
+            .map_err(|e| Error::Arena(e.to_string()))?;
+        self.policies.retain(|(id, _)| *id != agent_id);
+        self.arena_changed = true;
+
+        Ok(())
+    }
+
+    /// Re-read the cars and rebuild the observations after the arena was changed with `arena_mut`,
+    /// `add_car` or `remove_car`, without resetting the episode.
+    ///
+    /// Returns the observations of the cars controlled by the trainer, in the order of `external_agent_ids`
+    pub fn refresh(&mut self) -> Result<Shared<FullObs>> {
+        if self.last_state.is_none() {
+            return Err(Error::NotReset);
+        }
+
+        let state = self.arena.pin_mut().get_game_state().to_glam();
+        let agents = AgentMap::new(&state);
+        self.observations.validate(&agents, &self.shared_info)?;
+        self.action.validate(&agents, &self.shared_info)?;
+
+        self.shared_info_provider
+            .apply(&state, &mut self.shared_info);
+
+        let mut ctx = StepContext::new(&state)
+            .with_episode_ticks(state.tick_count.saturating_sub(self.episode_start_tick));
+        ctx.action_history = self.action_history.as_ref();
+        let mut obs = take_pooled(&mut self.obs_pool);
+        timed(
+            &mut self.watchdog,
+            &mut self.metrics,
+            Component::Obs,
+            || {
+                self.observations.build_obs_into(
+                    &ctx,
+                    &mut self.shared_info,
+                    Shared::make_mut(&mut obs),
+                )
+            },
+        );
+        check_obs_count(&state, &obs)?;
+
+        let external_obs = self.external_obs(&state, &obs);
+        self.last_state = Some(Shared::new(state));
+        self.set_last_obs(obs);
+        self.arena_changed = false;
+
+        Ok(external_obs)
+    }
+
+    /// Change the arena's gravity, boost strength, ball size, demos and respawn time
+    pub fn with_mutators(mut self, mutators: Mutators) -> Self {
+        mutators.apply(&mut self.arena);
+        self
+    }
+
+    /// Record goals, touches, bumps and demos on every tick of a step into `StepResult::events`
+    /// and `SharedInfoProvider::apply_events`.
+    ///
+    /// This replaces the arena's goal scored and car bump callbacks
+    pub fn with_event_capture(mut self) -> Self {
+        events::install(&mut self.arena);
+        self.capture_events = true;
+        self
+    }
+
+    /// Write every reset and step to `recorder`, including the ones of policy-driven cars
+    pub fn with_trajectory_recorder(mut self, recorder: TrajectoryRecorder) -> Self {
+        self.trajectory = Some(recorder);
+        self
+    }
+
+    /// Stop recording trajectories and get the recorder back, e.g. to flush it
+    pub fn take_trajectory_recorder(&mut self) -> Option<TrajectoryRecorder> {
+        self.trajectory.take()
+    }
+
+    /// Reset as part of the step that ends an episode, see `StepResult::final_obs`
+    pub fn with_auto_reset(mut self) -> Self {
+        self.auto_reset = true;
+        self
+    }
+
+    /// Time every component call and warn about the ones that are repeatedly too slow
+    pub fn with_watchdog(mut self, watchdog: Watchdog) -> Self {
+        self.watchdog = Some(watchdog);
+        self
+    }
+
+    pub fn watchdog(&self) -> Option<&Watchdog> {
+        self.watchdog.as_ref()
+    }
+
+    /// Time every part of a step and keep track of steps per second and episode rewards
+    pub fn with_metrics(mut self) -> Self {
+        self.metrics = Some(Metrics::new());
+        self
+    }
+
+    pub fn metrics(&self) -> Option<&Metrics> {
+        self.metrics.as_ref()
+    }
+
+    /// Start the metrics over, e.g. after logging them
+    pub fn reset_metrics(&mut self) {
+        if let Some(metrics) = &mut self.metrics {
+            metrics.reset();
+        }
+    }
+
+    /// What to do when sending to or receiving from the renderer fails after rendering was enabled
+    pub fn with_render_error_policy(mut self, policy: RenderErrorPolicy) -> Self {
+        self.render_error_policy = policy;
+        self
+    }
+
+    /// Only send every `every`th step to the renderer, to keep watching a run that steps faster than real time
+    /// without sending every state. The renderer is told the speed they play out at, see `Renderer::send_speed`.
+    ///
+    /// In a `VecEnv`, enable rendering in `make_env` for a single index to watch one env out of all of them
+    pub fn with_render_every(mut self, every: u32) -> Self {
+        self.render_every = every.max(1);
+        self
+    }
+
+    /// How many times talking to the renderer failed since the env was created
+    pub fn render_errors(&self) -> u64 {
+        self.render_errors
+    }
+
+    fn handle_render_error(&mut self, error: std::io::Error) -> Result<()> {
+        self.render_errors += 1;
+
+        match self.render_error_policy {
+            RenderErrorPolicy::Ignore => {
+                log::warn!("Render error #{}: {error}", self.render_errors);
+                Ok(())
+            }
+            RenderErrorPolicy::Disable => {
+                log::error!("Render error, rendering is now disabled: {error}");
+                self.renderer = None;
+                Ok(())
+            }
+            RenderErrorPolicy::Propagate => Err(Error::Render(error)),
+        }
+    }
+
+    /// Call at any time to start rendering the environment with `renderer`,
+    /// closing the renderer that was used before
+    pub fn enable_rendering(&mut self, renderer: Box<dyn Renderer>) -> Result<()> {
+        self.stop_rendering()?;
+        self.renderer = Some(renderer);
+
+        Ok(())
+    }
+
+    /// Call at any time to open RLViser and start rendering the environment,
+    /// does nothing if something is already rendering
+    #[cfg(feature = "rlviser")]
+    pub fn enable_rlviser(&mut self, config: &RenderConfig) -> Result<()> {
         if self.renderer.is_none() {
-            self.renderer = Some(RLViserSocketHandler::new().unwrap());
+            let renderer = RLViserSocketHandler::with_config(config).map_err(Error::Render)?;
+            let renderer: Box<dyn Renderer> = match config.render_thread {
+                Some(capacity) => Box::new(ThreadedRenderer::new(renderer, capacity)),
+                None => Box::new(renderer),
+            };
+            self.renderer = Some(renderer);
         }
+
+        Ok(())
     }
 
     /// Check if the game should be paused
     pub fn is_paused(&self) -> bool {
         self.renderer
             .as_ref()
-            .map(RLViserSocketHandler::is_paused)
+            .map(|renderer| renderer.is_paused())
             .unwrap_or_default()
     }
 
+    /// A handle to draw debug shapes in the renderer with, e.g. to keep in a reward or obs builder
+    pub fn debug_draw(&self) -> DebugDraw {
+        self.debug_draw.clone()
+    }
+
+    /// Replace the lines of debug draw group `group`, see `DebugDraw::lines`
+    pub fn render_lines(
+        &self,
+        group: i32,
+        lines: impl IntoIterator<Item = (Vec3A, Vec3A)>,
+        color: Color,
+    ) {
+        self.debug_draw.lines(group, lines, color);
+    }
+
+    /// Stop showing debug draw group `group`
+    pub fn clear_render(&self, group: i32) {
+        self.debug_draw.clear(group);
+    }
+
+    /// Whether this step's state goes to the renderer, one in every `render_every` steps
+    fn is_render_step(&self) -> bool {
+        self.renderer.is_some() && self.total_steps % u64::from(self.render_every) == 0
+    }
+
+    /// Send `state` to the renderer, with the speed it's playing at when only some steps are rendered
+    fn render_state(&mut self, state: &GameState) -> Result<()> {
+        let Some(renderer) = &mut self.renderer else {
+            return Ok(());
+        };
+
+        let mut result = renderer.send_state(state);
+        if self.render_every > 1 {
+            let now = Instant::now();
+            if let Some((last_tick, last_time)) = self.last_render {
+                let ticks = state.tick_count.saturating_sub(last_tick);
+                let elapsed = now.duration_since(last_time).as_secs_f32();
+                // a reset in between can move the tick count back, the next pair of states gets it right
+                if ticks > 0 && elapsed > 0. {
+                    let speed = ticks as f32 / (120. * elapsed);
+                    result = result.and_then(|()| renderer.send_speed(speed));
+                }
+            }
+            self.last_render = Some((state.tick_count, now));
+        }
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(e) => self.handle_render_error(e),
+        }
+    }
+
+    /// Send the debug shapes drawn since the last call, or drop them if nothing is rendering
+    fn flush_debug_draw(&mut self) -> Result<()> {
+        let messages = self.debug_draw.take();
+        let Some(renderer) = &mut self.renderer else {
+            return Ok(());
+        };
+
+        let error = messages
+            .iter()
+            .find_map(|message| renderer.send_render(message).err());
+        match error {
+            Some(e) => self.handle_render_error(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether something is rendering and still there to show states, e.g. to stop rendering once RLViser was closed
+    pub fn is_renderer_connected(&self) -> bool {
+        self.renderer
+            .as_ref()
+            .map_or(false, |renderer| renderer.is_connected())
+    }
+
     /// Tick rate, by default, should be `Duration::from_secs_f32(TICK_SKIP as f32 / 120.)`
-    pub fn handle_incoming_states(&mut self, tick_rate: &mut Duration) -> io::Result<()> {
+    pub fn handle_incoming_states(&mut self, tick_rate: &mut Duration) -> Result<()> {
+        let mut states = Vec::new();
         if let Some(renderer) = &mut self.renderer {
-            renderer.handle_return_message(&mut self.arena, tick_rate, ACT::get_tick_skip())?;
+            let result = renderer
+                .handle_messages(tick_rate, self.tick_skip, &mut |state| states.push(state));
+            if let Err(e) = result {
+                self.handle_render_error(e)?;
+            }
+        }
+
+        for state in states {
+            self.set_external_state(state)?;
         }
 
         Ok(())
     }
 
-    /// Call at any time to close RLViser
-    pub fn stop_rendering(&mut self) {
+    /// Look at, change or reject the states the renderer asks to set, e.g. the ones edited in RLViser.
+    ///
+    /// `hook` returns whether to set the state. Once one is set mid-episode, the shared info provider
+    /// and the obs builder are told with `on_state_set` and the next step starts from the new state
+    pub fn with_state_set_hook(
+        mut self,
+        hook: impl FnMut(&mut GameState) -> bool + MaybeSend + 'static,
+    ) -> Self {
+        self.state_set_hook = Some(Box::new(hook));
+        self
+    }
+
+    fn set_external_state(&mut self, mut state: GameState) -> Result<()> {
+        if let Some(hook) = &mut self.state_set_hook {
+            if !hook(&mut state) {
+                log::debug!("Rejected a state set from the renderer");
+                return Ok(());
+            }
+        }
+
+        if let Err(e) = self.arena.pin_mut().set_game_state(&state) {
+            log::error!("Error setting game state: {e}");
+            return Ok(());
+        }
+
+        if self.last_state.is_some() {
+            let state = self.arena.pin_mut().get_game_state().to_glam();
+            self.shared_info_provider
+                .on_state_set(&state, &mut self.shared_info);
+            self.observations
+                .on_state_set(&state, &mut self.shared_info);
+            self.arena_changed = true;
+        }
+
+        Ok(())
+    }
+
+    /// A clock for `step_realtime`, starting now
+    pub fn realtime_clock(&self) -> RealtimeClock {
+        RealtimeClock::new(self.tick_skip)
+    }
+
+    /// Step in real time at the game speed the renderer asks for, waiting on `clock` after the step.
+    ///
+    /// Also handles the states the renderer asks to set. Returns `None` without stepping while it's paused
+    pub fn step_realtime(
+        &mut self,
+        raw_actions: ACT::Input,
+        clock: &mut RealtimeClock,
+    ) -> Result<Option<StepResult<R>>> {
+        let result = if self.is_paused() {
+            None
+        } else {
+            Some(self.step(raw_actions)?)
+        };
+
+        self.handle_incoming_states(clock.interval_mut())?;
+        clock.wait();
+
+        Ok(result)
+    }
+
+    /// Call at any time to close the renderer
+    pub fn stop_rendering(&mut self) -> Result<()> {
         if let Some(renderer) = self.renderer.take() {
-            renderer.quit().unwrap();
+            renderer.quit().map_err(Error::Render)?;
         }
+
+        Ok(())
     }
 
-    pub fn get_obs_space(&self, agent_id: u32) -> usize {
+    pub fn get_obs_space(&self, agent_id: AgentId) -> usize {
         self.observations.get_obs_space(agent_id, &self.shared_info)
     }
 
-    pub fn get_action_space(&self, agent_id: u32) -> usize {
+    pub fn get_action_space(&self, agent_id: AgentId) -> usize {
         self.action.get_action_space(agent_id, &self.shared_info)
     }
 
+    pub fn get_obs_space_type(&self, agent_id: AgentId) -> SpaceType {
+        self.observations
+            .get_obs_space_type(agent_id, &self.shared_info)
+    }
+
+    pub fn get_action_space_type(&self, agent_id: AgentId) -> SpaceType {
+        self.action
+            .get_action_space_type(agent_id, &self.shared_info)
+    }
+
     pub fn num_cars(&self) -> usize {
         self.arena.num_cars()
     }
@@ -129,88 +782,868 @@ where
         &self.shared_info
     }
 
+    /// Change the shared info from outside the components, e.g. to push new curriculum parameters.
+    ///
+    /// Best done between episodes, components only see the change on their next call
+    pub fn shared_info_mut(&mut self) -> &mut SI {
+        &mut self.shared_info
+    }
+
+    /// Swap in a new shared info and return the old one, best done between episodes
+    pub fn replace_shared_info(&mut self, shared_info: SI) -> SI {
+        std::mem::replace(&mut self.shared_info, shared_info)
+    }
+
+    /// Let `policy` drive the car with the id `agent_id` from now on, replacing any previous policy for it.
+    ///
+    /// The car is then left out of the actions passed to `step`
+    /// as well as out of the returned observations and rewards
+    pub fn set_policy(&mut self, agent_id: AgentId, policy: Box<dyn Policy<SI>>) {
+        match self.policies.iter_mut().find(|(id, _)| *id == agent_id) {
+            Some((_, old_policy)) => *old_policy = policy,
+            None => self.policies.push((agent_id, policy)),
+        }
+    }
+
+    /// Hand control of the car with the id `agent_id` back to the trainer
+    pub fn remove_policy(&mut self, agent_id: AgentId) -> Option<Box<dyn Policy<SI>>> {
+        let index = self.policies.iter().position(|(id, _)| *id == agent_id)?;
+        Some(self.policies.remove(index).1)
+    }
+
+    /// Let `policy` drive every car on `team`, including cars that are added later.
+    ///
+    /// Policies set for a specific car with `set_policy` take priority
+    pub fn set_team_policy(&mut self, team: Team, policy: Box<dyn Policy<SI>>) {
+        match self.team_policies.iter_mut().find(|(t, _)| *t == team) {
+            Some((_, old_policy)) => *old_policy = policy,
+            None => self.team_policies.push((team, policy)),
+        }
+    }
+
+    pub fn remove_team_policy(&mut self, team: Team) -> Option<Box<dyn Policy<SI>>> {
+        let index = self.team_policies.iter().position(|(t, _)| *t == team)?;
+        Some(self.team_policies.remove(index).1)
+    }
+
+    /// Remove all per-car and per-team policies
+    pub fn clear_policies(&mut self) {
+        self.policies.clear();
+        self.team_policies.clear();
+    }
+
+    /// Starting from the next reset, every episode a checkpoint is sampled from `opponents`
+    /// and drives all the cars on its team
+    pub fn set_past_self_opponents(&mut self, opponents: PastSelfOpponents<SI>) {
+        self.opponents = Some(opponents);
+    }
+
+    pub fn past_self_opponents_mut(&mut self) -> Option<&mut PastSelfOpponents<SI>> {
+        self.opponents.as_mut()
+    }
+
+    pub fn take_past_self_opponents(&mut self) -> Option<PastSelfOpponents<SI>> {
+        self.opponents.take()
+    }
+
+    /// The controls applied to each car in the last step, from both the trainer and policies
+    pub fn last_controls(&self) -> &[(AgentId, CarControls)] {
+        &self.last_controls
+    }
+
+    /// The action mask of every agent controlled by the trainer in the current state, in the order of the observations
+    pub fn get_action_masks(&self) -> Result<Vec<Vec<bool>>> {
+        let state = self.last_state.as_ref().ok_or(Error::NotReset)?;
+
+        Ok(state
+            .cars
+            .iter()
+            .filter(|car| self.is_external(car))
+            .map(|car| {
+                self.action
+                    .get_action_mask(AgentId::of(car), state, &self.shared_info)
+            })
+            .collect())
+    }
+
+    /// Keep the controls of every car from the last `len` steps of the episode
+    /// for components to read from `StepContext::action_history`
+    pub fn with_action_history(mut self, len: usize) -> Self {
+        self.action_history = Some(ActionHistory::new(len));
+        self
+    }
+
+    pub fn action_history(&self) -> Option<&ActionHistory> {
+        self.action_history.as_ref()
+    }
+
+    /// Change the state setter, reward and terminal condition as training goes on, see `curriculum::StagedCurriculum`
+    pub fn with_curriculum(
+        mut self,
+        curriculum: impl Curriculum<SS, REW, TERM, SI> + 'static,
+    ) -> Self {
+        self.curriculum = Some(Box::new(curriculum));
+        self
+    }
+
+    /// Name of the curriculum's current stage
+    pub fn curriculum_stage(&self) -> Option<&str> {
+        self.curriculum.as_ref()?.stage()
+    }
+
+    /// A value for the curriculum to decide on at the next reset, e.g. a win rate from an evaluation
+    pub fn set_curriculum_metric(&mut self, metric: f32) {
+        self.curriculum_metric = Some(metric);
+    }
+
+    pub fn episode_metadata(&self) -> &EpisodeMetadata {
+        &self.episode_metadata
+    }
+
+    /// The state after the last reset or step
+    pub fn last_state(&self) -> Option<&GameStateA> {
+        self.last_state.as_deref()
+    }
+
+    /// Steps taken since the last reset
+    pub fn episode_step_count(&self) -> u64 {
+        self.episode_steps
+    }
+
+    /// Steps taken since the env was created
+    pub fn total_step_count(&self) -> u64 {
+        self.total_steps
+    }
+
+    /// Episodes that ended with a terminal or truncated step
+    pub fn episodes_completed(&self) -> u64 {
+        self.episodes_completed
+    }
+
+    /// Ids of the cars that are controlled by the trainer, in the order actions are expected
+    /// and observations and rewards are returned
+    pub fn external_agent_ids(&self) -> Vec<AgentId> {
+        self.last_state
+            .as_ref()
+            .map(|state| {
+                state
+                    .cars
+                    .iter()
+                    .filter(|car| self.is_external(car))
+                    .map(AgentId::of)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Where every car is in the state after the last reset or step
+    pub fn agent_map(&self) -> AgentMap {
+        self.last_state
+            .as_deref()
+            .map(AgentMap::new)
+            .unwrap_or_default()
+    }
+
+    /// Split values that belong to the cars controlled by the trainer,
+    /// like the observations or rewards returned from `step`, into the blue and orange team's values
+    pub fn split_by_team<T>(&self, values: Vec<T>) -> (Vec<T>, Vec<T>) {
+        let Some(state) = &self.last_state else {
+            return (values, Vec::new());
+        };
+
+        let mut blue = Vec::new();
+        let mut orange = Vec::new();
+
+        let external_cars = state.cars.iter().filter(|car| self.is_external(car));
+        for (value, car) in values.into_iter().zip(external_cars) {
+            if car.team == Team::Blue {
+                blue.push(value);
+            } else {
+                orange.push(value);
+            }
+        }
+
+        (blue, orange)
+    }
+
+    fn is_external(&self, car: &CarInfoA) -> bool {
+        let id = AgentId::of(car);
+
+        !self.policies.iter().any(|(policy_id, _)| *policy_id == id)
+            && !self.team_policies.iter().any(|(team, _)| *team == car.team)
+            && !self
+                .opponents
+                .as_ref()
+                .map_or(false, |opponents| opponents.controls(id))
+    }
+
+    /// Drops the values belonging to cars that are driven by a policy
+    fn external_only<T>(&self, state: &GameStateA, values: Vec<T>) -> Vec<T> {
+        values
+            .into_iter()
+            .zip(&state.cars)
+            .filter(|(_, car)| self.is_external(car))
+            .map(|(value, _)| value)
+            .collect()
+    }
+
+    /// The previous observations go to the pool, to be reused once nobody holds on to them
+    fn set_last_obs(&mut self, obs: Shared<FullObs>) {
+        if let Some(previous) = self.last_obs.replace(obs) {
+            if self.obs_pool.len() < OBS_POOL_SIZE {
+                self.obs_pool.push(previous);
+            }
+        }
+    }
+
+    fn external_obs(&self, state: &GameStateA, obs: &Shared<FullObs>) -> Shared<FullObs> {
+        if self.policies.is_empty() && self.team_policies.is_empty() && self.opponents.is_none() {
+            return obs.clone();
+        }
+
+        Shared::new(self.external_only(state, obs.to_vec()))
+    }
+
     /// returns next obs
-    pub fn reset(&mut self) -> Rc<FullObs> {
-        self.state_setter
-            .apply(&mut self.arena, &mut self.shared_info);
+    pub fn reset(&mut self) -> Result<Shared<FullObs>> {
+        let seed = self.episode_seeds.u64(..);
+        self.reset_with(seed)
+    }
 
-        let state = self.arena.pin_mut().get_game_state().to_glam();
+    /// Reset with a specific episode seed instead of the next one from the env's seed,
+    /// e.g. one from `EpisodeMetadata::seed` to re-simulate an episode seen in the logs
+    pub fn reset_with(&mut self, seed: u64) -> Result<Shared<FullObs>> {
+        log::debug!(
+            "Starting episode {} with seed {seed}",
+            self.episodes_completed
+        );
+
+        if let Some(curriculum) = &mut self.curriculum {
+            let progress = CurriculumProgress {
+                total_steps: self.total_steps,
+                episodes_completed: self.episodes_completed,
+                metric: self.curriculum_metric,
+            };
+            curriculum.update(
+                &progress,
+                &mut self.state_setter,
+                &mut self.reward,
+                &mut self.terminal,
+                &mut self.shared_info,
+            );
+        }
+
+        self.state_setter.seed(seed, &mut self.shared_info);
+        timed(
+            &mut self.watchdog,
+            &mut self.metrics,
+            Component::StateSetter,
+            || {
+                self.state_setter
+                    .apply(&mut self.arena, &mut self.shared_info)
+            },
+        );
+
+        self.start_episode(Some(seed))
+    }
+
+    /// Resets the environment to `state` instead of using the state setter,
+    /// e.g. to reproduce a situation captured from an earlier episode
+    pub fn reset_to(&mut self, state: &GameState) -> Result<Shared<FullObs>> {
+        self.arena
+            .pin_mut()
+            .set_game_state(state)
+            .map_err(|e| Error::Arena(e.to_string()))?;
+
+        self.start_episode(None)
+    }
+
+    fn start_episode(&mut self, seed: Option<u64>) -> Result<Shared<FullObs>> {
+        let raw_state = self.arena.pin_mut().get_game_state();
+        if let Some(trajectory) = &mut self.trajectory {
+            trajectory.record_reset(&raw_state)?;
+        }
+
+        let state = raw_state.to_glam();
         self.shared_info_provider
             .reset(&state, &mut self.shared_info);
         self.observations.reset(&state, &mut self.shared_info);
         self.action.reset(&state, &mut self.shared_info);
         self.terminal.reset(&state, &mut self.shared_info);
+        self.truncate.reset(&state, &mut self.shared_info);
         self.reward.reset(&state, &mut self.shared_info);
 
-        let obs = self.observations.build_obs(&state, &mut self.shared_info);
-        self.last_state = Some(Rc::new(state));
+        let policies = self.policies.iter_mut().map(|(_, policy)| policy);
+        let team_policies = self.team_policies.iter_mut().map(|(_, policy)| policy);
+        for policy in policies.chain(team_policies) {
+            policy.reset(&state, &mut self.shared_info);
+        }
+
+        if let Some(opponents) = &mut self.opponents {
+            opponents.start_episode(&state, &mut self.shared_info)?;
+        }
+
+        self.episode_steps = 0;
+        if let Some(action_history) = &mut self.action_history {
+            action_history.clear();
+        }
+        self.episode_metadata = EpisodeMetadata {
+            opponent_id: self
+                .opponents
+                .as_ref()
+                .and_then(|opponents| opponents.current().map(str::to_string)),
+            seed,
+        };
+
+        self.episode_start_tick = state.tick_count;
+
+        let mut ctx = StepContext::new(&state);
+        ctx.action_history = self.action_history.as_ref();
+        let mut obs = take_pooled(&mut self.obs_pool);
+        timed(
+            &mut self.watchdog,
+            &mut self.metrics,
+            Component::Obs,
+            || {
+                self.observations.build_obs_into(
+                    &ctx,
+                    &mut self.shared_info,
+                    Shared::make_mut(&mut obs),
+                )
+            },
+        );
+        check_obs_count(&state, &obs)?;
 
-        Rc::new(obs)
+        let external_obs = self.external_obs(&state, &obs);
+        self.last_state = Some(Shared::new(state));
+        self.arena_changed = false;
+        self.set_last_obs(obs);
+
+        Ok(external_obs)
+    }
+
+    /// Close the renderer, log the watchdog's stats and flush the logger.
+    ///
+    /// Call it before exiting, e.g. once a `shutdown::ShutdownSignal` is set,
+    /// so an interrupted run doesn't leave an RLViser window behind
+    pub fn shutdown(&mut self) -> Result<()> {
+        self.stop_rendering()?;
+
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.log_report();
+        }
+
+        if let Some(metrics) = &self.metrics {
+            metrics.log_report();
+        }
+
+        if let Some(trajectory) = &mut self.trajectory {
+            trajectory.flush()?;
+        }
+
+        log::info!(
+            "Shut down after {} steps and {} finished episodes",
+            self.total_steps,
+            self.episodes_completed
+        );
+        log::logger().flush();
+
+        Ok(())
+    }
+
+    /// Set the seed that every episode's seed is derived from, see `StateSetter::seed`
+    pub fn seed(&mut self, seed: u64) {
+        self.episode_seeds.seed(seed);
     }
 
-    pub fn step(&mut self, raw_actions: ACT::Input) -> StepResult {
-        let last_state = self.last_state.as_ref().expect("Must call reset() first!");
-        let parsed_actions =
-            self.action
-                .parse_actions(raw_actions, last_state, &mut self.shared_info);
-        let mapped_actions = parsed_actions
+    /// Check that the whole env is reproducible: twice, swap in a fresh arena from `make_arena`,
+    /// seed the env with `seed` and take `n_steps` steps with the actions from `actions`,
+    /// then compare the two runs' states step by step.
+    ///
+    /// The env keeps the second arena afterwards
+    pub fn audit_determinism(
+        &mut self,
+        seed: u64,
+        n_steps: usize,
+        mut make_arena: impl FnMut() -> UniquePtr<Arena>,
+        mut actions: impl FnMut(usize) -> ACT::Input,
+    ) -> Result<DiffReport> {
+        let mut run = |env: &mut Self| {
+            env.arena = EnvArena(make_arena());
+            if env.capture_events {
+                events::install(&mut env.arena);
+            }
+            env.seed(seed);
+            env.rollout((0..n_steps).map(&mut actions))
+        };
+
+        let first = run(self)?;
+        let second = run(self)?;
+        let report = DiffReport::compare(first, second, 0.);
+
+        if !report.is_identical() {
+            log::warn!(
+                "Two runs with seed {seed} diverged at step {}",
+                report
+                    .first_divergence()
+                    .map_or(report.steps_compared, |diff| diff.step)
+            );
+        }
+
+        Ok(report)
+    }
+
+    /// Resets the environment and then steps it once for every item in `actions`.
+    ///
+    /// Returns the state after the reset followed by the state after each step,
+    /// which can be compared against another rollout with `diff::DiffReport::compare`
+    pub fn rollout(
+        &mut self,
+        actions: impl IntoIterator<Item = ACT::Input>,
+    ) -> Result<Vec<Shared<GameStateA>>> {
+        self.reset()?;
+
+        let mut states = vec![self.last_state.clone().ok_or(Error::NotReset)?];
+        for actions in actions {
+            states.push(self.step(actions)?.state);
+        }
+
+        Ok(states)
+    }
+
+    /// Step with each action keyed by the agent it's for instead of in the order of `external_agent_ids`,
+    /// so actions can't end up on the wrong car when cars come and go mid-episode
+    pub fn step_by_id<T>(
+        &mut self,
+        actions: impl IntoIterator<Item = (AgentId, T)>,
+    ) -> Result<StepResult<R>>
+    where
+        ACT::Input: FromIterator<T>,
+    {
+        if self.arena_changed {
+            self.refresh()?;
+        }
+
+        let mut actions = actions.into_iter().collect::<BTreeMap<_, _>>();
+        let ordered = self
+            .external_agent_ids()
             .into_iter()
-            .enumerate()
-            .map(|(i, controls)| (last_state.cars[i].id, controls))
+            .map(|id| {
+                actions
+                    .remove(&id)
+                    .ok_or_else(|| Error::Invalid(format!("There's no action for agent {id}")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if let Some(id) = actions.keys().next() {
+            return Err(Error::Invalid(format!(
+                "Agent {id} got an action, but it isn't controlled by the trainer"
+            )));
+        }
+
+        self.step(ordered.into_iter().collect())
+    }
+
+    /// Step with one action per car controlled by the trainer, in the order of `external_agent_ids`.
+    ///
+    /// If the arena was changed since the last step, the env is refreshed first
+    /// and the actions have to match the cars after the change
+    pub fn step(&mut self, raw_actions: ACT::Input) -> Result<StepResult<R>> {
+        if self.arena_changed {
+            self.refresh()?;
+        }
+
+        let (Some(last_state), Some(last_obs)) = (&self.last_state, &self.last_obs) else {
+            return Err(Error::NotReset);
+        };
+        let previous_state = Shared::clone(last_state);
+        let previous_obs = Shared::clone(last_obs);
+        let mut parsed_actions = timed(
+            &mut self.watchdog,
+            &mut self.metrics,
+            Component::Action,
+            || {
+                self.action
+                    .parse_actions(raw_actions, last_state, &mut self.shared_info)
+            },
+        )
+        .into_iter();
+        let num_parsed = parsed_actions.len();
+        let last_tick = last_state.tick_count;
+
+        let policies = &mut self.policies;
+        let team_policies = &mut self.team_policies;
+        let opponents = &mut self.opponents;
+        let shared_info = &mut self.shared_info;
+        let mut missing_actions = 0;
+        let mapped_actions = last_state
+            .cars
+            .iter()
+            .zip(last_obs.iter())
+            .filter_map(|(car, obs)| {
+                let agent_id = AgentId::of(car);
+                let policy = policies
+                    .iter_mut()
+                    .find(|(id, _)| *id == agent_id)
+                    .map(|(_, policy)| policy)
+                    .or_else(|| {
+                        team_policies
+                            .iter_mut()
+                            .find(|(team, _)| *team == car.team)
+                            .map(|(_, policy)| policy)
+                    });
+
+                let controls = match policy {
+                    Some(policy) => policy.get_controls(agent_id, obs, last_state, shared_info),
+                    None => match opponents.as_mut().and_then(|opponents| {
+                        opponents.get_controls(agent_id, obs, last_state, shared_info)
+                    }) {
+                        Some(controls) => controls,
+                        None => {
+                            missing_actions += 1;
+                            parsed_actions.next()?
+                        }
+                    },
+                };
+
+                Some((car.id, controls))
+            })
+            .collect::<Vec<_>>();
+
+        // too few would leave cars without controls, too many means the actions are lined up with the wrong cars
+        if missing_actions != num_parsed {
+            return Err(Error::ActionCount {
+                expected: missing_actions,
+                actual: num_parsed,
+            });
+        }
+
+        let controls = mapped_actions
+            .iter()
+            .map(|(id, controls)| (AgentId(*id), *controls))
             .collect::<Vec<_>>();
+        timed(
+            &mut self.watchdog,
+            &mut self.metrics,
+            Component::SharedInfoProvider,
+            || {
+                self.shared_info_provider
+                    .pre_step(&controls, last_state, &mut self.shared_info)
+            },
+        );
 
         self.arena
             .pin_mut()
             .set_all_controls(&mapped_actions)
-            .unwrap();
-        self.arena.pin_mut().step(self.tick_skip);
+            .map_err(|e| Error::Arena(e.to_string()))?;
+        if self.capture_events {
+            events::clear();
+        }
+        timed(
+            &mut self.watchdog,
+            &mut self.metrics,
+            Component::Physics,
+            || self.arena.pin_mut().step(self.tick_skip),
+        );
+        if let Some(action_history) = &mut self.action_history {
+            action_history.push(&controls);
+        }
+        self.last_controls = controls;
 
         let raw_state = self.arena.pin_mut().get_game_state();
 
-        if let Some(renderer) = &mut self.renderer {
-            renderer.send_state(&raw_state).unwrap();
+        // shapes drawn on skipped steps are sent along with the next state that's rendered
+        let render_step = self.is_render_step();
+        if render_step {
+            self.render_state(&raw_state)?;
         }
 
-        let state = Rc::new(raw_state.to_glam());
-        self.shared_info_provider
-            .apply(&state, &mut self.shared_info);
-        let obs = self.observations.build_obs(&state, &mut self.shared_info);
-        let rewards = self.reward.get_rewards(&state, &mut self.shared_info);
-        let is_terminal = self.terminal.is_terminal(&state, &mut self.shared_info);
-        let truncated = self.truncate.should_truncate(&state, &mut self.shared_info);
+        let state = Shared::new(raw_state.to_glam());
+        let events = if self.capture_events {
+            events::take(&state, last_tick)
+        } else {
+            Vec::new()
+        };
+        let ctx = StepContext {
+            state: &state,
+            previous_state: Some(&previous_state),
+            actions: &self.last_controls,
+            events: &events,
+            episode_ticks: state.tick_count.saturating_sub(self.episode_start_tick),
+            action_history: self.action_history.as_ref(),
+        };
+        let watchdog = &mut self.watchdog;
+        let metrics = &mut self.metrics;
+        timed(watchdog, metrics, Component::SharedInfoProvider, || {
+            self.shared_info_provider
+                .apply_events(&events, &mut self.shared_info);
+            self.shared_info_provider
+                .apply(&state, &mut self.shared_info)
+        });
+        let mut obs = take_pooled(&mut self.obs_pool);
+        timed(watchdog, metrics, Component::Obs, || {
+            self.observations.build_obs_into(
+                &ctx,
+                &mut self.shared_info,
+                Shared::make_mut(&mut obs),
+            )
+        });
+        check_obs_count(&state, &obs)?;
+        let rewards = timed(watchdog, metrics, Component::Reward, || {
+            self.reward.get_rewards(&ctx, &mut self.shared_info)
+        });
+        check_reward_count(&state, &rewards)?;
+        let is_terminal = timed(watchdog, metrics, Component::Terminal, || {
+            self.terminal.is_terminal(&ctx, &mut self.shared_info)
+        });
+        let truncated = timed(watchdog, metrics, Component::Truncate, || {
+            self.truncate.should_truncate(&ctx, &mut self.shared_info)
+        });
+
+        if let Some(trajectory) = &mut self.trajectory {
+            trajectory.record_step(
+                &raw_state,
+                previous_state.cars.iter().map(AgentId::of),
+                &previous_obs,
+                &self.last_controls,
+                state.cars.iter().map(AgentId::of),
+                &rewards,
+                &events,
+                is_terminal,
+                truncated,
+            )?;
+        }
 
+        let external_obs = self.external_obs(&state, &obs);
+        let rewards = self.external_only(&state, rewards);
+        let reward_breakdown = self
+            .reward
+            .breakdown()
+            .map(|breakdown| self.external_only(&state, breakdown.to_vec()));
+        let agent_ids = state
+            .cars
+            .iter()
+            .filter(|car| self.is_external(car))
+            .map(AgentId::of)
+            .collect();
+        self.episode_steps += 1;
+        self.total_steps += 1;
+        if is_terminal || truncated {
+            self.episodes_completed += 1;
+        }
+        if let Some(opponents) = &mut self.opponents {
+            opponents.end_step(&state, is_terminal || truncated)?;
+        }
+        if let Some(metrics) = &mut self.metrics {
+            metrics.record_step(&rewards, is_terminal || truncated);
+        }
         self.last_state = Some(state.clone());
+        self.set_last_obs(obs);
+        if render_step {
+            self.flush_debug_draw()?;
+        }
 
-        StepResult {
-            obs: Rc::new(obs),
+        let (external_obs, final_obs) = if self.auto_reset && (is_terminal || truncated) {
+            (self.reset()?, Some(external_obs))
+        } else {
+            (external_obs, None)
+        };
+
+        Ok(StepResult {
+            obs: external_obs,
             rewards,
             is_terminal,
             truncated,
             state,
+            agent_ids,
+            events,
+            final_obs,
+            reward_breakdown,
+        })
+    }
+}
+
+impl<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R> Drop
+    for Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI, R>
+where
+    SS: StateSetter<SI>,
+    SIP: SharedInfoProvider<SI>,
+    OBS: Obs<SI>,
+    ACT: Action<SI>,
+    REW: Reward<SI, R>,
+    TERM: Terminal<SI>,
+    TRUNC: Truncate<SI>,
+{
+    /// Close the renderer like `stop_rendering`, so one that's still open doesn't outlive the run
+    fn drop(&mut self) {
+        if let Some(renderer) = self.renderer.take() {
+            if let Err(e) = renderer.quit() {
+                log::warn!("Error closing the renderer: {e}");
+            }
         }
     }
 }
 
+/// The arena of an `Env`.
+///
+/// RocketSim arenas aren't tied to the thread that made them, but the bindings don't mark them as `Send`
+struct EnvArena(UniquePtr<Arena>);
+
+// The env only ever uses its arena through `&mut self`,
+// and event capture collects the events on the thread that stepped before returning
+#[cfg(feature = "sync")]
+unsafe impl Send for EnvArena {}
+
+impl Deref for EnvArena {
+    type Target = UniquePtr<Arena>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for EnvArena {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// How many observation buffers an `Env` keeps around to reuse, see `Obs::build_obs_into`
+const OBS_POOL_SIZE: usize = 4;
+
+/// A buffer from `pool` that nobody else holds on to anymore, or a new one
+fn take_pooled(pool: &mut Vec<Shared<FullObs>>) -> Shared<FullObs> {
+    match pool
+        .iter_mut()
+        .position(|obs| Shared::get_mut(obs).is_some())
+    {
+        Some(index) => pool.swap_remove(index),
+        None => Shared::default(),
+    }
+}
+
+fn timed<T>(
+    watchdog: &mut Option<Watchdog>,
+    metrics: &mut Option<Metrics>,
+    component: Component,
+    f: impl FnOnce() -> T,
+) -> T {
+    if watchdog.is_none() && metrics.is_none() {
+        return f();
+    }
+
+    let start = Instant::now();
+    let result = f();
+    let duration = start.elapsed();
+
+    if let Some(watchdog) = watchdog {
+        watchdog.record(component, duration);
+    }
+    if let Some(metrics) = metrics {
+        metrics.record(component, duration);
+    }
+
+    result
+}
+
+fn check_obs_count(state: &GameStateA, obs: &FullObs) -> Result<()> {
+    if obs.len() != state.cars.len() {
+        return Err(Error::ObsCount {
+            expected: state.cars.len(),
+            actual: obs.len(),
+        });
+    }
+
+    Ok(())
+}
+
+fn check_reward_count<R>(state: &GameStateA, rewards: &[R]) -> Result<()> {
+    if rewards.len() != state.cars.len() {
+        return Err(Error::Invalid(format!(
+            "Expected a reward for each of the {} cars, got {}",
+            state.cars.len(),
+            rewards.len()
+        )));
+    }
+
+    Ok(())
+}
+
 pub trait SharedInfoProvider<SI> {
     fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI);
     fn apply(&mut self, game_state: &GameStateA, shared_info: &mut SI);
+    /// Called when the state was set from outside mid-episode, e.g. edited in RLViser, before the next step.
+    ///
+    /// Resets by default, since the new state may have nothing to do with the one before
+    fn on_state_set(&mut self, state: &GameStateA, shared_info: &mut SI) {
+        self.reset(state, shared_info);
+    }
+    /// Called with every car's controls right before the arena steps with them and `state` from before the step,
+    /// e.g. to keep the previous actions in the shared info for action stacking or input penalties
+    fn pre_step(
+        &mut self,
+        _actions: &[(AgentId, CarControls)],
+        _state: &GameStateA,
+        _shared_info: &mut SI,
+    ) {
+    }
+    /// Called before `apply` with the events of the step when `Env::with_event_capture` is on,
+    /// e.g. to keep the touches and demos in the shared info for rewards and terminals
+    fn apply_events(&mut self, _events: &[GameEvent], _shared_info: &mut SI) {}
 }
 
 pub trait StateSetter<SI> {
     fn apply(&mut self, arena: &mut UniquePtr<Arena>, shared_info: &mut SI);
+    /// Reseed any randomness used to pick states, including RNGs kept in the shared info,
+    /// so the same seed leads to the same sequence of states
+    fn seed(&mut self, _seed: u64, _shared_info: &mut SI) {}
+}
+
+impl<SI, T: StateSetter<SI> + ?Sized> StateSetter<SI> for Box<T> {
+    fn apply(&mut self, arena: &mut UniquePtr<Arena>, shared_info: &mut SI) {
+        (**self).apply(arena, shared_info);
+    }
+
+    fn seed(&mut self, seed: u64, shared_info: &mut SI) {
+        (**self).seed(seed, shared_info);
+    }
 }
 
 pub trait Obs<SI> {
-    fn get_obs_space(&self, agent_id: u32, shared_info: &SI) -> usize;
+    fn get_obs_space(&self, agent_id: AgentId, shared_info: &SI) -> usize;
+    /// Unbounded floats as long as `get_obs_space` by default
+    fn get_obs_space_type(&self, agent_id: AgentId, shared_info: &SI) -> SpaceType {
+        SpaceType::unbounded(self.get_obs_space(agent_id, shared_info))
+    }
     fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI);
-    fn build_obs(&mut self, state: &GameStateA, shared_info: &mut SI) -> FullObs;
+    fn build_obs(&mut self, ctx: &StepContext, shared_info: &mut SI) -> FullObs;
+    /// Called when the state was set from outside mid-episode, e.g. edited in RLViser, before the next step.
+    ///
+    /// Resets by default, so stacked observations don't mix states from before and after
+    fn on_state_set(&mut self, state: &GameStateA, shared_info: &mut SI) {
+        self.reset(state, shared_info);
+    }
+    /// Build the observations into `obs`, which holds the rows of an earlier step that nobody uses anymore.
+    ///
+    /// Override it to clear and refill the rows instead of allocating new ones on every step
+    fn build_obs_into(&mut self, ctx: &StepContext, shared_info: &mut SI, obs: &mut FullObs) {
+        *obs = self.build_obs(ctx, shared_info);
+    }
+    /// Check that observations can be built for `agents`, e.g. that there's enough padding for the biggest team
+    fn validate(&self, _agents: &AgentMap, _shared_info: &SI) -> Result<()> {
+        Ok(())
+    }
 }
 
 pub trait Action<SI> {
     type Input;
 
     fn get_tick_skip() -> u32;
-    fn get_action_space(&self, agent_id: u32, shared_info: &SI) -> usize;
+    fn get_action_space(&self, agent_id: AgentId, shared_info: &SI) -> usize;
+    /// A choice from `get_action_space` actions by default
+    fn get_action_space_type(&self, agent_id: AgentId, shared_info: &SI) -> SpaceType {
+        SpaceType::Discrete(self.get_action_space(agent_id, shared_info))
+    }
     fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI);
     fn parse_actions(
         &mut self,
@@ -218,19 +1651,123 @@ pub trait Action<SI> {
         state: &GameStateA,
         shared_info: &mut SI,
     ) -> Vec<CarControls>;
+    /// Which of the agent's discrete actions it can take in `state`, as long as its action space.
+    ///
+    /// Every action is allowed by default
+    fn get_action_mask(
+        &self,
+        agent_id: AgentId,
+        _state: &GameStateA,
+        shared_info: &SI,
+    ) -> Vec<bool> {
+        vec![true; self.get_action_space(agent_id, shared_info)]
+    }
+    /// Check that actions can be parsed for `agents`, e.g. that the action space matches the lookup table
+    fn validate(&self, _agents: &AgentMap, _shared_info: &SI) -> Result<()> {
+        Ok(())
+    }
 }
 
-pub trait Reward<SI> {
+/// Returns one `R` per car, `f32` unless the env is set up for multiple objectives
+pub trait Reward<SI, R = f32> {
     fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI);
-    fn get_rewards(&mut self, state: &GameStateA, shared_info: &mut SI) -> Vec<f32>;
+    fn get_rewards(&mut self, ctx: &StepContext, shared_info: &mut SI) -> Vec<R>;
+    /// For rewards made of several components like `CombinedReward`,
+    /// the weighted value of every component in the last `get_rewards`, one row per car
+    fn breakdown(&self) -> Option<&[Vec<R>]> {
+        None
+    }
+}
+
+impl<SI, R, T: Reward<SI, R> + ?Sized> Reward<SI, R> for Box<T> {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        (**self).reset(initial_state, shared_info);
+    }
+
+    fn get_rewards(&mut self, ctx: &StepContext, shared_info: &mut SI) -> Vec<R> {
+        (**self).get_rewards(ctx, shared_info)
+    }
+
+    fn breakdown(&self) -> Option<&[Vec<R>]> {
+        (**self).breakdown()
+    }
+}
+
+/// The reward of a single agent: an `f32`, or an `[f32; N]` with one reward per objective
+/// for multi-objective or constrained RL
+pub trait RewardValue: Copy + fmt::Debug {
+    fn zero() -> Self;
+    /// `self += other * weight`
+    fn add_weighted(&mut self, other: Self, weight: f32);
+    fn is_finite(&self) -> bool;
+    /// The reward of every objective
+    fn objectives(&self) -> &[f32];
+}
+
+impl RewardValue for f32 {
+    fn zero() -> Self {
+        0.
+    }
+
+    fn add_weighted(&mut self, other: Self, weight: f32) {
+        *self += other * weight;
+    }
+
+    fn is_finite(&self) -> bool {
+        f32::is_finite(*self)
+    }
+
+    fn objectives(&self) -> &[f32] {
+        std::slice::from_ref(self)
+    }
+}
+
+impl<const N: usize> RewardValue for [f32; N] {
+    fn zero() -> Self {
+        [0.; N]
+    }
+
+    fn add_weighted(&mut self, other: Self, weight: f32) {
+        for (value, other) in self.iter_mut().zip(other) {
+            *value += other * weight;
+        }
+    }
+
+    fn is_finite(&self) -> bool {
+        self.iter().all(|value| value.is_finite())
+    }
+
+    fn objectives(&self) -> &[f32] {
+        self
+    }
 }
 
 pub trait Terminal<SI> {
     fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI);
-    fn is_terminal(&mut self, state: &GameStateA, shared_info: &mut SI) -> bool;
+    fn is_terminal(&mut self, ctx: &StepContext, shared_info: &mut SI) -> bool;
 }
 
 pub trait Truncate<SI> {
     fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI);
-    fn should_truncate(&mut self, state: &GameStateA, shared_info: &mut SI) -> bool;
+    fn should_truncate(&mut self, ctx: &StepContext, shared_info: &mut SI) -> bool;
+}
+
+impl<SI, T: Terminal<SI> + ?Sized> Terminal<SI> for Box<T> {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        (**self).reset(initial_state, shared_info);
+    }
+
+    fn is_terminal(&mut self, ctx: &StepContext, shared_info: &mut SI) -> bool {
+        (**self).is_terminal(ctx, shared_info)
+    }
+}
+
+impl<SI, T: Truncate<SI> + ?Sized> Truncate<SI> for Box<T> {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        (**self).reset(initial_state, shared_info);
+    }
+
+    fn should_truncate(&mut self, ctx: &StepContext, shared_info: &mut SI) -> bool {
+        (**self).should_truncate(ctx, shared_info)
+    }
 }
@@ -0,0 +1,131 @@
+use crate::{watchdog::Component, RewardValue};
+use std::time::{Duration, Instant};
+
+/// Throughput of an `Env`: the time spent in every part of a step, steps per second,
+/// finished episodes and their mean reward.
+///
+/// Register one with `Env::with_metrics` and read it with `Env::metrics`
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    since: Instant,
+    phase_times: [Duration; Component::ALL.len()],
+    steps: u64,
+    episodes_completed: u64,
+    /// The summed rewards of the finished episodes
+    completed_reward: f64,
+    /// The reward of the current episode so far
+    episode_reward: f64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            since: Instant::now(),
+            phase_times: [Duration::ZERO; Component::ALL.len()],
+            steps: 0,
+            episodes_completed: 0,
+            completed_reward: 0.,
+            episode_reward: 0.,
+        }
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start counting from zero, the reward of the current episode is kept
+    pub fn reset(&mut self) {
+        *self = Self {
+            episode_reward: self.episode_reward,
+            ..Self::default()
+        };
+    }
+
+    pub(crate) fn record(&mut self, component: Component, duration: Duration) {
+        self.phase_times[component as usize] += duration;
+    }
+
+    /// `rewards` are the rewards of the agents controlled by the trainer,
+    /// an episode's reward is the sum of the mean reward over its steps
+    pub(crate) fn record_step<R: RewardValue>(&mut self, rewards: &[R], episode_done: bool) {
+        self.steps += 1;
+
+        if !rewards.is_empty() {
+            let total = rewards
+                .iter()
+                .flat_map(RewardValue::objectives)
+                .map(|&reward| f64::from(reward))
+                .sum::<f64>();
+            self.episode_reward += total / rewards.len() as f64;
+        }
+
+        if episode_done {
+            self.episodes_completed += 1;
+            self.completed_reward += self.episode_reward;
+            self.episode_reward = 0.;
+        }
+    }
+
+    /// Time since the metrics were created or last reset
+    pub fn elapsed(&self) -> Duration {
+        self.since.elapsed()
+    }
+
+    /// Total time spent in `component` since the metrics were created or last reset
+    pub fn phase_time(&self, component: Component) -> Duration {
+        self.phase_times[component as usize]
+    }
+
+    /// Mean time spent in `component` per step
+    pub fn mean_phase_time(&self, component: Component) -> Duration {
+        match u32::try_from(self.steps) {
+            Ok(0) => Duration::ZERO,
+            Ok(steps) => self.phase_time(component) / steps,
+            Err(_) => Duration::from_secs_f64(
+                self.phase_time(component).as_secs_f64() / self.steps as f64,
+            ),
+        }
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn steps_per_second(&self) -> f64 {
+        self.steps as f64 / self.elapsed().as_secs_f64()
+    }
+
+    pub fn episodes_completed(&self) -> u64 {
+        self.episodes_completed
+    }
+
+    /// The mean over finished episodes of the summed reward of every objective,
+    /// `None` before the first episode finishes
+    pub fn mean_episode_reward(&self) -> Option<f64> {
+        (self.episodes_completed != 0)
+            .then(|| self.completed_reward / self.episodes_completed as f64)
+    }
+
+    /// Log the throughput and the mean time per step of every part of it that took any time
+    pub fn log_report(&self) {
+        log::info!(
+            "{} steps at {:.2} steps/s, {} episodes finished with a mean reward of {}",
+            self.steps,
+            self.steps_per_second(),
+            self.episodes_completed,
+            self.mean_episode_reward()
+                .map_or_else(|| String::from("n/a"), |reward| format!("{reward:.3}"))
+        );
+
+        for component in Component::ALL {
+            if !self.phase_time(component).is_zero() {
+                log::info!(
+                    "{component}: {:?} per step",
+                    self.mean_phase_time(component)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use rocketsim_rs::{
+    cxx::UniquePtr,
+    sim::{Arena, DemoMode},
+};
+
+/// Changes to RocketSim's mutators, anything left unset keeps the game mode's default.
+///
+/// Apply them to an env's arena with `Env::with_mutators`
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Mutators {
+    pub gravity_z: Option<f32>,
+    pub boost_accel_ground: Option<f32>,
+    pub boost_accel_air: Option<f32>,
+    pub ball_radius: Option<f32>,
+    /// Seconds before a demolished car respawns
+    pub respawn_delay: Option<f32>,
+    pub demo_mode: Option<DemoMode>,
+}
+
+impl Mutators {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_gravity_z(mut self, gravity_z: f32) -> Self {
+        self.gravity_z = Some(gravity_z);
+        self
+    }
+
+    /// How hard boosting accelerates cars on the ground and in the air
+    pub fn with_boost_accel(mut self, ground: f32, air: f32) -> Self {
+        self.boost_accel_ground = Some(ground);
+        self.boost_accel_air = Some(air);
+        self
+    }
+
+    pub fn with_ball_radius(mut self, ball_radius: f32) -> Self {
+        self.ball_radius = Some(ball_radius);
+        self
+    }
+
+    pub fn with_respawn_delay(mut self, respawn_delay: f32) -> Self {
+        self.respawn_delay = Some(respawn_delay);
+        self
+    }
+
+    pub fn with_demo_mode(mut self, demo_mode: DemoMode) -> Self {
+        self.demo_mode = Some(demo_mode);
+        self
+    }
+
+    /// Set the mutators that are set here, leaving the others as they are
+    pub fn apply(&self, arena: &mut UniquePtr<Arena>) {
+        let mut mutators = arena.get_mutator_config();
+        if let Some(gravity_z) = self.gravity_z {
+            mutators.gravity.z = gravity_z;
+        }
+        if let Some(boost_accel_ground) = self.boost_accel_ground {
+            mutators.boost_accel_ground = boost_accel_ground;
+        }
+        if let Some(boost_accel_air) = self.boost_accel_air {
+            mutators.boost_accel_air = boost_accel_air;
+        }
+        if let Some(ball_radius) = self.ball_radius {
+            mutators.ball_radius = ball_radius;
+        }
+        if let Some(respawn_delay) = self.respawn_delay {
+            mutators.respawn_delay = respawn_delay;
+        }
+        if let Some(demo_mode) = self.demo_mode {
+            mutators.demo_mode = demo_mode;
+        }
+        arena.pin_mut().set_mutator_config(mutators);
+    }
+}
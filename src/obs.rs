@@ -0,0 +1,1072 @@
+use crate::{
+    common_values::{pad_cooldown, pad_timer},
+    game_modes::SOCCAR_BOOST_PADS,
+    AgentId, AgentMap, Error, FullObs, Obs, Result, SpaceType, StepContext,
+};
+use glam::Vec3A;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use rocketsim_rs::{
+    glam_ext::{CarInfoA, GameStateA},
+    sim::Team,
+};
+#[cfg(feature = "config")]
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+const POS_COEF: f32 = 1. / 2300.;
+const LIN_VEL_COEF: f32 = 1. / 2300.;
+const ANG_VEL_COEF: f32 = 1. / PI;
+
+const BALL_OBS: usize = 9;
+const PREV_ACTION_OBS: usize = 8;
+const CAR_OBS: usize = 19;
+/// Position and velocity relative to the ball, or to the observing car
+const RELATIVE_OBS: usize = 6;
+
+/// Mirror the field for orange so every car sees itself attacking toward +y
+fn mirror(team: Team, v: Vec3A) -> Vec3A {
+    if team == Team::Orange {
+        Vec3A::new(-v.x, -v.y, v.z)
+    } else {
+        v
+    }
+}
+
+fn push_vec(obs: &mut Vec<f32>, team: Team, v: Vec3A, coef: f32) {
+    obs.extend((mirror(team, v) * coef).to_array());
+}
+
+fn push_ball(obs: &mut Vec<f32>, state: &GameStateA, team: Team) {
+    push_vec(obs, team, state.ball.pos, POS_COEF);
+    push_vec(obs, team, state.ball.vel, LIN_VEL_COEF);
+    push_vec(obs, team, state.ball.ang_vel, ANG_VEL_COEF);
+}
+
+fn push_prev_action(obs: &mut Vec<f32>, car: &CarInfoA) {
+    let controls = &car.state.last_controls;
+    obs.extend([
+        controls.throttle,
+        controls.steer,
+        controls.pitch,
+        controls.yaw,
+        controls.roll,
+        f32::from(u8::from(controls.jump)),
+        f32::from(u8::from(controls.boost)),
+        f32::from(u8::from(controls.handbrake)),
+    ]);
+}
+
+/// Pads are mirrored for orange by reversing their order, and padded or cut to `num_pads`.
+///
+/// Each pad is whether it's active, or with `timers` the share of its cooldown that's left
+fn push_pads(obs: &mut Vec<f32>, state: &GameStateA, team: Team, num_pads: usize, timers: bool) {
+    let active = state.pads.iter().map(|pad| {
+        if timers {
+            pad_timer(pad) / pad_cooldown(pad)
+        } else {
+            f32::from(u8::from(pad.state.is_active))
+        }
+    });
+
+    let start = obs.len();
+    if team == Team::Orange {
+        obs.extend(active.rev().take(num_pads));
+    } else {
+        obs.extend(active.take(num_pads));
+    }
+    obs.resize(start + num_pads, 0.);
+}
+
+fn push_car(obs: &mut Vec<f32>, car: &CarInfoA, team: Team) {
+    let state = &car.state;
+    push_vec(obs, team, state.pos, POS_COEF);
+    push_vec(obs, team, state.rot_mat.x_axis, 1.);
+    push_vec(obs, team, state.rot_mat.z_axis, 1.);
+    push_vec(obs, team, state.vel, LIN_VEL_COEF);
+    push_vec(obs, team, state.ang_vel, ANG_VEL_COEF);
+    obs.extend([
+        state.boost / 100.,
+        f32::from(u8::from(state.is_on_ground)),
+        f32::from(u8::from(!state.has_flipped && !state.has_double_jumped)),
+        f32::from(u8::from(state.is_demoed)),
+    ]);
+}
+
+fn push_relative(obs: &mut Vec<f32>, team: Team, pos: Vec3A, vel: Vec3A, to: &CarInfoA) {
+    push_vec(obs, team, pos - to.state.pos, POS_COEF);
+    push_vec(obs, team, vel - to.state.vel, LIN_VEL_COEF);
+}
+
+/// The ball relative to `car` and then the car itself, like RLGym's `AdvancedObs._add_player_to_obs`
+fn push_car_with_ball(obs: &mut Vec<f32>, state: &GameStateA, car: &CarInfoA, team: Team) {
+    push_relative(obs, team, state.ball.pos, state.ball.vel, car);
+    push_car(obs, car, team);
+}
+
+/// Everyone but `car`, teammates first, both in spawn order
+fn others<'a>(state: &'a GameStateA, car: &CarInfoA) -> (Vec<&'a CarInfoA>, Vec<&'a CarInfoA>) {
+    state
+        .cars
+        .iter()
+        .filter(|other| other.id != car.id)
+        .partition(|other| other.team == car.team)
+}
+
+/// Fill one row per car with room for `size` values, keeping the allocations of the rows from earlier steps.
+///
+/// The rows are filled in parallel with the `rayon` feature
+fn fill_rows(
+    obs: &mut FullObs,
+    state: &GameStateA,
+    size: usize,
+    fill: impl Fn(&mut Vec<f32>, &CarInfoA) + Send + Sync,
+) {
+    obs.resize_with(state.cars.len(), Vec::new);
+
+    let fill_row = |(row, car): (&mut Vec<f32>, &CarInfoA)| {
+        row.clear();
+        row.reserve(size);
+        fill(row, car);
+    };
+
+    #[cfg(feature = "rayon")]
+    obs.par_iter_mut()
+        .zip(state.cars.par_iter())
+        .for_each(fill_row);
+    #[cfg(not(feature = "rayon"))]
+    obs.iter_mut().zip(&state.cars).for_each(fill_row);
+}
+
+fn check_team_size(agents: &AgentMap, max_team_size: usize) -> Result<()> {
+    for team in [Team::Blue, Team::Orange] {
+        let team_size = agents.team(team).count();
+        if team_size > max_team_size {
+            return Err(Error::Invalid(format!(
+                "Obs is padded for teams of {max_team_size}, but there's a team of {team_size}"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Lays out the cars an agent observes in fixed slots, so one policy can play in 1v1, 2v2 and 3v3 arenas.
+///
+/// The observing car comes first, then its teammates and then its opponents, both in spawn order.
+/// Missing cars are zero padded up to `max_team_size` per team, and every slot is exactly `car_size` values
+#[derive(Clone, Copy, Debug)]
+pub struct TeamPadding {
+    pub max_team_size: usize,
+    pub car_size: usize,
+    /// Add a value per slot after the cars, 1 if it holds a car and 0 if it's padding
+    pub mask: bool,
+    /// Give the observing car the first slot, turn it off to push it yourself, e.g. with other values than the rest
+    pub self_slot: bool,
+}
+
+impl TeamPadding {
+    pub fn new(max_team_size: usize, car_size: usize) -> Self {
+        Self {
+            max_team_size,
+            car_size,
+            mask: false,
+            self_slot: true,
+        }
+    }
+
+    pub fn with_mask(mut self, mask: bool) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    pub fn with_self_slot(mut self, self_slot: bool) -> Self {
+        self.self_slot = self_slot;
+        self
+    }
+
+    fn self_slots(&self) -> usize {
+        if self.self_slot {
+            self.max_team_size.min(1)
+        } else {
+            0
+        }
+    }
+
+    /// How many values `push_cars` adds
+    pub fn size(&self) -> usize {
+        let slots = self.max_team_size.saturating_sub(1) + self.max_team_size + self.self_slots();
+        if self.mask {
+            (self.car_size + 1) * slots
+        } else {
+            self.car_size * slots
+        }
+    }
+
+    /// Push every car's values with `push_car` into its slot around `car`.
+    ///
+    /// Values past `car_size` are cut, so a wrong count can't shift the cars after it
+    pub fn push_cars(
+        &self,
+        obs: &mut Vec<f32>,
+        state: &GameStateA,
+        car: &CarInfoA,
+        mut push_car: impl FnMut(&mut Vec<f32>, &CarInfoA),
+    ) {
+        let (allies, enemies) = others(state, car);
+        let mut mask = Vec::with_capacity(self.max_team_size * 2);
+
+        for (cars, slots) in [
+            (vec![car], self.self_slots()),
+            (allies, self.max_team_size.saturating_sub(1)),
+            (enemies, self.max_team_size),
+        ] {
+            for slot in 0..slots {
+                let start = obs.len();
+                if let Some(other) = cars.get(slot) {
+                    push_car(obs, other);
+                }
+                obs.resize(start + self.car_size, 0.);
+                mask.push(f32::from(u8::from(slot < cars.len())));
+            }
+        }
+
+        if self.mask {
+            obs.extend(mask);
+        }
+    }
+
+    /// Check that no team in `agents` is bigger than `max_team_size`
+    pub fn validate(&self, agents: &AgentMap) -> Result<()> {
+        check_team_size(agents, self.max_team_size)
+    }
+}
+
+/// The ball, the car's previous controls, the boost pads and then every car,
+/// with the car itself first, then its teammates and then its opponents.
+///
+/// Everything is normalized and mirrored for orange so both teams see the same field.
+/// Missing cars are zero padded up to `max_team_size` per team
+#[derive(Clone, Copy, Debug)]
+pub struct DefaultObs {
+    pub max_team_size: usize,
+    /// Boost pads in the game mode, see `game_modes::num_boost_pads`. Extra pads are cut and missing ones are zero
+    pub num_pads: usize,
+    /// Observe how long until each pad is back instead of only whether it's active
+    pub pad_timers: bool,
+}
+
+impl DefaultObs {
+    pub fn new(max_team_size: usize) -> Self {
+        Self {
+            max_team_size,
+            num_pads: SOCCAR_BOOST_PADS,
+            pad_timers: false,
+        }
+    }
+
+    pub fn with_num_pads(mut self, num_pads: usize) -> Self {
+        self.num_pads = num_pads;
+        self
+    }
+
+    pub fn with_pad_timers(mut self, pad_timers: bool) -> Self {
+        self.pad_timers = pad_timers;
+        self
+    }
+
+    fn padding(&self) -> TeamPadding {
+        TeamPadding::new(self.max_team_size, CAR_OBS)
+    }
+}
+
+impl<SI> Obs<SI> for DefaultObs {
+    fn get_obs_space(&self, _agent_id: AgentId, _shared_info: &SI) -> usize {
+        BALL_OBS + PREV_ACTION_OBS + self.num_pads + self.padding().size()
+    }
+
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn build_obs(&mut self, ctx: &StepContext, shared_info: &mut SI) -> FullObs {
+        let mut obs = FullObs::new();
+        <Self as Obs<SI>>::build_obs_into(self, ctx, shared_info, &mut obs);
+        obs
+    }
+
+    fn build_obs_into(&mut self, ctx: &StepContext, shared_info: &mut SI, obs: &mut FullObs) {
+        let state = ctx.state;
+        let size = <Self as Obs<SI>>::get_obs_space(self, AgentId(0), shared_info);
+        let padding = self.padding();
+
+        fill_rows(obs, state, size, |obs, car| {
+            let team = car.team;
+            push_ball(obs, state, team);
+            push_prev_action(obs, car);
+            push_pads(obs, state, team, self.num_pads, self.pad_timers);
+            padding.push_cars(obs, state, car, |obs, other| push_car(obs, other, team));
+        });
+    }
+
+    fn validate(&self, agents: &AgentMap, _shared_info: &SI) -> Result<()> {
+        self.padding().validate(agents)
+    }
+}
+
+/// Like `DefaultObs`, plus the ball's position and velocity relative to every car before its values,
+/// and every other car's position and velocity relative to the observing car after them,
+/// matching RLGym's Python `AdvancedObs` when there are no missing cars
+#[derive(Clone, Copy, Debug)]
+pub struct AdvancedObs {
+    pub max_team_size: usize,
+    /// Boost pads in the game mode, see `game_modes::num_boost_pads`. Extra pads are cut and missing ones are zero
+    pub num_pads: usize,
+    /// Observe how long until each pad is back instead of only whether it's active
+    pub pad_timers: bool,
+}
+
+impl AdvancedObs {
+    pub fn new(max_team_size: usize) -> Self {
+        Self {
+            max_team_size,
+            num_pads: SOCCAR_BOOST_PADS,
+            pad_timers: false,
+        }
+    }
+
+    pub fn with_num_pads(mut self, num_pads: usize) -> Self {
+        self.num_pads = num_pads;
+        self
+    }
+
+    pub fn with_pad_timers(mut self, pad_timers: bool) -> Self {
+        self.pad_timers = pad_timers;
+        self
+    }
+
+    /// The other cars, the observing car is pushed before them without the values relative to itself
+    fn padding(&self) -> TeamPadding {
+        TeamPadding::new(self.max_team_size, RELATIVE_OBS + CAR_OBS + RELATIVE_OBS)
+            .with_self_slot(false)
+    }
+}
+
+impl<SI> Obs<SI> for AdvancedObs {
+    fn get_obs_space(&self, _agent_id: AgentId, _shared_info: &SI) -> usize {
+        BALL_OBS + PREV_ACTION_OBS + self.num_pads + RELATIVE_OBS + CAR_OBS + self.padding().size()
+    }
+
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn build_obs(&mut self, ctx: &StepContext, shared_info: &mut SI) -> FullObs {
+        let mut obs = FullObs::new();
+        <Self as Obs<SI>>::build_obs_into(self, ctx, shared_info, &mut obs);
+        obs
+    }
+
+    fn build_obs_into(&mut self, ctx: &StepContext, shared_info: &mut SI, obs: &mut FullObs) {
+        let state = ctx.state;
+        let size = <Self as Obs<SI>>::get_obs_space(self, AgentId(0), shared_info);
+        let padding = self.padding();
+
+        fill_rows(obs, state, size, |obs, car| {
+            let team = car.team;
+            push_ball(obs, state, team);
+            push_prev_action(obs, car);
+            push_pads(obs, state, team, self.num_pads, self.pad_timers);
+            push_car_with_ball(obs, state, car, team);
+            padding.push_cars(obs, state, car, |obs, other| {
+                push_car_with_ball(obs, state, other, team);
+                push_relative(obs, team, other.state.pos, other.state.vel, car);
+            });
+        });
+    }
+
+    fn validate(&self, agents: &AgentMap, _shared_info: &SI) -> Result<()> {
+        self.padding().validate(agents)
+    }
+}
+
+/// One agent's observation split into its own features and a set of entities,
+/// for attention or set transformer policies that handle any number of cars
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntityObservation {
+    /// Everything about the agent itself and the field
+    pub self_features: Vec<f32>,
+    /// One row per entity, e.g. every other car, as many as there are
+    pub entities: Vec<Vec<f32>>,
+}
+
+impl EntityObservation {
+    /// Whether each of `max_entities` slots holds an entity or is padding
+    pub fn mask(&self, max_entities: usize) -> Vec<bool> {
+        (0..max_entities).map(|i| i < self.entities.len()).collect()
+    }
+
+    /// Append the self features, the entities zero padded or cut to `max_entities` rows of `entity_size`,
+    /// and then the mask with a 1 for every real entity
+    pub fn flatten_into(&self, row: &mut Vec<f32>, entity_size: usize, max_entities: usize) {
+        row.extend_from_slice(&self.self_features);
+
+        for entity in self.entities.iter().take(max_entities) {
+            let start = row.len();
+            row.extend(entity.iter().take(entity_size));
+            row.resize(start + entity_size, 0.);
+        }
+        row.resize(
+            row.len() + entity_size * max_entities.saturating_sub(self.entities.len()),
+            0.,
+        );
+
+        row.extend(
+            self.mask(max_entities)
+                .into_iter()
+                .map(|real| f32::from(u8::from(real))),
+        );
+    }
+}
+
+/// Builds structured observations, one `EntityObservation` per car in the order of `GameStateA::cars`.
+///
+/// Wrap it in a `FlattenedEntityObs` to use it as an env's `Obs`
+pub trait EntityObs<SI> {
+    fn self_size(&self) -> usize;
+    fn entity_size(&self) -> usize;
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI);
+    fn build_entity_obs(
+        &mut self,
+        ctx: &StepContext,
+        shared_info: &mut SI,
+    ) -> Vec<EntityObservation>;
+    /// The most entities an agent can see with `agents` in the arena, every other car by default
+    fn max_entities(&self, agents: &AgentMap) -> usize {
+        agents.len().saturating_sub(1)
+    }
+}
+
+/// The same values as `AdvancedObs`, with every other car as an entity instead of in a padded slot.
+///
+/// The self features are the ball, the car's previous controls, the boost pads, the ball relative to the car
+/// and the car itself. Each entity is the ball relative to a car, the car, its position and velocity
+/// relative to the observing car and whether it's a teammate, teammates first
+#[derive(Clone, Copy, Debug)]
+pub struct DefaultEntityObs {
+    /// Boost pads in the game mode, see `game_modes::num_boost_pads`. Extra pads are cut and missing ones are zero
+    pub num_pads: usize,
+    /// Observe how long until each pad is back instead of only whether it's active
+    pub pad_timers: bool,
+}
+
+impl Default for DefaultEntityObs {
+    fn default() -> Self {
+        Self {
+            num_pads: SOCCAR_BOOST_PADS,
+            pad_timers: false,
+        }
+    }
+}
+
+impl DefaultEntityObs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_num_pads(mut self, num_pads: usize) -> Self {
+        self.num_pads = num_pads;
+        self
+    }
+
+    pub fn with_pad_timers(mut self, pad_timers: bool) -> Self {
+        self.pad_timers = pad_timers;
+        self
+    }
+}
+
+impl<SI> EntityObs<SI> for DefaultEntityObs {
+    fn self_size(&self) -> usize {
+        BALL_OBS + PREV_ACTION_OBS + self.num_pads + RELATIVE_OBS + CAR_OBS
+    }
+
+    fn entity_size(&self) -> usize {
+        RELATIVE_OBS + CAR_OBS + RELATIVE_OBS + 1
+    }
+
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn build_entity_obs(
+        &mut self,
+        ctx: &StepContext,
+        _shared_info: &mut SI,
+    ) -> Vec<EntityObservation> {
+        let state = ctx.state;
+        let self_size = <Self as EntityObs<SI>>::self_size(self);
+        let entity_size = <Self as EntityObs<SI>>::entity_size(self);
+
+        state
+            .cars
+            .iter()
+            .map(|car| {
+                let team = car.team;
+                let mut self_features = Vec::with_capacity(self_size);
+                push_ball(&mut self_features, state, team);
+                push_prev_action(&mut self_features, car);
+                push_pads(
+                    &mut self_features,
+                    state,
+                    team,
+                    self.num_pads,
+                    self.pad_timers,
+                );
+                push_car_with_ball(&mut self_features, state, car, team);
+
+                let (allies, enemies) = others(state, car);
+                let entities = allies
+                    .iter()
+                    .map(|other| (other, true))
+                    .chain(enemies.iter().map(|other| (other, false)))
+                    .map(|(other, is_teammate)| {
+                        let mut entity = Vec::with_capacity(entity_size);
+                        push_car_with_ball(&mut entity, state, other, team);
+                        push_relative(&mut entity, team, other.state.pos, other.state.vel, car);
+                        entity.push(f32::from(u8::from(is_teammate)));
+                        entity
+                    })
+                    .collect();
+
+                EntityObservation {
+                    self_features,
+                    entities,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Flattens an `EntityObs` into rows an env can send: the self features,
+/// then `max_entities` zero padded entities and then a mask that's 1 for the real ones.
+///
+/// Split the rows back up with the sizes of the `EntityObs` on the learner's side
+#[derive(Clone, Copy, Debug)]
+pub struct FlattenedEntityObs<E> {
+    pub obs: E,
+    pub max_entities: usize,
+}
+
+impl<E> FlattenedEntityObs<E> {
+    pub fn new(obs: E, max_entities: usize) -> Self {
+        Self { obs, max_entities }
+    }
+}
+
+impl<SI, E: EntityObs<SI>> Obs<SI> for FlattenedEntityObs<E> {
+    fn get_obs_space(&self, _agent_id: AgentId, _shared_info: &SI) -> usize {
+        self.obs.self_size() + (self.obs.entity_size() + 1) * self.max_entities
+    }
+
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        self.obs.reset(initial_state, shared_info);
+    }
+
+    fn build_obs(&mut self, ctx: &StepContext, shared_info: &mut SI) -> FullObs {
+        let mut obs = FullObs::new();
+        <Self as Obs<SI>>::build_obs_into(self, ctx, shared_info, &mut obs);
+        obs
+    }
+
+    fn build_obs_into(&mut self, ctx: &StepContext, shared_info: &mut SI, obs: &mut FullObs) {
+        let size = <Self as Obs<SI>>::get_obs_space(self, AgentId(0), shared_info);
+        let entity_size = self.obs.entity_size();
+        let observations = self.obs.build_entity_obs(ctx, shared_info);
+
+        obs.resize_with(observations.len(), Vec::new);
+        for (row, observation) in obs.iter_mut().zip(&observations) {
+            row.clear();
+            row.reserve(size);
+            observation.flatten_into(row, entity_size, self.max_entities);
+        }
+    }
+
+    fn validate(&self, agents: &AgentMap, _shared_info: &SI) -> Result<()> {
+        let entities = self.obs.max_entities(agents);
+        if entities > self.max_entities {
+            return Err(Error::Invalid(format!(
+                "Obs is padded for {} entities, but agents can see {entities}",
+                self.max_entities
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Running mean and variance of every observation value, updated with Welford's algorithm
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "config", derive(Serialize, Deserialize))]
+pub struct RunningStats {
+    pub count: Vec<u64>,
+    pub mean: Vec<f64>,
+    /// Sum of the squared differences from the mean
+    pub m2: Vec<f64>,
+}
+
+impl RunningStats {
+    pub fn update(&mut self, values: &[f32]) {
+        if self.mean.len() < values.len() {
+            self.count.resize(values.len(), 0);
+            self.mean.resize(values.len(), 0.);
+            self.m2.resize(values.len(), 0.);
+        }
+
+        for (i, &value) in values.iter().enumerate() {
+            let value = f64::from(value);
+            self.count[i] += 1;
+            let delta = value - self.mean[i];
+            self.mean[i] += delta / self.count[i] as f64;
+            self.m2[i] += delta * (value - self.mean[i]);
+        }
+    }
+
+    pub fn variance(&self, index: usize) -> f64 {
+        match self.count.get(index) {
+            Some(&count) if count > 0 => self.m2[index] / count as f64,
+            _ => 1.,
+        }
+    }
+
+    #[cfg(feature = "config")]
+    pub fn from_yaml(text: &str) -> Result<Self> {
+        serde_yaml::from_str(text).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    #[cfg(feature = "config")]
+    pub fn to_yaml(&self) -> Result<String> {
+        serde_yaml::to_string(self).map_err(|e| Error::Config(e.to_string()))
+    }
+}
+
+/// Normalizes the observations of `obs` to zero mean and unit variance per value,
+/// from running statistics of every observation it built so far.
+///
+/// Freeze the statistics for evaluation, and save them with `stats` to use them again after a restart
+pub struct NormalizedObs<O> {
+    pub obs: O,
+    stats: RunningStats,
+    frozen: bool,
+    /// Normalized values are clipped to this range around zero
+    pub clip: f32,
+}
+
+impl<O> NormalizedObs<O> {
+    pub fn new(obs: O) -> Self {
+        Self {
+            obs,
+            stats: RunningStats::default(),
+            frozen: false,
+            clip: 5.,
+        }
+    }
+
+    /// Start from statistics of an earlier run
+    pub fn with_stats(mut self, stats: RunningStats) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    pub fn with_clip(mut self, clip: f32) -> Self {
+        self.clip = clip;
+        self
+    }
+
+    pub fn stats(&self) -> &RunningStats {
+        &self.stats
+    }
+
+    /// Stop or resume updating the statistics, the observations are still normalized
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    fn normalize(&self, row: &mut [f32]) {
+        for (i, value) in row.iter_mut().enumerate() {
+            let mean = self.stats.mean.get(i).copied().unwrap_or_default();
+            let std = (self.stats.variance(i) + 1e-8).sqrt();
+            *value = (((f64::from(*value) - mean) / std) as f32).clamp(-self.clip, self.clip);
+        }
+    }
+}
+
+impl<SI, O: Obs<SI>> Obs<SI> for NormalizedObs<O> {
+    fn get_obs_space(&self, agent_id: AgentId, shared_info: &SI) -> usize {
+        self.obs.get_obs_space(agent_id, shared_info)
+    }
+
+    fn get_obs_space_type(&self, agent_id: AgentId, shared_info: &SI) -> SpaceType {
+        let len = self.obs.get_obs_space(agent_id, shared_info);
+        SpaceType::Box {
+            low: -self.clip,
+            high: self.clip,
+            shape: vec![len],
+        }
+    }
+
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        self.obs.reset(initial_state, shared_info);
+    }
+
+    fn build_obs(&mut self, ctx: &StepContext, shared_info: &mut SI) -> FullObs {
+        let mut obs = FullObs::new();
+        self.build_obs_into(ctx, shared_info, &mut obs);
+        obs
+    }
+
+    fn build_obs_into(&mut self, ctx: &StepContext, shared_info: &mut SI, obs: &mut FullObs) {
+        self.obs.build_obs_into(ctx, shared_info, obs);
+
+        if !self.frozen {
+            for row in obs.iter() {
+                self.stats.update(row);
+            }
+        }
+        for row in obs.iter_mut() {
+            self.normalize(row);
+        }
+    }
+
+    fn validate(&self, agents: &AgentMap, shared_info: &SI) -> Result<()> {
+        self.obs.validate(agents, shared_info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{NoSharedInfo, ScriptedEpisode, StateBuilder};
+    use glam::Mat3A;
+    use rocketsim_rs::glam_ext::BoostPadA;
+
+    fn build(obs: &mut impl Obs<()>, state: &GameStateA) -> FullObs {
+        obs.reset(state, &mut ());
+        obs.build_obs(&StepContext::new(state), &mut ())
+    }
+
+    fn pad(is_active: bool) -> BoostPadA {
+        let mut pad = BoostPadA::default();
+        pad.state.is_active = is_active;
+        pad
+    }
+
+    /// A blue car and an orange car on opposite sides of a ball that's standing on the center spot
+    fn mirrored_1v1() -> GameStateA {
+        let mut state = StateBuilder::new()
+            .with_ball(Vec3A::new(0., 0., 93.), Vec3A::new(0., 0., 300.))
+            .with_car_info(Team::Blue, |car| {
+                car.state.pos = Vec3A::new(1000., -2000., 17.);
+                car.state.vel = Vec3A::new(100., 200., 0.);
+                car.state.ang_vel = Vec3A::new(0., 0., 1.);
+                car.state.rot_mat = Mat3A::IDENTITY;
+                car.state.boost = 50.;
+            })
+            .with_car_info(Team::Orange, |car| {
+                car.state.pos = Vec3A::new(-1000., 2000., 17.);
+                car.state.vel = Vec3A::new(-100., -200., 0.);
+                car.state.ang_vel = Vec3A::new(0., 0., 1.);
+                car.state.rot_mat = Mat3A::from_cols(-Vec3A::X, -Vec3A::Y, Vec3A::Z);
+                car.state.boost = 50.;
+            })
+            .build();
+        state.pads = vec![pad(true), pad(false), pad(false)];
+        state
+    }
+
+    #[test]
+    fn default_obs_size_matches_rlgym() {
+        // ball, previous action, pads and 19 values per car
+        assert_eq!(DefaultObs::new(1).get_obs_space(AgentId(0), &()), 89);
+        assert_eq!(DefaultObs::new(2).get_obs_space(AgentId(0), &()), 127);
+        assert_eq!(DefaultObs::new(3).get_obs_space(AgentId(0), &()), 165);
+        // plus the ball relative to every car and 6 more values per other car
+        assert_eq!(AdvancedObs::new(1).get_obs_space(AgentId(0), &()), 107);
+        assert_eq!(AdvancedObs::new(2).get_obs_space(AgentId(0), &()), 169);
+    }
+
+    #[test]
+    fn advanced_obs_matches_rlgym() {
+        let state = StateBuilder::new()
+            .with_ball(Vec3A::new(0., 0., 93.), Vec3A::ZERO)
+            .with_car_info(Team::Blue, |car| {
+                car.state.pos = Vec3A::new(0., -2300., 17.);
+                car.state.vel = Vec3A::new(0., 1150., 0.);
+                car.state.rot_mat = Mat3A::IDENTITY;
+                car.state.boost = 50.;
+                car.state.is_on_ground = true;
+            })
+            .with_car_info(Team::Orange, |car| {
+                car.state.pos = Vec3A::new(0., 2300., 17.);
+                car.state.vel = Vec3A::new(0., -2300., 0.);
+                car.state.rot_mat = Mat3A::from_cols(-Vec3A::Y, Vec3A::X, Vec3A::Z);
+                car.state.boost = 100.;
+            })
+            .build();
+        let obs = build(&mut AdvancedObs::new(1), &state);
+        assert_eq!(obs[0].len(), 107);
+        assert_eq!(obs[1].len(), 107);
+
+        // what RLGym's AdvancedObs builds for the blue car in the same state
+        let z = 17. / 2300.;
+        let ball_z = 93. / 2300.;
+        let mut expected = vec![0., 0., ball_z, 0., 0., 0., 0., 0., 0.];
+        expected.extend([0.; 8]);
+        expected.extend([0.; 34]);
+        // the ball relative to the car, then the car
+        expected.extend([0., 1., ball_z - z, 0., -0.5, 0.]);
+        expected.extend([0., -1., z, 1., 0., 0., 0., 0., 1., 0., 0.5, 0.]);
+        expected.extend([0., 0., 0., 0.5, 1., 1., 0.]);
+        // the same for the opponent, then the opponent relative to the car
+        expected.extend([0., -1., ball_z - z, 0., 1., 0.]);
+        expected.extend([0., 1., z, 0., -1., 0., 0., 0., 1., 0., -1., 0.]);
+        expected.extend([0., 0., 0., 1., 0., 1., 0.]);
+        expected.extend([0., 2., 0., 0., -1.5, 0.]);
+
+        assert_eq!(expected.len(), 107);
+        for (i, (actual, expected)) in obs[0].iter().zip(&expected).enumerate() {
+            assert!(
+                (actual - expected).abs() < 1e-6,
+                "value {i} is {actual}, expected {expected}"
+            );
+        }
+
+        // orange sees the ball in front of it too
+        assert!(obs[1][51].abs() < 1e-6);
+        assert!((obs[1][52] - 1.).abs() < 1e-6);
+        assert!((obs[1][55] - -1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn default_obs_layout() {
+        let mut state = mirrored_1v1();
+        state.cars[0].state.is_on_ground = true;
+        state.cars[0].state.last_controls.throttle = 1.;
+        state.cars[0].state.last_controls.jump = true;
+
+        let obs = build(&mut DefaultObs::new(1), &state);
+        let row = &obs[0];
+        assert_eq!(row.len(), 89);
+
+        assert_eq!(row[2], 93. * POS_COEF);
+        assert_eq!(row[8], 0.);
+        assert_eq!(row[9..17], [1., 0., 0., 0., 0., 1., 0., 0.]);
+        assert_eq!(row[17..20], [1., 0., 0.]);
+        assert!(row[20..51].iter().all(|&pad| pad == 0.));
+
+        let car = &row[51..70];
+        assert_eq!(
+            car[..3],
+            [1000. * POS_COEF, -2000. * POS_COEF, 17. * POS_COEF]
+        );
+        assert_eq!(car[3..9], [1., 0., 0., 0., 0., 1.]);
+        assert_eq!(car[9..12], [100. * LIN_VEL_COEF, 200. * LIN_VEL_COEF, 0.]);
+        assert_eq!(car[12..15], [0., 0., ANG_VEL_COEF]);
+        assert_eq!(car[15..], [0.5, 1., 1., 0.]);
+
+        let opponent = &row[70..89];
+        assert_eq!(opponent[..2], [-1000. * POS_COEF, 2000. * POS_COEF]);
+    }
+
+    #[test]
+    fn orange_is_mirrored() {
+        let obs = build(&mut DefaultObs::new(1).with_num_pads(4), &mirrored_1v1());
+
+        // pads are reversed for orange, everything else sees the same field
+        assert_eq!(obs[0][17..21], [1., 0., 0., 0.]);
+        assert_eq!(obs[1][17..21], [0., 0., 1., 0.]);
+        assert_eq!(obs[0][..17], obs[1][..17]);
+        assert_eq!(obs[0][21..], obs[1][21..]);
+
+        let mut state = mirrored_1v1();
+        state.ball.pos = Vec3A::new(500., 1000., 93.);
+        let obs = build(&mut DefaultObs::new(1), &state);
+        assert_eq!(
+            obs[1][..3],
+            [-500. * POS_COEF, -1000. * POS_COEF, 93. * POS_COEF]
+        );
+    }
+
+    #[test]
+    fn default_obs_pads_missing_cars() {
+        let obs = build(&mut DefaultObs::new(3), &mirrored_1v1());
+
+        for row in &obs {
+            assert_eq!(row.len(), 165);
+            // teammates
+            assert!(row[70..108].iter().all(|&value| value == 0.));
+            assert!(row[108..127].iter().any(|&value| value != 0.));
+            // other opponents
+            assert!(row[127..].iter().all(|&value| value == 0.));
+        }
+
+        let state = StateBuilder::new()
+            .with_car(Team::Blue, Vec3A::ZERO)
+            .with_car(Team::Blue, Vec3A::ZERO)
+            .build();
+        let agents = AgentMap::new(&state);
+        assert!(DefaultObs::new(1).validate(&agents, &()).is_err());
+        assert!(DefaultObs::new(2).validate(&agents, &()).is_ok());
+    }
+
+    #[test]
+    fn team_padding() {
+        let state = StateBuilder::new()
+            .with_car(Team::Orange, Vec3A::ZERO)
+            .with_car(Team::Blue, Vec3A::ZERO)
+            .with_car(Team::Orange, Vec3A::ZERO)
+            .build();
+        let padding = TeamPadding::new(2, 2).with_mask(true);
+        assert_eq!(padding.size(), 12);
+
+        let mut row = Vec::new();
+        // one value too many, which is cut
+        padding.push_cars(&mut row, &state, &state.cars[0], |row, car| {
+            row.extend([car.id as f32; 3]);
+        });
+        assert_eq!(
+            row,
+            [1., 1., 3., 3., 2., 2., 0., 0., 1., 1., 1., 0.],
+            "self, teammate, opponent, padding and then the mask"
+        );
+
+        let mut row = Vec::new();
+        let padding_without_self = padding.with_self_slot(false);
+        assert_eq!(padding_without_self.size(), 9);
+        padding_without_self.push_cars(&mut row, &state, &state.cars[0], |row, car| {
+            row.extend([car.id as f32; 2]);
+        });
+        assert_eq!(row, [3., 3., 2., 2., 0., 0., 1., 1., 0.]);
+
+        let agents = AgentMap::new(&state);
+        assert!(TeamPadding::new(1, 2).validate(&agents).is_err());
+        assert!(padding.validate(&agents).is_ok());
+    }
+
+    #[test]
+    fn flattened_entity_obs() {
+        let state = StateBuilder::new()
+            .with_car(Team::Blue, Vec3A::new(0., -1000., 17.))
+            .with_car(Team::Orange, Vec3A::new(0., 1000., 17.))
+            .with_car(Team::Blue, Vec3A::new(500., -1000., 17.))
+            .build();
+        let entity_obs = DefaultEntityObs::new();
+        let self_size = EntityObs::<()>::self_size(&entity_obs);
+        let entity_size = EntityObs::<()>::entity_size(&entity_obs);
+        assert_eq!((self_size, entity_size), (76, 32));
+
+        let mut obs = FlattenedEntityObs::new(entity_obs, 3);
+        assert_eq!(obs.get_obs_space(AgentId(0), &()), 76 + 33 * 3);
+
+        let rows = build(&mut obs, &state);
+        assert_eq!(rows.len(), 3);
+        for row in &rows {
+            assert_eq!(row.len(), 175);
+            assert_eq!(row[row.len() - 3..], [1., 1., 0.]);
+            // the last entity is padding
+            assert!(row[76 + 32 * 2..76 + 32 * 3]
+                .iter()
+                .all(|&value| value == 0.));
+        }
+
+        // the teammate comes first, flagged as one
+        let first = &rows[0][76..76 + 32];
+        assert_eq!(first[31], 1.);
+        assert_eq!(first[25..28], [500. * POS_COEF, 0., 0.]);
+        assert_eq!(rows[0][76 + 32 + 31], 0.);
+        // the orange car sees both blue cars as opponents
+        assert_eq!(rows[1][76 + 31], 0.);
+        assert_eq!(rows[1][76 + 32 + 31], 0.);
+
+        let agents = AgentMap::new(&state);
+        assert!(Obs::<()>::validate(&obs, &agents, &()).is_ok());
+        assert!(
+            Obs::<()>::validate(&FlattenedEntityObs::new(entity_obs, 1), &agents, &()).is_err()
+        );
+    }
+
+    #[test]
+    fn flattening_cuts_extra_entities() {
+        let observation = EntityObservation {
+            self_features: vec![9.],
+            entities: vec![vec![1., 2., 3.], vec![4.], vec![5., 6.]],
+        };
+
+        let mut row = Vec::new();
+        observation.flatten_into(&mut row, 2, 2);
+        assert_eq!(row, [9., 1., 2., 4., 0., 1., 1.]);
+    }
+
+    #[test]
+    fn welford_matches_two_pass_statistics() {
+        let mut stats = RunningStats::default();
+        // a big offset is where the naive sum of squares loses precision
+        for value in [4., 7., 13., 16.] {
+            stats.update(&[1e6 + value, value]);
+        }
+
+        assert_eq!(stats.count, [4, 4]);
+        assert!((stats.mean[0] - (1e6 + 10.)).abs() < 1e-9);
+        assert!((stats.mean[1] - 10.).abs() < 1e-12);
+        assert!((stats.variance(0) - 22.5).abs() < 1e-9);
+        assert!((stats.variance(1) - 22.5).abs() < 1e-12);
+        // nothing seen yet
+        assert_eq!(stats.variance(2), 1.);
+
+        // shorter rows only update the values they have
+        stats.update(&[1e6 + 10.]);
+        assert_eq!(stats.count, [5, 4]);
+    }
+
+    /// Observes the tick count, the same for every car
+    struct TickObs;
+
+    impl Obs<()> for TickObs {
+        fn get_obs_space(&self, _agent_id: AgentId, _shared_info: &()) -> usize {
+            1
+        }
+
+        fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut ()) {}
+
+        fn build_obs(&mut self, ctx: &StepContext, _shared_info: &mut ()) -> FullObs {
+            vec![vec![ctx.state.tick_count as f32]; ctx.state.cars.len()]
+        }
+    }
+
+    #[test]
+    fn normalized_obs() {
+        let states = [0, 8, 16]
+            .into_iter()
+            .map(|tick| {
+                StateBuilder::new()
+                    .with_tick_count(tick)
+                    .with_car(Team::Blue, Vec3A::ZERO)
+                    .build()
+            })
+            .collect();
+
+        let mut obs = NormalizedObs::new(TickObs).with_clip(1.);
+        let rows = ScriptedEpisode::new(states, NoSharedInfo, ()).obs(&mut obs);
+
+        assert_eq!(rows[0], [[0.]]);
+        // 16 is 1.22 standard deviations above the mean of 8, clipped to 1
+        assert_eq!(rows[2], [[1.]]);
+        assert_eq!(obs.stats().count, [3]);
+        assert!((obs.stats().variance(0) - 128. / 3.).abs() < 1e-9);
+
+        obs.set_frozen(true);
+        let state = StateBuilder::new()
+            .with_tick_count(8)
+            .with_car(Team::Blue, Vec3A::ZERO)
+            .build();
+        assert_eq!(build(&mut obs, &state), [[0.]]);
+        assert_eq!(obs.stats().count, [3]);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{
+    opponent_pool::{Checkpoint, PolicyLoader},
+    remote::FromRemoteActions,
+    Action, AgentId, MaybeSend, Policy, Result,
+};
+use rocketsim_rs::{glam_ext::GameStateA, sim::CarControls};
+use std::{io, path::Path};
+use tract_onnx::prelude::*;
+
+type Model = TypedRunnableModel<TypedModel>;
+
+fn model_error(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+/// What the model's output means
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnnxOutput {
+    /// The output is the action itself, e.g. for `ContinuousAction`
+    Actions,
+    /// The output has one logit per action, the highest one is picked, e.g. for `LookupTableAction`
+    #[default]
+    Argmax,
+}
+
+/// Drives cars with a frozen ONNX checkpoint, e.g. an opponent for self-play evaluation.
+///
+/// The model is given one agent's observation as a `[1, obs_size]` tensor
+/// and its output goes through `action` like the trainer's actions would
+pub struct OnnxPolicy<ACT> {
+    model: Model,
+    action: ACT,
+    output: OnnxOutput,
+}
+
+impl<ACT> OnnxPolicy<ACT> {
+    pub fn load(
+        path: impl AsRef<Path>,
+        obs_size: usize,
+        action: ACT,
+        output: OnnxOutput,
+    ) -> Result<Self> {
+        let model = tract_onnx::onnx()
+            .model_for_path(path)
+            .and_then(|model| {
+                model.with_input_fact(
+                    0,
+                    InferenceFact::dt_shape(f32::datum_type(), tvec!(1, obs_size)),
+                )
+            })
+            .and_then(|model| model.into_optimized())
+            .and_then(|model| model.into_runnable())
+            .map_err(model_error)?;
+
+        Ok(Self {
+            model,
+            action,
+            output,
+        })
+    }
+
+    fn run(&self, obs: &[f32]) -> TractResult<Vec<f32>> {
+        let input = Tensor::from_shape(&[1, obs.len()], obs)?;
+        let outputs = self.model.run(tvec!(input.into()))?;
+        let output = outputs[0].as_slice::<f32>()?;
+
+        Ok(match self.output {
+            OnnxOutput::Actions => output.to_vec(),
+            OnnxOutput::Argmax => {
+                let best = output
+                    .iter()
+                    .enumerate()
+                    .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .map_or(0, |(index, _)| index);
+                vec![best as f32]
+            }
+        })
+    }
+}
+
+impl<SI, ACT> Policy<SI> for OnnxPolicy<ACT>
+where
+    ACT: Action<SI> + MaybeSend,
+    ACT::Input: FromRemoteActions,
+{
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        self.action.reset(initial_state, shared_info);
+    }
+
+    fn get_controls(
+        &mut self,
+        agent_id: AgentId,
+        obs: &[f32],
+        state: &GameStateA,
+        shared_info: &mut SI,
+    ) -> CarControls {
+        let action = match self.run(obs) {
+            Ok(action) => action,
+            Err(e) => {
+                log::error!("ONNX policy failed for agent {agent_id:?}: {e}");
+                return CarControls::default();
+            }
+        };
+
+        self.action
+            .parse_actions(ACT::Input::from_remote(vec![action]), state, shared_info)
+            .into_iter()
+            .next()
+            .unwrap_or_default()
+    }
+}
+
+/// Loads `OnnxPolicy`s from the checkpoints of an `OpponentPool`, to play against past versions of a bot
+pub struct OnnxPolicyLoader<ACT> {
+    obs_size: usize,
+    action: ACT,
+    output: OnnxOutput,
+}
+
+impl<ACT> OnnxPolicyLoader<ACT> {
+    /// Every loaded policy gets a clone of `action`
+    pub fn new(obs_size: usize, action: ACT, output: OnnxOutput) -> Self {
+        Self {
+            obs_size,
+            action,
+            output,
+        }
+    }
+}
+
+impl<SI, ACT> PolicyLoader<SI> for OnnxPolicyLoader<ACT>
+where
+    ACT: Action<SI> + Clone + MaybeSend + 'static,
+    ACT::Input: FromRemoteActions,
+{
+    fn load(&mut self, checkpoint: &Checkpoint) -> Result<Box<dyn Policy<SI>>> {
+        Ok(Box::new(OnnxPolicy::load(
+            &checkpoint.path,
+            self.obs_size,
+            self.action.clone(),
+            self.output,
+        )?))
+    }
+}
@@ -0,0 +1,444 @@
+use crate::{
+    game_modes::scoring_team,
+    league::SharedLeague,
+    rating::{Elo, MatchOutcome},
+    AgentId, MaybeSend, Policy, Result,
+};
+use rocketsim_rs::{
+    glam_ext::GameStateA,
+    sim::{CarControls, GameMode, Team},
+};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// A frozen policy snapshot that can be loaded as an opponent
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    pub id: String,
+    /// Where the weights are stored, the pool doesn't care about the format (ONNX, safetensors, etc.)
+    pub path: PathBuf,
+    pub rating: f32,
+    pub games_played: u32,
+    /// Games the checkpoint won against the main agent
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SamplingStrategy {
+    /// Every checkpoint is equally likely
+    Uniform,
+    /// Checkpoints that are more likely to beat the main agent are picked more often,
+    /// the chance of winning is raised to `exponent` to get the sampling weight
+    Prioritized { exponent: f32 },
+    /// Pick the newest checkpoint with `latest_probability`, otherwise pick uniformly from the rest
+    LatestHeavy { latest_probability: f32 },
+    /// Checkpoints rated close to the main agent are picked more often,
+    /// the sampling weight halves every `scale` Elo points of difference
+    Matchmaking { scale: f32 },
+}
+
+/// Stores frozen checkpoints of past policies, rates them against the main agent and samples opponents from them
+pub struct OpponentPool {
+    checkpoints: Vec<Checkpoint>,
+    strategy: SamplingStrategy,
+    elo: Elo,
+    main_rating: f32,
+    max_size: Option<usize>,
+    rng: fastrand::Rng,
+}
+
+impl OpponentPool {
+    pub fn new(strategy: SamplingStrategy) -> Self {
+        Self {
+            checkpoints: Vec::new(),
+            strategy,
+            elo: Elo::default(),
+            main_rating: Elo::INITIAL_RATING,
+            max_size: None,
+            rng: fastrand::Rng::new(),
+        }
+    }
+
+    /// Load every file in `dir` as a checkpoint, ordered by file name so the newest should be last.
+    ///
+    /// The id of each checkpoint is its file stem
+    pub fn from_dir(dir: impl AsRef<Path>, strategy: SamplingStrategy) -> Result<Self> {
+        let mut pool = Self::new(strategy);
+        pool.refresh(dir)?;
+        Ok(pool)
+    }
+
+    /// Add the files in `dir` that aren't in the pool yet, e.g. checkpoints the trainer saved since the last call.
+    ///
+    /// New files are added in order of file name, returns how many there were
+    pub fn refresh(&mut self, dir: impl AsRef<Path>) -> Result<usize> {
+        let mut paths = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        paths.retain(|path| {
+            path.is_file()
+                && !self
+                    .checkpoints
+                    .iter()
+                    .any(|checkpoint| checkpoint.path == *path)
+        });
+        paths.sort();
+
+        let added = paths.len();
+        for path in paths {
+            let id = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            self.add(id, path);
+        }
+
+        Ok(added)
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = fastrand::Rng::with_seed(seed);
+        self
+    }
+
+    pub fn with_elo(mut self, elo: Elo) -> Self {
+        self.elo = elo;
+        self
+    }
+
+    /// Once the pool holds `max_size` checkpoints, adding a new one drops the oldest
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Add a snapshot of the main agent, it starts with the main agent's current rating
+    pub fn add(&mut self, id: impl Into<String>, path: impl Into<PathBuf>) {
+        if let Some(max_size) = self.max_size {
+            while self.checkpoints.len() >= max_size.max(1) {
+                self.checkpoints.remove(0);
+            }
+        }
+
+        self.checkpoints.push(Checkpoint {
+            id: id.into(),
+            path: path.into(),
+            rating: self.main_rating,
+            games_played: 0,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+        });
+    }
+
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Checkpoint> {
+        self.checkpoints
+            .iter()
+            .find(|checkpoint| checkpoint.id == id)
+    }
+
+    pub fn latest(&self) -> Option<&Checkpoint> {
+        self.checkpoints.last()
+    }
+
+    pub fn main_rating(&self) -> f32 {
+        self.main_rating
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checkpoints.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    /// Pick an opponent for the next episode according to the sampling strategy
+    pub fn sample(&mut self) -> Option<&Checkpoint> {
+        let index = match self.checkpoints.len() {
+            0 => return None,
+            1 => 0,
+            len => match self.strategy {
+                SamplingStrategy::Uniform => self.rng.usize(..len),
+                SamplingStrategy::Prioritized { exponent } => {
+                    let weights = self
+                        .checkpoints
+                        .iter()
+                        .map(|checkpoint| {
+                            Elo::expected_score(checkpoint.rating, self.main_rating).powf(exponent)
+                        })
+                        .collect::<Vec<_>>();
+                    weighted_index(&mut self.rng, &weights)
+                }
+                SamplingStrategy::LatestHeavy { latest_probability } => {
+                    if self.rng.f32() < latest_probability {
+                        len - 1
+                    } else {
+                        self.rng.usize(..len - 1)
+                    }
+                }
+                SamplingStrategy::Matchmaking { scale } => {
+                    let weights = self
+                        .checkpoints
+                        .iter()
+                        .map(|checkpoint| {
+                            let difference = (checkpoint.rating - self.main_rating).abs();
+                            0.5f32.powf(difference / scale.max(f32::EPSILON))
+                        })
+                        .collect::<Vec<_>>();
+                    weighted_index(&mut self.rng, &weights)
+                }
+            },
+        };
+
+        Some(&self.checkpoints[index])
+    }
+
+    /// Update the ratings after the main agent played against the checkpoint `id`,
+    /// `outcome` is from the point of view of the main agent.
+    ///
+    /// Returns `false` if there's no checkpoint with that id
+    pub fn record_result(&mut self, id: &str, outcome: MatchOutcome) -> bool {
+        let Some(checkpoint) = self
+            .checkpoints
+            .iter_mut()
+            .find(|checkpoint| checkpoint.id == id)
+        else {
+            return false;
+        };
+
+        self.elo
+            .update(&mut self.main_rating, &mut checkpoint.rating, outcome);
+        checkpoint.games_played += 1;
+        match outcome {
+            MatchOutcome::Win => checkpoint.losses += 1,
+            MatchOutcome::Loss => checkpoint.wins += 1,
+            MatchOutcome::Draw => checkpoint.draws += 1,
+        }
+
+        true
+    }
+
+    /// Update the ratings after the main agent played against an opponent that isn't in the pool
+    pub fn rate_against_main(&mut self, opponent_rating: &mut f32, outcome: MatchOutcome) {
+        self.elo
+            .update(&mut self.main_rating, opponent_rating, outcome);
+    }
+}
+
+/// Sample an index with probability proportional to its weight, falls back to uniform if all weights are zero
+pub(crate) fn weighted_index(rng: &mut fastrand::Rng, weights: &[f32]) -> usize {
+    let total = weights.iter().sum::<f32>();
+    if total.is_nan() || total <= 0. {
+        return rng.usize(..weights.len());
+    }
+
+    let mut target = rng.f32() * total;
+    for (i, weight) in weights.iter().enumerate() {
+        if target < *weight {
+            return i;
+        }
+
+        target -= weight;
+    }
+
+    weights.len() - 1
+}
+
+/// Turns a checkpoint into a policy that can drive cars, e.g. by loading an ONNX model
+pub trait PolicyLoader<SI>: MaybeSend {
+    fn load(&mut self, checkpoint: &Checkpoint) -> Result<Box<dyn Policy<SI>>>;
+}
+
+/// Hands every car on one team to a checkpoint sampled from an `OpponentPool` at the start of each episode.
+///
+/// Checkpoints are only loaded the first time they're sampled, after that the loaded policy is reused.
+/// With `with_league`, the opponents come from a league's matchups instead
+pub struct PastSelfOpponents<SI> {
+    pub pool: OpponentPool,
+    loader: Box<dyn PolicyLoader<SI>>,
+    team: Team,
+    cache: HashMap<String, Box<dyn Policy<SI>>>,
+    current: Option<String>,
+    agent_ids: Vec<AgentId>,
+    league: Option<(SharedLeague, usize)>,
+    game_mode: GameMode,
+    /// Goals of the main agent and of the opponents in the current episode
+    goals: (u32, u32),
+    was_scored: bool,
+}
+
+impl<SI> PastSelfOpponents<SI> {
+    /// Controls the orange team by default
+    pub fn new(pool: OpponentPool, loader: Box<dyn PolicyLoader<SI>>) -> Self {
+        Self {
+            pool,
+            loader,
+            team: Team::Orange,
+            cache: HashMap::new(),
+            current: None,
+            agent_ids: Vec::new(),
+            league: None,
+            game_mode: GameMode::SOCCAR,
+            goals: (0, 0),
+            was_scored: false,
+        }
+    }
+
+    /// Play the matchups `league` assigns to env `env_index` instead of sampling from `pool`,
+    /// and report the result of every episode to it when the episode ends.
+    ///
+    /// Give every env of a `VecEnv` or `EnvPool` a clone of the same league and its own index in `make_env`,
+    /// after calling `LeagueScheduler::assign` with the number of envs
+    pub fn with_league(mut self, league: SharedLeague, env_index: usize) -> Self {
+        self.league = Some((league, env_index));
+        self
+    }
+
+    /// The game mode the env plays, to tell when a goal is scored for league results. Soccar by default
+    pub fn with_game_mode(mut self, game_mode: GameMode) -> Self {
+        self.game_mode = game_mode;
+        self
+    }
+
+    pub fn with_team(mut self, team: Team) -> Self {
+        self.team = team;
+        self
+    }
+
+    pub fn team(&self) -> Team {
+        self.team
+    }
+
+    /// Id of the checkpoint playing in the current episode
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    /// Whether the car with the id `agent_id` is driven by the current checkpoint
+    pub fn controls(&self, agent_id: AgentId) -> bool {
+        self.agent_ids.contains(&agent_id)
+    }
+
+    /// Sample the opponent for a new episode and take control of every car on the team in `initial_state`
+    pub fn start_episode(
+        &mut self,
+        initial_state: &GameStateA,
+        shared_info: &mut SI,
+    ) -> Result<()> {
+        self.current = None;
+        self.agent_ids.clear();
+        self.goals = (0, 0);
+        self.was_scored = false;
+
+        let sampled;
+        let checkpoint = match &self.league {
+            Some((league, env_index)) => {
+                let league = league
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                sampled = league.opponent(*env_index)?;
+                sampled.as_ref()
+            }
+            None => {
+                let pool = &self.pool;
+                self.cache.retain(|id, _| pool.get(id).is_some());
+                self.pool.sample()
+            }
+        };
+
+        // self-play, the trainer drives every car
+        let Some(checkpoint) = checkpoint else {
+            return Ok(());
+        };
+
+        let policy = match self.cache.entry(checkpoint.id.clone()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(self.loader.load(checkpoint)?),
+        };
+        policy.reset(initial_state, shared_info);
+
+        self.current = Some(checkpoint.id.clone());
+        self.agent_ids.extend(
+            initial_state
+                .cars
+                .iter()
+                .filter(|car| car.team == self.team)
+                .map(AgentId::of),
+        );
+
+        Ok(())
+    }
+
+    /// `None` if the car isn't driven by the current checkpoint
+    pub fn get_controls(
+        &mut self,
+        agent_id: AgentId,
+        obs: &[f32],
+        state: &GameStateA,
+        shared_info: &mut SI,
+    ) -> Option<CarControls> {
+        if !self.controls(agent_id) {
+            return None;
+        }
+
+        let policy = self.cache.get_mut(self.current.as_ref()?)?;
+        Some(policy.get_controls(agent_id, obs, state, shared_info))
+    }
+
+    /// Count the goals of the episode and report its result to the league once it's `done`
+    pub(crate) fn end_step(&mut self, state: &GameStateA, done: bool) -> Result<()> {
+        let scorer = scoring_team(self.game_mode, state);
+        if let (Some(team), false) = (scorer, self.was_scored) {
+            if team == self.team {
+                self.goals.1 += 1;
+            } else {
+                self.goals.0 += 1;
+            }
+        }
+        self.was_scored = scorer.is_some();
+
+        let Some((league, env_index)) = &self.league else {
+            return Ok(());
+        };
+        if !done {
+            return Ok(());
+        }
+
+        let outcome = MatchOutcome::from_goals(self.goals.0, self.goals.1);
+        let mut league = league
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        league.record_result(*env_index, outcome)?;
+
+        Ok(())
+    }
+
+    /// Update the ratings with the outcome of the current episode from the point of view of the main agent.
+    ///
+    /// With a league, results are reported to it when episodes end and this does nothing
+    pub fn record_result(&mut self, outcome: MatchOutcome) -> bool {
+        if self.league.is_some() {
+            return false;
+        }
+
+        match &self.current {
+            Some(id) => self.pool.record_result(id, outcome),
+            None => false,
+        }
+    }
+
+    /// Drop all loaded policies, they'll be loaded again the next time they're sampled
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+}
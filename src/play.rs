@@ -0,0 +1,155 @@
+use crate::{
+    Action, AgentId, Env, Error, FullObs, Obs, Policy, RenderConfig, Result, Reward, Shared,
+    SharedInfoProvider, StateSetter, Terminal, Truncate,
+};
+use rocketsim_rs::sim::Team;
+
+/// Play against your own bot: opens RLViser and runs the env in real time,
+/// with a human driving one car and the trained policy driving the rest.
+///
+/// The human's input comes from any `Policy`, typically a `human::HumanAgent` reading a gamepad
+pub struct PlayMode<SI> {
+    human: Box<dyn Policy<SI>>,
+    human_team: Team,
+    max_episodes: Option<usize>,
+    render_config: RenderConfig,
+}
+
+impl<SI> PlayMode<SI> {
+    /// The human drives the first blue car by default
+    pub fn new(human: Box<dyn Policy<SI>>) -> Self {
+        Self {
+            human,
+            human_team: Team::Blue,
+            max_episodes: None,
+            render_config: RenderConfig::default(),
+        }
+    }
+
+    pub fn with_team(mut self, team: Team) -> Self {
+        self.human_team = team;
+        self
+    }
+
+    /// Stop after this many episodes instead of playing until RLViser is closed
+    pub fn with_max_episodes(mut self, max_episodes: usize) -> Self {
+        self.max_episodes = Some(max_episodes);
+        self
+    }
+
+    /// Where to find RLViser, if it's not `./rlviser` with the default ports
+    pub fn with_render_config(mut self, render_config: RenderConfig) -> Self {
+        self.render_config = render_config;
+        self
+    }
+
+    /// `policy` turns the observations of every car not driven by the human into actions
+    pub fn run<SS, SIP, OBS, ACT, REW, TERM, TRUNC>(
+        self,
+        env: &mut Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI>,
+        mut policy: impl FnMut(&FullObs) -> ACT::Input,
+    ) -> Result<()>
+    where
+        SS: StateSetter<SI>,
+        SIP: SharedInfoProvider<SI>,
+        OBS: Obs<SI>,
+        ACT: Action<SI>,
+        REW: Reward<SI>,
+        TERM: Terminal<SI>,
+        TRUNC: Truncate<SI>,
+    {
+        let Self {
+            human,
+            human_team,
+            max_episodes,
+            render_config,
+        } = self;
+
+        env.reset()?;
+        let mut human = Some(human);
+        let mut human_id = None;
+        let mut obs = take_control(env, human_team, &mut human, &mut human_id)?;
+
+        env.enable_rlviser(&render_config)?;
+
+        let mut clock = env.realtime_clock();
+        let mut episodes = 0;
+
+        loop {
+            // closing RLViser ends the game, or the loop would keep stepping with nobody watching
+            if !env.is_renderer_connected() {
+                log::info!("RLViser was closed, stopping");
+                break;
+            }
+
+            if !env.is_paused() {
+                let result = env.step(policy(&obs))?;
+
+                if result.is_terminal || result.truncated {
+                    episodes += 1;
+                    if max_episodes.map_or(false, |max_episodes| episodes >= max_episodes) {
+                        break;
+                    }
+
+                    env.reset()?;
+                    obs = take_control(env, human_team, &mut human, &mut human_id)?;
+                } else {
+                    obs = result.obs;
+                }
+            }
+
+            env.handle_incoming_states(clock.interval_mut())?;
+            clock.wait();
+        }
+
+        if let Some(id) = human_id {
+            env.remove_policy(id);
+        }
+        env.stop_rendering()
+    }
+}
+
+/// Make sure the human still drives a car after a reset, in case the car ids changed.
+///
+/// Returns the observations for the cars that aren't driven by the human
+fn take_control<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI>(
+    env: &mut Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI>,
+    team: Team,
+    human: &mut Option<Box<dyn Policy<SI>>>,
+    human_id: &mut Option<AgentId>,
+) -> Result<Shared<FullObs>>
+where
+    SS: StateSetter<SI>,
+    SIP: SharedInfoProvider<SI>,
+    OBS: Obs<SI>,
+    ACT: Action<SI>,
+    REW: Reward<SI>,
+    TERM: Terminal<SI>,
+    TRUNC: Truncate<SI>,
+{
+    let (Some(state), Some(obs)) = (env.last_state.clone(), env.last_obs.clone()) else {
+        return Err(Error::NotReset);
+    };
+    let still_exists = human_id.map_or(false, |id| {
+        state.cars.iter().any(|car| AgentId::of(car) == id)
+    });
+
+    if !still_exists {
+        if let Some(id) = human_id.take() {
+            *human = env.remove_policy(id);
+        }
+
+        let new_id = state
+            .cars
+            .iter()
+            .find(|car| car.team == team)
+            .map(AgentId::of);
+        if let (Some(id), Some(mut policy)) = (new_id, human.take()) {
+            policy.reset(&state, &mut env.shared_info);
+            env.set_policy(id, policy);
+            *human_id = Some(id);
+        }
+    }
+
+    Ok(env.external_obs(&state, &obs))
+}
@@ -0,0 +1,98 @@
+use crate::{AgentId, MaybeSend};
+use rocketsim_rs::{glam_ext::GameStateA, sim::CarControls};
+#[cfg(feature = "sync")]
+use std::sync::{Arc, Mutex, PoisonError};
+#[cfg(not(feature = "sync"))]
+use std::{cell::RefCell, rc::Rc};
+
+/// A policy with several owners, e.g. an env and a tournament keeping track of it.
+///
+/// `Rc<RefCell<P>>`, or `Arc<Mutex<P>>` with the `sync` feature
+#[cfg(not(feature = "sync"))]
+pub type SharedPolicy<P> = Rc<RefCell<P>>;
+#[cfg(feature = "sync")]
+pub type SharedPolicy<P> = Arc<Mutex<P>>;
+
+#[cfg(not(feature = "sync"))]
+pub fn share_policy<P>(policy: P) -> SharedPolicy<P> {
+    Rc::new(RefCell::new(policy))
+}
+
+#[cfg(feature = "sync")]
+pub fn share_policy<P>(policy: P) -> SharedPolicy<P> {
+    Arc::new(Mutex::new(policy))
+}
+
+/// Drives a car from inside the environment instead of from the trainer.
+///
+/// Register one on an `Env` with `Env::set_policy`,
+/// the cars it controls are then excluded from the actions, observations and rewards
+/// that are exchanged with the trainer.
+pub trait Policy<SI>: MaybeSend {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI);
+    /// `obs` is the observation built for `agent_id` from `state`
+    fn get_controls(
+        &mut self,
+        agent_id: AgentId,
+        obs: &[f32],
+        state: &GameStateA,
+        shared_info: &mut SI,
+    ) -> CarControls;
+}
+
+impl<SI, P: Policy<SI> + ?Sized> Policy<SI> for Box<P> {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        (**self).reset(initial_state, shared_info);
+    }
+
+    fn get_controls(
+        &mut self,
+        agent_id: AgentId,
+        obs: &[f32],
+        state: &GameStateA,
+        shared_info: &mut SI,
+    ) -> CarControls {
+        (**self).get_controls(agent_id, obs, state, shared_info)
+    }
+}
+
+/// Lets one policy be shared, e.g. between an env and a tournament keeping track of it
+#[cfg(not(feature = "sync"))]
+impl<SI, P: Policy<SI> + ?Sized> Policy<SI> for Rc<RefCell<P>> {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        self.borrow_mut().reset(initial_state, shared_info);
+    }
+
+    fn get_controls(
+        &mut self,
+        agent_id: AgentId,
+        obs: &[f32],
+        state: &GameStateA,
+        shared_info: &mut SI,
+    ) -> CarControls {
+        self.borrow_mut()
+            .get_controls(agent_id, obs, state, shared_info)
+    }
+}
+
+/// Lets one policy be shared, e.g. between an env and a tournament keeping track of it
+#[cfg(feature = "sync")]
+impl<SI, P: Policy<SI> + ?Sized> Policy<SI> for Arc<Mutex<P>> {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .reset(initial_state, shared_info);
+    }
+
+    fn get_controls(
+        &mut self,
+        agent_id: AgentId,
+        obs: &[f32],
+        state: &GameStateA,
+        shared_info: &mut SI,
+    ) -> CarControls {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_controls(agent_id, obs, state, shared_info)
+    }
+}
@@ -0,0 +1,18 @@
+pub use crate::{
+    bots::{Atba, BallChaser, Goalie},
+    common_values::{opponent_goal_center, own_goal_center},
+    conditions::{
+        AllCondition, AnyCondition, GoalScoredCondition, NoTouchTimeoutCondition, TimeoutCondition,
+    },
+    game_modes::scoring_team,
+    remote::{FromRemoteActions, RemoteEnv},
+    teams::{MultiAction, MultiObs, TeamObs},
+    Action, ActionHistory, AgentId, AgentMap, Env, Error, FullObs, GameEvent, Mutators, Obs,
+    Policy, Result, Reward, RewardValue, SharedInfoProvider, SpaceType, StateSetter, StepContext,
+    StepResult, Terminal, Truncate,
+};
+pub use rocketsim_rs::{
+    cxx::UniquePtr,
+    glam_ext::{BallA, CarInfoA, GameStateA},
+    sim::{Arena, CarControls, Team},
+};
@@ -0,0 +1,294 @@
+use crate::{test_utils::StateBuilder, Action, AgentId, Obs, Reward, RewardValue, StepContext};
+use glam::Vec3A;
+use proptest::{
+    prelude::*,
+    test_runner::{TestCaseResult, TestRunner},
+};
+use rocketsim_rs::{glam_ext::GameStateA, sim::Team};
+
+const MAX_SPEED: f32 = 2300.;
+const MAX_BALL_SPEED: f32 = 6000.;
+
+fn arb_pos() -> impl Strategy<Value = Vec3A> {
+    (-4096f32..4096., -5120f32..5120., 17f32..2044.).prop_map(|(x, y, z)| Vec3A::new(x, y, z))
+}
+
+fn arb_vel(max_speed: f32) -> impl Strategy<Value = Vec3A> {
+    (
+        -max_speed..max_speed,
+        -max_speed..max_speed,
+        -max_speed..max_speed,
+    )
+        .prop_map(|(x, y, z)| Vec3A::new(x, y, z).clamp_length_max(max_speed))
+}
+
+/// A car in a `StateSpec`
+#[derive(Clone, Debug)]
+pub struct CarSpec {
+    pub pos: Vec3A,
+    pub vel: Vec3A,
+    pub boost: f32,
+}
+
+/// A random game state, kept in this form so it can be shrunk and printed when a check fails
+#[derive(Clone, Debug)]
+pub struct StateSpec {
+    pub ball_pos: Vec3A,
+    pub ball_vel: Vec3A,
+    pub blue: Vec<CarSpec>,
+    pub orange: Vec<CarSpec>,
+}
+
+impl StateSpec {
+    pub fn num_cars(&self) -> usize {
+        self.blue.len() + self.orange.len()
+    }
+
+    /// Blue cars come first, then orange
+    pub fn to_state(&self) -> GameStateA {
+        let blue = self.blue.iter().map(|car| (Team::Blue, car));
+        let orange = self.orange.iter().map(|car| (Team::Orange, car));
+
+        blue.chain(orange)
+            .fold(
+                StateBuilder::new().with_ball(self.ball_pos, self.ball_vel),
+                |builder, (team, spec)| {
+                    builder.with_car_info(team, |car| {
+                        car.state.pos = spec.pos;
+                        car.state.vel = spec.vel;
+                        car.state.boost = spec.boost;
+                    })
+                },
+            )
+            .build()
+    }
+}
+
+fn arb_car() -> impl Strategy<Value = CarSpec> {
+    (arb_pos(), arb_vel(MAX_SPEED), 0f32..=100.).prop_map(|(pos, vel, boost)| CarSpec {
+        pos,
+        vel,
+        boost,
+    })
+}
+
+/// Random game states inside the field with 0 to `max_team_size` cars on each team
+pub fn arb_state(max_team_size: usize) -> impl Strategy<Value = StateSpec> {
+    (
+        arb_pos(),
+        arb_vel(MAX_BALL_SPEED),
+        prop::collection::vec(arb_car(), 0..=max_team_size),
+        prop::collection::vec(arb_car(), 0..=max_team_size),
+    )
+        .prop_map(|(ball_pos, ball_vel, blue, orange)| StateSpec {
+            ball_pos,
+            ball_vel,
+            blue,
+            orange,
+        })
+}
+
+/// The observation builder returns one observation per car, each as long as its reported obs space
+pub fn check_obs<SI>(
+    obs: &mut impl Obs<SI>,
+    state: &GameStateA,
+    shared_info: &mut SI,
+) -> TestCaseResult {
+    obs.reset(state, shared_info);
+    let full_obs = obs.build_obs(&StepContext::new(state), shared_info);
+    prop_assert_eq!(full_obs.len(), state.cars.len(), "one observation per car");
+
+    for (car, car_obs) in state.cars.iter().zip(&full_obs) {
+        let id = AgentId::of(car);
+        prop_assert_eq!(
+            car_obs.len(),
+            obs.get_obs_space(id, shared_info),
+            "obs length of agent {}",
+            id
+        );
+    }
+
+    Ok(())
+}
+
+/// The action parser returns controls for every car
+pub fn check_actions<ACT: Action<SI>, SI>(
+    action: &mut ACT,
+    actions: ACT::Input,
+    state: &GameStateA,
+    shared_info: &mut SI,
+) -> TestCaseResult {
+    action.reset(state, shared_info);
+    let controls = action.parse_actions(actions, state, shared_info);
+    prop_assert_eq!(
+        controls.len(),
+        state.cars.len(),
+        "one set of controls per car"
+    );
+
+    Ok(())
+}
+
+/// The reward function returns a finite reward for every car
+pub fn check_rewards<R: RewardValue, SI>(
+    reward: &mut impl Reward<SI, R>,
+    state: &GameStateA,
+    shared_info: &mut SI,
+) -> TestCaseResult {
+    reward.reset(state, shared_info);
+    let rewards = reward.get_rewards(&StepContext::new(state), shared_info);
+    prop_assert_eq!(rewards.len(), state.cars.len(), "one reward per car");
+
+    for (car, reward) in state.cars.iter().zip(&rewards) {
+        prop_assert!(
+            reward.is_finite(),
+            "reward of agent {} is {:?}",
+            car.id,
+            reward
+        );
+    }
+
+    Ok(())
+}
+
+/// Run `check_obs` on random states, panicking with the smallest failing state.
+///
+/// `make` is called for every case so state can't leak between them
+pub fn assert_obs_properties<OBS: Obs<SI>, SI>(max_team_size: usize, make: impl Fn() -> (OBS, SI)) {
+    let result = TestRunner::default().run(&arb_state(max_team_size), |spec| {
+        let (mut obs, mut shared_info) = make();
+        check_obs(&mut obs, &spec.to_state(), &mut shared_info)
+    });
+
+    if let Err(e) = result {
+        panic!("{e}");
+    }
+}
+
+/// Run `check_actions` on random states with actions from `arb_actions`,
+/// which is given the number of cars
+pub fn assert_action_properties<ACT, S, SI>(
+    max_team_size: usize,
+    arb_actions: impl Fn(usize) -> S,
+    make: impl Fn() -> (ACT, SI),
+) where
+    ACT: Action<SI>,
+    S: Strategy<Value = ACT::Input>,
+{
+    let strategy = arb_state(max_team_size).prop_flat_map(move |spec| {
+        let num_cars = spec.num_cars();
+        (Just(spec), arb_actions(num_cars))
+    });
+
+    let result = TestRunner::default().run(&strategy, |(spec, actions)| {
+        let (mut action, mut shared_info) = make();
+        check_actions(&mut action, actions, &spec.to_state(), &mut shared_info)
+    });
+
+    if let Err(e) = result {
+        panic!("{e}");
+    }
+}
+
+/// Run `check_rewards` on random states
+pub fn assert_reward_properties<R: RewardValue, REW: Reward<SI, R>, SI>(
+    max_team_size: usize,
+    make: impl Fn() -> (REW, SI),
+) {
+    let result = TestRunner::default().run(&arb_state(max_team_size), |spec| {
+        let (mut reward, mut shared_info) = make();
+        check_rewards(&mut reward, &spec.to_state(), &mut shared_info)
+    });
+
+    if let Err(e) = result {
+        panic!("{e}");
+    }
+}
+
+#[cfg(all(test, feature = "proptest"))]
+mod tests {
+    use super::*;
+    use crate::{
+        actions::{ContinuousAction, LookupTableAction, MultiDiscreteAction},
+        obs::{AdvancedObs, DefaultObs},
+        rewards::{
+            AlignBallGoalReward, ClippedReward, CombinedReward, EventReward, FaceBallReward,
+            LiuDistanceBallToGoalReward, LiuDistanceReward, NormalizedReward, ScheduledReward,
+            VelocityBallToGoalReward, VelocityTowardBallReward, WeightSchedule,
+        },
+    };
+
+    #[test]
+    fn builtin_obs() {
+        assert_obs_properties(3, || (DefaultObs::new(3), ()));
+        assert_obs_properties(3, || (AdvancedObs::new(3), ()));
+    }
+
+    #[test]
+    fn builtin_actions() {
+        // including indices that aren't in the table
+        assert_action_properties(
+            3,
+            |num_cars| prop::collection::vec(-1i32..100, num_cars),
+            || (LookupTableAction::<8>::new(), ()),
+        );
+        assert_action_properties(
+            3,
+            |num_cars| prop::collection::vec(prop::collection::vec(-2f32..2., 8), num_cars),
+            || (ContinuousAction::<8>, ()),
+        );
+        assert_action_properties(
+            3,
+            |num_cars| prop::collection::vec(prop::collection::vec(0f32..3., 8), num_cars),
+            || (MultiDiscreteAction::<8>, ()),
+        );
+    }
+
+    #[test]
+    fn builtin_rewards() {
+        assert_reward_properties::<f32, _, _>(3, || (VelocityTowardBallReward::default(), ()));
+        assert_reward_properties::<f32, _, _>(3, || (VelocityBallToGoalReward::default(), ()));
+        assert_reward_properties::<f32, _, _>(3, || (FaceBallReward, ()));
+        assert_reward_properties::<f32, _, _>(3, || (AlignBallGoalReward::default(), ()));
+        assert_reward_properties::<f32, _, _>(3, || (LiuDistanceReward, ()));
+        assert_reward_properties::<f32, _, _>(3, || (LiuDistanceBallToGoalReward::default(), ()));
+        assert_reward_properties::<f32, _, _>(3, || {
+            let events = EventReward::new()
+                .with_goal(10.)
+                .with_touch(1.)
+                .with_boost_pickup(0.1);
+            (events, ())
+        });
+    }
+
+    #[test]
+    fn reward_wrappers() {
+        let schedule = WeightSchedule::Exponential {
+            start: 1.,
+            end: 0.,
+            half_life: 100.,
+        };
+
+        assert_reward_properties::<f32, _, _>(3, || {
+            let combined = CombinedReward::new()
+                .with("distance", 0.5, LiuDistanceReward)
+                .with("face", 0.1, FaceBallReward);
+            (combined, ())
+        });
+        assert_reward_properties::<f32, _, _>(3, || {
+            (ScheduledReward::new(FaceBallReward, schedule), ())
+        });
+        assert_reward_properties::<f32, _, _>(3, || {
+            (
+                NormalizedReward::new(VelocityTowardBallReward::default(), 0.99),
+                (),
+            )
+        });
+        assert_reward_properties::<f32, _, _>(3, || {
+            (
+                ClippedReward::new(VelocityTowardBallReward::default(), -0.5, 0.5),
+                (),
+            )
+        });
+    }
+}
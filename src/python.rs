@@ -0,0 +1,549 @@
+use crate::{
+    actions::{ContinuousAction, LookupTableAction},
+    remote::{FromRemoteActions, RemoteEnv},
+    state_setters::{KickoffStateSetter, RandomStateSetter},
+    Action, AgentId, Env, Error, FullObs, GameEvent, Obs, Reward, SharedInfoProvider, SpaceType,
+    StateSetter, StepContext, Terminal, Truncate,
+};
+use glam::Vec3A;
+use numpy::{PyArray1, PyArray2, PyReadonlyArray2};
+use pyo3::{
+    exceptions::{PyRuntimeError, PyValueError},
+    prelude::*,
+    types::PyDict,
+};
+use rocketsim_rs::{
+    glam_ext::{CarInfoA, GameStateA},
+    init,
+    sim::{Arena, CarConfig, CarControls, Team},
+};
+use std::sync::Once;
+
+static INIT: Once = Once::new();
+
+impl From<Error> for PyErr {
+    fn from(error: Error) -> Self {
+        PyRuntimeError::new_err(error.to_string())
+    }
+}
+
+/// The shared info of a Python env: the state and the step's context as Python dicts,
+/// converted once per step for all callables, and the first exception raised by a callable since the env last checked
+#[derive(Default)]
+struct PyShared {
+    state: Option<PyObject>,
+    previous_state: Option<PyObject>,
+    context: Option<PyObject>,
+    error: Option<PyErr>,
+}
+
+impl PyShared {
+    /// The context dict of this step, converted by the first callable that needs it
+    fn context(&mut self, py: Python, ctx: &StepContext) -> PyResult<PyObject> {
+        if let Some(context) = &self.context {
+            return Ok(context.clone_ref(py));
+        }
+
+        let previous_state = ctx
+            .previous_state
+            .and(self.previous_state.as_ref())
+            .map(|state| state.clone_ref(py));
+        let context = context_dict(py, ctx, previous_state)?;
+        self.context = Some(context.clone_ref(py));
+
+        Ok(context)
+    }
+
+    /// Call `method` on `object` with the current state, and the step's context if there's one,
+    /// an exception is kept to be raised once the step is done and `None` is returned instead
+    fn call<T: for<'py> FromPyObject<'py>>(
+        &mut self,
+        object: &PyObject,
+        method: &str,
+        ctx: Option<&StepContext>,
+    ) -> Option<T> {
+        Python::with_gil(|py| {
+            let state = self.state.as_ref()?.clone_ref(py);
+            let result = match ctx {
+                Some(ctx) => self
+                    .context(py, ctx)
+                    .and_then(|context| object.bind(py).call_method1(method, (state, context))),
+                None => object.bind(py).call_method1(method, (state,)),
+            }
+            .and_then(|value| value.extract());
+
+            match result {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    self.error.get_or_insert(e);
+                    None
+                }
+            }
+        })
+    }
+
+    /// `reset` is optional for callables without any state of their own
+    fn reset(&mut self, object: &PyObject) {
+        let has_reset = Python::with_gil(|py| object.bind(py).hasattr("reset").unwrap_or_default());
+        if has_reset {
+            self.call::<PyObject>(object, "reset", None);
+        }
+    }
+}
+
+fn vec_array(py: Python, v: Vec3A) -> PyObject {
+    PyArray1::from_slice_bound(py, &v.to_array()).into_py(py)
+}
+
+fn car_dict<'py>(py: Python<'py>, car: &CarInfoA) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("id", car.id)?;
+    dict.set_item("team", u8::from(car.team == Team::Orange))?;
+    dict.set_item("pos", vec_array(py, car.state.pos))?;
+    dict.set_item("vel", vec_array(py, car.state.vel))?;
+    dict.set_item("ang_vel", vec_array(py, car.state.ang_vel))?;
+    dict.set_item("forward", vec_array(py, car.state.rot_mat.x_axis))?;
+    dict.set_item("up", vec_array(py, car.state.rot_mat.z_axis))?;
+    dict.set_item("boost", car.state.boost)?;
+    dict.set_item("is_on_ground", car.state.is_on_ground)?;
+    dict.set_item("has_flipped", car.state.has_flipped)?;
+    dict.set_item("is_demoed", car.state.is_demoed)?;
+
+    Ok(dict)
+}
+
+/// The state as it's passed to the Python callables:
+/// `tick_count`, `ball` (`pos`, `vel` and `ang_vel`), `cars` (a list of dicts, teams are 0 for blue and 1 for orange)
+/// and `pads` (whether each boost pad is active), vectors are numpy arrays
+fn state_dict(py: Python, state: &GameStateA) -> PyResult<PyObject> {
+    let ball = PyDict::new_bound(py);
+    ball.set_item("pos", vec_array(py, state.ball.pos))?;
+    ball.set_item("vel", vec_array(py, state.ball.vel))?;
+    ball.set_item("ang_vel", vec_array(py, state.ball.ang_vel))?;
+
+    let cars = state
+        .cars
+        .iter()
+        .map(|car| car_dict(py, car))
+        .collect::<PyResult<Vec<_>>>()?;
+    let pads = state.pads.iter().map(|pad| pad.state.is_active).collect();
+
+    let dict = PyDict::new_bound(py);
+    dict.set_item("tick_count", state.tick_count)?;
+    dict.set_item("ball", ball)?;
+    dict.set_item("cars", cars)?;
+    dict.set_item("pads", PyArray1::from_vec_bound(py, pads))?;
+
+    Ok(dict.into_py(py))
+}
+
+fn controls_dict<'py>(
+    py: Python<'py>,
+    agent_id: AgentId,
+    controls: &CarControls,
+) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("agent_id", agent_id.0)?;
+    dict.set_item("throttle", controls.throttle)?;
+    dict.set_item("steer", controls.steer)?;
+    dict.set_item("pitch", controls.pitch)?;
+    dict.set_item("yaw", controls.yaw)?;
+    dict.set_item("roll", controls.roll)?;
+    dict.set_item("jump", controls.jump)?;
+    dict.set_item("boost", controls.boost)?;
+    dict.set_item("handbrake", controls.handbrake)?;
+
+    Ok(dict)
+}
+
+fn event_dict<'py>(py: Python<'py>, event: &GameEvent) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new_bound(py);
+    dict.set_item("tick", event.tick())?;
+    match *event {
+        GameEvent::Goal { team, .. } => {
+            dict.set_item("type", "goal")?;
+            dict.set_item("team", u8::from(team == Team::Orange))?;
+        }
+        GameEvent::Touch { agent_id, .. } => {
+            dict.set_item("type", "touch")?;
+            dict.set_item("agent_id", agent_id.0)?;
+        }
+        GameEvent::Bump { bumper, victim, .. } => {
+            dict.set_item("type", "bump")?;
+            dict.set_item("bumper", bumper.0)?;
+            dict.set_item("victim", victim.0)?;
+        }
+        GameEvent::Demo {
+            attacker, victim, ..
+        } => {
+            dict.set_item("type", "demo")?;
+            dict.set_item("attacker", attacker.0)?;
+            dict.set_item("victim", victim.0)?;
+        }
+    }
+
+    Ok(dict)
+}
+
+/// The context as it's passed to the Python callables next to the state, like `StepContext`:
+/// `previous_state` (a state dict, `None` right after a reset), `actions` (a dict of controls per car with its `agent_id`),
+/// `events` (dicts with a `type` of `goal`, `touch`, `bump` or `demo` and a `tick`) and `episode_ticks`
+fn context_dict(
+    py: Python,
+    ctx: &StepContext,
+    previous_state: Option<PyObject>,
+) -> PyResult<PyObject> {
+    let actions = ctx
+        .actions
+        .iter()
+        .map(|(agent_id, controls)| controls_dict(py, *agent_id, controls))
+        .collect::<PyResult<Vec<_>>>()?;
+    let events = ctx
+        .events
+        .iter()
+        .map(|event| event_dict(py, event))
+        .collect::<PyResult<Vec<_>>>()?;
+
+    let dict = PyDict::new_bound(py);
+    dict.set_item("previous_state", previous_state)?;
+    dict.set_item("actions", actions)?;
+    dict.set_item("events", events)?;
+    dict.set_item("episode_ticks", ctx.episode_ticks)?;
+
+    Ok(dict.into_py(py))
+}
+
+struct PyStateProvider;
+
+impl PyStateProvider {
+    fn convert(state: &GameStateA, shared_info: &mut PyShared) {
+        shared_info.context = None;
+        match Python::with_gil(|py| state_dict(py, state)) {
+            Ok(state) => shared_info.state = Some(state),
+            Err(e) => {
+                shared_info.state = None;
+                shared_info.error.get_or_insert(e);
+            }
+        }
+    }
+}
+
+impl SharedInfoProvider<PyShared> for PyStateProvider {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut PyShared) {
+        shared_info.previous_state = None;
+        Self::convert(initial_state, shared_info);
+    }
+
+    fn apply(&mut self, game_state: &GameStateA, shared_info: &mut PyShared) {
+        shared_info.previous_state = shared_info.state.take();
+        Self::convert(game_state, shared_info);
+    }
+}
+
+/// `get_obs_space(agent_id) -> int`, `build_obs(state, ctx)` with one row per car and an optional `reset(state)`
+struct PyObs(PyObject);
+
+impl Obs<PyShared> for PyObs {
+    fn get_obs_space(&self, agent_id: AgentId, _shared_info: &PyShared) -> usize {
+        Python::with_gil(|py| {
+            self.0
+                .bind(py)
+                .call_method1("get_obs_space", (agent_id.0,))
+                .and_then(|space| space.extract())
+                .unwrap_or_else(|e| {
+                    log::error!("get_obs_space failed: {e}");
+                    0
+                })
+        })
+    }
+
+    fn reset(&mut self, _initial_state: &GameStateA, shared_info: &mut PyShared) {
+        shared_info.reset(&self.0);
+    }
+
+    fn build_obs(&mut self, ctx: &StepContext, shared_info: &mut PyShared) -> FullObs {
+        shared_info
+            .call(&self.0, "build_obs", Some(ctx))
+            .unwrap_or_default()
+    }
+}
+
+/// `get_rewards(state, ctx)` with one reward per car and an optional `reset(state)`
+struct PyReward(PyObject);
+
+impl Reward<PyShared> for PyReward {
+    fn reset(&mut self, _initial_state: &GameStateA, shared_info: &mut PyShared) {
+        shared_info.reset(&self.0);
+    }
+
+    fn get_rewards(&mut self, ctx: &StepContext, shared_info: &mut PyShared) -> Vec<f32> {
+        shared_info
+            .call(&self.0, "get_rewards", Some(ctx))
+            .unwrap_or_else(|| vec![0.; ctx.state.cars.len()])
+    }
+}
+
+/// `is_terminal(state, ctx)` for terminals or `should_truncate(state, ctx)` for truncates, and an optional `reset(state)`
+struct PyCondition(PyObject);
+
+impl Terminal<PyShared> for PyCondition {
+    fn reset(&mut self, _initial_state: &GameStateA, shared_info: &mut PyShared) {
+        shared_info.reset(&self.0);
+    }
+
+    fn is_terminal(&mut self, ctx: &StepContext, shared_info: &mut PyShared) -> bool {
+        shared_info
+            .call(&self.0, "is_terminal", Some(ctx))
+            .unwrap_or_default()
+    }
+}
+
+impl Truncate<PyShared> for PyCondition {
+    fn reset(&mut self, _initial_state: &GameStateA, shared_info: &mut PyShared) {
+        shared_info.reset(&self.0);
+    }
+
+    fn should_truncate(&mut self, ctx: &StepContext, shared_info: &mut PyShared) -> bool {
+        shared_info
+            .call(&self.0, "should_truncate", Some(ctx))
+            .unwrap_or_default()
+    }
+}
+
+/// The action parsers that can be picked by name from Python
+enum PyAction {
+    Lookup(LookupTableAction),
+    Continuous(ContinuousAction),
+}
+
+impl Action<PyShared> for PyAction {
+    type Input = Vec<Vec<f32>>;
+
+    fn get_tick_skip() -> u32 {
+        <ContinuousAction as Action<PyShared>>::get_tick_skip()
+    }
+
+    fn get_action_space(&self, agent_id: AgentId, shared_info: &PyShared) -> usize {
+        match self {
+            Self::Lookup(action) => action.get_action_space(agent_id, shared_info),
+            Self::Continuous(action) => action.get_action_space(agent_id, shared_info),
+        }
+    }
+
+    fn get_action_space_type(&self, agent_id: AgentId, shared_info: &PyShared) -> SpaceType {
+        match self {
+            Self::Lookup(action) => action.get_action_space_type(agent_id, shared_info),
+            Self::Continuous(action) => action.get_action_space_type(agent_id, shared_info),
+        }
+    }
+
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut PyShared) {}
+
+    fn get_action_mask(
+        &self,
+        agent_id: AgentId,
+        state: &GameStateA,
+        shared_info: &PyShared,
+    ) -> Vec<bool> {
+        match self {
+            Self::Lookup(action) => action.get_action_mask(agent_id, state, shared_info),
+            Self::Continuous(action) => action.get_action_mask(agent_id, state, shared_info),
+        }
+    }
+
+    fn parse_actions(
+        &mut self,
+        actions: Vec<Vec<f32>>,
+        state: &GameStateA,
+        shared_info: &mut PyShared,
+    ) -> Vec<CarControls> {
+        match self {
+            Self::Lookup(action) => {
+                action.parse_actions(Vec::from_remote(actions), state, shared_info)
+            }
+            Self::Continuous(action) => action.parse_actions(actions, state, shared_info),
+        }
+    }
+}
+
+type PyEnvInner = Env<
+    Box<dyn StateSetter<PyShared>>,
+    PyStateProvider,
+    PyObs,
+    PyAction,
+    PyReward,
+    PyCondition,
+    PyCondition,
+    PyShared,
+>;
+
+/// `Env` for Python, with the obs builder, reward and conditions written in Python
+/// and the rest of the step loop in Rust.
+///
+/// Observations are returned as a 2D float32 array with a row per agent,
+/// actions are passed the same way with a single column of indices for the `lookup` action parser.
+///
+/// The callables get the step's events with `capture_events`
+#[pyclass(name = "Env", unsendable)]
+pub struct PyEnv {
+    env: PyEnvInner,
+}
+
+impl PyEnv {
+    /// Raise the first exception from a Python callable during the last reset or step
+    fn check_callbacks(&mut self) -> PyResult<()> {
+        match self.env.shared_info_mut().error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+fn obs_array<'py>(py: Python<'py>, obs: &FullObs) -> PyResult<Bound<'py, PyArray2<f32>>> {
+    PyArray2::from_vec2_bound(py, obs).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pymethods]
+impl PyEnv {
+    #[new]
+    #[pyo3(signature = (obs, reward, terminal, truncate, blue_size = 1, orange_size = 1, action = "lookup", state_setter = "kickoff", seed = None, capture_events = false))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        obs: PyObject,
+        reward: PyObject,
+        terminal: PyObject,
+        truncate: PyObject,
+        blue_size: usize,
+        orange_size: usize,
+        action: &str,
+        state_setter: &str,
+        seed: Option<u64>,
+        capture_events: bool,
+    ) -> PyResult<Self> {
+        let action = match action {
+            "lookup" => PyAction::Lookup(LookupTableAction::new()),
+            "continuous" => PyAction::Continuous(ContinuousAction),
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "Unknown action parser {action}"
+                )))
+            }
+        };
+        let state_setter: Box<dyn StateSetter<PyShared>> = match state_setter {
+            "kickoff" => Box::new(KickoffStateSetter::new()),
+            "random" => Box::new(RandomStateSetter::new()),
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "Unknown state setter {state_setter}"
+                )))
+            }
+        };
+
+        INIT.call_once(|| init(None, true));
+
+        let mut arena = Arena::default_standard();
+        for (team, size) in [(Team::Blue, blue_size), (Team::Orange, orange_size)] {
+            for _ in 0..size {
+                let _ = arena.pin_mut().add_car(team, CarConfig::octane());
+            }
+        }
+
+        let mut env = Env::try_new(
+            arena,
+            state_setter,
+            PyStateProvider,
+            PyObs(obs),
+            action,
+            PyReward(reward),
+            PyCondition(terminal),
+            PyCondition(truncate),
+            PyShared::default(),
+        )?;
+
+        if capture_events {
+            env = env.with_event_capture();
+        }
+        if let Some(seed) = seed {
+            env.seed(seed);
+        }
+
+        Ok(Self { env })
+    }
+
+    fn reset<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        let obs = self.env.reset();
+        self.check_callbacks()?;
+
+        obs_array(py, &obs?)
+    }
+
+    /// Returns the observations, rewards, whether the episode is terminal and whether it was truncated
+    #[allow(clippy::type_complexity)]
+    fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        actions: PyReadonlyArray2<f32>,
+    ) -> PyResult<(
+        Bound<'py, PyArray2<f32>>,
+        Bound<'py, PyArray1<f32>>,
+        bool,
+        bool,
+    )> {
+        let actions = actions
+            .as_array()
+            .rows()
+            .into_iter()
+            .map(|row| row.to_vec())
+            .collect();
+        let result = self.env.step(actions);
+        self.check_callbacks()?;
+        let result = result?;
+
+        Ok((
+            obs_array(py, &result.obs)?,
+            PyArray1::from_vec_bound(py, result.rewards),
+            result.is_terminal,
+            result.truncated,
+        ))
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.env.seed(seed);
+    }
+
+    /// The ids of the agents controlled from Python, in the order of the observation rows
+    fn agent_ids(&self) -> Vec<u32> {
+        self.env
+            .external_agent_ids()
+            .into_iter()
+            .map(|id| id.0)
+            .collect()
+    }
+
+    fn obs_spaces(&self) -> Vec<usize> {
+        RemoteEnv::obs_spaces(&self.env)
+    }
+
+    fn action_spaces(&self) -> Vec<usize> {
+        RemoteEnv::action_spaces(&self.env)
+    }
+
+    /// A bool array per agent of the actions it can take, all true for the `continuous` action parser
+    fn action_masks(&self) -> PyResult<Vec<Vec<bool>>> {
+        Ok(self.env.get_action_masks()?)
+    }
+
+    /// The state dict from the last reset or step
+    fn state(&self, py: Python) -> Option<PyObject> {
+        self.env
+            .shared_info()
+            .state
+            .as_ref()
+            .map(|state| state.clone_ref(py))
+    }
+}
+
+#[pymodule]
+fn rlgym_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyEnv>()
+}
@@ -0,0 +1,228 @@
+/// Result of a match from the point of view of the first party
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchOutcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+impl MatchOutcome {
+    /// 1 for a win, 0.5 for a draw and 0 for a loss
+    pub fn score(self) -> f32 {
+        match self {
+            Self::Win => 1.,
+            Self::Draw => 0.5,
+            Self::Loss => 0.,
+        }
+    }
+
+    /// The same outcome from the point of view of the other party
+    pub fn reversed(self) -> Self {
+        match self {
+            Self::Win => Self::Loss,
+            Self::Loss => Self::Win,
+            Self::Draw => Self::Draw,
+        }
+    }
+
+    pub fn from_goals(goals_for: u32, goals_against: u32) -> Self {
+        match goals_for.cmp(&goals_against) {
+            std::cmp::Ordering::Greater => Self::Win,
+            std::cmp::Ordering::Less => Self::Loss,
+            std::cmp::Ordering::Equal => Self::Draw,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Elo {
+    pub k_factor: f32,
+}
+
+impl Default for Elo {
+    fn default() -> Self {
+        Self { k_factor: 32. }
+    }
+}
+
+impl Elo {
+    pub const INITIAL_RATING: f32 = 1500.;
+
+    /// Probability of `rating` beating `opponent`
+    pub fn expected_score(rating: f32, opponent: f32) -> f32 {
+        1. / (1. + 10f32.powf((opponent - rating) / 400.))
+    }
+
+    /// Update both ratings in place, `outcome` is from the point of view of `rating`
+    pub fn update(&self, rating: &mut f32, opponent: &mut f32, outcome: MatchOutcome) {
+        let delta = self.k_factor * (outcome.score() - Self::expected_score(*rating, *opponent));
+        *rating += delta;
+        *opponent -= delta;
+    }
+}
+
+/// A way of rating players from the outcomes of their matches
+pub trait RatingSystem {
+    type Rating: Clone;
+
+    fn initial_rating(&self) -> Self::Rating;
+    /// Update both ratings in place, `outcome` is from the point of view of `rating`
+    fn update(&self, rating: &mut Self::Rating, opponent: &mut Self::Rating, outcome: MatchOutcome);
+    /// A single number to rank players by
+    fn skill(&self, rating: &Self::Rating) -> f32;
+}
+
+impl RatingSystem for Elo {
+    type Rating = f32;
+
+    fn initial_rating(&self) -> f32 {
+        Self::INITIAL_RATING
+    }
+
+    fn update(&self, rating: &mut f32, opponent: &mut f32, outcome: MatchOutcome) {
+        Elo::update(self, rating, opponent, outcome);
+    }
+
+    fn skill(&self, rating: &f32) -> f32 {
+        *rating
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrueSkillRating {
+    pub mu: f32,
+    pub sigma: f32,
+}
+
+/// Two player TrueSkill
+#[derive(Clone, Copy, Debug)]
+pub struct TrueSkill {
+    pub mu: f32,
+    pub sigma: f32,
+    /// Skill difference that gives an ~76% chance of winning
+    pub beta: f32,
+    /// Added uncertainty before every match so ratings can keep changing
+    pub tau: f32,
+    /// Performance difference below which a match is considered a draw
+    pub draw_margin: f32,
+}
+
+impl Default for TrueSkill {
+    fn default() -> Self {
+        Self {
+            mu: 25.,
+            sigma: 25. / 3.,
+            beta: 25. / 6.,
+            tau: 25. / 300.,
+            // 10% draw probability
+            draw_margin: 0.74,
+        }
+    }
+}
+
+impl RatingSystem for TrueSkill {
+    type Rating = TrueSkillRating;
+
+    fn initial_rating(&self) -> TrueSkillRating {
+        TrueSkillRating {
+            mu: self.mu,
+            sigma: self.sigma,
+        }
+    }
+
+    fn update(
+        &self,
+        rating: &mut TrueSkillRating,
+        opponent: &mut TrueSkillRating,
+        outcome: MatchOutcome,
+    ) {
+        // the formulas below are from the point of view of the winner
+        let (winner, loser) = match outcome {
+            MatchOutcome::Win | MatchOutcome::Draw => (rating, opponent),
+            MatchOutcome::Loss => (opponent, rating),
+        };
+
+        let tau_sq = f64::from(self.tau).powi(2);
+        let winner_var = f64::from(winner.sigma).powi(2) + tau_sq;
+        let loser_var = f64::from(loser.sigma).powi(2) + tau_sq;
+
+        let c_sq = 2. * f64::from(self.beta).powi(2) + winner_var + loser_var;
+        let c = c_sq.sqrt();
+        let t = f64::from(winner.mu - loser.mu) / c;
+        let e = f64::from(self.draw_margin) / c;
+
+        let (v, w) = if outcome == MatchOutcome::Draw {
+            v_w_draw(t, e)
+        } else {
+            v_w_win(t, e)
+        };
+
+        winner.mu += (winner_var / c * v) as f32;
+        loser.mu -= (loser_var / c * v) as f32;
+        winner.sigma = (winner_var * (1. - winner_var / c_sq * w).max(0.)).sqrt() as f32;
+        loser.sigma = (loser_var * (1. - loser_var / c_sq * w).max(0.)).sqrt() as f32;
+    }
+
+    /// Conservative estimate that the true skill is above with ~99% certainty
+    fn skill(&self, rating: &TrueSkillRating) -> f32 {
+        rating.mu - 3. * rating.sigma
+    }
+}
+
+fn pdf(x: f64) -> f64 {
+    (-x * x / 2.).exp() / (2. * std::f64::consts::PI).sqrt()
+}
+
+fn cdf(x: f64) -> f64 {
+    0.5 * erfc(-x / std::f64::consts::SQRT_2)
+}
+
+/// Complementary error function with a fractional error below 1.2e-7
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1. / (1. + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277))))))));
+    let r = t * poly.exp();
+
+    if x >= 0. {
+        r
+    } else {
+        2. - r
+    }
+}
+
+fn v_w_win(t: f64, e: f64) -> (f64, f64) {
+    let x = t - e;
+    let denom = cdf(x);
+
+    let v = if denom < f64::EPSILON {
+        -x
+    } else {
+        pdf(x) / denom
+    };
+
+    (v, v * (v + x))
+}
+
+fn v_w_draw(t: f64, e: f64) -> (f64, f64) {
+    // the formulas assume a positive t, flip v back afterwards
+    let sign = t.signum();
+    let t = t.abs();
+    let denom = cdf(e - t) - cdf(-e - t);
+
+    if denom < f64::EPSILON {
+        return (-t * sign, 1.);
+    }
+
+    let v = (pdf(-e - t) - pdf(e - t)) / denom;
+    let w = v * v + ((e - t) * pdf(e - t) + (e + t) * pdf(e + t)) / denom;
+
+    (v * sign, w)
+}
@@ -0,0 +1,52 @@
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// Paces steps to real time, e.g. to watch an env in RLViser at the game speed it asks for.
+///
+/// Pass `interval_mut` to `Env::handle_incoming_states` so speed changes are picked up,
+/// or let `Env::step_realtime` do both
+#[derive(Clone, Copy, Debug)]
+pub struct RealtimeClock {
+    interval: Duration,
+    next_time: Instant,
+}
+
+impl RealtimeClock {
+    /// One step of `tick_skip` ticks every `tick_skip / 120` seconds
+    pub fn new(tick_skip: u32) -> Self {
+        let interval = Duration::from_secs_f32(tick_skip as f32 / 120.);
+
+        Self {
+            interval,
+            next_time: Instant::now() + interval,
+        }
+    }
+
+    /// Time between steps at the current game speed
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn interval_mut(&mut self) -> &mut Duration {
+        &mut self.interval
+    }
+
+    /// Sleep until the next step is due
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        if self.next_time > now {
+            sleep(self.next_time - now);
+            self.next_time += self.interval;
+        } else {
+            // we fell behind, e.g. while paused, so don't try to catch up
+            self.next_time = now + self.interval;
+        }
+    }
+
+    /// Start counting from now, e.g. after a pause that shouldn't be caught up on
+    pub fn restart(&mut self) {
+        self.next_time = Instant::now() + self.interval;
+    }
+}
@@ -0,0 +1,487 @@
+use crate::{
+    wire::{invalid_data, Reader, Writer},
+    AgentId, Error, GameEvent, Result, RewardValue,
+};
+use rocketsim_rs::{
+    bytes::{FromBytes, ToBytes},
+    sim::{CarControls, Team},
+    GameState,
+};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+const MAGIC: &[u8; 4] = b"RLRC";
+const VERSION: u32 = 1;
+const TRAJECTORY_MAGIC: &[u8; 4] = b"RLTJ";
+const TRAJECTORY_VERSION: u32 = 2;
+
+/// Writes game states to a file as they happen, so an episode can be watched in RLViser later.
+///
+/// The file starts with a magic number and version,
+/// then every state is its length as a little-endian `u32` followed by RocketSim's byte encoding of it
+pub struct Recorder<W: Write> {
+    writer: W,
+    frames: usize,
+}
+
+impl Recorder<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+
+        Ok(Self { writer, frames: 0 })
+    }
+
+    pub fn record(&mut self, state: &GameState) -> Result<()> {
+        let bytes = state.to_bytes();
+        self.writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&bytes)?;
+        self.frames += 1;
+
+        Ok(())
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Flush everything that was recorded and return the writer
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Read the `len` bytes of a frame, growing the buffer as they come in instead of trusting `len` up front
+fn read_bytes(reader: &mut impl Read, len: u32) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader
+        .by_ref()
+        .take(u64::from(len))
+        .read_to_end(&mut bytes)?;
+    if bytes.len() != len as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "The file ends in the middle of a frame",
+        ));
+    }
+
+    Ok(bytes)
+}
+
+/// Read every state written by a `Recorder`
+pub fn read_recording(reader: impl Read) -> Result<Vec<GameState>> {
+    let mut reader = BufReader::new(reader);
+
+    let mut header = [0; 8];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != MAGIC || header[4..] != VERSION.to_le_bytes() {
+        return Err(Error::Protocol(String::from(
+            "Not a recording of a compatible version",
+        )));
+    }
+
+    let mut states = Vec::new();
+    loop {
+        let mut len = [0; 4];
+        match reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(states),
+            Err(e) => return Err(e.into()),
+        }
+
+        let bytes = read_bytes(&mut reader, u32::from_le_bytes(len))?;
+        if bytes.len() < GameState::MIN_NUM_BYTES || bytes.len() != GameState::get_num_bytes(&bytes)
+        {
+            return Err(Error::Protocol(format!(
+                "Frame {} of the recording is corrupt",
+                states.len()
+            )));
+        }
+
+        states.push(GameState::from_bytes(&bytes));
+    }
+}
+
+/// One step of a trajectory recorded by an `Env` with a `TrajectoryRecorder`
+pub struct RecordedStep {
+    /// The state after the step
+    pub state: GameState,
+    /// The observation every car acted on
+    pub obs: Vec<(AgentId, Vec<f32>)>,
+    /// The controls of every car during the step
+    pub controls: Vec<(AgentId, CarControls)>,
+    /// The reward of every car, with one value per objective
+    pub rewards: Vec<(AgentId, Vec<f32>)>,
+    /// Empty unless the env captures events, see `Env::with_event_capture`
+    pub events: Vec<GameEvent>,
+    pub is_terminal: bool,
+    pub truncated: bool,
+}
+
+pub enum TrajectoryFrame {
+    /// A new episode started from `state`
+    Reset {
+        state: GameState,
+    },
+    Step(RecordedStep),
+}
+
+/// Writes every reset and step of an `Env` to a file, see `Env::with_trajectory_recorder`,
+/// to debug rewards or collect datasets for imitation learning. Read it back with a `TrajectoryReader`.
+///
+/// Like a `Recorder`'s file, it starts with a magic number and version, then every frame is its length
+/// as a little-endian `u32` followed by the frame
+pub struct TrajectoryRecorder {
+    writer: Box<dyn Write + Send>,
+    frames: usize,
+}
+
+impl TrajectoryRecorder {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn new(mut writer: impl Write + Send + 'static) -> Result<Self> {
+        writer.write_all(TRAJECTORY_MAGIC)?;
+        writer.write_all(&TRAJECTORY_VERSION.to_le_bytes())?;
+
+        Ok(Self {
+            writer: Box::new(writer),
+            frames: 0,
+        })
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+
+    fn write_frame(&mut self, mut frame: Writer) -> Result<()> {
+        let bytes = frame.finish();
+        self.writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&bytes)?;
+        self.frames += 1;
+
+        Ok(())
+    }
+
+    pub(crate) fn record_reset(&mut self, state: &GameState) -> Result<()> {
+        let mut frame = Writer::default();
+        frame.u8(0).bytes.extend(state.to_bytes());
+
+        self.write_frame(frame)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn record_step<R: RewardValue>(
+        &mut self,
+        state: &GameState,
+        obs_ids: impl IntoIterator<Item = AgentId>,
+        obs: &[Vec<f32>],
+        controls: &[(AgentId, CarControls)],
+        reward_ids: impl IntoIterator<Item = AgentId>,
+        rewards: &[R],
+        events: &[GameEvent],
+        is_terminal: bool,
+        truncated: bool,
+    ) -> Result<()> {
+        let mut frame = Writer::default();
+        frame.u8(1).bool(is_terminal).bool(truncated);
+
+        frame.u32(obs.len() as u32);
+        for (id, obs) in obs_ids.into_iter().zip(obs) {
+            frame.u32(id.0).floats(obs);
+        }
+
+        frame.u32(controls.len() as u32);
+        for (id, controls) in controls {
+            frame
+                .u32(id.0)
+                .f32(controls.throttle)
+                .f32(controls.steer)
+                .f32(controls.pitch)
+                .f32(controls.yaw)
+                .f32(controls.roll)
+                .bool(controls.jump)
+                .bool(controls.boost)
+                .bool(controls.handbrake);
+        }
+
+        frame.u32(rewards.len() as u32);
+        for (id, reward) in reward_ids.into_iter().zip(rewards) {
+            frame.u32(id.0).floats(reward.objectives());
+        }
+
+        frame.u32(events.len() as u32);
+        for event in events {
+            let (kind, a, b) = match *event {
+                GameEvent::Goal { team, .. } => (0, u32::from(team == Team::Orange), 0),
+                GameEvent::Touch { agent_id, .. } => (1, agent_id.0, 0),
+                GameEvent::Bump { bumper, victim, .. } => (2, bumper.0, victim.0),
+                GameEvent::Demo {
+                    attacker, victim, ..
+                } => (3, attacker.0, victim.0),
+            };
+            frame.u8(kind).u32(a).u32(b).u64(event.tick());
+        }
+
+        // the state goes last since it knows its own length
+        frame.bytes.extend(state.to_bytes());
+
+        self.write_frame(frame)
+    }
+}
+
+fn read_state(reader: &mut Reader) -> io::Result<GameState> {
+    let bytes = reader.rest();
+    if bytes.len() < GameState::MIN_NUM_BYTES || bytes.len() != GameState::get_num_bytes(bytes) {
+        return Err(invalid_data("Corrupt game state"));
+    }
+
+    Ok(GameState::from_bytes(bytes))
+}
+
+fn read_step(reader: &mut Reader) -> io::Result<RecordedStep> {
+    let is_terminal = reader.bool()?;
+    let truncated = reader.bool()?;
+
+    let obs = (0..reader.u32()?)
+        .map(|_| Ok((AgentId(reader.u32()?), reader.floats()?)))
+        .collect::<io::Result<_>>()?;
+
+    let controls = (0..reader.u32()?)
+        .map(|_| {
+            Ok((
+                AgentId(reader.u32()?),
+                CarControls {
+                    throttle: reader.f32()?,
+                    steer: reader.f32()?,
+                    pitch: reader.f32()?,
+                    yaw: reader.f32()?,
+                    roll: reader.f32()?,
+                    jump: reader.bool()?,
+                    boost: reader.bool()?,
+                    handbrake: reader.bool()?,
+                },
+            ))
+        })
+        .collect::<io::Result<_>>()?;
+
+    let rewards = (0..reader.u32()?)
+        .map(|_| Ok((AgentId(reader.u32()?), reader.floats()?)))
+        .collect::<io::Result<_>>()?;
+
+    let events = (0..reader.u32()?)
+        .map(|_| {
+            let kind = reader.u8()?;
+            let (a, b) = (reader.u32()?, reader.u32()?);
+            let tick = reader.u64()?;
+
+            Ok(match kind {
+                0 => GameEvent::Goal {
+                    team: if a == 0 { Team::Blue } else { Team::Orange },
+                    tick,
+                },
+                1 => GameEvent::Touch {
+                    agent_id: AgentId(a),
+                    tick,
+                },
+                2 => GameEvent::Bump {
+                    bumper: AgentId(a),
+                    victim: AgentId(b),
+                    tick,
+                },
+                3 => GameEvent::Demo {
+                    attacker: AgentId(a),
+                    victim: AgentId(b),
+                    tick,
+                },
+                _ => return Err(invalid_data(format!("Unknown event kind {kind}"))),
+            })
+        })
+        .collect::<io::Result<_>>()?;
+
+    Ok(RecordedStep {
+        state: read_state(reader)?,
+        obs,
+        controls,
+        rewards,
+        events,
+        is_terminal,
+        truncated,
+    })
+}
+
+/// Iterates the frames of a file written by a `TrajectoryRecorder`
+pub struct TrajectoryReader<R: Read> {
+    reader: BufReader<R>,
+    frames: usize,
+}
+
+impl TrajectoryReader<File> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(File::open(path)?)
+    }
+}
+
+impl<R: Read> TrajectoryReader<R> {
+    pub fn new(reader: R) -> Result<Self> {
+        let mut reader = BufReader::new(reader);
+
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != TRAJECTORY_MAGIC || header[4..] != TRAJECTORY_VERSION.to_le_bytes() {
+            return Err(Error::Protocol(String::from(
+                "Not a trajectory of a compatible version",
+            )));
+        }
+
+        Ok(Self { reader, frames: 0 })
+    }
+
+    fn read_frame(&mut self) -> Result<Option<TrajectoryFrame>> {
+        let mut len = [0; 4];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        let bytes = read_bytes(&mut self.reader, u32::from_le_bytes(len))?;
+
+        let mut reader = Reader::new(&bytes);
+        let frame = match reader.u8() {
+            Ok(0) => read_state(&mut reader).map(|state| TrajectoryFrame::Reset { state }),
+            Ok(1) => read_step(&mut reader).map(TrajectoryFrame::Step),
+            Ok(kind) => Err(invalid_data(format!("Unknown frame kind {kind}"))),
+            Err(e) => Err(e),
+        };
+
+        let frame = frame.map_err(|e| {
+            Error::Protocol(format!(
+                "Frame {} of the trajectory is corrupt: {e}",
+                self.frames
+            ))
+        })?;
+        self.frames += 1;
+
+        Ok(Some(frame))
+    }
+}
+
+impl<R: Read> Iterator for TrajectoryReader<R> {
+    type Item = Result<TrajectoryFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}
+
+/// Open RLViser and show `states` in real time, `tick_skip` ticks apart
+#[cfg(feature = "rlviser")]
+pub fn replay(states: &[GameState], tick_skip: u32) -> Result<()> {
+    let mut playback = Playback::new(&crate::RenderConfig::default())?.with_tick_skip(tick_skip);
+    playback.play(states)?;
+    playback.quit()
+}
+
+/// Shows recorded states in RLViser without stepping an arena,
+/// following the game speed and pausing that are set in RLViser.
+///
+/// States RLViser asks to set are ignored, there's nothing to simulate them with
+#[cfg(feature = "rlviser")]
+pub struct Playback {
+    renderer: crate::RLViserSocketHandler,
+    tick_skip: u32,
+    interval: std::time::Duration,
+}
+
+#[cfg(feature = "rlviser")]
+impl Playback {
+    const DEFAULT_TICK_SKIP: u32 = 8;
+
+    pub fn new(config: &crate::RenderConfig) -> Result<Self> {
+        let renderer = crate::RLViserSocketHandler::with_config(config).map_err(Error::Render)?;
+
+        Ok(Self {
+            renderer,
+            tick_skip: Self::DEFAULT_TICK_SKIP,
+            interval: Self::interval(Self::DEFAULT_TICK_SKIP),
+        })
+    }
+
+    /// Ticks between the recorded states, 8 by default
+    pub fn with_tick_skip(mut self, tick_skip: u32) -> Self {
+        self.tick_skip = tick_skip;
+        self.interval = Self::interval(tick_skip);
+        self
+    }
+
+    fn interval(tick_skip: u32) -> std::time::Duration {
+        std::time::Duration::from_secs_f32(tick_skip as f32 / 120.)
+    }
+
+    pub fn play<'a>(&mut self, states: impl IntoIterator<Item = &'a GameState>) -> Result<()> {
+        for state in states {
+            self.show(state)?;
+        }
+
+        Ok(())
+    }
+
+    /// Play the state of every reset and step of a trajectory
+    pub fn play_trajectory<R: Read>(&mut self, reader: TrajectoryReader<R>) -> Result<()> {
+        for frame in reader {
+            let state = match frame? {
+                TrajectoryFrame::Reset { state } => state,
+                TrajectoryFrame::Step(step) => step.state,
+            };
+            self.show(&state)?;
+        }
+
+        Ok(())
+    }
+
+    fn show(&mut self, state: &GameState) -> Result<()> {
+        use crate::Renderer;
+        use std::{thread::sleep, time::Instant};
+
+        self.handle_messages()?;
+        while self.renderer.is_paused() {
+            sleep(self.interval);
+            self.handle_messages()?;
+        }
+
+        let start = Instant::now();
+        self.renderer.send_state(state).map_err(Error::Render)?;
+        sleep(self.interval.saturating_sub(start.elapsed()));
+
+        Ok(())
+    }
+
+    fn handle_messages(&mut self) -> Result<()> {
+        self.renderer
+            .receive(&mut self.interval, self.tick_skip, |_| {
+                log::debug!("Ignoring a state set from RLViser during playback");
+            })
+            .map_err(Error::Render)
+    }
+
+    /// Close RLViser
+    pub fn quit(self) -> Result<()> {
+        self.renderer.quit().map_err(Error::Render)
+    }
+}
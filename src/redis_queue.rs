@@ -0,0 +1,211 @@
+use crate::{
+    distributed::{QueueUpdate, Rollout, RolloutQueue},
+    remote::Transition,
+    Error, Result,
+};
+use redis::{
+    streams::{StreamRangeReply, StreamReadReply},
+    Commands, Connection, Value,
+};
+use std::{collections::HashMap, io};
+
+/// Read from the control stream by `RedisQueue::poll_control`.
+///
+/// Every entry has a `kind` field of `policy`, `config` or `stop`.
+/// Policy entries also have a `version` field and the model in a `data` field,
+/// config entries have the config as a UTF-8 string in a `data` field
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControlMessage {
+    Policy { version: u64, model: Vec<u8> },
+    Config(String),
+    Stop,
+}
+
+impl ControlMessage {
+    fn parse(fields: &HashMap<String, Value>) -> Option<Self> {
+        let field = |name: &str| -> Option<Vec<u8>> {
+            fields
+                .get(name)
+                .and_then(|value| redis::from_redis_value(value).ok())
+        };
+        let kind = String::from_utf8(field("kind")?).ok()?;
+
+        match kind.as_str() {
+            "policy" => Some(Self::Policy {
+                version: String::from_utf8(field("version")?).ok()?.parse().ok()?,
+                model: field("data")?,
+            }),
+            "config" => Some(Self::Config(String::from_utf8(field("data")?).ok()?)),
+            "stop" => Some(Self::Stop),
+            _ => None,
+        }
+    }
+}
+
+/// How many of the newest control messages the first poll searches for the current policy
+const POLICY_LOOKBACK: usize = 64;
+
+/// Pushes rollouts to a Redis stream and polls another stream for control messages,
+/// give it to `RolloutWorker::new` to collect the rollouts.
+///
+/// Each rollout is added to the rollout stream as an entry with `worker`, `policy_version`
+/// and `data` fields, `data` being the rollout encoded with `Rollout::encode`.
+///
+/// Control messages that were added before the first poll are skipped,
+/// except for the newest policy among the last few messages so a worker that joins late can start right away
+pub struct RedisQueue {
+    connection: Connection,
+    worker_id: String,
+    rollout_key: String,
+    control_key: String,
+    max_len: Option<usize>,
+    /// `None` until the first poll, which starts after the newest control message
+    last_control_id: Option<String>,
+    on_config: Option<Box<dyn FnMut(&str)>>,
+}
+
+impl RedisQueue {
+    /// Connect to the Redis server at `url`, e.g. `redis://127.0.0.1/`
+    pub fn connect(url: &str, worker_id: impl Into<String>) -> Result<Self> {
+        let connection = redis::Client::open(url)
+            .and_then(|client| client.get_connection())
+            .map_err(redis_error)?;
+
+        Ok(Self {
+            connection,
+            worker_id: worker_id.into(),
+            rollout_key: String::from("rlgym:rollouts"),
+            control_key: String::from("rlgym:control"),
+            max_len: None,
+            last_control_id: None,
+            on_config: None,
+        })
+    }
+
+    /// Defaults to `rlgym:rollouts` and `rlgym:control`
+    pub fn with_keys(
+        mut self,
+        rollout_key: impl Into<String>,
+        control_key: impl Into<String>,
+    ) -> Self {
+        self.rollout_key = rollout_key.into();
+        self.control_key = control_key.into();
+        self
+    }
+
+    /// Let Redis trim the rollout stream to roughly `max_len` entries, unlimited by default
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    /// Call `on_config` with every config message, e.g. to update reward weights
+    pub fn with_config_handler(mut self, on_config: impl FnMut(&str) + 'static) -> Self {
+        self.on_config = Some(Box::new(on_config));
+        self
+    }
+
+    /// Add a rollout to the rollout stream, returns the id of the new entry
+    pub fn push(&mut self, policy_version: u64, transitions: Vec<Transition>) -> Result<String> {
+        let rollout = Rollout {
+            worker_id: self.worker_id.clone(),
+            policy_version,
+            transitions,
+        };
+
+        let mut command = redis::cmd("XADD");
+        command.arg(&self.rollout_key);
+        if let Some(max_len) = self.max_len {
+            command.arg("MAXLEN").arg("~").arg(max_len);
+        }
+        command
+            .arg("*")
+            .arg("worker")
+            .arg(&self.worker_id)
+            .arg("policy_version")
+            .arg(policy_version)
+            .arg("data")
+            .arg(rollout.encode())
+            .query(&mut self.connection)
+            .map_err(redis_error)
+    }
+
+    /// All the control messages added since the last poll, without blocking.
+    ///
+    /// The first poll starts with the newest policy that was published before it, if there is one.
+    /// Entries that can't be parsed are skipped
+    pub fn poll_control(&mut self) -> Result<Vec<ControlMessage>> {
+        let mut messages = Vec::new();
+        let mut last_control_id = match self.last_control_id.take() {
+            Some(id) => id,
+            None => {
+                // the newest entry first
+                let recent: StreamRangeReply = self
+                    .connection
+                    .xrevrange_count(&self.control_key, "+", "-", POLICY_LOOKBACK)
+                    .map_err(redis_error)?;
+                messages.extend(
+                    recent
+                        .ids
+                        .iter()
+                        .filter_map(|entry| ControlMessage::parse(&entry.map))
+                        .find(|message| matches!(message, ControlMessage::Policy { .. })),
+                );
+
+                recent
+                    .ids
+                    .first()
+                    .map_or_else(|| String::from("0-0"), |entry| entry.id.clone())
+            }
+        };
+        self.last_control_id = Some(last_control_id.clone());
+
+        // Redis replies with nil when there's nothing new
+        let reply: Option<StreamReadReply> = self
+            .connection
+            .xread(&[&self.control_key], &[&last_control_id])
+            .map_err(redis_error)?;
+
+        let entries = reply
+            .into_iter()
+            .flat_map(|reply| reply.keys)
+            .flat_map(|key| key.ids);
+        for entry in entries {
+            messages.extend(ControlMessage::parse(&entry.map));
+            last_control_id = entry.id;
+        }
+        self.last_control_id = Some(last_control_id);
+
+        Ok(messages)
+    }
+}
+
+/// Control messages are only polled between rollouts, so each rollout is collected with one policy
+impl RolloutQueue for RedisQueue {
+    fn push_rollout(&mut self, policy_version: u64, transitions: Vec<Transition>) -> Result<()> {
+        self.push(policy_version, transitions).map(drop)
+    }
+
+    fn poll(&mut self) -> Result<QueueUpdate> {
+        let mut update = QueueUpdate::Unchanged;
+        for message in self.poll_control()? {
+            match message {
+                ControlMessage::Policy { version, model } => {
+                    update = QueueUpdate::Policy { version, model };
+                }
+                ControlMessage::Config(config) => {
+                    if let Some(on_config) = &mut self.on_config {
+                        on_config(&config);
+                    }
+                }
+                ControlMessage::Stop => return Ok(QueueUpdate::Stop),
+            }
+        }
+
+        Ok(update)
+    }
+}
+
+fn redis_error(error: redis::RedisError) -> Error {
+    io::Error::new(io::ErrorKind::Other, error).into()
+}
@@ -0,0 +1,253 @@
+use crate::{
+    handshake::Handshake,
+    render::{UdpPacketTypes, RLVISER_PORT, ROCKETSIM_PORT},
+    wire::invalid_data,
+    Result,
+};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use std::{
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+const GAME_STATE: u8 = UdpPacketTypes::GameState as u8;
+const QUIT: u8 = UdpPacketTypes::Quit as u8;
+const CONNECTION: u8 = UdpPacketTypes::Connection as u8;
+
+const FLAG_COMPRESSED: u8 = 1;
+
+/// How often the relay wakes up to notice that the other direction stopped
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long to wait for the payload that follows a packet type before dropping the packet
+const PAYLOAD_TIMEOUT: Duration = Duration::from_secs(1);
+/// Payloads come from single UDP packets, so anything longer isn't one
+const MAX_PAYLOAD_LEN: usize = u16::MAX as usize;
+
+/// Version of the frames exchanged by relays, checked with a `Handshake` when they connect
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Relays the RLViser packet stream over TCP, so training on a remote machine can be watched locally.
+///
+/// Run `forward` on the training machine in place of RLViser and `receive` on the watching machine next to RLViser,
+/// the env and RLViser then talk to the relay as if they were on the same machine.
+///
+/// After a `Handshake`, every UDP packet (or packet type and payload pair) becomes one TCP frame:
+/// the packet type, a flags byte, the payload length as a little-endian `u32` and the payload
+#[derive(Clone, Copy, Debug)]
+pub struct RenderRelay {
+    decimation: u32,
+    compression: bool,
+}
+
+impl Default for RenderRelay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderRelay {
+    pub fn new() -> Self {
+        Self {
+            decimation: 1,
+            compression: false,
+        }
+    }
+
+    /// Only forward every `decimation`th game state, defaults to 1.
+    /// RLViser interpolates between states, so this mostly costs smoothness
+    pub fn with_decimation(mut self, decimation: u32) -> Self {
+        self.decimation = decimation.max(1);
+        self
+    }
+
+    /// Deflate payloads before sending them, defaults to false
+    pub fn with_compression(mut self, compression: bool) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Connect to a `receive`ing relay at `addr` and forward the env's packets to it until either side disconnects
+    pub fn forward(&self, addr: impl ToSocketAddrs) -> Result<()> {
+        let stream = TcpStream::connect(addr)?;
+        let udp = UdpSocket::bind((Ipv4Addr::LOCALHOST, RLVISER_PORT))?;
+        let env = SocketAddr::from((Ipv4Addr::LOCALHOST, ROCKETSIM_PORT));
+
+        Ok(self.bridge(udp, env, stream)?)
+    }
+
+    /// Listen on `addr` for a `forward`ing relay and pass its packets on to RLViser,
+    /// accepting a new connection whenever the previous one ends
+    pub fn receive(&self, addr: impl ToSocketAddrs) -> Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        let rlviser = SocketAddr::from((Ipv4Addr::LOCALHOST, RLVISER_PORT));
+
+        for stream in listener.incoming() {
+            let udp = UdpSocket::bind((Ipv4Addr::LOCALHOST, ROCKETSIM_PORT))?;
+            if let Err(e) = self.bridge(udp, rlviser, stream?) {
+                log::warn!("Render relay connection ended: {e}");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn bridge(&self, udp: UdpSocket, peer: SocketAddr, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        Handshake::new(PROTOCOL_VERSION).exchange(&mut stream)?;
+        udp.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let tcp_to_udp = {
+            let udp = udp.try_clone()?;
+            let stream = stream.try_clone()?;
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let result = tcp_to_udp(&udp, peer, stream);
+                stop.store(true, Ordering::Relaxed);
+                result
+            })
+        };
+
+        let result = self.udp_to_tcp(&udp, stream.try_clone()?, &stop);
+        stop.store(true, Ordering::Relaxed);
+        // wakes up the other direction if it's waiting on the stream, which may already be closed
+        let _ = stream.shutdown(Shutdown::Both);
+
+        let other = tcp_to_udp.join().unwrap_or_else(|_| {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                "The render relay thread panicked",
+            ))
+        });
+        result.and(other)
+    }
+
+    fn udp_to_tcp(&self, udp: &UdpSocket, stream: TcpStream, stop: &AtomicBool) -> io::Result<()> {
+        let mut writer = BufWriter::new(stream.try_clone()?);
+        let mut buffer = vec![0; u16::MAX as usize];
+        let mut states = 0u32;
+
+        while !stop.load(Ordering::Relaxed) {
+            let len = match udp.recv(&mut buffer) {
+                Ok(len) => len,
+                Err(e) if is_timeout(&e) => continue,
+                Err(e) => return Err(e),
+            };
+
+            let Some(&packet_type) = buffer[..len].first() else {
+                continue;
+            };
+
+            // every other packet type is followed by a payload in the next packet
+            let payload = if matches!(packet_type, QUIT | CONNECTION) {
+                Vec::new()
+            } else {
+                udp.set_read_timeout(Some(PAYLOAD_TIMEOUT))?;
+                let received = udp.recv(&mut buffer);
+                udp.set_read_timeout(Some(POLL_INTERVAL))?;
+
+                match received {
+                    Ok(len) => buffer[..len].to_vec(),
+                    Err(e) if is_timeout(&e) => {
+                        log::debug!(
+                            "Dropping a packet of type {packet_type} whose payload never came"
+                        );
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                }
+            };
+
+            if packet_type == GAME_STATE {
+                states = states.wrapping_add(1);
+                if states % self.decimation != 0 {
+                    continue;
+                }
+            }
+
+            write_frame(&mut writer, packet_type, &payload, self.compression)?;
+            if packet_type == QUIT {
+                // the peer stopping doesn't need the relay anymore
+                return stream.shutdown(Shutdown::Both);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_frame(
+    writer: &mut impl Write,
+    packet_type: u8,
+    payload: &[u8],
+    compression: bool,
+) -> io::Result<()> {
+    let (flags, payload) = if compression && !payload.is_empty() {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(payload)?;
+        (FLAG_COMPRESSED, encoder.finish()?)
+    } else {
+        (0, payload.to_vec())
+    };
+
+    writer.write_all(&[packet_type, flags])?;
+    writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+    writer.write_all(&payload)?;
+    writer.flush()
+}
+
+fn tcp_to_udp(udp: &UdpSocket, peer: SocketAddr, stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        let mut header = [0; 6];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        }
+
+        let [packet_type, flags, len @ ..] = header;
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_PAYLOAD_LEN {
+            return Err(invalid_data(format!(
+                "A payload of {len} bytes is longer than any UDP packet"
+            )));
+        }
+
+        let mut payload = vec![0; len];
+        reader.read_exact(&mut payload)?;
+
+        if flags & FLAG_COMPRESSED != 0 {
+            let mut decompressed = Vec::new();
+            DeflateDecoder::new(payload.as_slice())
+                .take(MAX_PAYLOAD_LEN as u64 + 1)
+                .read_to_end(&mut decompressed)?;
+            if decompressed.len() > MAX_PAYLOAD_LEN {
+                return Err(invalid_data(
+                    "A compressed payload is longer than any UDP packet",
+                ));
+            }
+
+            payload = decompressed;
+        }
+
+        udp.send_to(&[packet_type], peer)?;
+        if !payload.is_empty() {
+            udp.send_to(&payload, peer)?;
+        }
+    }
+}
+
+fn is_timeout(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
@@ -0,0 +1,175 @@
+use crate::{
+    wire::{Reader, Writer},
+    Action, Env, FullObs, Obs, Result, Reward, Shared, SharedInfoProvider, StateSetter, Terminal,
+    Truncate,
+};
+use rocketsim_rs::{glam_ext::GameStateA, sim::CarControls, GameState};
+use std::io;
+
+/// Builds an action parser's input from the per-agent float vectors sent by a remote trainer
+pub trait FromRemoteActions: Sized {
+    fn from_remote(actions: Vec<Vec<f32>>) -> Self;
+}
+
+/// Discrete actions, the first value of each agent is the action index
+impl FromRemoteActions for Vec<i32> {
+    fn from_remote(actions: Vec<Vec<f32>>) -> Self {
+        actions
+            .into_iter()
+            .map(|action| action.first().copied().unwrap_or_default() as i32)
+            .collect()
+    }
+}
+
+impl FromRemoteActions for Vec<Vec<f32>> {
+    fn from_remote(actions: Vec<Vec<f32>>) -> Self {
+        actions
+    }
+}
+
+/// Raw controls in the order throttle, steer, pitch, yaw, roll, jump, boost, handbrake,
+/// buttons are pressed if their value is above 0.5
+impl FromRemoteActions for Vec<CarControls> {
+    fn from_remote(actions: Vec<Vec<f32>>) -> Self {
+        actions
+            .into_iter()
+            .map(|action| {
+                let value = |i: usize| action.get(i).copied().unwrap_or_default();
+
+                CarControls {
+                    throttle: value(0),
+                    steer: value(1),
+                    pitch: value(2),
+                    yaw: value(3),
+                    roll: value(4),
+                    jump: value(5) > 0.5,
+                    boost: value(6) > 0.5,
+                    handbrake: value(7) > 0.5,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The result of a step without any shared pointers, ready to be sent over the network
+#[derive(Clone, Debug, Default)]
+pub struct RemoteStep {
+    pub obs: FullObs,
+    pub rewards: Vec<f32>,
+    pub is_terminal: bool,
+    pub truncated: bool,
+}
+
+/// One step of a trajectory collected by a remote worker,
+/// `obs` is the observation the actions were chosen from
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Transition {
+    pub obs: FullObs,
+    pub actions: Vec<Vec<f32>>,
+    pub rewards: Vec<f32>,
+    pub is_terminal: bool,
+    pub truncated: bool,
+    /// The last observation of the episode if this step ended it, e.g. to bootstrap from a truncated episode
+    pub final_obs: Option<FullObs>,
+}
+
+/// Writes the obs, actions, rewards, `is_terminal`, `truncated` and `final_obs` in that order,
+/// `final_obs` being a flag followed by the observations if it's set
+pub(crate) fn write_transition(writer: &mut Writer, transition: &Transition) {
+    writer
+        .float_lists(&transition.obs)
+        .float_lists(&transition.actions)
+        .floats(&transition.rewards)
+        .bool(transition.is_terminal)
+        .bool(transition.truncated)
+        .bool(transition.final_obs.is_some());
+
+    if let Some(final_obs) = &transition.final_obs {
+        writer.float_lists(final_obs);
+    }
+}
+
+pub(crate) fn read_transition(reader: &mut Reader) -> io::Result<Transition> {
+    Ok(Transition {
+        obs: reader.float_lists()?,
+        actions: reader.float_lists()?,
+        rewards: reader.floats()?,
+        is_terminal: reader.bool()?,
+        truncated: reader.bool()?,
+        final_obs: if reader.bool()? {
+            Some(reader.float_lists()?)
+        } else {
+            None
+        },
+    })
+}
+
+/// An object safe view of an `Env` that only deals in plain numbers,
+/// used by everything that serves environments to other processes
+pub trait RemoteEnv {
+    fn reset(&mut self) -> Result<FullObs>;
+    fn step(&mut self, actions: Vec<Vec<f32>>) -> Result<RemoteStep>;
+    /// Observation size of each agent controlled by the trainer
+    fn obs_spaces(&self) -> Vec<usize>;
+    /// Action size of each agent controlled by the trainer
+    fn action_spaces(&self) -> Vec<usize>;
+    /// The state after the last reset or step
+    fn state(&self) -> Option<Shared<GameStateA>>;
+    /// The arena's current state in the format used by RLViser
+    fn snapshot(&mut self) -> GameState;
+    fn reset_to(&mut self, state: &GameState) -> Result<FullObs>;
+}
+
+impl<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI> RemoteEnv
+    for Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI>
+where
+    SS: StateSetter<SI>,
+    SIP: SharedInfoProvider<SI>,
+    OBS: Obs<SI>,
+    ACT: Action<SI>,
+    ACT::Input: FromRemoteActions,
+    REW: Reward<SI>,
+    TERM: Terminal<SI>,
+    TRUNC: Truncate<SI>,
+{
+    fn reset(&mut self) -> Result<FullObs> {
+        Env::reset(self).map(|obs| obs.as_ref().clone())
+    }
+
+    fn step(&mut self, actions: Vec<Vec<f32>>) -> Result<RemoteStep> {
+        let result = Env::step(self, ACT::Input::from_remote(actions))?;
+
+        Ok(RemoteStep {
+            obs: result.obs.as_ref().clone(),
+            rewards: result.rewards,
+            is_terminal: result.is_terminal,
+            truncated: result.truncated,
+        })
+    }
+
+    fn obs_spaces(&self) -> Vec<usize> {
+        self.external_agent_ids()
+            .into_iter()
+            .map(|id| self.get_obs_space(id))
+            .collect()
+    }
+
+    fn action_spaces(&self) -> Vec<usize> {
+        self.external_agent_ids()
+            .into_iter()
+            .map(|id| self.get_action_space(id))
+            .collect()
+    }
+
+    fn state(&self) -> Option<Shared<GameStateA>> {
+        self.last_state.clone()
+    }
+
+    fn snapshot(&mut self) -> GameState {
+        self.arena.pin_mut().get_game_state()
+    }
+
+    fn reset_to(&mut self, state: &GameState) -> Result<FullObs> {
+        Env::reset_to(self, state).map(|obs| obs.as_ref().clone())
+    }
+}
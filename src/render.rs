@@ -1,24 +1,34 @@
+use crate::{handshake::ROCKETSIM_VERSION, Renderer};
 use rocketsim_rs::{
     bytes::{FromBytes, FromBytesExact, ToBytes},
-    cxx::UniquePtr,
-    sim::Arena,
+    render::RenderMessage,
     GameState,
 };
 use std::{
     io,
-    net::{IpAddr, SocketAddr, UdpSocket},
-    process::Command,
-    str::FromStr,
-    time::Duration,
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+    path::PathBuf,
+    process::{Child, Command},
+    thread::sleep,
+    time::{Duration, Instant},
 };
 
 /// Pass this into rlviser as the first argument
 /// default: 45243
-const RLVISER_PORT: u16 = 45243;
+pub(crate) const RLVISER_PORT: u16 = 45243;
 
 /// Pass this into rlviser as the second argument
 /// default: 34254
-const ROCKETSIM_PORT: u16 = 34254;
+pub(crate) const ROCKETSIM_PORT: u16 = 34254;
+
+/// Version of the packets exchanged with RLViser, optionally sent after the type byte of connection packets.
+///
+/// Peers that don't know about it only read the type byte, and one that doesn't send a version
+/// is taken to speak this one. The render relay forwards connection packets without it
+pub(crate) const RLVISER_PROTOCOL_VERSION: u32 = 1;
+
+/// A game state from RLViser has to fit in a single UDP packet
+const MAX_PACKET_LEN: usize = u16::MAX as usize;
 
 const RLVISER_PATH: &str = if cfg!(windows) {
     "./rlviser.exe"
@@ -26,9 +36,130 @@ const RLVISER_PATH: &str = if cfg!(windows) {
     "./rlviser"
 };
 
+/// Whether rendering starts RLViser or uses one that's already running
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LaunchMode {
+    /// Connect to an RLViser that's already running, or will be started by hand
+    Connect,
+    /// Always launch RLViser
+    #[default]
+    Spawn,
+    /// Launch RLViser only if none answers within `RenderConfig::connect_timeout`, or half a second without one
+    SpawnIfMissing,
+}
+
+/// Where RLViser is and how to talk to it, the default matches RLViser's own defaults.
+///
+/// Give every env on a machine its own pair of ports to render several of them at once
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenderConfig {
+    /// The RLViser binary, launched with the two ports and then `args` as its arguments
+    pub path: PathBuf,
+    /// Extra arguments for RLViser when it's launched
+    pub args: Vec<String>,
+    pub launch: LaunchMode,
+    /// Wait this long for RLViser to answer before rendering starts, failing if it doesn't.
+    ///
+    /// Rendering starts right away by default and RLViser gets the states once it's up
+    pub connect_timeout: Option<Duration>,
+    /// The port RLViser listens on
+    pub rlviser_port: u16,
+    /// The port the env listens on for messages from RLViser
+    pub rocketsim_port: u16,
+    /// The address the env's socket is bound to
+    pub bind_addr: IpAddr,
+    /// How often to try to reach RLViser again after it quit or went away,
+    /// `None` to report it as a render error on every step instead, see `RenderErrorPolicy`
+    pub reconnect_interval: Option<Duration>,
+    /// More addresses to send every game state to, e.g. another RLViser or a dashboard,
+    /// in the same packets RLViser gets
+    pub spectators: Vec<SocketAddr>,
+    /// Talk to RLViser from its own thread through a queue of this many packets, see `ThreadedRenderer`
+    pub render_thread: Option<usize>,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from(RLVISER_PATH),
+            args: Vec::new(),
+            launch: LaunchMode::default(),
+            connect_timeout: None,
+            rlviser_port: RLVISER_PORT,
+            rocketsim_port: ROCKETSIM_PORT,
+            bind_addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            reconnect_interval: Some(Duration::from_secs(1)),
+            spectators: Vec::new(),
+            render_thread: None,
+        }
+    }
+}
+
+impl RenderConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// `LaunchMode::Spawn` if `spawn`, otherwise `LaunchMode::Connect`
+    pub fn with_spawn(mut self, spawn: bool) -> Self {
+        self.launch = if spawn {
+            LaunchMode::Spawn
+        } else {
+            LaunchMode::Connect
+        };
+        self
+    }
+
+    pub fn with_launch(mut self, launch: LaunchMode) -> Self {
+        self.launch = launch;
+        self
+    }
+
+    pub fn with_args<S: Into<String>>(mut self, args: impl IntoIterator<Item = S>) -> Self {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    pub fn with_spectator(mut self, spectator: SocketAddr) -> Self {
+        self.spectators.push(spectator);
+        self
+    }
+
+    pub fn with_render_thread(mut self, capacity: usize) -> Self {
+        self.render_thread = Some(capacity);
+        self
+    }
+
+    pub fn with_ports(mut self, rlviser_port: u16, rocketsim_port: u16) -> Self {
+        self.rlviser_port = rlviser_port;
+        self.rocketsim_port = rocketsim_port;
+        self
+    }
+
+    pub fn with_bind_addr(mut self, bind_addr: IpAddr) -> Self {
+        self.bind_addr = bind_addr;
+        self
+    }
+
+    pub fn with_reconnect_interval(mut self, reconnect_interval: Option<Duration>) -> Self {
+        self.reconnect_interval = reconnect_interval;
+        self
+    }
+}
+
 #[repr(u8)]
 #[derive(Debug, Clone, Copy)]
-enum UdpPacketTypes {
+pub(crate) enum UdpPacketTypes {
     Quit,
     GameState,
     Connection,
@@ -37,124 +168,491 @@ enum UdpPacketTypes {
     Render,
 }
 
-impl From<u8> for UdpPacketTypes {
-    fn from(val: u8) -> Self {
+impl TryFrom<u8> for UdpPacketTypes {
+    type Error = io::Error;
+
+    fn try_from(val: u8) -> io::Result<Self> {
         match val {
-            0 => Self::Quit,
-            1 => Self::GameState,
-            2 => Self::Connection,
-            3 => Self::Paused,
-            4 => Self::Speed,
-            5 => Self::Render,
-            _ => panic!("Invalid packet type"),
+            0 => Ok(Self::Quit),
+            1 => Ok(Self::GameState),
+            2 => Ok(Self::Connection),
+            3 => Ok(Self::Paused),
+            4 => Ok(Self::Speed),
+            5 => Ok(Self::Render),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid packet type {val}"),
+            )),
         }
     }
 }
 
+/// Renders with RLViser, which is launched from `./rlviser` and talked to over UDP.
+///
+/// RLViser is taken to be gone once it sends a quit packet or the OS reports its port as closed,
+/// no states are sent to it until it answers a reconnection attempt.
+///
+/// Spectators get every state whether RLViser is there or not, but can't set states, pause or change the speed.
+/// Dropping the handler quits RLViser like `quit`, and reaps it if it was launched by the handler
 pub struct RLViserSocketHandler {
     socket: UdpSocket,
     rlviser_addr: SocketAddr,
     min_game_state_buf: [u8; GameState::MIN_NUM_BYTES],
     game_state_buffer: Vec<u8>,
     paused: bool,
+    connected: bool,
+    reconnect_interval: Option<Duration>,
+    last_attempt: Instant,
+    spectators: Vec<SocketAddr>,
+    child: Option<Child>,
 }
 
 impl RLViserSocketHandler {
     pub fn new() -> io::Result<Self> {
-        // launch rlviser
-        if let Err(e) = Command::new(RLVISER_PATH).spawn() {
-            eprintln!("Failed to launch RLViser ({RLVISER_PATH}): {e}");
-        }
+        Self::with_config(&RenderConfig::default())
+    }
 
+    pub fn with_config(config: &RenderConfig) -> io::Result<Self> {
         // open rlviser socket
-        let socket = UdpSocket::bind(("0.0.0.0", ROCKETSIM_PORT))?;
-        // print the socket address
-        println!("Listening on {}", socket.local_addr()?);
-
-        let rlviser_addr = SocketAddr::new(IpAddr::from_str("127.0.0.1").unwrap(), RLVISER_PORT);
+        let socket = UdpSocket::bind((config.bind_addr, config.rocketsim_port))?;
+        // log the socket address
+        log::info!("Listening on {}", socket.local_addr()?);
 
         // We now don't want to wait for anything UDP so set to non-blocking
         socket.set_nonblocking(true)?;
 
+        let mut handler = Self {
+            socket,
+            rlviser_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, config.rlviser_port)),
+            min_game_state_buf: [0; GameState::MIN_NUM_BYTES],
+            game_state_buffer: Vec::new(),
+            paused: false,
+            connected: true,
+            reconnect_interval: config.reconnect_interval,
+            last_attempt: Instant::now(),
+            spectators: config.spectators.clone(),
+            child: None,
+        };
+
+        let spawn = match config.launch {
+            LaunchMode::Connect => false,
+            LaunchMode::Spawn => true,
+            LaunchMode::SpawnIfMissing => {
+                let timeout = config.connect_timeout.unwrap_or(Duration::from_millis(500));
+                !handler.wait_for_rlviser(timeout)?
+            }
+        };
+
+        if spawn {
+            let spawned = Command::new(&config.path)
+                .arg(config.rlviser_port.to_string())
+                .arg(config.rocketsim_port.to_string())
+                .args(&config.args)
+                .spawn();
+
+            match spawned {
+                Ok(child) => handler.child = Some(child),
+                Err(e) => log::warn!("Failed to launch RLViser ({}): {e}", config.path.display()),
+            }
+        }
+
         // notify rlviser that we're connected
         // it will send us info on the desired game speed / if the game should be paused
         // if you choose to ignore this, at least send the right game speed / paused state back
         // otherwise things like packet interpolation will be off
-        socket.send_to(&[UdpPacketTypes::Connection as u8], rlviser_addr)?;
+        handler
+            .socket
+            .send_to(&connection_packet(), handler.rlviser_addr)?;
 
-        Ok(Self {
-            socket,
-            rlviser_addr,
-            min_game_state_buf: [0; GameState::MIN_NUM_BYTES],
-            game_state_buffer: Vec::new(),
-            paused: false,
-        })
+        if let Some(timeout) = config.connect_timeout {
+            if !handler.wait_for_rlviser(timeout)? {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("RLViser didn't answer within {timeout:?}"),
+                ));
+            }
+        }
+
+        Ok(handler)
     }
 
-    pub fn is_paused(&self) -> bool {
-        self.paused
+    /// Ask RLViser to connect until it answers or `timeout` passes, returns whether it answered.
+    ///
+    /// Its answer is only peeked at, so `receive` still handles it
+    fn wait_for_rlviser(&self, timeout: Duration) -> io::Result<bool> {
+        const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+        let start = Instant::now();
+        let mut last_sent = None::<Instant>;
+        let mut byte_buffer = [0];
+
+        while start.elapsed() < timeout {
+            if last_sent.map_or(true, |last_sent| last_sent.elapsed() >= RETRY_INTERVAL) {
+                // fails while nothing listens on the port, e.g. while RLViser is starting
+                let _ = self.socket.send_to(&connection_packet(), self.rlviser_addr);
+                last_sent = Some(Instant::now());
+            }
+
+            match self.socket.peek_from(&mut byte_buffer) {
+                Ok(_) => return Ok(true),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock
+                            | io::ErrorKind::ConnectionRefused
+                            | io::ErrorKind::ConnectionReset
+                    ) =>
+                {
+                    sleep(Duration::from_millis(10));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(false)
     }
 
-    pub fn send_state(&mut self, game_state: &GameState) -> io::Result<()> {
-        self.socket
-            .send_to(&[UdpPacketTypes::GameState as u8], self.rlviser_addr)?;
-        self.socket
-            .send_to(&game_state.to_bytes(), self.rlviser_addr)?;
+    /// Start sending game states to `spectator` too
+    pub fn add_spectator(&mut self, spectator: SocketAddr) {
+        if !self.spectators.contains(&spectator) {
+            self.spectators.push(spectator);
+        }
+    }
+
+    pub fn remove_spectator(&mut self, spectator: SocketAddr) {
+        self.spectators.retain(|addr| *addr != spectator);
+    }
+
+    pub fn spectators(&self) -> &[SocketAddr] {
+        &self.spectators
+    }
+
+    fn disconnect(&mut self, reason: &str) {
+        if self.connected {
+            log::warn!("RLViser {reason}, no longer sending it states");
+            self.connected = false;
+            self.last_attempt = Instant::now();
+        }
+    }
+
+    /// Ask RLViser to connect again every `reconnect_interval`, it answers with a connection packet once it's back
+    fn reconnect(&mut self) -> io::Result<()> {
+        let Some(interval) = self.reconnect_interval else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "RLViser quit or went away",
+            ));
+        };
+
+        if self.last_attempt.elapsed() >= interval {
+            self.last_attempt = Instant::now();
+            // fails while nothing listens on the port, which is expected until RLViser is back
+            let _ = self.socket.send_to(&connection_packet(), self.rlviser_addr);
+        }
 
         Ok(())
     }
 
-    pub fn handle_return_message(
+    /// Handle every message RLViser sent since the last call,
+    /// passing the states it asks to be set to `set_state`
+    pub(crate) fn receive(
         &mut self,
-        arena: &mut UniquePtr<Arena>,
         interval: &mut Duration,
         tick_skip: u32,
+        mut set_state: impl FnMut(GameState),
     ) -> io::Result<()> {
-        let mut byte_buffer = [0];
+        // room for the version after the type of a connection packet, every other packet type comes alone
+        let mut packet = [0; CONNECTION_PACKET_LEN];
 
-        while let Ok((_, src)) = self.socket.recv_from(&mut byte_buffer) {
-            let packet_type = UdpPacketTypes::from(byte_buffer[0]);
+        loop {
+            let (len, src) = match self.socket.recv_from(&mut packet) {
+                Ok(received) => received,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                // the OS saw that nothing listens on RLViser's port anymore
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::ConnectionRefused | io::ErrorKind::ConnectionReset
+                    ) =>
+                {
+                    self.disconnect("went away");
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+
+            // spectators only watch, and whatever they send is dropped one packet at a time
+            if self.spectators.contains(&src) {
+                continue;
+            }
+
+            if !self.connected {
+                log::info!("RLViser is back, sending it states again");
+                self.connected = true;
+            }
+
+            let packet_type = UdpPacketTypes::try_from(packet[0])?;
 
             match packet_type {
                 UdpPacketTypes::GameState => {
-                    self.socket.peek_from(&mut self.min_game_state_buf)?;
-
-                    let num_bytes = GameState::get_num_bytes(&self.min_game_state_buf);
-                    self.game_state_buffer.resize(num_bytes, 0);
-                    self.socket.recv_from(&mut self.game_state_buffer)?;
+                    let (peeked, _) = self.socket.peek_from(&mut self.min_game_state_buf)?;
+                    let num_bytes = (peeked == GameState::MIN_NUM_BYTES)
+                        .then(|| GameState::get_num_bytes(&self.min_game_state_buf));
 
-                    // set the game state
-                    let game_state = GameState::from_bytes(&self.game_state_buffer);
-                    if let Err(e) = arena.pin_mut().set_game_state(&game_state) {
-                        println!("Error setting game state: {e}");
+                    // one byte more than expected, so a longer state isn't cut down to the right length
+                    let buffer_len = match num_bytes {
+                        Some(num_bytes) if num_bytes < MAX_PACKET_LEN => num_bytes + 1,
+                        _ => MAX_PACKET_LEN,
                     };
+                    self.game_state_buffer.resize(buffer_len, 0);
+                    let (received, _) = self.socket.recv_from(&mut self.game_state_buffer)?;
+
+                    if num_bytes != Some(received) {
+                        return Err(mismatched_state(received, num_bytes));
+                    }
+
+                    set_state(GameState::from_bytes(&self.game_state_buffer[..received]));
                 }
                 UdpPacketTypes::Connection => {
-                    println!("Connection established to {src}");
+                    if let Err(e) = check_protocol_version(&packet[1..len]) {
+                        self.disconnect("speaks another protocol version");
+                        return Err(e);
+                    }
+
+                    log::info!("Connection established to {src}");
                 }
                 UdpPacketTypes::Speed => {
-                    let mut speed_buffer = [0; f32::NUM_BYTES];
-                    self.socket.recv_from(&mut speed_buffer)?;
-                    let speed = f32::from_bytes(&speed_buffer);
+                    let mut speed_buffer = [0; f32::NUM_BYTES + 1];
+                    let (received, _) = self.socket.recv_from(&mut speed_buffer)?;
+                    if received != f32::NUM_BYTES {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!(
+                                "RLViser sent a {received} byte game speed instead of {}, \
+                                 it may be built for another version of rocketsim_rs than {ROCKETSIM_VERSION}",
+                                f32::NUM_BYTES
+                            ),
+                        ));
+                    }
+
+                    let speed = f32::from_bytes(&speed_buffer[..received]);
                     *interval = Duration::from_secs_f32(tick_skip as f32 / (120. * speed));
                 }
                 UdpPacketTypes::Paused => {
-                    self.socket.recv_from(&mut byte_buffer)?;
-                    self.paused = byte_buffer[0] == 1;
+                    let mut paused = [0];
+                    self.socket.recv_from(&mut paused)?;
+                    self.paused = paused[0] == 1;
+                }
+                UdpPacketTypes::Quit => {
+                    self.disconnect("quit");
+                    break;
+                }
+                UdpPacketTypes::Render => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("We shouldn't be receiving packets of type {packet_type:?}"),
+                    ));
                 }
-                UdpPacketTypes::Quit | UdpPacketTypes::Render => {
-                    panic!("We shouldn't be receiving packets of type {packet_type:?}")
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Close the connection, and quit RLViser if this handler launched it.
+    /// An RLViser that was already running, e.g. with `LaunchMode::Connect`, is left open
+    pub fn quit(mut self) -> io::Result<()> {
+        self.shutdown()
+    }
+
+    /// Tell the RLViser we launched to quit and wait for it to exit, killing it if it doesn't in time
+    fn shutdown(&mut self) -> io::Result<()> {
+        const EXIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+        self.connected = false;
+        // it might still be starting up, so it's told to quit even if it never answered
+        let sent = if self.child.is_some() {
+            self.socket
+                .send_to(&[UdpPacketTypes::Quit as u8], self.rlviser_addr)
+                .map(|_| ())
+        } else {
+            Ok(())
+        };
+
+        if let Some(mut child) = self.child.take() {
+            let start = Instant::now();
+            while child.try_wait()?.is_none() {
+                if start.elapsed() >= EXIT_TIMEOUT {
+                    log::warn!("RLViser didn't quit within {EXIT_TIMEOUT:?}, killing it");
+                    child.kill()?;
+                    child.wait()?;
+                    break;
                 }
+
+                sleep(Duration::from_millis(10));
+            }
+        }
+
+        sent
+    }
+}
+
+impl Drop for RLViserSocketHandler {
+    fn drop(&mut self) {
+        if let Err(e) = self.shutdown() {
+            log::warn!("Error closing RLViser: {e}");
+        }
+    }
+}
+
+const CONNECTION_PACKET_LEN: usize = 1 + std::mem::size_of::<u32>();
+
+/// The connection packet type followed by `RLVISER_PROTOCOL_VERSION`
+fn connection_packet() -> [u8; CONNECTION_PACKET_LEN] {
+    let mut packet = [UdpPacketTypes::Connection as u8; CONNECTION_PACKET_LEN];
+    packet[1..].copy_from_slice(&RLVISER_PROTOCOL_VERSION.to_le_bytes());
+    packet
+}
+
+/// Check the version after the type of a connection packet, if the peer sent one
+fn check_protocol_version(payload: &[u8]) -> io::Result<()> {
+    let Some(&[a, b, c, d]) = payload.get(..4) else {
+        return Ok(());
+    };
+
+    let version = u32::from_le_bytes([a, b, c, d]);
+    if version == RLVISER_PROTOCOL_VERSION {
+        return Ok(());
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "RLViser speaks protocol version {version} but rlgym_rs speaks {RLVISER_PROTOCOL_VERSION}, \
+             use an RLViser built for rocketsim_rs {ROCKETSIM_VERSION}"
+        ),
+    ))
+}
+
+fn mismatched_state(received: usize, expected: Option<usize>) -> io::Error {
+    let expected = match expected {
+        Some(num_bytes) => num_bytes.to_string(),
+        None => format!("at least {}", GameState::MIN_NUM_BYTES),
+    };
+
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "RLViser sent a {received} byte game state where {expected} were expected, \
+             it may be built for another version of rocketsim_rs than {ROCKETSIM_VERSION}"
+        ),
+    )
+}
+
+impl Renderer for RLViserSocketHandler {
+    fn send_state(&mut self, game_state: &GameState) -> io::Result<()> {
+        if !self.spectators.is_empty() {
+            let bytes = game_state.to_bytes();
+            for spectator in &self.spectators {
+                // a spectator that's gone shouldn't stop anyone else from watching
+                let _ = self
+                    .socket
+                    .send_to(&[UdpPacketTypes::GameState as u8], spectator);
+                let _ = self.socket.send_to(&bytes, spectator);
             }
         }
 
+        if !self.connected {
+            return self.reconnect();
+        }
+
+        self.socket
+            .send_to(&[UdpPacketTypes::GameState as u8], self.rlviser_addr)?;
+        self.socket
+            .send_to(&game_state.to_bytes(), self.rlviser_addr)?;
+
         Ok(())
     }
 
-    pub fn quit(self) -> io::Result<()> {
+    fn send_render(&mut self, message: &RenderMessage) -> io::Result<()> {
+        let bytes = message.to_bytes();
+        for spectator in &self.spectators {
+            let _ = self
+                .socket
+                .send_to(&[UdpPacketTypes::Render as u8], spectator);
+            let _ = self.socket.send_to(&bytes, spectator);
+        }
+
+        // shapes are dropped while RLViser is gone, `send_state` takes care of reconnecting
+        if !self.connected {
+            return Ok(());
+        }
+
+        self.socket
+            .send_to(&[UdpPacketTypes::Render as u8], self.rlviser_addr)?;
+        self.socket.send_to(&bytes, self.rlviser_addr)?;
+
+        Ok(())
+    }
+
+    /// RLViser uses it to smooth out the movement between states that are far apart
+    fn send_speed(&mut self, speed: f32) -> io::Result<()> {
+        if !self.connected {
+            return Ok(());
+        }
+
+        self.socket
+            .send_to(&[UdpPacketTypes::Speed as u8], self.rlviser_addr)?;
         self.socket
-            .send_to(&[UdpPacketTypes::Quit as u8], self.rlviser_addr)?;
+            .send_to(&speed.to_le_bytes(), self.rlviser_addr)?;
 
         Ok(())
     }
+
+    fn handle_messages(
+        &mut self,
+        interval: &mut Duration,
+        tick_skip: u32,
+        set_state: &mut dyn FnMut(GameState),
+    ) -> io::Result<()> {
+        self.receive(interval, tick_skip, set_state)
+    }
+
+    /// A game paused in an RLViser that's gone can't be resumed, so it's only paused while connected
+    fn is_paused(&self) -> bool {
+        self.paused && self.connected
+    }
+
+    fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    fn quit(self: Box<Self>) -> io::Result<()> {
+        RLViserSocketHandler::quit(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connection_packet_carries_the_version() {
+        let packet = connection_packet();
+        assert_eq!(packet[0], UdpPacketTypes::Connection as u8);
+        assert!(check_protocol_version(&packet[1..]).is_ok());
+    }
+
+    #[test]
+    fn peers_without_a_version_are_accepted() {
+        assert!(check_protocol_version(&[]).is_ok());
+    }
+
+    #[test]
+    fn other_versions_are_refused() {
+        let error =
+            check_protocol_version(&(RLVISER_PROTOCOL_VERSION + 1).to_le_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+        assert!(error.to_string().contains(ROCKETSIM_VERSION));
+    }
 }
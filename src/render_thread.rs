@@ -0,0 +1,201 @@
+use crate::Renderer;
+use rocketsim_rs::{render::RenderMessage, GameState};
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TryRecvError, TrySendError},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// How long the render thread waits for something to send before it checks for messages again
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Errors the env hasn't taken yet, more are only logged
+const ERROR_CAPACITY: usize = 16;
+
+enum Command {
+    State(GameState),
+    Render(RenderMessage),
+    Speed(f32),
+}
+
+/// What the render thread knows about the renderer, for the env to read without waiting on it
+struct Status {
+    paused: AtomicBool,
+    connected: AtomicBool,
+    /// The last state the renderer asked to set, replacing older ones the env didn't take yet
+    state: Mutex<Option<GameState>>,
+    /// Time between two ticks at the speed the renderer last asked for, taken by the next `handle_messages`
+    tick_interval: Mutex<Option<Duration>>,
+}
+
+/// Runs another renderer on its own thread, so stepping never waits on its sockets or messages.
+///
+/// States and debug shapes go through a queue of `capacity` entries, and are dropped while it's full,
+/// like UDP packets that got lost. Errors, states to set and speed changes from the renderer
+/// reach the env on its next call to `handle_messages`, where only the newest state to set is kept
+pub struct ThreadedRenderer {
+    commands: Option<SyncSender<Command>>,
+    errors: Receiver<io::Error>,
+    status: Arc<Status>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl ThreadedRenderer {
+    pub fn new<R: Renderer + Send + 'static>(renderer: R, capacity: usize) -> Self {
+        let (commands, command_rx) = sync_channel(capacity.max(1));
+        let (error_tx, errors) = sync_channel(ERROR_CAPACITY);
+        let status = Arc::new(Status {
+            paused: AtomicBool::new(renderer.is_paused()),
+            connected: AtomicBool::new(renderer.is_connected()),
+            state: Mutex::new(None),
+            tick_interval: Mutex::new(None),
+        });
+
+        let thread_status = status.clone();
+        let thread = thread::spawn(move || run(renderer, command_rx, error_tx, &thread_status));
+
+        Self {
+            commands: Some(commands),
+            errors,
+            status,
+            thread: Some(thread),
+        }
+    }
+
+    fn push(&self, command: Command) -> io::Result<()> {
+        let Some(commands) = &self.commands else {
+            return Ok(());
+        };
+
+        match commands.try_send(command) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                log::debug!("Render queue is full, dropping a packet");
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(_)) => Err(thread_stopped()),
+        }
+    }
+
+    /// Stop the thread once it sent everything in the queue, and close the renderer
+    fn stop(&mut self) -> io::Result<()> {
+        // closing the queue stops the thread
+        self.commands.take();
+
+        match self.thread.take() {
+            Some(thread) => thread.join().unwrap_or_else(|_| Err(thread_stopped())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Renderer for ThreadedRenderer {
+    fn send_state(&mut self, game_state: &GameState) -> io::Result<()> {
+        self.push(Command::State(game_state.clone()))
+    }
+
+    fn send_render(&mut self, message: &RenderMessage) -> io::Result<()> {
+        self.push(Command::Render(message.clone()))
+    }
+
+    fn send_speed(&mut self, speed: f32) -> io::Result<()> {
+        self.push(Command::Speed(speed))
+    }
+
+    fn handle_messages(
+        &mut self,
+        interval: &mut Duration,
+        tick_skip: u32,
+        set_state: &mut dyn FnMut(GameState),
+    ) -> io::Result<()> {
+        if let Some(tick_interval) = lock(&self.status.tick_interval).take() {
+            *interval = tick_interval * tick_skip;
+        }
+        if let Some(state) = lock(&self.status.state).take() {
+            set_state(state);
+        }
+
+        match self.errors.try_recv() {
+            Ok(e) => Err(e),
+            Err(TryRecvError::Empty) => Ok(()),
+            Err(TryRecvError::Disconnected) => Err(thread_stopped()),
+        }
+    }
+
+    fn is_paused(&self) -> bool {
+        self.status.paused.load(Ordering::Relaxed)
+    }
+
+    fn is_connected(&self) -> bool {
+        self.status.connected.load(Ordering::Relaxed)
+    }
+
+    fn quit(mut self: Box<Self>) -> io::Result<()> {
+        self.stop()
+    }
+}
+
+impl Drop for ThreadedRenderer {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            log::warn!("Error closing the render thread: {e}");
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn thread_stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "The render thread stopped")
+}
+
+fn run(
+    mut renderer: impl Renderer,
+    commands: Receiver<Command>,
+    errors: SyncSender<io::Error>,
+    status: &Status,
+) -> io::Result<()> {
+    let report = |result: io::Result<()>| {
+        if let Err(e) = result {
+            if let Err(TrySendError::Full(e)) = errors.try_send(e) {
+                log::warn!("Render error the env didn't take in time: {e}");
+            }
+        }
+    };
+
+    loop {
+        match commands.recv_timeout(POLL_INTERVAL) {
+            Ok(Command::State(state)) => report(renderer.send_state(&state)),
+            Ok(Command::Render(message)) => report(renderer.send_render(&message)),
+            Ok(Command::Speed(speed)) => report(renderer.send_speed(speed)),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        // asked for one tick at a time, the env scales it to its own tick skip
+        let mut tick_interval = Duration::ZERO;
+        report(
+            renderer.handle_messages(&mut tick_interval, 1, &mut |state| {
+                *lock(&status.state) = Some(state);
+            }),
+        );
+        if tick_interval != Duration::ZERO {
+            *lock(&status.tick_interval) = Some(tick_interval);
+        }
+
+        status.paused.store(renderer.is_paused(), Ordering::Relaxed);
+        status
+            .connected
+            .store(renderer.is_connected(), Ordering::Relaxed);
+    }
+
+    Box::new(renderer).quit()
+}
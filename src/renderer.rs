@@ -0,0 +1,48 @@
+use crate::MaybeSend;
+use rocketsim_rs::{render::RenderMessage, GameState};
+use std::{io, time::Duration};
+
+/// Shows the environment while it runs, `RLViserSocketHandler` with the `rlviser` feature
+/// or your own, e.g. a web visualizer. Enable one with `Env::enable_rendering`
+pub trait Renderer: MaybeSend {
+    fn send_state(&mut self, game_state: &GameState) -> io::Result<()>;
+    /// Draw or clear debug shapes from `DebugDraw`, renderers that can't show them ignore them
+    fn send_render(&mut self, _message: &RenderMessage) -> io::Result<()> {
+        Ok(())
+    }
+    /// Tell the renderer how fast the states it's sent play out compared to real time,
+    /// e.g. when only some steps are rendered with `Env::with_render_every`
+    fn send_speed(&mut self, _speed: f32) -> io::Result<()> {
+        Ok(())
+    }
+    /// Handle anything the renderer sent back since the last call, like a state to pass to `set_state`
+    /// or a new game speed, which changes the `interval` between steps of `tick_skip` ticks
+    fn handle_messages(
+        &mut self,
+        interval: &mut Duration,
+        tick_skip: u32,
+        set_state: &mut dyn FnMut(GameState),
+    ) -> io::Result<()>;
+    /// Whether the user paused the game in the renderer
+    fn is_paused(&self) -> bool {
+        false
+    }
+    /// Whether the renderer is still there to show states, `false` once it quit or stopped answering
+    fn is_connected(&self) -> bool {
+        true
+    }
+    /// Close the renderer
+    fn quit(self: Box<Self>) -> io::Result<()>;
+}
+
+/// What an `Env` does when talking to its renderer fails mid-training
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RenderErrorPolicy {
+    /// Log the error and keep rendering, packets are sent over UDP so losing some is expected
+    #[default]
+    Ignore,
+    /// Log the error and stop rendering
+    Disable,
+    /// Return the error from the call that caused it
+    Propagate,
+}
@@ -0,0 +1,1068 @@
+use crate::{
+    common_values::{
+        opponent_goal_back, own_goal_back, own_goal_center, BACK_NET_Y, BACK_WALL_Y,
+        BALL_MAX_SPEED, BALL_RADIUS, CAR_MAX_SPEED,
+    },
+    game_modes::scoring_team,
+    obs::RunningStats,
+    AgentId, Reward, RewardValue, StepContext,
+};
+use glam::Vec3A;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use rocketsim_rs::{
+    glam_ext::{CarInfoA, GameStateA},
+    sim::{GameMode, Team},
+};
+
+/// Every car's reward, computed in parallel with the `rayon` feature
+#[cfg(not(feature = "rayon"))]
+fn per_car(state: &GameStateA, f: impl Fn(&CarInfoA) -> f32) -> Vec<f32> {
+    state.cars.iter().map(f).collect()
+}
+
+/// Every car's reward, computed in parallel with the `rayon` feature
+#[cfg(feature = "rayon")]
+fn per_car(state: &GameStateA, f: impl Fn(&CarInfoA) -> f32 + Send + Sync) -> Vec<f32> {
+    state.cars.par_iter().map(f).collect()
+}
+
+fn cosine_similarity(a: Vec3A, b: Vec3A) -> f32 {
+    a.normalize_or_zero().dot(b.normalize_or_zero())
+}
+
+/// How fast the car is moving toward the ball, from -1 to 1 at max car speed.
+///
+/// With `use_scalar_projection` the reward is the car's speed toward the ball in uu/s instead
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VelocityTowardBallReward {
+    pub use_scalar_projection: bool,
+}
+
+impl<SI> Reward<SI> for VelocityTowardBallReward {
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn get_rewards(&mut self, ctx: &StepContext, _shared_info: &mut SI) -> Vec<f32> {
+        let state = ctx.state;
+        per_car(state, |car| {
+            let to_ball = (state.ball.pos - car.state.pos).normalize_or_zero();
+            let speed_toward_ball = car.state.vel.dot(to_ball);
+
+            if self.use_scalar_projection {
+                speed_toward_ball
+            } else {
+                speed_toward_ball / CAR_MAX_SPEED
+            }
+        })
+    }
+}
+
+/// How fast the ball is moving toward the back of the opponent's net, from -1 to 1 at max ball speed.
+///
+/// With `own_goal` it's the speed toward the car's own net instead, usually with a negative weight
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VelocityBallToGoalReward {
+    pub own_goal: bool,
+}
+
+impl<SI> Reward<SI> for VelocityBallToGoalReward {
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn get_rewards(&mut self, ctx: &StepContext, _shared_info: &mut SI) -> Vec<f32> {
+        let state = ctx.state;
+        per_car(state, |car| {
+            let goal = if self.own_goal {
+                own_goal_back(car.team)
+            } else {
+                opponent_goal_back(car.team)
+            };
+
+            state
+                .ball
+                .vel
+                .dot((goal - state.ball.pos).normalize_or_zero())
+                / BALL_MAX_SPEED
+        })
+    }
+}
+
+/// How directly the car's nose points at the ball, from -1 to 1
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FaceBallReward;
+
+impl<SI> Reward<SI> for FaceBallReward {
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn get_rewards(&mut self, ctx: &StepContext, _shared_info: &mut SI) -> Vec<f32> {
+        let state = ctx.state;
+        per_car(state, |car| {
+            cosine_similarity(car.state.rot_mat.x_axis, state.ball.pos - car.state.pos)
+        })
+    }
+}
+
+/// Rewards being between the ball and the back of the car's own net (`defense`),
+/// and having the ball between the car and the back of the opponent's net (`offense`)
+#[derive(Clone, Copy, Debug)]
+pub struct AlignBallGoalReward {
+    pub defense: f32,
+    pub offense: f32,
+}
+
+impl Default for AlignBallGoalReward {
+    fn default() -> Self {
+        Self {
+            defense: 1.,
+            offense: 1.,
+        }
+    }
+}
+
+impl<SI> Reward<SI> for AlignBallGoalReward {
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn get_rewards(&mut self, ctx: &StepContext, _shared_info: &mut SI) -> Vec<f32> {
+        let state = ctx.state;
+        per_car(state, |car| {
+            let pos = car.state.pos;
+            let to_ball = state.ball.pos - pos;
+
+            let defense = cosine_similarity(to_ball, pos - own_goal_back(car.team));
+            let offense = cosine_similarity(to_ball, opponent_goal_back(car.team) - pos);
+
+            self.defense * defense + self.offense * offense
+        })
+    }
+}
+
+/// Exponentially decaying reward for being close to the ball, 1 when touching it
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LiuDistanceReward;
+
+impl<SI> Reward<SI> for LiuDistanceReward {
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn get_rewards(&mut self, ctx: &StepContext, _shared_info: &mut SI) -> Vec<f32> {
+        let state = ctx.state;
+        per_car(state, |car| {
+            let distance = car.state.pos.distance(state.ball.pos) - BALL_RADIUS;
+            (-0.5 * distance.max(0.) / CAR_MAX_SPEED).exp()
+        })
+    }
+}
+
+/// Exponentially decaying reward for the ball being close to the opponent's goal, 1 when it's in the net
+///
+/// With `own_goal` it's the distance to the car's own goal instead, usually with a negative weight
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LiuDistanceBallToGoalReward {
+    pub own_goal: bool,
+}
+
+impl<SI> Reward<SI> for LiuDistanceBallToGoalReward {
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+    fn get_rewards(&mut self, ctx: &StepContext, _shared_info: &mut SI) -> Vec<f32> {
+        let state = ctx.state;
+        per_car(state, |car| {
+            let goal = if self.own_goal {
+                own_goal_back(car.team)
+            } else {
+                opponent_goal_back(car.team)
+            };
+            let distance = goal.distance(state.ball.pos) - (BACK_NET_Y - BACK_WALL_Y + BALL_RADIUS);
+
+            (-0.5 * distance.max(0.) / BALL_MAX_SPEED).exp()
+        })
+    }
+}
+
+/// The ball is this close to a goal when a touch counts as a save
+const SAVE_DISTANCE: f32 = 2000.;
+
+/// A weighted sum of things that happened since the last step, every weight defaults to 0.
+///
+/// Goals are detected in soccar unless another game mode is set with `with_game_mode`
+#[derive(Clone)]
+pub struct EventReward {
+    /// The car's team scored
+    pub goal: f32,
+    /// The other team scored
+    pub concede: f32,
+    /// The car touched the ball
+    pub touch: f32,
+    /// The car touched the ball while it was heading into its own goal from close by
+    pub save: f32,
+    /// The car was demolished
+    pub demoed: f32,
+    /// The car's boost went up, e.g. from a pad
+    pub boost_pickup: f32,
+    game_mode: GameMode,
+    last_tick_count: u64,
+    last_scoring_team: Option<Team>,
+    last_ball_vel: Vec3A,
+    last_ball_pos: Vec3A,
+    last_cars: Vec<(AgentId, bool, f32)>,
+}
+
+impl Default for EventReward {
+    fn default() -> Self {
+        Self {
+            goal: 0.,
+            concede: 0.,
+            touch: 0.,
+            save: 0.,
+            demoed: 0.,
+            boost_pickup: 0.,
+            game_mode: GameMode::SOCCAR,
+            last_tick_count: 0,
+            last_scoring_team: None,
+            last_ball_vel: Vec3A::ZERO,
+            last_ball_pos: Vec3A::ZERO,
+            last_cars: Vec::new(),
+        }
+    }
+}
+
+impl EventReward {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_game_mode(mut self, game_mode: GameMode) -> Self {
+        self.game_mode = game_mode;
+        self
+    }
+
+    pub fn with_goal(mut self, goal: f32) -> Self {
+        self.goal = goal;
+        self
+    }
+
+    pub fn with_concede(mut self, concede: f32) -> Self {
+        self.concede = concede;
+        self
+    }
+
+    pub fn with_touch(mut self, touch: f32) -> Self {
+        self.touch = touch;
+        self
+    }
+
+    pub fn with_save(mut self, save: f32) -> Self {
+        self.save = save;
+        self
+    }
+
+    pub fn with_demoed(mut self, demoed: f32) -> Self {
+        self.demoed = demoed;
+        self
+    }
+
+    pub fn with_boost_pickup(mut self, boost_pickup: f32) -> Self {
+        self.boost_pickup = boost_pickup;
+        self
+    }
+
+    fn remember(&mut self, state: &GameStateA) {
+        self.last_tick_count = state.tick_count;
+        self.last_scoring_team = scoring_team(self.game_mode, state);
+        self.last_ball_vel = state.ball.vel;
+        self.last_ball_pos = state.ball.pos;
+        self.last_cars.clear();
+        self.last_cars.extend(
+            state
+                .cars
+                .iter()
+                .map(|car| (AgentId::of(car), car.state.is_demoed, car.state.boost)),
+        );
+    }
+
+    /// If the ball was heading into `team`'s goal from close by before this step
+    fn was_saveable(&self, team: Team) -> bool {
+        let goal = own_goal_center(team);
+        let toward_goal = goal - self.last_ball_pos;
+
+        toward_goal.length() < SAVE_DISTANCE && self.last_ball_vel.dot(toward_goal) > 0.
+    }
+}
+
+impl<SI> Reward<SI> for EventReward {
+    fn reset(&mut self, initial_state: &GameStateA, _shared_info: &mut SI) {
+        self.remember(initial_state);
+    }
+
+    fn get_rewards(&mut self, ctx: &StepContext, _shared_info: &mut SI) -> Vec<f32> {
+        let state = ctx.state;
+        let scored =
+            scoring_team(self.game_mode, state).filter(|_| self.last_scoring_team.is_none());
+
+        let rewards = per_car(state, |car| {
+            let (was_demoed, last_boost) = self
+                .last_cars
+                .iter()
+                .find(|(id, _, _)| *id == AgentId::of(car))
+                .map_or((false, car.state.boost), |(_, demoed, boost)| {
+                    (*demoed, *boost)
+                });
+            let hit = &car.state.ball_hit_info;
+            let touched = hit.is_valid && hit.tick_count_when_hit > self.last_tick_count;
+
+            let mut reward = 0.;
+            match scored {
+                Some(team) if team == car.team => reward += self.goal,
+                Some(_) => reward += self.concede,
+                None => {}
+            }
+            if touched {
+                reward += self.touch;
+                if self.was_saveable(car.team) {
+                    reward += self.save;
+                }
+            }
+            if car.state.is_demoed && !was_demoed {
+                reward += self.demoed;
+            }
+            if car.state.boost > last_boost {
+                reward += self.boost_pickup;
+            }
+
+            reward
+        });
+
+        self.remember(state);
+        rewards
+    }
+}
+
+/// The sum of every reward times its weight, keeping how much each of them contributed
+/// so they can be logged, see `StepResult::reward_breakdown`
+pub struct CombinedReward<SI, R = f32> {
+    components: Vec<(String, f32, Box<dyn Reward<SI, R>>)>,
+    breakdown: Vec<Vec<R>>,
+}
+
+impl<SI, R> Default for CombinedReward<SI, R> {
+    fn default() -> Self {
+        Self {
+            components: Vec::new(),
+            breakdown: Vec::new(),
+        }
+    }
+}
+
+impl<SI, R> CombinedReward<SI, R> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(
+        mut self,
+        name: impl Into<String>,
+        weight: f32,
+        reward: impl Reward<SI, R> + 'static,
+    ) -> Self {
+        self.push(name, weight, Box::new(reward));
+        self
+    }
+
+    /// Combine `(weight, reward)` pairs, each component is named after its index
+    pub fn from_weighted(rewards: impl IntoIterator<Item = (f32, Box<dyn Reward<SI, R>>)>) -> Self {
+        let mut combined = Self::new();
+        for (index, (weight, reward)) in rewards.into_iter().enumerate() {
+            combined.push(index.to_string(), weight, reward);
+        }
+        combined
+    }
+
+    pub fn push(&mut self, name: impl Into<String>, weight: f32, reward: Box<dyn Reward<SI, R>>) {
+        self.components.push((name.into(), weight, reward));
+    }
+
+    /// Change the weight of the component called `name`, returns `false` if there's none
+    pub fn set_weight(&mut self, name: &str, weight: f32) -> bool {
+        match self
+            .components
+            .iter_mut()
+            .find(|(component, _, _)| component.as_str() == name)
+        {
+            Some((_, old, _)) => {
+                *old = weight;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The name of every component, in the order of each car's breakdown
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.components.iter().map(|(name, _, _)| name.as_str())
+    }
+}
+
+impl<SI, R: RewardValue> Reward<SI, R> for CombinedReward<SI, R> {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        for (_, _, reward) in &mut self.components {
+            reward.reset(initial_state, shared_info);
+        }
+    }
+
+    fn get_rewards(&mut self, ctx: &StepContext, shared_info: &mut SI) -> Vec<R> {
+        self.breakdown.resize_with(ctx.state.cars.len(), Vec::new);
+        for contributions in &mut self.breakdown {
+            contributions.clear();
+        }
+
+        for (_, weight, reward) in &mut self.components {
+            for (contributions, value) in self
+                .breakdown
+                .iter_mut()
+                .zip(reward.get_rewards(ctx, shared_info))
+            {
+                let mut contribution = R::zero();
+                contribution.add_weighted(value, *weight);
+                contributions.push(contribution);
+            }
+        }
+
+        self.breakdown
+            .iter()
+            .map(|contributions| {
+                contributions
+                    .iter()
+                    .fold(R::zero(), |mut total, &contribution| {
+                        total.add_weighted(contribution, 1.);
+                        total
+                    })
+            })
+            .collect()
+    }
+
+    fn breakdown(&self) -> Option<&[Vec<R>]> {
+        Some(&self.breakdown)
+    }
+}
+
+/// How a weight changes over the steps or episodes it was used for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WeightSchedule {
+    Constant(f32),
+    /// From `start` to `end` over `duration` steps or episodes, then stays at `end`
+    Linear {
+        start: f32,
+        end: f32,
+        duration: u64,
+    },
+    /// From `start` toward `end`, halving the distance between them every `half_life` steps or episodes
+    Exponential {
+        start: f32,
+        end: f32,
+        half_life: f32,
+    },
+}
+
+impl WeightSchedule {
+    /// The weight after `progress` steps or episodes
+    pub fn weight(&self, progress: u64) -> f32 {
+        match *self {
+            Self::Constant(weight) => weight,
+            Self::Linear {
+                start,
+                end,
+                duration,
+            } => {
+                let t = if duration == 0 {
+                    1.
+                } else {
+                    (progress as f32 / duration as f32).min(1.)
+                };
+                start + (end - start) * t
+            }
+            Self::Exponential {
+                start,
+                end,
+                half_life,
+            } => end + (start - end) * 0.5f32.powf(progress as f32 / half_life.max(f32::EPSILON)),
+        }
+    }
+}
+
+/// What a `ScheduledReward`'s schedule counts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScheduleUnit {
+    #[default]
+    Steps,
+    Episodes,
+}
+
+/// Scales `reward` by a weight that follows a schedule, e.g. to anneal dense shaping rewards away.
+///
+/// The schedule counts the steps or episodes this reward was used for, so it starts over with a new env
+pub struct ScheduledReward<T> {
+    pub reward: T,
+    pub schedule: WeightSchedule,
+    pub unit: ScheduleUnit,
+    steps: u64,
+    resets: u64,
+}
+
+impl<T> ScheduledReward<T> {
+    pub fn new(reward: T, schedule: WeightSchedule) -> Self {
+        Self {
+            reward,
+            schedule,
+            unit: ScheduleUnit::Steps,
+            steps: 0,
+            resets: 0,
+        }
+    }
+
+    pub fn with_unit(mut self, unit: ScheduleUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Start the schedule at `progress` steps or episodes, e.g. when resuming a run
+    pub fn with_progress(mut self, progress: u64) -> Self {
+        match self.unit {
+            ScheduleUnit::Steps => self.steps = progress,
+            // the next reset starts the episode after the ones already done
+            ScheduleUnit::Episodes => self.resets = progress,
+        }
+        self
+    }
+
+    /// Steps or episodes the schedule is at
+    pub fn progress(&self) -> u64 {
+        match self.unit {
+            ScheduleUnit::Steps => self.steps,
+            // the first reset starts the first episode
+            ScheduleUnit::Episodes => self.resets.saturating_sub(1),
+        }
+    }
+
+    /// The weight the next rewards are scaled by
+    pub fn weight(&self) -> f32 {
+        self.schedule.weight(self.progress())
+    }
+}
+
+impl<SI, R: RewardValue, T: Reward<SI, R>> Reward<SI, R> for ScheduledReward<T> {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        self.resets += 1;
+        self.reward.reset(initial_state, shared_info);
+    }
+
+    fn get_rewards(&mut self, ctx: &StepContext, shared_info: &mut SI) -> Vec<R> {
+        let weight = self.weight();
+        self.steps += 1;
+
+        self.reward
+            .get_rewards(ctx, shared_info)
+            .into_iter()
+            .map(|value| {
+                let mut scaled = R::zero();
+                scaled.add_weighted(value, weight);
+                scaled
+            })
+            .collect()
+    }
+}
+
+/// Scales `reward` by the running standard deviation of each agent's discounted return,
+/// like stable-baselines' `VecNormalize`, then clips it to `clip` around zero.
+///
+/// Save the statistics with `stats` and pass them to `with_stats` to pick up where a run left off
+pub struct NormalizedReward<T> {
+    pub reward: T,
+    pub gamma: f32,
+    pub clip: f32,
+    stats: RunningStats,
+    returns: Vec<f32>,
+    frozen: bool,
+}
+
+impl<T> NormalizedReward<T> {
+    pub fn new(reward: T, gamma: f32) -> Self {
+        Self {
+            reward,
+            gamma,
+            clip: 10.,
+            stats: RunningStats::default(),
+            returns: Vec::new(),
+            frozen: false,
+        }
+    }
+
+    pub fn with_clip(mut self, clip: f32) -> Self {
+        self.clip = clip;
+        self
+    }
+
+    pub fn with_stats(mut self, stats: RunningStats) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    pub fn stats(&self) -> &RunningStats {
+        &self.stats
+    }
+
+    /// Stop or resume updating the statistics, the rewards are still scaled
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+}
+
+impl<SI, T: Reward<SI>> Reward<SI> for NormalizedReward<T> {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        self.returns.clear();
+        self.reward.reset(initial_state, shared_info);
+    }
+
+    fn get_rewards(&mut self, ctx: &StepContext, shared_info: &mut SI) -> Vec<f32> {
+        let mut rewards = self.reward.get_rewards(ctx, shared_info);
+        self.returns.resize(rewards.len(), 0.);
+
+        for (discounted, reward) in self.returns.iter_mut().zip(&rewards) {
+            *discounted = *discounted * self.gamma + reward;
+            if !self.frozen {
+                self.stats.update(&[*discounted]);
+            }
+        }
+
+        let std = (self.stats.variance(0) + 1e-8).sqrt() as f32;
+        for reward in &mut rewards {
+            *reward = (*reward / std).clamp(-self.clip, self.clip);
+        }
+
+        rewards
+    }
+}
+
+/// Clamps every reward of `reward` to `min..=max`
+#[derive(Clone, Copy, Debug)]
+pub struct ClippedReward<T> {
+    pub reward: T,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl<T> ClippedReward<T> {
+    pub fn new(reward: T, min: f32, max: f32) -> Self {
+        Self { reward, min, max }
+    }
+}
+
+impl<SI, T: Reward<SI>> Reward<SI> for ClippedReward<T> {
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        self.reward.reset(initial_state, shared_info);
+    }
+
+    fn get_rewards(&mut self, ctx: &StepContext, shared_info: &mut SI) -> Vec<f32> {
+        let mut rewards = self.reward.get_rewards(ctx, shared_info);
+        for reward in &mut rewards {
+            *reward = reward.clamp(self.min, self.max);
+        }
+        rewards
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common_values::GOAL_HEIGHT,
+        test_utils::{NoSharedInfo, ScriptedEpisode, StateBuilder},
+    };
+    use glam::Mat3A;
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 1e-4,
+                "expected {expected:?}, got {actual:?}"
+            );
+        }
+    }
+
+    fn rewards(reward: &mut impl Reward<()>, state: &GameStateA) -> Vec<f32> {
+        reward.reset(state, &mut ());
+        reward.get_rewards(&StepContext::new(state), &mut ())
+    }
+
+    fn start_episode(reward: &mut impl Reward<()>, state: &GameStateA) {
+        reward.reset(state, &mut ());
+    }
+
+    /// The tick count for every car
+    struct TickReward;
+
+    impl<SI> Reward<SI> for TickReward {
+        fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+        fn get_rewards(&mut self, ctx: &StepContext, _shared_info: &mut SI) -> Vec<f32> {
+            vec![ctx.state.tick_count as f32; ctx.state.cars.len()]
+        }
+    }
+
+    fn at_tick(tick_count: u64) -> GameStateA {
+        StateBuilder::new()
+            .with_tick_count(tick_count)
+            .with_car(Team::Blue, Vec3A::ZERO)
+            .build()
+    }
+
+    fn next_rewards(reward: &mut impl Reward<()>, ctx: &StepContext) -> Vec<f32> {
+        reward.get_rewards(ctx, &mut ())
+    }
+
+    /// The same reward for every car
+    struct ConstantReward(f32);
+
+    impl<SI> Reward<SI> for ConstantReward {
+        fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+
+        fn get_rewards(&mut self, ctx: &StepContext, _shared_info: &mut SI) -> Vec<f32> {
+            vec![self.0; ctx.state.cars.len()]
+        }
+    }
+
+    #[test]
+    fn velocity_toward_ball() {
+        let state = StateBuilder::new()
+            .with_ball(Vec3A::new(0., 1000., 17.), Vec3A::ZERO)
+            .with_car_info(Team::Blue, |car| {
+                car.state.pos = Vec3A::new(0., 0., 17.);
+                car.state.vel = Vec3A::new(0., 1150., 0.);
+            })
+            .build();
+
+        assert_close(
+            &rewards(&mut VelocityTowardBallReward::default(), &state),
+            &[0.5],
+        );
+        let mut scalar = VelocityTowardBallReward {
+            use_scalar_projection: true,
+        };
+        assert_close(&rewards(&mut scalar, &state), &[1150.]);
+    }
+
+    #[test]
+    fn velocity_ball_to_goal() {
+        let state = StateBuilder::new()
+            .with_ball(
+                Vec3A::new(0., 0., GOAL_HEIGHT / 2.),
+                Vec3A::new(0., BALL_MAX_SPEED, 0.),
+            )
+            .with_car(Team::Blue, Vec3A::ZERO)
+            .with_car(Team::Orange, Vec3A::ZERO)
+            .build();
+        assert_close(
+            &rewards(&mut VelocityBallToGoalReward::default(), &state),
+            &[1., -1.],
+        );
+        let mut own_goal = VelocityBallToGoalReward { own_goal: true };
+        assert_close(&rewards(&mut own_goal, &state), &[-1., 1.]);
+
+        // aimed at the back of the net, not the goal line
+        let state = StateBuilder::new()
+            .with_ball(
+                Vec3A::new(1000., 0., BALL_RADIUS),
+                Vec3A::new(0., 3000., 0.),
+            )
+            .with_car(Team::Blue, Vec3A::ZERO)
+            .build();
+        assert_close(
+            &rewards(&mut VelocityBallToGoalReward::default(), &state),
+            &[0.492_849],
+        );
+    }
+
+    #[test]
+    fn face_ball() {
+        let state = StateBuilder::new()
+            .with_ball(Vec3A::new(1000., 1000., 17.), Vec3A::ZERO)
+            .with_car_info(Team::Blue, |car| {
+                car.state.pos = Vec3A::new(0., 0., 17.);
+                car.state.rot_mat = Mat3A::IDENTITY;
+            })
+            .build();
+
+        assert_close(
+            &rewards(&mut FaceBallReward, &state),
+            &[std::f32::consts::FRAC_1_SQRT_2],
+        );
+    }
+
+    #[test]
+    fn align_ball_goal() {
+        let state = StateBuilder::new()
+            .with_ball(Vec3A::new(0., 0., 17.), Vec3A::ZERO)
+            .with_car(Team::Blue, Vec3A::new(0., -1000., 17.))
+            .build();
+
+        assert_close(
+            &rewards(&mut AlignBallGoalReward::default(), &state),
+            &[0.998_152 + 0.999_056],
+        );
+        let mut offense = AlignBallGoalReward {
+            defense: 0.,
+            offense: 2.,
+        };
+        assert_close(&rewards(&mut offense, &state), &[2. * 0.999_056]);
+    }
+
+    #[test]
+    fn liu_distance() {
+        let state = StateBuilder::new()
+            .with_ball(Vec3A::new(0., 1000., 17.), Vec3A::ZERO)
+            .with_car(Team::Blue, Vec3A::new(0., 0., 17.))
+            .with_car(Team::Orange, Vec3A::new(0., 1000. - BALL_RADIUS, 17.))
+            .build();
+
+        assert_close(&rewards(&mut LiuDistanceReward, &state), &[0.821_003, 1.]);
+    }
+
+    #[test]
+    fn liu_distance_ball_to_goal() {
+        let state = StateBuilder::new()
+            .with_ball(Vec3A::new(0., 0., BALL_RADIUS), Vec3A::ZERO)
+            .with_car(Team::Blue, Vec3A::ZERO)
+            .with_car(Team::Orange, Vec3A::ZERO)
+            .build();
+        assert_close(
+            &rewards(&mut LiuDistanceBallToGoalReward::default(), &state),
+            &[0.657_507, 0.657_507],
+        );
+
+        let state = StateBuilder::new()
+            .with_ball(
+                Vec3A::new(0., BACK_NET_Y - BALL_RADIUS, BALL_RADIUS),
+                Vec3A::ZERO,
+            )
+            .with_car(Team::Blue, Vec3A::ZERO)
+            .build();
+        assert_close(
+            &rewards(&mut LiuDistanceBallToGoalReward::default(), &state),
+            &[1.],
+        );
+        let mut own_goal = LiuDistanceBallToGoalReward { own_goal: true };
+        assert_close(&rewards(&mut own_goal, &state), &[0.401_965]);
+    }
+
+    #[test]
+    fn event_reward() {
+        let kickoff = StateBuilder::new()
+            .with_ball(Vec3A::new(0., 0., BALL_RADIUS), Vec3A::ZERO)
+            .with_car_info(Team::Blue, |car| car.state.boost = 33.)
+            .with_car_info(Team::Orange, |car| car.state.boost = 33.)
+            .build();
+        let scored = StateBuilder::new()
+            .with_tick_count(8)
+            .with_ball(Vec3A::new(0., 5300., BALL_RADIUS), Vec3A::ZERO)
+            .with_car_info(Team::Blue, |car| {
+                car.state.boost = 33.;
+                car.state.ball_hit_info.is_valid = true;
+                car.state.ball_hit_info.tick_count_when_hit = 4;
+            })
+            .with_car_info(Team::Orange, |car| car.state.boost = 100.)
+            .build();
+        let mut still_in_goal = scored.clone();
+        still_in_goal.tick_count = 16;
+
+        let mut reward = EventReward::new()
+            .with_goal(10.)
+            .with_concede(-10.)
+            .with_touch(1.)
+            .with_boost_pickup(0.5);
+        let rewards = ScriptedEpisode::new(vec![kickoff, scored, still_in_goal], NoSharedInfo, ())
+            .rewards(&mut reward);
+
+        assert_eq!(rewards, [vec![11., -9.5], vec![0., 0.]]);
+    }
+
+    #[test]
+    fn event_reward_game_mode() {
+        let kickoff = StateBuilder::new()
+            .with_ball(Vec3A::new(0., 0., 500.), Vec3A::ZERO)
+            .with_car(Team::Blue, Vec3A::ZERO)
+            .build();
+        // fell through an orange dropshot tile, nowhere near a soccar goal
+        let scored = StateBuilder::new()
+            .with_tick_count(8)
+            .with_ball(Vec3A::new(0., 2000., -200.), Vec3A::ZERO)
+            .with_car(Team::Blue, Vec3A::ZERO)
+            .build();
+
+        let mut soccar = EventReward::new().with_goal(1.);
+        let mut dropshot = soccar.clone().with_game_mode(GameMode::DROPSHOT);
+        let states = vec![kickoff, scored];
+
+        assert_eq!(
+            ScriptedEpisode::new(states.clone(), NoSharedInfo, ()).rewards(&mut soccar),
+            [vec![0.]]
+        );
+        assert_eq!(
+            ScriptedEpisode::new(states, NoSharedInfo, ()).rewards(&mut dropshot),
+            [vec![1.]]
+        );
+    }
+
+    #[test]
+    fn weight_schedules() {
+        assert_eq!(WeightSchedule::Constant(2.).weight(1000), 2.);
+
+        let linear = WeightSchedule::Linear {
+            start: 1.,
+            end: 0.,
+            duration: 10,
+        };
+        assert_close(
+            &[0, 5, 10, 20].map(|progress| linear.weight(progress)),
+            &[1., 0.5, 0., 0.],
+        );
+        let instant = WeightSchedule::Linear {
+            start: 1.,
+            end: 0.,
+            duration: 0,
+        };
+        assert_eq!(instant.weight(0), 0.);
+
+        let exponential = WeightSchedule::Exponential {
+            start: 1.,
+            end: 0.5,
+            half_life: 10.,
+        };
+        assert_close(
+            &[0, 10, 20].map(|progress| exponential.weight(progress)),
+            &[1., 0.75, 0.625],
+        );
+    }
+
+    #[test]
+    fn scheduled_reward_resumes() {
+        let state = StateBuilder::new()
+            .with_car(Team::Blue, Vec3A::ZERO)
+            .build();
+        let ctx = StepContext::new(&state);
+        let linear = WeightSchedule::Linear {
+            start: 1.,
+            end: 0.,
+            duration: 10,
+        };
+
+        let mut steps = ScheduledReward::new(ConstantReward(1.), linear).with_progress(5);
+        start_episode(&mut steps, &state);
+        assert_close(&next_rewards(&mut steps, &ctx), &[0.5]);
+        assert_close(&next_rewards(&mut steps, &ctx), &[0.4]);
+        assert_eq!(steps.progress(), 7);
+
+        // a run that went through 5 episodes, and the same run resumed from them
+        let mut unbroken =
+            ScheduledReward::new(ConstantReward(1.), linear).with_unit(ScheduleUnit::Episodes);
+        for _ in 0..6 {
+            start_episode(&mut unbroken, &state);
+        }
+        let mut resumed = ScheduledReward::new(ConstantReward(1.), linear)
+            .with_unit(ScheduleUnit::Episodes)
+            .with_progress(5);
+        start_episode(&mut resumed, &state);
+
+        assert_eq!(resumed.progress(), 5);
+        assert_eq!(unbroken.progress(), resumed.progress());
+        assert_close(&next_rewards(&mut resumed, &ctx), &[0.5]);
+        // steps don't move an episode schedule
+        assert_close(&next_rewards(&mut resumed, &ctx), &[0.5]);
+        start_episode(&mut resumed, &state);
+        assert_close(&next_rewards(&mut resumed, &ctx), &[0.4]);
+    }
+
+    #[test]
+    fn combined_reward_breakdown() {
+        let state = StateBuilder::new()
+            .with_car(Team::Blue, Vec3A::ZERO)
+            .with_car(Team::Orange, Vec3A::ZERO)
+            .build();
+        let ctx = StepContext::new(&state);
+        let mut combined = CombinedReward::<()>::new()
+            .with("goal", 10., ConstantReward(1.))
+            .with("touch", 0.5, ConstantReward(-2.));
+        assert!(combined.names().eq(["goal", "touch"]));
+
+        start_episode(&mut combined, &state);
+        assert_eq!(next_rewards(&mut combined, &ctx), [9., 9.]);
+        let breakdown = Reward::<()>::breakdown(&combined).unwrap();
+        assert_eq!(breakdown, [vec![10., -1.], vec![10., -1.]]);
+
+        assert!(combined.set_weight("touch", 2.));
+        assert!(!combined.set_weight("save", 1.));
+        assert_eq!(next_rewards(&mut combined, &ctx), [6., 6.]);
+        let breakdown = Reward::<()>::breakdown(&combined).unwrap();
+        assert_eq!(breakdown[0], [10., -4.]);
+
+        let weighted = CombinedReward::<()>::from_weighted([
+            (1., Box::new(ConstantReward(1.)) as Box<dyn Reward<()>>),
+            (2., Box::new(ConstantReward(1.))),
+        ]);
+        assert!(weighted.names().eq(["0", "1"]));
+    }
+
+    #[test]
+    fn normalized_reward() {
+        let mut reward = NormalizedReward::new(TickReward, 0.);
+        start_episode(&mut reward, &at_tick(0));
+        let mut step = |tick| next_rewards(&mut reward, &StepContext::new(&at_tick(tick)));
+
+        // nothing to scale by yet, so it's clipped
+        assert_close(&step(2), &[10.]);
+        // the returns so far have a standard deviation of 1
+        assert_close(&step(4), &[4.]);
+        assert_close(&step(6), &[6. / (8f32 / 3.).sqrt()]);
+        assert_eq!(reward.stats().count, [3]);
+
+        reward.set_frozen(true);
+        next_rewards(&mut reward, &StepContext::new(&at_tick(100)));
+        assert_eq!(reward.stats().count, [3]);
+    }
+
+    #[test]
+    fn normalized_reward_discounts_within_an_episode() {
+        let mut reward = NormalizedReward::new(TickReward, 0.5).with_clip(100.);
+        start_episode(&mut reward, &at_tick(0));
+        next_rewards(&mut reward, &StepContext::new(&at_tick(2)));
+        // the returns are 2 and 2 * 0.5 + 4 = 5
+        assert_close(
+            &next_rewards(&mut reward, &StepContext::new(&at_tick(4))),
+            &[4. / 1.5],
+        );
+
+        // a new episode starts its return over, but keeps the statistics
+        start_episode(&mut reward, &at_tick(0));
+        assert_close(
+            &next_rewards(&mut reward, &StepContext::new(&at_tick(2))),
+            &[2. / 2f32.sqrt()],
+        );
+        assert_eq!(reward.stats().count, [3]);
+        assert!((reward.stats().mean[0] - 3.).abs() < 1e-9);
+
+        // picking up saved statistics, the returns are now 2, 5, 2 and 3
+        let stats = reward.stats().clone();
+        let mut resumed = NormalizedReward::new(TickReward, 0.5).with_stats(stats);
+        start_episode(&mut resumed, &at_tick(0));
+        assert_close(
+            &next_rewards(&mut resumed, &StepContext::new(&at_tick(3))),
+            &[3. / 1.5f32.sqrt()],
+        );
+    }
+
+    #[test]
+    fn clipped_reward() {
+        let state = at_tick(0);
+        for (value, clipped) in [(5., 1.), (-5., -1.), (0.5, 0.5)] {
+            let mut reward = ClippedReward::new(ConstantReward(value), -1., 1.);
+            assert_eq!(rewards(&mut reward, &state), [clipped]);
+        }
+    }
+}
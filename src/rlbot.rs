@@ -0,0 +1,309 @@
+use crate::{common_values::SUPERSONIC_THRESHOLD, AgentId, MaybeSend, Policy};
+use glam::Vec3A;
+use rocketsim_rs::{
+    glam_ext::{CarInfoA, GameStateA},
+    sim::{CarControls, Team},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vector3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl From<Vec3A> for Vector3 {
+    fn from(vec: Vec3A) -> Self {
+        Self {
+            x: vec.x,
+            y: vec.y,
+            z: vec.z,
+        }
+    }
+}
+
+/// Euler angles in radians, using the same conventions as RLBot
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rotator {
+    pub pitch: f32,
+    pub yaw: f32,
+    pub roll: f32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Physics {
+    pub location: Vector3,
+    pub rotation: Rotator,
+    pub velocity: Vector3,
+    pub angular_velocity: Vector3,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlayerInfo {
+    pub physics: Physics,
+    pub is_demolished: bool,
+    pub has_wheel_contact: bool,
+    pub is_super_sonic: bool,
+    pub jumped: bool,
+    pub double_jumped: bool,
+    /// 0 for blue, 1 for orange
+    pub team: u8,
+    pub boost: f32,
+    /// The RocketSim car id
+    pub spawn_id: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BallInfo {
+    pub physics: Physics,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BoostPadState {
+    pub is_active: bool,
+    pub timer: f32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GameInfo {
+    pub seconds_elapsed: f32,
+    pub is_round_active: bool,
+    pub game_speed: f32,
+}
+
+/// The game state in the layout RLBot bots expect, with the players in the order of `GameStateA::cars`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GameTickPacket {
+    pub players: Vec<PlayerInfo>,
+    pub ball: BallInfo,
+    pub boost_pads: Vec<BoostPadState>,
+    pub game_info: GameInfo,
+}
+
+impl GameTickPacket {
+    pub fn from_state(state: &GameStateA) -> Self {
+        Self {
+            players: state.cars.iter().map(player_info).collect(),
+            ball: BallInfo {
+                physics: Physics {
+                    location: state.ball.pos.into(),
+                    rotation: Rotator::default(),
+                    velocity: state.ball.vel.into(),
+                    angular_velocity: state.ball.ang_vel.into(),
+                },
+            },
+            boost_pads: state
+                .pads
+                .iter()
+                .map(|pad| BoostPadState {
+                    is_active: pad.state.is_active,
+                    timer: pad.state.cooldown,
+                })
+                .collect(),
+            game_info: GameInfo {
+                seconds_elapsed: state.tick_count as f32 / state.tick_rate,
+                is_round_active: true,
+                game_speed: 1.,
+            },
+        }
+    }
+}
+
+fn player_info(car: &CarInfoA) -> PlayerInfo {
+    let forward = car.state.rot_mat.x_axis;
+    let right = car.state.rot_mat.y_axis;
+    let up = car.state.rot_mat.z_axis;
+
+    PlayerInfo {
+        physics: Physics {
+            location: car.state.pos.into(),
+            rotation: Rotator {
+                pitch: forward.z.clamp(-1., 1.).asin(),
+                yaw: forward.y.atan2(forward.x),
+                roll: (-right.z).atan2(up.z),
+            },
+            velocity: car.state.vel.into(),
+            angular_velocity: car.state.ang_vel.into(),
+        },
+        is_demolished: car.state.is_demoed,
+        has_wheel_contact: car.state.is_on_ground,
+        is_super_sonic: car.state.vel.length() >= SUPERSONIC_THRESHOLD,
+        jumped: car.state.has_jumped,
+        double_jumped: car.state.has_double_jumped,
+        team: u8::from(car.team == Team::Orange),
+        boost: car.state.boost,
+        spawn_id: car.id,
+    }
+}
+
+/// RLBot's controller output
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ControllerState {
+    pub throttle: f32,
+    pub steer: f32,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub roll: f32,
+    pub jump: bool,
+    pub boost: bool,
+    pub handbrake: bool,
+}
+
+impl From<ControllerState> for CarControls {
+    fn from(controller: ControllerState) -> Self {
+        Self {
+            throttle: controller.throttle.clamp(-1., 1.),
+            steer: controller.steer.clamp(-1., 1.),
+            pitch: controller.pitch.clamp(-1., 1.),
+            yaw: controller.yaw.clamp(-1., 1.),
+            roll: controller.roll.clamp(-1., 1.),
+            jump: controller.jump,
+            boost: controller.boost,
+            handbrake: controller.handbrake,
+        }
+    }
+}
+
+/// The RLBot agent interface.
+///
+/// Bots that run in another process (e.g. Python bots like Necto or Nexto)
+/// can be used by implementing this over whatever IPC they're hosted with
+pub trait RLBotAgent: MaybeSend {
+    /// Called for every car at the start of every episode, `index` is the car's index in `GameTickPacket::players`
+    fn initialize_agent(&mut self, _index: usize, _team: u8) {}
+    fn get_output(&mut self, index: usize, packet: &GameTickPacket) -> ControllerState;
+}
+
+/// Drives cars in the env with an RLBot agent, e.g. as an opponent with `Env::set_policy`.
+///
+/// The agent gets a fresh `GameTickPacket` for every car it drives on every step
+pub struct RLBotPolicy<A: RLBotAgent> {
+    pub agent: A,
+}
+
+impl<A: RLBotAgent> RLBotPolicy<A> {
+    pub fn new(agent: A) -> Self {
+        Self { agent }
+    }
+}
+
+impl<SI, A: RLBotAgent> Policy<SI> for RLBotPolicy<A> {
+    fn reset(&mut self, initial_state: &GameStateA, _shared_info: &mut SI) {
+        for (index, car) in initial_state.cars.iter().enumerate() {
+            self.agent
+                .initialize_agent(index, u8::from(car.team == Team::Orange));
+        }
+    }
+
+    fn get_controls(
+        &mut self,
+        agent_id: AgentId,
+        _obs: &[f32],
+        state: &GameStateA,
+        _shared_info: &mut SI,
+    ) -> CarControls {
+        let Some(index) = state
+            .cars
+            .iter()
+            .position(|car| AgentId::of(car) == agent_id)
+        else {
+            log::warn!("RLBot agent was asked to drive car {agent_id}, which isn't in the state");
+            return CarControls::default();
+        };
+
+        let packet = GameTickPacket::from_state(state);
+        self.agent.get_output(index, &packet).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::StateBuilder;
+    use glam::Mat3A;
+
+    /// Drives at full throttle, steering toward the ball and boosting while it's far away
+    struct BallChaser {
+        initialized: Vec<(usize, u8)>,
+    }
+
+    impl RLBotAgent for BallChaser {
+        fn initialize_agent(&mut self, index: usize, team: u8) {
+            self.initialized.push((index, team));
+        }
+
+        fn get_output(&mut self, index: usize, packet: &GameTickPacket) -> ControllerState {
+            let car = &packet.players[index].physics;
+            let ball = packet.ball.physics.location;
+            let (dx, dy) = (ball.x - car.location.x, ball.y - car.location.y);
+            let angle = dy.atan2(dx) - car.rotation.yaw;
+
+            ControllerState {
+                throttle: 2.,
+                steer: angle.sin() * 10.,
+                boost: (dx * dx + dy * dy).sqrt() > 1000.,
+                ..Default::default()
+            }
+        }
+    }
+
+    #[test]
+    fn packet_from_state() {
+        let state = StateBuilder::new()
+            .with_ball(Vec3A::new(0., 0., 93.), Vec3A::new(0., 500., 0.))
+            .with_car(Team::Blue, Vec3A::new(0., -3000., 17.))
+            .with_car_info(Team::Orange, |car| {
+                car.state.pos = Vec3A::new(0., 3000., 17.);
+                car.state.rot_mat = Mat3A::from_cols(Vec3A::Y, -Vec3A::X, Vec3A::Z);
+                car.state.vel = Vec3A::new(0., 2300., 0.);
+                car.state.boost = 42.;
+            })
+            .build();
+        let packet = GameTickPacket::from_state(&state);
+
+        assert_eq!(packet.players.len(), 2);
+        assert_eq!(
+            packet.ball.physics.velocity,
+            Vector3 {
+                x: 0.,
+                y: 500.,
+                z: 0.
+            }
+        );
+
+        let orange = &packet.players[1];
+        assert_eq!((orange.team, orange.spawn_id, orange.boost), (1, 2, 42.));
+        assert!(orange.is_super_sonic);
+        assert!((orange.physics.rotation.yaw - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        assert_eq!(orange.physics.rotation.pitch, 0.);
+        assert_eq!(orange.physics.rotation.roll, 0.);
+    }
+
+    #[test]
+    fn policy_turns_packets_into_controls() {
+        // the blue car faces +x, with the ball to its left
+        let state = StateBuilder::new()
+            .with_ball(Vec3A::new(0., 0., 93.), Vec3A::ZERO)
+            .with_car_info(Team::Blue, |car| {
+                car.state.pos = Vec3A::new(0., -3000., 17.);
+                car.state.rot_mat = Mat3A::IDENTITY;
+            })
+            .with_car(Team::Orange, Vec3A::new(0., 3000., 17.))
+            .build();
+
+        let mut policy = RLBotPolicy::new(BallChaser {
+            initialized: Vec::new(),
+        });
+        Policy::<()>::reset(&mut policy, &state, &mut ());
+        assert_eq!(policy.agent.initialized, [(0, 0), (1, 1)]);
+
+        let controls = policy.get_controls(AgentId(1), &[], &state, &mut ());
+        // clamped to the range RocketSim takes
+        assert_eq!((controls.throttle, controls.steer), (1., 1.));
+        assert!(controls.boost);
+        assert!(!controls.jump);
+
+        let missing = policy.get_controls(AgentId(7), &[], &state, &mut ());
+        assert_eq!(missing.throttle, 0.);
+    }
+}
@@ -0,0 +1,285 @@
+use crate::{
+    handshake::{config_hash, ROCKETSIM_VERSION},
+    remote::RemoteEnv,
+    wire::MAX_FRAME_LEN,
+    FullObs,
+};
+use std::{collections::HashMap, net::SocketAddr, thread};
+use tokio::sync::{mpsc, oneshot};
+use tonic::{transport::Server, Request, Response, Status};
+
+pub mod proto {
+    tonic::include_proto!("rlgym.v1");
+}
+
+use proto::{
+    env_service_server::{EnvService, EnvServiceServer},
+    CloseEnvRequest, CloseEnvResponse, CreateEnvRequest, CreateEnvResponse, EnvStep, FloatList,
+    ResetRequest, ResetResponse, SpacesRequest, SpacesResponse, StepRequest, StepResponse,
+    VersionRequest, VersionResponse,
+};
+
+/// Version of `proto/rlgym.proto`, clients should refuse to talk to servers with a different version
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Creates an env from the config string a client sent with `CreateEnv`
+pub type EnvFactory = Box<dyn FnMut(&str) -> Result<Box<dyn RemoteEnv>, String> + Send>;
+
+enum Command {
+    Create {
+        config: String,
+        reply: oneshot::Sender<Result<u64, String>>,
+    },
+    Close {
+        env_id: u64,
+        reply: oneshot::Sender<bool>,
+    },
+    Reset {
+        env_id: u64,
+        reply: oneshot::Sender<Result<ResetResponse, Status>>,
+    },
+    Spaces {
+        env_id: u64,
+        reply: oneshot::Sender<Option<SpacesResponse>>,
+    },
+    Step {
+        envs: Vec<(u64, Vec<Vec<f32>>)>,
+        reply: oneshot::Sender<Result<Vec<EnvStep>, Status>>,
+    },
+}
+
+/// Serve envs over gRPC on `addr` until the server fails.
+///
+/// Envs aren't `Send`, so they're all created by `factory` and stepped on one dedicated thread.
+/// Requests over 64 MiB are refused before they're decoded.
+/// Run one server per process and several processes per machine to use more cores.
+///
+/// Building with the `grpc` feature requires `protoc` to be installed
+pub async fn serve(addr: SocketAddr, factory: EnvFactory) -> Result<(), tonic::transport::Error> {
+    let (commands, receiver) = mpsc::unbounded_channel();
+    thread::spawn(move || run_envs(factory, receiver));
+
+    Server::builder()
+        .add_service(
+            EnvServiceServer::new(EnvServer { commands }).max_decoding_message_size(MAX_FRAME_LEN),
+        )
+        .serve(addr)
+        .await
+}
+
+fn to_float_lists(obs: FullObs) -> Vec<FloatList> {
+    obs.into_iter().map(|values| FloatList { values }).collect()
+}
+
+fn to_u32s(values: Vec<usize>) -> Vec<u32> {
+    values.into_iter().map(|value| value as u32).collect()
+}
+
+fn env_not_found(env_id: u64) -> Status {
+    Status::not_found(format!("No env with id {env_id}"))
+}
+
+fn check_actions(
+    envs: &HashMap<u64, Box<dyn RemoteEnv>>,
+    actions: &[(u64, Vec<Vec<f32>>)],
+) -> Result<(), Status> {
+    for (index, (env_id, env_actions)) in actions.iter().enumerate() {
+        let env = envs.get(env_id).ok_or_else(|| env_not_found(*env_id))?;
+        if actions[..index].iter().any(|(id, _)| id == env_id) {
+            return Err(Status::invalid_argument(format!(
+                "Env {env_id} is in the batch more than once"
+            )));
+        }
+
+        if env.state().is_none() {
+            return Err(Status::failed_precondition(format!(
+                "Env {env_id} hasn't been reset yet"
+            )));
+        }
+
+        let num_agents = env.action_spaces().len();
+        if env_actions.len() != num_agents {
+            return Err(Status::invalid_argument(format!(
+                "Env {env_id} expects actions for {num_agents} agents, got {}",
+                env_actions.len()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+fn run_envs(mut factory: EnvFactory, mut commands: mpsc::UnboundedReceiver<Command>) {
+    let mut envs: HashMap<u64, Box<dyn RemoteEnv>> = HashMap::new();
+    let mut next_id = 0;
+
+    while let Some(command) = commands.blocking_recv() {
+        match command {
+            Command::Create { config, reply } => {
+                let result = factory(&config).map(|env| {
+                    let env_id = next_id;
+                    next_id += 1;
+                    envs.insert(env_id, env);
+                    env_id
+                });
+                let _ = reply.send(result);
+            }
+            Command::Close { env_id, reply } => {
+                let _ = reply.send(envs.remove(&env_id).is_some());
+            }
+            Command::Reset { env_id, reply } => {
+                let Some(env) = envs.get_mut(&env_id) else {
+                    let _ = reply.send(Err(env_not_found(env_id)));
+                    continue;
+                };
+
+                let response = env.reset().map(|obs| {
+                    let obs_spaces = env.obs_spaces();
+                    let action_spaces = env.action_spaces();
+                    ResetResponse {
+                        obs: to_float_lists(obs),
+                        config_hash: config_hash(&obs_spaces, &action_spaces),
+                        obs_spaces: to_u32s(obs_spaces),
+                        action_spaces: to_u32s(action_spaces),
+                    }
+                });
+                let _ = reply.send(response.map_err(|e| Status::internal(e.to_string())));
+            }
+            Command::Spaces { env_id, reply } => {
+                let _ = reply.send(envs.get(&env_id).map(|env| {
+                    let obs_spaces = env.obs_spaces();
+                    let action_spaces = env.action_spaces();
+                    SpacesResponse {
+                        config_hash: config_hash(&obs_spaces, &action_spaces),
+                        obs_spaces: to_u32s(obs_spaces),
+                        action_spaces: to_u32s(action_spaces),
+                    }
+                }));
+            }
+            Command::Step {
+                envs: actions,
+                reply,
+            } => {
+                // check every env's actions before stepping anything so a bad request doesn't half-step the batch
+                if let Err(status) = check_actions(&envs, &actions) {
+                    let _ = reply.send(Err(status));
+                    continue;
+                }
+
+                let steps = actions
+                    .into_iter()
+                    .map(|(env_id, actions)| {
+                        let step = envs.get_mut(&env_id).unwrap().step(actions)?;
+                        Ok(EnvStep {
+                            env_id,
+                            obs: to_float_lists(step.obs),
+                            rewards: step.rewards,
+                            is_terminal: step.is_terminal,
+                            truncated: step.truncated,
+                        })
+                    })
+                    .collect::<crate::Result<_>>();
+                let _ = reply.send(steps.map_err(|e| Status::internal(e.to_string())));
+            }
+        }
+    }
+}
+
+struct EnvServer {
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl EnvServer {
+    async fn send<T>(
+        &self,
+        command: impl FnOnce(oneshot::Sender<T>) -> Command,
+    ) -> Result<T, Status> {
+        let (reply, response) = oneshot::channel();
+        self.commands
+            .send(command(reply))
+            .map_err(|_| Status::unavailable("The env thread has stopped"))?;
+        response
+            .await
+            .map_err(|_| Status::internal("The env thread dropped the request"))
+    }
+}
+
+#[tonic::async_trait]
+impl EnvService for EnvServer {
+    async fn get_version(
+        &self,
+        _request: Request<VersionRequest>,
+    ) -> Result<Response<VersionResponse>, Status> {
+        Ok(Response::new(VersionResponse {
+            protocol_version: PROTOCOL_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            rocketsim_version: ROCKETSIM_VERSION.to_string(),
+        }))
+    }
+
+    async fn create_env(
+        &self,
+        request: Request<CreateEnvRequest>,
+    ) -> Result<Response<CreateEnvResponse>, Status> {
+        let config = request.into_inner().config;
+        let env_id = self
+            .send(|reply| Command::Create { config, reply })
+            .await?
+            .map_err(Status::invalid_argument)?;
+
+        Ok(Response::new(CreateEnvResponse { env_id }))
+    }
+
+    async fn close_env(
+        &self,
+        request: Request<CloseEnvRequest>,
+    ) -> Result<Response<CloseEnvResponse>, Status> {
+        let env_id = request.into_inner().env_id;
+        if !self.send(|reply| Command::Close { env_id, reply }).await? {
+            return Err(env_not_found(env_id));
+        }
+
+        Ok(Response::new(CloseEnvResponse {}))
+    }
+
+    async fn reset(
+        &self,
+        request: Request<ResetRequest>,
+    ) -> Result<Response<ResetResponse>, Status> {
+        let env_id = request.into_inner().env_id;
+        self.send(|reply| Command::Reset { env_id, reply })
+            .await?
+            .map(Response::new)
+    }
+
+    async fn get_spaces(
+        &self,
+        request: Request<SpacesRequest>,
+    ) -> Result<Response<SpacesResponse>, Status> {
+        let env_id = request.into_inner().env_id;
+        self.send(|reply| Command::Spaces { env_id, reply })
+            .await?
+            .map(Response::new)
+            .ok_or_else(|| env_not_found(env_id))
+    }
+
+    async fn step(&self, request: Request<StepRequest>) -> Result<Response<StepResponse>, Status> {
+        let envs = request
+            .into_inner()
+            .envs
+            .into_iter()
+            .map(|env| {
+                let actions = env
+                    .actions
+                    .into_iter()
+                    .map(|action| action.values)
+                    .collect();
+                (env.env_id, actions)
+            })
+            .collect();
+
+        let envs = self.send(|reply| Command::Step { envs, reply }).await??;
+
+        Ok(Response::new(StepResponse { envs }))
+    }
+}
@@ -0,0 +1,571 @@
+use crate::{remote::RemoteEnv, Error, Result};
+use memmap2::MmapMut;
+use std::{
+    fs::OpenOptions,
+    hint, io,
+    path::Path,
+    sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+const MAGIC: u32 = u32::from_le_bytes(*b"RLSM");
+const VERSION: u32 = 3;
+const HEADER_SIZE: usize = 64;
+
+// header offsets
+const NUM_ENVS: usize = 8;
+const MAX_AGENTS: usize = 12;
+const OBS_SIZE: usize = 16;
+const ACTION_SIZE: usize = 20;
+const COMMAND: usize = 24;
+const STATUS: usize = 28;
+const REQUEST_SEQ: usize = 32;
+const RESPONSE_SEQ: usize = 40;
+const WORKER_PID: usize = 48;
+const PARENT_PID: usize = 52;
+
+const COMMAND_RESET: u32 = 1;
+const COMMAND_STEP: u32 = 2;
+const COMMAND_STOP: u32 = 3;
+
+const STATUS_OK: u32 = 0;
+const STATUS_ERROR: u32 = 1;
+
+/// How often a waiting worker checks that the parent is still running
+const PARENT_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// each slot starts with num_agents, is_terminal and truncated, padded to 4 floats
+const SLOT_HEADER: usize = 4;
+
+/// The sizes that determine the layout of a shared memory file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShmLayout {
+    /// Envs stepped by the worker process
+    pub num_envs: usize,
+    pub max_agents: usize,
+    pub obs_size: usize,
+    pub action_size: usize,
+}
+
+impl ShmLayout {
+    fn slot_len(&self) -> usize {
+        SLOT_HEADER + self.max_agents * (self.action_size + 1 + self.obs_size)
+    }
+
+    /// Size of the shared memory file in bytes
+    pub fn size(&self) -> usize {
+        HEADER_SIZE + self.num_envs * self.slot_len() * 4
+    }
+}
+
+struct Shared {
+    mmap: MmapMut,
+    layout: ShmLayout,
+}
+
+impl Shared {
+    fn header(&self, offset: usize) -> u32 {
+        u32::from_le_bytes(self.mmap[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn atomic_u32(&self, offset: usize) -> &AtomicU32 {
+        // SAFETY: the offset is within the header and 4 byte aligned because mmaps are page aligned
+        unsafe { &*self.mmap.as_ptr().add(offset).cast::<AtomicU32>() }
+    }
+
+    fn atomic_u64(&self, offset: usize) -> &AtomicU64 {
+        // SAFETY: the offset is within the header and 8 byte aligned because mmaps are page aligned
+        unsafe { &*self.mmap.as_ptr().add(offset).cast::<AtomicU64>() }
+    }
+
+    fn check_env(&self, env: usize) -> Result<()> {
+        if env >= self.layout.num_envs {
+            return Err(Error::Invalid(format!(
+                "There's no env {env}, the layout has {}",
+                self.layout.num_envs
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn slot(&self, env: usize) -> Result<&[f32]> {
+        self.check_env(env)?;
+        let len = self.layout.slot_len();
+
+        // SAFETY: slots are 4 byte aligned, within the mapping and only written by
+        // the other process while we're waiting on the sequence numbers
+        unsafe {
+            let start = self
+                .mmap
+                .as_ptr()
+                .add(HEADER_SIZE)
+                .cast::<f32>()
+                .add(env * len);
+            Ok(std::slice::from_raw_parts(start, len))
+        }
+    }
+
+    fn slot_mut(&mut self, env: usize) -> Result<&mut [f32]> {
+        self.check_env(env)?;
+        let len = self.layout.slot_len();
+
+        // SAFETY: see `slot`
+        unsafe {
+            let start = self
+                .mmap
+                .as_mut_ptr()
+                .add(HEADER_SIZE)
+                .cast::<f32>()
+                .add(env * len);
+            Ok(std::slice::from_raw_parts_mut(start, len))
+        }
+    }
+
+    /// Spin, then yield, then sleep until `done` returns true or `timeout` passes.
+    ///
+    /// Past the first 256 tries this wakes up every 50µs, see `ShmParent` for why nothing signals it instead
+    fn wait(&self, timeout: Option<Duration>, mut done: impl FnMut(&Self) -> bool) -> bool {
+        let start = Instant::now();
+        let mut tries = 0u32;
+
+        while !done(self) {
+            if timeout.map_or(false, |timeout| start.elapsed() >= timeout) {
+                return false;
+            }
+
+            tries += 1;
+            if tries < 128 {
+                hint::spin_loop();
+            } else if tries < 256 {
+                thread::yield_now();
+            } else {
+                thread::sleep(Duration::from_micros(50));
+            }
+        }
+
+        true
+    }
+}
+
+fn slot_ranges(layout: &ShmLayout, num_agents: usize) -> [std::ops::Range<usize>; 3] {
+    let actions = SLOT_HEADER;
+    let rewards = actions + layout.max_agents * layout.action_size;
+    let obs = rewards + layout.max_agents;
+
+    [
+        actions..actions + num_agents * layout.action_size,
+        rewards..rewards + num_agents,
+        obs..obs + num_agents * layout.obs_size,
+    ]
+}
+
+/// Whether the process `pid` is still running, which is only known on Linux
+fn is_running(pid: u32) -> bool {
+    if cfg!(target_os = "linux") {
+        Path::new(&format!("/proc/{pid}")).exists()
+    } else {
+        true
+    }
+}
+
+/// The training process's side of a shared memory link to one worker process.
+///
+/// The worker steps all of its envs for every request, so a parent with several workers
+/// can send all of their requests before waiting on any of them.
+///
+/// On Linux, create the file in `/dev/shm` so that it never touches the disk.
+///
+/// Both sides poll the sequence numbers instead of blocking on an eventfd or futex, so the protocol stays
+/// atomics in a mapped file that any language can follow on any platform, with no wake up call to forget.
+/// The cost is latency and CPU time while waiting: a response that takes longer than a few microseconds is seen
+/// up to 50µs late plus the OS's timer slack, about 100µs on Linux, and an idle waiter uses a few percent of a core.
+/// That's small next to stepping several envs per request
+///
+/// Learners in other languages can be the parent by mapping the same file, everything is little-endian:
+/// - The 64 byte header holds the `u32`s `b"RLSM"`, version 3, `num_envs`, `max_agents`, `obs_size`,
+///   `action_size`, the command (1 reset, 2 step, 3 stop) and the status (0 ok, 1 error) at bytes 0 to 28,
+///   the request and response sequence numbers as `u64`s at bytes 32 and 40
+///   and the worker's and parent's process ids as `u32`s at bytes 48 and 52
+/// - The parent writes its process id when it creates the file, the worker stops once that process is gone.
+///   Leave it at 0 to keep the worker running
+/// - The worker writes its process id once it checked the header, wait for it before the first request
+/// - Write the actions and command, then increment the request sequence number.
+///   The request is handled once the response sequence number matches it
+/// - After the header, every env has a slot of `f32`s: the number of agents, `is_terminal` and `truncated`
+///   padded to 4 floats, then `max_agents * action_size` actions, `max_agents` rewards
+///   and `max_agents * obs_size` observations
+pub struct ShmParent {
+    shared: Shared,
+    seq: u64,
+    timeout: Option<Duration>,
+}
+
+impl ShmParent {
+    /// Create the shared memory file at `path`, then start a worker with `ShmWorker::open` on the same path
+    pub fn create(path: impl AsRef<Path>, layout: ShmLayout) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(layout.size() as u64)?;
+
+        // SAFETY: the file is only shared with worker processes that follow the same protocol
+        let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+        for (offset, value) in [
+            (0, MAGIC),
+            (4, VERSION),
+            (NUM_ENVS, layout.num_envs as u32),
+            (MAX_AGENTS, layout.max_agents as u32),
+            (OBS_SIZE, layout.obs_size as u32),
+            (ACTION_SIZE, layout.action_size as u32),
+            (PARENT_PID, std::process::id()),
+        ] {
+            mmap[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+
+        Ok(Self {
+            shared: Shared { mmap, layout },
+            seq: 0,
+            timeout: Some(Duration::from_secs(30)),
+        })
+    }
+
+    /// How long to wait for the worker before giving up with `io::ErrorKind::TimedOut`,
+    /// `None` waits forever. Defaults to 30 seconds
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn layout(&self) -> ShmLayout {
+        self.shared.layout
+    }
+
+    /// Wait for a worker to open the file and return its process id
+    pub fn wait_for_worker(&self) -> Result<u32> {
+        let attached = self.shared.wait(self.timeout, |shared| {
+            shared.atomic_u32(WORKER_PID).load(Ordering::Acquire) != 0
+        });
+
+        if !attached {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "No worker opened the shared memory file in time",
+            )
+            .into());
+        }
+
+        Ok(self.shared.atomic_u32(WORKER_PID).load(Ordering::Acquire))
+    }
+
+    fn request(&mut self, command: u32) {
+        self.seq += 1;
+        self.shared
+            .atomic_u32(COMMAND)
+            .store(command, Ordering::Relaxed);
+        self.shared
+            .atomic_u64(REQUEST_SEQ)
+            .store(self.seq, Ordering::Release);
+    }
+
+    /// Wait for the worker to finish the last request
+    pub fn wait(&self) -> Result<()> {
+        let seq = self.seq;
+        let finished = self.shared.wait(self.timeout, |shared| {
+            shared.atomic_u64(RESPONSE_SEQ).load(Ordering::Acquire) == seq
+        });
+
+        if !finished {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "The worker didn't respond in time",
+            )
+            .into());
+        }
+
+        if self.shared.atomic_u32(STATUS).load(Ordering::Relaxed) == STATUS_ERROR {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "The worker failed to handle the request",
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Ask the worker to reset all of its envs without waiting for it
+    pub fn send_reset(&mut self) {
+        self.request(COMMAND_RESET);
+    }
+
+    /// Write `actions[env][agent]` and ask the worker to step all of its envs without waiting for it
+    pub fn send_step(&mut self, actions: &[Vec<Vec<f32>>]) -> Result<()> {
+        let layout = self.shared.layout;
+        if actions.len() != layout.num_envs {
+            return Err(Error::ActionCount {
+                expected: layout.num_envs,
+                actual: actions.len(),
+            });
+        }
+
+        for (env, actions) in actions.iter().enumerate() {
+            let slot = self.shared.slot_mut(env)?;
+            let [range, _, _] = slot_ranges(&layout, slot[0] as usize);
+
+            slot[range].fill(0.);
+            for (agent, action) in actions.iter().take(layout.max_agents).enumerate() {
+                let start = SLOT_HEADER + agent * layout.action_size;
+                let len = action.len().min(layout.action_size);
+                slot[start..start + len].copy_from_slice(&action[..len]);
+            }
+        }
+
+        self.request(COMMAND_STEP);
+        Ok(())
+    }
+
+    pub fn reset(&mut self) -> Result<()> {
+        self.send_reset();
+        self.wait()
+    }
+
+    pub fn step(&mut self, actions: &[Vec<Vec<f32>>]) -> Result<()> {
+        self.send_step(actions)?;
+        self.wait()
+    }
+
+    /// Ask the worker to return from `ShmWorker::run`
+    pub fn stop(&mut self) -> Result<()> {
+        self.request(COMMAND_STOP);
+        self.wait()
+    }
+
+    pub fn num_agents(&self, env: usize) -> Result<usize> {
+        Ok(self.shared.slot(env)?[0] as usize)
+    }
+
+    pub fn is_terminal(&self, env: usize) -> Result<bool> {
+        Ok(self.shared.slot(env)?[1] != 0.)
+    }
+
+    pub fn truncated(&self, env: usize) -> Result<bool> {
+        Ok(self.shared.slot(env)?[2] != 0.)
+    }
+
+    pub fn rewards(&self, env: usize) -> Result<&[f32]> {
+        let [_, range, _] = slot_ranges(&self.shared.layout, self.num_agents(env)?);
+        Ok(&self.shared.slot(env)?[range])
+    }
+
+    /// The flattened observations of every agent in `env`, `obs_size` floats each.
+    ///
+    /// Envs are reset by the worker when their episode ends,
+    /// so these are the first observations of the next episode if the env is terminal or truncated
+    pub fn obs(&self, env: usize) -> Result<&[f32]> {
+        let [_, _, range] = slot_ranges(&self.shared.layout, self.num_agents(env)?);
+        Ok(&self.shared.slot(env)?[range])
+    }
+}
+
+/// The worker process's side of a shared memory link to the training process
+pub struct ShmWorker {
+    shared: Shared,
+    handled: u64,
+}
+
+impl ShmWorker {
+    /// Open a file created by `ShmParent::create` and let the parent know with `ShmParent::wait_for_worker`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+
+        // SAFETY: the file is only shared with a parent process that follows the same protocol
+        let mmap = unsafe { MmapMut::map_mut(&file)? };
+        if mmap.len() < HEADER_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Shared memory file is too small",
+            )
+            .into());
+        }
+
+        let mut shared = Shared {
+            mmap,
+            layout: ShmLayout {
+                num_envs: 0,
+                max_agents: 0,
+                obs_size: 0,
+                action_size: 0,
+            },
+        };
+
+        if shared.header(0) != MAGIC || shared.header(4) != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a shared memory file of a compatible version",
+            )
+            .into());
+        }
+
+        shared.layout = ShmLayout {
+            num_envs: shared.header(NUM_ENVS) as usize,
+            max_agents: shared.header(MAX_AGENTS) as usize,
+            obs_size: shared.header(OBS_SIZE) as usize,
+            action_size: shared.header(ACTION_SIZE) as usize,
+        };
+
+        if shared.mmap.len() < shared.layout.size() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Shared memory file is smaller than its layout",
+            )
+            .into());
+        }
+
+        let handled = shared.atomic_u64(RESPONSE_SEQ).load(Ordering::Acquire);
+        shared
+            .atomic_u32(WORKER_PID)
+            .store(std::process::id(), Ordering::Release);
+
+        Ok(Self { shared, handled })
+    }
+
+    pub fn layout(&self) -> ShmLayout {
+        self.shared.layout
+    }
+
+    fn write(
+        &mut self,
+        env: usize,
+        obs: &[Vec<f32>],
+        step: Option<(&[f32], bool, bool)>,
+    ) -> Result<()> {
+        let layout = self.shared.layout;
+        if obs.len() > layout.max_agents || obs.iter().any(|obs| obs.len() > layout.obs_size) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "An env has more agents or larger observations than the layout allows",
+            )
+            .into());
+        }
+
+        let (rewards, is_terminal, truncated) = step.unwrap_or((&[], false, false));
+        let slot = self.shared.slot_mut(env)?;
+        slot[0] = obs.len() as f32;
+        slot[1] = f32::from(u8::from(is_terminal));
+        slot[2] = f32::from(u8::from(truncated));
+
+        let [_, reward_range, obs_range] = slot_ranges(&layout, obs.len());
+        slot[reward_range.clone()].fill(0.);
+        for (slot, reward) in slot[reward_range].iter_mut().zip(rewards) {
+            *slot = *reward;
+        }
+
+        slot[obs_range.clone()].fill(0.);
+        for (agent, obs) in obs.iter().enumerate() {
+            let start = obs_range.start + agent * layout.obs_size;
+            slot[start..start + obs.len()].copy_from_slice(obs);
+        }
+
+        Ok(())
+    }
+
+    fn handle(&mut self, command: u32, envs: &mut [Box<dyn RemoteEnv>]) -> Result<()> {
+        let layout = self.shared.layout;
+
+        for (index, env) in envs.iter_mut().enumerate() {
+            if command == COMMAND_RESET {
+                let obs = env.reset()?;
+                self.write(index, &obs, None)?;
+            } else {
+                let slot = self.shared.slot(index)?;
+                let [range, _, _] = slot_ranges(&layout, slot[0] as usize);
+                let actions = slot[range]
+                    .chunks(layout.action_size.max(1))
+                    .map(<[f32]>::to_vec)
+                    .collect();
+
+                let step = env.step(actions)?;
+                if step.is_terminal || step.truncated {
+                    let obs = env.reset()?;
+                    self.write(
+                        index,
+                        &obs,
+                        Some((&step.rewards, step.is_terminal, step.truncated)),
+                    )?;
+                } else {
+                    self.write(index, &step.obs, Some((&step.rewards, false, false)))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle the parent's requests with `envs` until it sends a stop request or exits.
+    ///
+    /// There must be one env for every env in the layout
+    pub fn run(&mut self, envs: &mut [Box<dyn RemoteEnv>]) -> Result<()> {
+        if envs.len() != self.shared.layout.num_envs {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "The parent expects {} envs but the worker has {}",
+                    self.shared.layout.num_envs,
+                    envs.len()
+                ),
+            )
+            .into());
+        }
+
+        let parent_pid = self.shared.header(PARENT_PID);
+        loop {
+            let handled = self.handled;
+            let requested = self.shared.wait(Some(PARENT_CHECK_INTERVAL), |shared| {
+                shared.atomic_u64(REQUEST_SEQ).load(Ordering::Acquire) != handled
+            });
+            if !requested {
+                if parent_pid != 0 && !is_running(parent_pid) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::BrokenPipe,
+                        "The parent process exited without sending a stop request",
+                    )
+                    .into());
+                }
+
+                continue;
+            }
+
+            let seq = self.shared.atomic_u64(REQUEST_SEQ).load(Ordering::Acquire);
+            let command = self.shared.atomic_u32(COMMAND).load(Ordering::Relaxed);
+
+            let result = match command {
+                COMMAND_RESET | COMMAND_STEP => self.handle(command, envs),
+                _ => Ok(()),
+            };
+
+            let status = if result.is_ok() {
+                STATUS_OK
+            } else {
+                STATUS_ERROR
+            };
+            self.shared
+                .atomic_u32(STATUS)
+                .store(status, Ordering::Relaxed);
+            self.shared
+                .atomic_u64(RESPONSE_SEQ)
+                .store(seq, Ordering::Release);
+            self.handled = seq;
+
+            if command == COMMAND_STOP {
+                return Ok(());
+            }
+
+            result?;
+        }
+    }
+}
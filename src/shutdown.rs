@@ -0,0 +1,48 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Set when the run should stop, e.g. by ctrl-c.
+///
+/// Training loops check `is_requested` every step and call `Env::shutdown` when it's set,
+/// instead of being killed halfway through writing a file
+#[derive(Clone, Debug, Default)]
+pub struct ShutdownSignal {
+    requested: Arc<AtomicBool>,
+}
+
+impl ShutdownSignal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A signal that's set by ctrl-c, or SIGTERM on Unix.
+    ///
+    /// Can only be called once per process, a second ctrl-c exits immediately
+    #[cfg(feature = "ctrlc")]
+    pub fn ctrl_c() -> crate::Result<Self> {
+        let signal = Self::new();
+        let handler_signal = signal.clone();
+
+        ctrlc::set_handler(move || {
+            if handler_signal.is_requested() {
+                std::process::exit(130);
+            }
+
+            log::info!("Shutting down, press ctrl-c again to exit immediately");
+            handler_signal.request();
+        })
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+
+        Ok(signal)
+    }
+
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,34 @@
+/// What an observation or action space looks like, so learners can set up their inputs and outputs from it
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpaceType {
+    /// One of `n` choices, sent as a single index
+    Discrete(usize),
+    /// One choice per value, each from its own number of options
+    MultiDiscrete(Vec<usize>),
+    /// Floats from `low` to `high`
+    Box {
+        low: f32,
+        high: f32,
+        shape: Vec<usize>,
+    },
+}
+
+impl SpaceType {
+    /// Unbounded floats, like most observations
+    pub fn unbounded(len: usize) -> Self {
+        Self::Box {
+            low: f32::NEG_INFINITY,
+            high: f32::INFINITY,
+            shape: vec![len],
+        }
+    }
+
+    /// How many values an agent sends or receives for the space
+    pub fn num_values(&self) -> usize {
+        match self {
+            Self::Discrete(_) => 1,
+            Self::MultiDiscrete(options) => options.len(),
+            Self::Box { shape, .. } => shape.iter().product(),
+        }
+    }
+}
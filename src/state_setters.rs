@@ -0,0 +1,351 @@
+use crate::{
+    common_values::{
+        BACK_WALL_Y, BALL_RADIUS, CAR_MAX_SPEED, CEILING_Z, CORNER_WALL_XY, SIDE_WALL_X,
+    },
+    recording::read_recording,
+    Error, Result, StateSetter,
+};
+use rocketsim_rs::{
+    cxx::UniquePtr,
+    math::{RotMat, Vec3},
+    sim::{Arena, Team},
+    GameState,
+};
+use std::{fs::File, path::Path};
+
+/// A random kickoff position for every car, like at the start of a match
+#[derive(Clone, Debug)]
+pub struct KickoffStateSetter {
+    rng: fastrand::Rng,
+}
+
+impl Default for KickoffStateSetter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KickoffStateSetter {
+    pub fn new() -> Self {
+        Self {
+            rng: fastrand::Rng::new(),
+        }
+    }
+}
+
+impl<SI> StateSetter<SI> for KickoffStateSetter {
+    fn apply(&mut self, arena: &mut UniquePtr<Arena>, _shared_info: &mut SI) {
+        arena.pin_mut().reset_tick_count();
+        arena
+            .pin_mut()
+            .reset_to_random_kickoff(Some(self.rng.i32(0..i32::MAX)));
+    }
+
+    fn seed(&mut self, seed: u64, _shared_info: &mut SI) {
+        self.rng.seed(seed);
+    }
+}
+
+/// Puts the ball and every car somewhere random on the field, moving in a random direction,
+/// and gives every car a random amount of boost and a random heading.
+///
+/// Cars are kept apart from each other and from the ball
+#[derive(Clone, Debug)]
+pub struct RandomStateSetter {
+    /// Cars can be put in the air instead of only on the ground
+    pub cars_in_air: bool,
+    rng: fastrand::Rng,
+}
+
+impl Default for RandomStateSetter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RandomStateSetter {
+    /// Cars are only put on the ground
+    pub fn new() -> Self {
+        Self {
+            cars_in_air: false,
+            rng: fastrand::Rng::new(),
+        }
+    }
+
+    pub fn with_cars_in_air(mut self, cars_in_air: bool) -> Self {
+        self.cars_in_air = cars_in_air;
+        self
+    }
+
+    fn range(&mut self, max: f32) -> f32 {
+        (self.rng.f32() * 2. - 1.) * max
+    }
+
+    /// A position inside the field, at least `margin` away from the walls and floor
+    fn pos(&mut self, margin: f32, min_z: f32, max_z: f32) -> Vec3 {
+        // the corner walls are at 45 degrees, so the margin along x and y is longer
+        let max_xy = CORNER_WALL_XY - margin * std::f32::consts::SQRT_2;
+
+        loop {
+            let x = self.range(SIDE_WALL_X - margin);
+            let y = self.range(BACK_WALL_Y - margin);
+
+            if x.abs() + y.abs() <= max_xy {
+                return Vec3::new(x, y, min_z + self.rng.f32() * (max_z - min_z));
+            }
+        }
+    }
+
+    /// Like `pos`, but at least `MIN_SPACING` away from everything in `taken`,
+    /// or the last position tried if there's no room after `MAX_TRIES`
+    fn free_pos(&mut self, margin: f32, min_z: f32, max_z: f32, taken: &[Vec3]) -> Vec3 {
+        let mut pos = self.pos(margin, min_z, max_z);
+        for _ in 1..MAX_TRIES {
+            if taken
+                .iter()
+                .all(|&other| distance(pos, other) >= MIN_SPACING)
+            {
+                break;
+            }
+            pos = self.pos(margin, min_z, max_z);
+        }
+
+        pos
+    }
+
+    fn vel(&mut self, max_speed: f32, vertical: bool) -> Vec3 {
+        let speed = self.rng.f32() * max_speed;
+        let angle = self.rng.f32() * std::f32::consts::TAU;
+        let z = if vertical {
+            self.range(max_speed / 2.)
+        } else {
+            0.
+        };
+
+        Vec3::new(angle.cos() * speed, angle.sin() * speed, z)
+    }
+}
+
+/// Cars resting on the ground are this high up
+const CAR_REST_Z: f32 = 17.;
+/// Cars are put at least this far from each other and the ball
+const MIN_SPACING: f32 = 300.;
+/// Random positions tried for a car before it's put close to something anyway
+const MAX_TRIES: usize = 100;
+
+fn distance(a: Vec3, b: Vec3) -> f32 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2) + (a.z - b.z).powi(2)).sqrt()
+}
+
+/// Facing `yaw` radians from +x, flat on the ground
+fn yaw_rot_mat(yaw: f32) -> RotMat {
+    let (sin, cos) = yaw.sin_cos();
+    RotMat {
+        forward: Vec3::new(cos, sin, 0.),
+        right: Vec3::new(-sin, cos, 0.),
+        up: Vec3::new(0., 0., 1.),
+    }
+}
+
+impl<SI> StateSetter<SI> for RandomStateSetter {
+    fn apply(&mut self, arena: &mut UniquePtr<Arena>, _shared_info: &mut SI) {
+        arena.pin_mut().reset_tick_count();
+
+        let mut state = arena.pin_mut().get_game_state();
+
+        state.ball.pos = self.pos(BALL_RADIUS, BALL_RADIUS, CEILING_Z / 2.);
+        state.ball.vel = self.vel(CAR_MAX_SPEED, true);
+        state.ball.ang_vel = Vec3::new(0., 0., 0.);
+
+        let mut taken = vec![state.ball.pos];
+        for car in &mut state.cars {
+            let max_z = if self.cars_in_air {
+                CEILING_Z / 2.
+            } else {
+                CAR_REST_Z
+            };
+
+            car.state.pos = self.free_pos(BALL_RADIUS * 2., CAR_REST_Z, max_z, &taken);
+            taken.push(car.state.pos);
+            car.state.rot_mat = yaw_rot_mat(self.rng.f32() * std::f32::consts::TAU);
+            car.state.vel = self.vel(CAR_MAX_SPEED, self.cars_in_air);
+            car.state.ang_vel = Vec3::new(0., 0., 0.);
+            car.state.boost = self.rng.f32() * 100.;
+        }
+
+        if let Err(e) = arena.pin_mut().set_game_state(&state) {
+            log::warn!("Failed to set a random state: {e}");
+        }
+    }
+
+    fn seed(&mut self, seed: u64, _shared_info: &mut SI) {
+        self.rng.seed(seed);
+    }
+}
+
+/// Teleports the ball and cars to a random frame of a recording on every reset,
+/// e.g. states extracted from real replays and saved with a `recording::Recorder`.
+///
+/// Recorded cars are matched to the arena's cars by team in order,
+/// so only frames with at least as many cars on each team as the arena are used
+pub struct ReplaySetter {
+    frames: Vec<GameState>,
+    /// Indices of the frames that fit the arena's blue and orange team sizes
+    matching: Option<((usize, usize), Vec<usize>)>,
+    rng: fastrand::Rng,
+}
+
+fn team_sizes(state: &GameState) -> (usize, usize) {
+    let blue = state
+        .cars
+        .iter()
+        .filter(|car| car.team == Team::Blue)
+        .count();
+    (blue, state.cars.len() - blue)
+}
+
+impl ReplaySetter {
+    pub fn new(frames: Vec<GameState>) -> Self {
+        Self {
+            frames,
+            matching: None,
+            rng: fastrand::Rng::new(),
+        }
+    }
+
+    /// Load every frame of a file written by a `recording::Recorder`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let frames = read_recording(File::open(path)?)?;
+        if frames.is_empty() {
+            return Err(Error::Invalid(String::from("The replay has no frames")));
+        }
+
+        Ok(Self::new(frames))
+    }
+
+    pub fn num_frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    fn pick(&mut self, sizes: (usize, usize)) -> Option<&GameState> {
+        if self.matching.as_ref().map(|(cached, _)| *cached) != Some(sizes) {
+            let (blue, orange) = sizes;
+            let indices = self
+                .frames
+                .iter()
+                .enumerate()
+                .filter(|(_, frame)| {
+                    let (frame_blue, frame_orange) = team_sizes(frame);
+                    frame_blue >= blue && frame_orange >= orange
+                })
+                .map(|(index, _)| index)
+                .collect();
+            self.matching = Some((sizes, indices));
+        }
+
+        let (_, indices) = self.matching.as_ref()?;
+        let index = *indices.get(self.rng.usize(..indices.len().max(1)))?;
+
+        self.frames.get(index)
+    }
+}
+
+impl<SI> StateSetter<SI> for ReplaySetter {
+    fn apply(&mut self, arena: &mut UniquePtr<Arena>, _shared_info: &mut SI) {
+        arena.pin_mut().reset_tick_count();
+
+        let mut state = arena.pin_mut().get_game_state();
+        let sizes = team_sizes(&state);
+        let Some(frame) = self.pick(sizes) else {
+            log::warn!(
+                "No frame of the replay has at least {} blue and {} orange cars",
+                sizes.0,
+                sizes.1
+            );
+            return;
+        };
+
+        state.ball = frame.ball;
+        for team in [Team::Blue, Team::Orange] {
+            let recorded = frame.cars.iter().filter(|car| car.team == team);
+            let cars = state.cars.iter_mut().filter(|car| car.team == team);
+
+            for (car, recorded) in cars.zip(recorded) {
+                car.state = recorded.state;
+            }
+        }
+
+        if let Err(e) = arena.pin_mut().set_game_state(&state) {
+            log::warn!("Failed to set a state from the replay: {e}");
+        }
+    }
+
+    fn seed(&mut self, seed: u64, _shared_info: &mut SI) {
+        self.rng.seed(seed);
+    }
+}
+
+/// Picks one of several state setters at random every reset, e.g. mostly kickoffs with some random states
+pub struct WeightedStateSetter<SI> {
+    setters: Vec<(f32, Box<dyn StateSetter<SI>>)>,
+    rng: fastrand::Rng,
+}
+
+impl<SI> Default for WeightedStateSetter<SI> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<SI> WeightedStateSetter<SI> {
+    pub fn new() -> Self {
+        Self {
+            setters: Vec::new(),
+            rng: fastrand::Rng::new(),
+        }
+    }
+
+    /// `weight` is relative to the other setters' weights
+    pub fn with_setter(mut self, weight: f32, setter: impl StateSetter<SI> + 'static) -> Self {
+        self.setters.push((weight.max(0.), Box::new(setter)));
+        self
+    }
+
+    pub fn setters_mut(&mut self) -> &mut [(f32, Box<dyn StateSetter<SI>>)] {
+        &mut self.setters
+    }
+
+    fn pick(&mut self) -> Option<&mut Box<dyn StateSetter<SI>>> {
+        let total = self.setters.iter().map(|(weight, _)| weight).sum::<f32>();
+        let mut target = self.rng.f32() * total;
+
+        let index = self
+            .setters
+            .iter()
+            .position(|(weight, _)| {
+                target -= weight;
+                target < 0.
+            })
+            .unwrap_or(self.setters.len().checked_sub(1)?);
+
+        Some(&mut self.setters[index].1)
+    }
+}
+
+impl<SI> StateSetter<SI> for WeightedStateSetter<SI> {
+    fn apply(&mut self, arena: &mut UniquePtr<Arena>, shared_info: &mut SI) {
+        match self.pick() {
+            Some(setter) => setter.apply(arena, shared_info),
+            None => log::warn!("WeightedStateSetter has no state setters"),
+        }
+    }
+
+    /// Every child is given a different seed derived from `seed`
+    fn seed(&mut self, seed: u64, shared_info: &mut SI) {
+        self.rng.seed(seed);
+
+        for (_, setter) in &mut self.setters {
+            setter.seed(self.rng.u64(..), shared_info);
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{Error, Result};
+use std::{
+    io,
+    process::{Child, ExitStatus},
+    time::{Duration, Instant},
+};
+
+/// Something that went wrong with a worker, and what the supervisor did about it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Incident {
+    /// The worker process exited on its own
+    Crashed {
+        worker: usize,
+        status: ExitStatus,
+    },
+    /// The worker didn't send a heartbeat in time, e.g. because its arena is stuck in a physics edge case
+    Hung {
+        worker: usize,
+        silent_for: Duration,
+    },
+    Restarted {
+        worker: usize,
+        seed: u64,
+    },
+    RestartFailed {
+        worker: usize,
+        error: String,
+    },
+}
+
+/// Receives every incident as it happens, e.g. to forward them to a metrics backend
+pub trait IncidentSink {
+    fn report(&mut self, incident: &Incident);
+}
+
+impl<F: FnMut(&Incident)> IncidentSink for F {
+    fn report(&mut self, incident: &Incident) {
+        self(incident);
+    }
+}
+
+/// Starts a worker process, given its index and the seed it should use
+pub type SpawnWorker = Box<dyn FnMut(usize, u64) -> io::Result<Child>>;
+
+struct Worker {
+    child: Option<Child>,
+    seed: u64,
+    last_heartbeat: Instant,
+    restarts: u32,
+    /// Restarts since the worker last sent a heartbeat
+    failures: u32,
+    /// When to start the worker again while it's not running
+    restart_at: Instant,
+}
+
+/// Keeps a fixed number of worker processes running.
+///
+/// Whatever receives messages from the workers should call `heartbeat` for every message,
+/// and `check` should be called regularly to restart workers that crashed or stopped sending them.
+/// A worker that keeps failing without ever sending a heartbeat is restarted less and less often
+pub struct Supervisor {
+    spawn: SpawnWorker,
+    workers: Vec<Worker>,
+    started: bool,
+    heartbeat_timeout: Duration,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    rng: fastrand::Rng,
+    sink: Option<Box<dyn IncidentSink>>,
+}
+
+impl Supervisor {
+    pub fn new(num_workers: usize, spawn: SpawnWorker) -> Self {
+        let now = Instant::now();
+
+        Self {
+            spawn,
+            workers: (0..num_workers)
+                .map(|_| Worker {
+                    child: None,
+                    seed: 0,
+                    last_heartbeat: now,
+                    restarts: 0,
+                    failures: 0,
+                    restart_at: now,
+                })
+                .collect(),
+            started: false,
+            heartbeat_timeout: Duration::from_secs(30),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            rng: fastrand::Rng::new(),
+            sink: None,
+        }
+    }
+
+    /// How long a worker can go without a heartbeat before it's considered hung, defaults to 30 seconds
+    pub fn with_heartbeat_timeout(mut self, heartbeat_timeout: Duration) -> Self {
+        self.heartbeat_timeout = heartbeat_timeout;
+        self
+    }
+
+    /// A worker's first restart is immediate, then the wait starts at `initial`
+    /// and doubles with every restart up to `max` until the worker sends a heartbeat.
+    /// Defaults to 1 and 60 seconds
+    pub fn with_restart_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Seed the generator of worker seeds, so that a whole run can be reproduced
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = fastrand::Rng::with_seed(seed);
+        self
+    }
+
+    pub fn with_sink(mut self, sink: impl IncidentSink + 'static) -> Self {
+        self.sink = Some(Box::new(sink));
+        self
+    }
+
+    fn record(&mut self, incidents: &mut Vec<Incident>, incident: Incident) {
+        if let Some(sink) = &mut self.sink {
+            sink.report(&incident);
+        }
+
+        incidents.push(incident);
+    }
+
+    fn spawn_worker(&mut self, worker: usize) -> io::Result<u64> {
+        let seed = self.rng.u64(..);
+        let child = (self.spawn)(worker, seed)?;
+
+        let state = &mut self.workers[worker];
+        state.child = Some(child);
+        state.seed = seed;
+        state.last_heartbeat = Instant::now();
+
+        Ok(seed)
+    }
+
+    /// Schedule the next start of a worker that stopped or failed to start
+    fn back_off(&mut self, worker: usize) {
+        let state = &mut self.workers[worker];
+        let backoff = match state.failures {
+            0 => Duration::ZERO,
+            failures => self
+                .initial_backoff
+                .saturating_mul(2u32.saturating_pow(failures - 1))
+                .min(self.max_backoff),
+        };
+
+        state.child = None;
+        state.failures = state.failures.saturating_add(1);
+        state.restart_at = Instant::now() + backoff;
+    }
+
+    /// Spawn every worker
+    pub fn start(&mut self) -> Result<()> {
+        for worker in 0..self.workers.len() {
+            self.spawn_worker(worker)?;
+        }
+        self.started = true;
+
+        Ok(())
+    }
+
+    /// Let the supervisor know that `worker` is alive
+    pub fn heartbeat(&mut self, worker: usize) -> Result<()> {
+        let num_workers = self.workers.len();
+        let state = self.workers.get_mut(worker).ok_or_else(|| {
+            Error::Invalid(format!(
+                "There's no worker {worker}, there are {num_workers}"
+            ))
+        })?;
+
+        state.last_heartbeat = Instant::now();
+        state.failures = 0;
+        Ok(())
+    }
+
+    pub fn num_workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// The seed `worker` was last started with
+    pub fn seed(&self, worker: usize) -> Option<u64> {
+        self.workers.get(worker).map(|state| state.seed)
+    }
+
+    pub fn restarts(&self, worker: usize) -> Option<u32> {
+        self.workers.get(worker).map(|state| state.restarts)
+    }
+
+    /// Restart every worker that exited or is hung with a fresh seed, returning what happened.
+    ///
+    /// Does nothing before `start` or after `shutdown`
+    pub fn check(&mut self) -> Vec<Incident> {
+        let mut incidents = Vec::new();
+        if !self.started {
+            return incidents;
+        }
+
+        for worker in 0..self.workers.len() {
+            let state = &mut self.workers[worker];
+            let silent_for = state.last_heartbeat.elapsed();
+
+            let incident = match &mut state.child {
+                Some(child) => match child.try_wait() {
+                    Ok(Some(status)) => Some(Incident::Crashed { worker, status }),
+                    Ok(None) if silent_for > self.heartbeat_timeout => {
+                        // it's getting replaced either way, so it doesn't matter if it already exited
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        Some(Incident::Hung { worker, silent_for })
+                    }
+                    Ok(None) => None,
+                    Err(e) => {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        Some(Incident::RestartFailed {
+                            worker,
+                            error: e.to_string(),
+                        })
+                    }
+                },
+                // waiting to be restarted
+                None => None,
+            };
+
+            if let Some(incident) = incident {
+                self.record(&mut incidents, incident);
+                self.back_off(worker);
+            }
+
+            let state = &self.workers[worker];
+            if state.child.is_some() || Instant::now() < state.restart_at {
+                continue;
+            }
+
+            self.workers[worker].restarts += 1;
+            let incident = match self.spawn_worker(worker) {
+                Ok(seed) => Incident::Restarted { worker, seed },
+                Err(e) => {
+                    self.back_off(worker);
+                    Incident::RestartFailed {
+                        worker,
+                        error: e.to_string(),
+                    }
+                }
+            };
+            self.record(&mut incidents, incident);
+        }
+
+        incidents
+    }
+
+    /// Kill every worker
+    pub fn shutdown(&mut self) {
+        self.started = false;
+        for worker in &mut self.workers {
+            if let Some(mut child) = worker.child.take() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
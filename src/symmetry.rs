@@ -0,0 +1,340 @@
+use crate::{AgentId, AgentMap, FullObs, Obs, Result, SpaceType, StepContext};
+use glam::{Mat3A, Vec3A};
+use rocketsim_rs::{
+    glam_ext::GameStateA,
+    sim::{CarControls, Team},
+};
+
+/// A symmetry of the field, to look at a state from another side or to augment training data.
+///
+/// Physics behave the same in the transformed state, as long as the controls go through `controls` too
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    /// Turn the field around so orange attacks the goal blue attacks and swap the teams of the cars,
+    /// the controls stay the same
+    FlipTeams,
+    /// Mirror the field left to right, steering, yaw and roll are flipped
+    MirrorX,
+}
+
+impl Symmetry {
+    /// Transform a position or velocity
+    pub fn vec(self, v: Vec3A) -> Vec3A {
+        match self {
+            Self::FlipTeams => Vec3A::new(-v.x, -v.y, v.z),
+            Self::MirrorX => Vec3A::new(-v.x, v.y, v.z),
+        }
+    }
+
+    /// Transform an angular velocity, which flips the other way in a mirror
+    pub fn ang_vel(self, v: Vec3A) -> Vec3A {
+        match self {
+            Self::FlipTeams => self.vec(v),
+            Self::MirrorX => Vec3A::new(v.x, -v.y, -v.z),
+        }
+    }
+
+    /// Transform a car's orientation, the car's left becomes its right in a mirror
+    pub fn rot_mat(self, rot_mat: Mat3A) -> Mat3A {
+        let y_axis = match self {
+            Self::FlipTeams => self.vec(rot_mat.y_axis),
+            Self::MirrorX => -self.vec(rot_mat.y_axis),
+        };
+
+        Mat3A::from_cols(self.vec(rot_mat.x_axis), y_axis, self.vec(rot_mat.z_axis))
+    }
+
+    pub fn team(self, team: Team) -> Team {
+        match (self, team) {
+            (Self::FlipTeams, Team::Blue) => Team::Orange,
+            (Self::FlipTeams, _) => Team::Blue,
+            (Self::MirrorX, team) => team,
+        }
+    }
+
+    /// The controls that do in the transformed state what `controls` do in the original one.
+    ///
+    /// Both symmetries are their own inverse, so this also turns actions picked in the transformed state back
+    pub fn controls(self, mut controls: CarControls) -> CarControls {
+        if self == Self::MirrorX {
+            controls.steer = -controls.steer;
+            controls.yaw = -controls.yaw;
+            controls.roll = -controls.roll;
+        }
+
+        controls
+    }
+
+    pub fn actions(self, actions: &[(AgentId, CarControls)]) -> Vec<(AgentId, CarControls)> {
+        actions
+            .iter()
+            .map(|(agent_id, controls)| (*agent_id, self.controls(*controls)))
+            .collect()
+    }
+
+    /// Transform the ball, the cars and the boost pads of `state`.
+    ///
+    /// Cars keep their ids and order, pads keep their order and take the state of the pad they're moved onto.
+    /// Each car's `ball_hit_info` is left as is
+    pub fn state(self, state: &GameStateA) -> GameStateA {
+        let mut transformed = state.clone();
+
+        transformed.ball.pos = self.vec(state.ball.pos);
+        transformed.ball.vel = self.vec(state.ball.vel);
+        transformed.ball.ang_vel = self.ang_vel(state.ball.ang_vel);
+
+        for car in &mut transformed.cars {
+            car.team = self.team(car.team);
+            car.state.pos = self.vec(car.state.pos);
+            car.state.vel = self.vec(car.state.vel);
+            car.state.ang_vel = self.ang_vel(car.state.ang_vel);
+            car.state.rot_mat = self.rot_mat(car.state.rot_mat);
+            car.state.last_controls = self.controls(car.state.last_controls);
+        }
+
+        for pad in &mut transformed.pads {
+            let source = state.pads.iter().min_by(|a, b| {
+                self.vec(a.position)
+                    .distance_squared(pad.position)
+                    .total_cmp(&self.vec(b.position).distance_squared(pad.position))
+            });
+            if let Some(source) = source {
+                pad.state = source.state;
+            }
+        }
+
+        transformed
+    }
+}
+
+/// Builds every orange car's observation in a flipped state, so all agents see the field like blue does.
+///
+/// A policy trained on these always attacks the same goal. Meant for obs builders that don't mirror
+/// orange themselves, unlike the ones in `obs`. The orange rows come from a clone of `obs`
+/// that only ever sees flipped states, step events and action history are passed to it as they are
+pub struct CanonicalObs<O> {
+    pub obs: O,
+    orange: O,
+}
+
+impl<O: Clone> CanonicalObs<O> {
+    pub fn new(obs: O) -> Self {
+        Self {
+            orange: obs.clone(),
+            obs,
+        }
+    }
+}
+
+impl<SI, O: Obs<SI>> Obs<SI> for CanonicalObs<O> {
+    fn get_obs_space(&self, agent_id: AgentId, shared_info: &SI) -> usize {
+        self.obs.get_obs_space(agent_id, shared_info)
+    }
+
+    fn get_obs_space_type(&self, agent_id: AgentId, shared_info: &SI) -> SpaceType {
+        self.obs.get_obs_space_type(agent_id, shared_info)
+    }
+
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        self.obs.reset(initial_state, shared_info);
+        self.orange
+            .reset(&Symmetry::FlipTeams.state(initial_state), shared_info);
+    }
+
+    fn build_obs(&mut self, ctx: &StepContext, shared_info: &mut SI) -> FullObs {
+        let state = Symmetry::FlipTeams.state(ctx.state);
+        let previous_state = ctx
+            .previous_state
+            .map(|state| Symmetry::FlipTeams.state(state));
+        let actions = Symmetry::FlipTeams.actions(ctx.actions);
+
+        let flipped = StepContext {
+            state: &state,
+            previous_state: previous_state.as_ref(),
+            actions: &actions,
+            ..*ctx
+        };
+
+        let mut obs = self.obs.build_obs(ctx, shared_info);
+        let orange_obs = self.orange.build_obs(&flipped, shared_info);
+
+        for ((row, orange_row), car) in obs.iter_mut().zip(orange_obs).zip(&ctx.state.cars) {
+            if car.team == Team::Orange {
+                *row = orange_row;
+            }
+        }
+
+        obs
+    }
+
+    fn validate(&self, agents: &AgentMap, shared_info: &SI) -> Result<()> {
+        self.obs.validate(agents, shared_info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::StateBuilder;
+    use rocketsim_rs::glam_ext::BoostPadA;
+
+    const SYMMETRIES: [Symmetry; 2] = [Symmetry::FlipTeams, Symmetry::MirrorX];
+
+    fn tilted() -> Mat3A {
+        Mat3A::from_rotation_z(0.7) * Mat3A::from_rotation_y(-0.4) * Mat3A::from_rotation_x(0.2)
+    }
+
+    fn pad_at(x: f32, y: f32, is_active: bool) -> BoostPadA {
+        let mut pad = BoostPadA {
+            position: Vec3A::new(x, y, 73.),
+            ..Default::default()
+        };
+        pad.state.is_active = is_active;
+        pad
+    }
+
+    fn state() -> GameStateA {
+        let mut state = StateBuilder::new()
+            .with_ball(Vec3A::new(300., 500., 93.), Vec3A::new(-200., 1000., 50.))
+            .with_car_info(Team::Blue, |car| {
+                car.state.pos = Vec3A::new(-1000., -1000., 17.);
+                car.state.vel = Vec3A::new(500., 300., 0.);
+                car.state.ang_vel = Vec3A::new(1., -2., 3.);
+                car.state.rot_mat = tilted();
+                car.state.last_controls.steer = 0.5;
+                car.state.last_controls.roll = -1.;
+            })
+            .with_car(Team::Orange, Vec3A::new(1000., 1000., 17.))
+            .build();
+        state.cars[1].state.rot_mat = Mat3A::IDENTITY;
+        state.pads = vec![
+            pad_at(-3000., 0., true),
+            pad_at(3000., 0., false),
+            pad_at(0., -4000., false),
+            pad_at(0., 4000., false),
+        ];
+        state
+    }
+
+    fn active_pads(state: &GameStateA) -> Vec<bool> {
+        state.pads.iter().map(|pad| pad.state.is_active).collect()
+    }
+
+    #[test]
+    fn applying_twice_restores() {
+        let v = Vec3A::new(1., -2., 3.);
+        let controls = CarControls {
+            throttle: 1.,
+            steer: -0.5,
+            yaw: 0.25,
+            roll: 1.,
+            ..Default::default()
+        };
+
+        for symmetry in SYMMETRIES {
+            assert_eq!(symmetry.vec(symmetry.vec(v)), v);
+            assert_eq!(symmetry.ang_vel(symmetry.ang_vel(v)), v);
+            assert_eq!(symmetry.rot_mat(symmetry.rot_mat(tilted())), tilted());
+            assert!(symmetry.team(symmetry.team(Team::Blue)) == Team::Blue);
+
+            let restored = symmetry.controls(symmetry.controls(controls));
+            assert_eq!(
+                [
+                    restored.throttle,
+                    restored.steer,
+                    restored.yaw,
+                    restored.roll
+                ],
+                [1., -0.5, 0.25, 1.]
+            );
+
+            let state = state();
+            let restored = symmetry.state(&symmetry.state(&state));
+            assert_eq!(restored.ball.pos, state.ball.pos);
+            assert_eq!(restored.ball.vel, state.ball.vel);
+            for (restored, car) in restored.cars.iter().zip(&state.cars) {
+                assert!(restored.team == car.team);
+                assert_eq!(restored.state.pos, car.state.pos);
+                assert_eq!(restored.state.vel, car.state.vel);
+                assert_eq!(restored.state.ang_vel, car.state.ang_vel);
+                assert_eq!(restored.state.rot_mat, car.state.rot_mat);
+                assert_eq!(
+                    restored.state.last_controls.steer,
+                    car.state.last_controls.steer
+                );
+                assert_eq!(
+                    restored.state.last_controls.roll,
+                    car.state.last_controls.roll
+                );
+            }
+            assert_eq!(active_pads(&restored), active_pads(&state));
+        }
+    }
+
+    #[test]
+    fn orientations_stay_rotations() {
+        for symmetry in SYMMETRIES {
+            let rot_mat = symmetry.rot_mat(tilted());
+            assert!((rot_mat.determinant() - 1.).abs() < 1e-5);
+            // still the car's forward, left and up
+            assert!(rot_mat
+                .x_axis
+                .cross(rot_mat.y_axis)
+                .abs_diff_eq(rot_mat.z_axis, 1e-5));
+        }
+    }
+
+    #[test]
+    fn pads_move_onto_their_mirror() {
+        let state = state();
+        assert_eq!(
+            active_pads(&Symmetry::MirrorX.state(&state)),
+            [false, true, false, false]
+        );
+
+        let mut state = state;
+        state.pads[0].state.is_active = false;
+        state.pads[2].state.is_active = true;
+        assert_eq!(
+            active_pads(&Symmetry::FlipTeams.state(&state)),
+            [false, false, false, true]
+        );
+        // the pads on the center line stay where they are in a mirror
+        assert_eq!(
+            active_pads(&Symmetry::MirrorX.state(&state)),
+            [false, false, true, false]
+        );
+    }
+
+    /// The ball's and the car's own y position
+    #[derive(Clone)]
+    struct DepthObs;
+
+    impl Obs<()> for DepthObs {
+        fn get_obs_space(&self, _agent_id: AgentId, _shared_info: &()) -> usize {
+            2
+        }
+
+        fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut ()) {}
+
+        fn build_obs(&mut self, ctx: &StepContext, _shared_info: &mut ()) -> FullObs {
+            ctx.state
+                .cars
+                .iter()
+                .map(|car| vec![ctx.state.ball.pos.y, car.state.pos.y])
+                .collect()
+        }
+    }
+
+    #[test]
+    fn canonical_obs_flips_orange() {
+        let state = state();
+        let mut obs = CanonicalObs::new(DepthObs);
+        obs.reset(&state, &mut ());
+        let rows = obs.build_obs(&StepContext::new(&state), &mut ());
+
+        assert_eq!(rows[0], [500., -1000.]);
+        // orange sees the ball in its own half, from its own half
+        assert_eq!(rows[1], [-500., -1000.]);
+    }
+}
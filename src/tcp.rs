@@ -0,0 +1,167 @@
+use crate::{
+    handshake::config_hash,
+    remote::RemoteEnv,
+    wire::{invalid_data, read_f32, read_u32},
+    Error, FullObs, Result,
+};
+use std::{
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+};
+
+/// Version of the messages below
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Sent first after connecting: `[protocol_version, config_hash...]`,
+/// the config hash from `handshake::config_hash` is split into four 16 bit parts, lowest first
+pub const ENV_VERSION_HEADER: [f32; 3] = [81771., 82271., 82772.];
+/// Sent once after the version: `[obs_size, action_size, num_agents]`
+pub const ENV_SHAPES_HEADER: [f32; 3] = [82772., 83273., 83774.];
+/// Sent after every reset: `[num_agents, obs_size, obs...]`
+pub const ENV_RESET_STATE_HEADER: [f32; 3] = [83273., 83774., 84275.];
+/// Sent after every step: `[num_agents, is_terminal, truncated, rewards..., obs...]`
+pub const ENV_STEP_DATA_HEADER: [f32; 3] = [83774., 84275., 84776.];
+/// Sent by the trainer: `[actions...]`, split evenly between the agents
+pub const POLICY_ACTIONS_HEADER: [f32; 3] = [84275., 84776., 85277.];
+/// Sent by the trainer to make the worker return
+pub const STOP_MESSAGE_HEADER: [f32; 3] = [85277., 85778., 86279.];
+
+/// Runs an env for a trainer over TCP, with a message format of this crate's own.
+///
+/// Every message is a `u32` count followed by that many `f32`s, all little-endian.
+/// The first three floats are one of the headers above and the rest is the payload.
+///
+/// The worker sends its version, the env's shapes and the first reset state, then waits for actions.
+/// After each step it sends the step data with the observations that follow the actions,
+/// and if the episode ended it resets and sends the new reset state before waiting for the next actions.
+/// Trainers should refuse to continue if the protocol version or config hash isn't what they expect.
+///
+/// Every agent must have the same observation and action size
+pub struct TcpWorker {
+    reader: BufReader<TcpStream>,
+    writer: BufWriter<TcpStream>,
+}
+
+impl TcpWorker {
+    /// Connect to the trainer listening at `addr`
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: BufWriter::new(stream),
+        })
+    }
+
+    fn send(&mut self, header: [f32; 3], payload: &[f32]) -> io::Result<()> {
+        self.writer
+            .write_all(&((header.len() + payload.len()) as u32).to_le_bytes())?;
+        for value in header.iter().chain(payload) {
+            self.writer.write_all(&value.to_le_bytes())?;
+        }
+
+        self.writer.flush()
+    }
+
+    /// Read a message with at most `max_payload` floats after its header
+    fn recv(&mut self, max_payload: usize) -> io::Result<([f32; 3], Vec<f32>)> {
+        let len = read_u32(&mut self.reader)? as usize;
+        if len < 3 {
+            return Err(invalid_data("Message is shorter than its header"));
+        }
+        if len - 3 > max_payload {
+            return Err(invalid_data(format!(
+                "Message has {} values, more than the {max_payload} actions of the env",
+                len - 3
+            )));
+        }
+
+        let mut header = [0.; 3];
+        for value in &mut header {
+            *value = read_f32(&mut self.reader)?;
+        }
+
+        let mut bytes = vec![0; (len - 3) * 4];
+        self.reader.read_exact(&mut bytes)?;
+        let payload = bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+
+        Ok((header, payload))
+    }
+
+    fn send_reset_state(&mut self, obs: &FullObs) -> io::Result<()> {
+        let obs_size = obs.first().map_or(0, Vec::len);
+        let mut payload = vec![obs.len() as f32, obs_size as f32];
+        payload.extend(obs.iter().flatten());
+
+        self.send(ENV_RESET_STATE_HEADER, &payload)
+    }
+
+    /// Step `env` with the trainer's actions until it sends a stop message or disconnects
+    pub fn run(&mut self, env: &mut dyn RemoteEnv) -> Result<()> {
+        let obs = env.reset()?;
+
+        let obs_spaces = env.obs_spaces();
+        let action_spaces = env.action_spaces();
+        let hash = config_hash(&obs_spaces, &action_spaces);
+        let mut version = vec![PROTOCOL_VERSION as f32];
+        version.extend((0..4).map(|i| ((hash >> (i * 16)) & 0xffff) as f32));
+        self.send(ENV_VERSION_HEADER, &version)?;
+
+        let num_agents = obs.len();
+        let obs_size = obs_spaces.first().copied().unwrap_or_default();
+        let action_size = action_spaces.first().copied().unwrap_or_default();
+        self.send(
+            ENV_SHAPES_HEADER,
+            &[obs_size as f32, action_size as f32, num_agents as f32],
+        )?;
+        self.send_reset_state(&obs)?;
+
+        loop {
+            let max_actions = env.action_spaces().iter().sum();
+            let (header, actions) = match self.recv(max_actions) {
+                Ok(message) => message,
+                // the trainer closing the connection is as good as a stop message
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(e.into()),
+            };
+
+            if header == STOP_MESSAGE_HEADER {
+                return Ok(());
+            }
+
+            if header != POLICY_ACTIONS_HEADER {
+                return Err(Error::Protocol(format!(
+                    "Expected actions, got header {header:?}"
+                )));
+            }
+
+            let num_agents = env.action_spaces().len().max(1);
+            let per_agent = actions.len() / num_agents;
+            let actions = if per_agent == 0 {
+                Vec::new()
+            } else {
+                actions.chunks(per_agent).map(<[f32]>::to_vec).collect()
+            };
+
+            let step = env.step(actions)?;
+
+            let mut payload = vec![
+                step.rewards.len() as f32,
+                f32::from(u8::from(step.is_terminal)),
+                f32::from(u8::from(step.truncated)),
+            ];
+            payload.extend(&step.rewards);
+            payload.extend(step.obs.iter().flatten());
+            self.send(ENV_STEP_DATA_HEADER, &payload)?;
+
+            if step.is_terminal || step.truncated {
+                let obs = env.reset()?;
+                self.send_reset_state(&obs)?;
+            }
+        }
+    }
+}
@@ -0,0 +1,488 @@
+use crate::{
+    remote::FromRemoteActions, Action, AgentId, AgentMap, Error, FullObs, Obs, Result, SpaceType,
+    StepContext,
+};
+use rocketsim_rs::{
+    glam_ext::GameStateA,
+    sim::{CarControls, Team},
+};
+
+fn record_teams(teams: &mut Vec<(AgentId, Team)>, state: &GameStateA) {
+    teams.clear();
+    teams.extend(state.cars.iter().map(|car| (AgentId::of(car), car.team)));
+}
+
+fn is_orange(teams: &[(AgentId, Team)], agent_id: AgentId) -> bool {
+    teams
+        .iter()
+        .any(|(id, team)| *id == agent_id && *team == Team::Orange)
+}
+
+/// Builds the observations of blue cars with `blue` and of orange cars with `orange`.
+///
+/// The team of an agent is only known after the first reset,
+/// until then `get_obs_space` reports the blue builder's space
+pub struct TeamObs<B, O> {
+    pub blue: B,
+    pub orange: O,
+    teams: Vec<(AgentId, Team)>,
+}
+
+impl<B, O> TeamObs<B, O> {
+    pub fn new(blue: B, orange: O) -> Self {
+        Self {
+            blue,
+            orange,
+            teams: Vec::new(),
+        }
+    }
+}
+
+impl<SI, B: Obs<SI>, O: Obs<SI>> Obs<SI> for TeamObs<B, O> {
+    fn get_obs_space(&self, agent_id: AgentId, shared_info: &SI) -> usize {
+        if is_orange(&self.teams, agent_id) {
+            self.orange.get_obs_space(agent_id, shared_info)
+        } else {
+            self.blue.get_obs_space(agent_id, shared_info)
+        }
+    }
+
+    fn get_obs_space_type(&self, agent_id: AgentId, shared_info: &SI) -> SpaceType {
+        if is_orange(&self.teams, agent_id) {
+            self.orange.get_obs_space_type(agent_id, shared_info)
+        } else {
+            self.blue.get_obs_space_type(agent_id, shared_info)
+        }
+    }
+
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        record_teams(&mut self.teams, initial_state);
+        self.blue.reset(initial_state, shared_info);
+        self.orange.reset(initial_state, shared_info);
+    }
+
+    fn build_obs(&mut self, ctx: &StepContext, shared_info: &mut SI) -> FullObs {
+        record_teams(&mut self.teams, ctx.state);
+        let blue = self.blue.build_obs(ctx, shared_info);
+        let orange = self.orange.build_obs(ctx, shared_info);
+
+        ctx.state
+            .cars
+            .iter()
+            .zip(blue.into_iter().zip(orange))
+            .map(|(car, (blue, orange))| if car.team == Team::Blue { blue } else { orange })
+            .collect()
+    }
+
+    fn validate(&self, agents: &AgentMap, shared_info: &SI) -> Result<()> {
+        self.blue.validate(agents, shared_info)?;
+        self.orange.validate(agents, shared_info)
+    }
+}
+
+/// Which of the components of a `Routes` an agent uses
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Route {
+    Default,
+    Agent(usize),
+    Team(usize),
+}
+
+/// A component for each agent: the one registered for it, then the one for its team, and otherwise `default`
+struct Routes<T> {
+    default: T,
+    agents: Vec<(AgentId, T)>,
+    team_routes: Vec<(Team, T)>,
+    teams: Vec<(AgentId, Team)>,
+}
+
+impl<T> Routes<T> {
+    fn new(default: T) -> Self {
+        Self {
+            default,
+            agents: Vec::new(),
+            team_routes: Vec::new(),
+            teams: Vec::new(),
+        }
+    }
+
+    fn set_agent(&mut self, agent_id: AgentId, component: T) {
+        self.agents.retain(|(id, _)| *id != agent_id);
+        self.agents.push((agent_id, component));
+    }
+
+    fn set_team(&mut self, team: Team, component: T) {
+        self.team_routes
+            .retain(|(route_team, _)| *route_team != team);
+        self.team_routes.push((team, component));
+    }
+
+    /// The route of `agent_id`, with the team it had in the last recorded state if `team` isn't given
+    fn route(&self, agent_id: AgentId, team: Option<Team>) -> Route {
+        if let Some(index) = self.agents.iter().position(|(id, _)| *id == agent_id) {
+            return Route::Agent(index);
+        }
+
+        let team = team.or_else(|| {
+            self.teams
+                .iter()
+                .find(|(id, _)| *id == agent_id)
+                .map(|(_, team)| *team)
+        });
+        team.and_then(|team| {
+            self.team_routes
+                .iter()
+                .position(|(route_team, _)| *route_team == team)
+        })
+        .map_or(Route::Default, Route::Team)
+    }
+
+    /// The route of every car in `state`, in car order
+    fn routes(&self, state: &GameStateA) -> Vec<Route> {
+        state
+            .cars
+            .iter()
+            .map(|car| self.route(AgentId::of(car), Some(car.team)))
+            .collect()
+    }
+
+    fn get(&self, route: Route) -> &T {
+        match route {
+            Route::Default => &self.default,
+            Route::Agent(index) => &self.agents[index].1,
+            Route::Team(index) => &self.team_routes[index].1,
+        }
+    }
+
+    fn get_mut(&mut self, route: Route) -> &mut T {
+        match route {
+            Route::Default => &mut self.default,
+            Route::Agent(index) => &mut self.agents[index].1,
+            Route::Team(index) => &mut self.team_routes[index].1,
+        }
+    }
+
+    fn of(&self, agent_id: AgentId) -> &T {
+        self.get(self.route(agent_id, None))
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &T> {
+        std::iter::once(&self.default)
+            .chain(self.agents.iter().map(|(_, component)| component))
+            .chain(self.team_routes.iter().map(|(_, component)| component))
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        std::iter::once(&mut self.default)
+            .chain(self.agents.iter_mut().map(|(_, component)| component))
+            .chain(self.team_routes.iter_mut().map(|(_, component)| component))
+    }
+}
+
+/// Builds each agent's observations with the builder registered for it, then the one for its team,
+/// and otherwise `default`, e.g. to give a scripted bot different inputs than the learner.
+///
+/// Like `TeamObs`, an agent's team is only known after the first reset
+pub struct MultiObs<SI> {
+    routes: Routes<Box<dyn Obs<SI>>>,
+}
+
+impl<SI> MultiObs<SI> {
+    pub fn new(default: Box<dyn Obs<SI>>) -> Self {
+        Self {
+            routes: Routes::new(default),
+        }
+    }
+
+    /// Build the observations of `agent_id` with `obs`, replacing any builder registered for it before
+    pub fn with_agent(mut self, agent_id: AgentId, obs: Box<dyn Obs<SI>>) -> Self {
+        self.routes.set_agent(agent_id, obs);
+        self
+    }
+
+    /// Build the observations of every car on `team` with `obs`, unless one is registered for the agent itself
+    pub fn with_team(mut self, team: Team, obs: Box<dyn Obs<SI>>) -> Self {
+        self.routes.set_team(team, obs);
+        self
+    }
+}
+
+impl<SI> Obs<SI> for MultiObs<SI> {
+    fn get_obs_space(&self, agent_id: AgentId, shared_info: &SI) -> usize {
+        self.routes
+            .of(agent_id)
+            .get_obs_space(agent_id, shared_info)
+    }
+
+    fn get_obs_space_type(&self, agent_id: AgentId, shared_info: &SI) -> SpaceType {
+        self.routes
+            .of(agent_id)
+            .get_obs_space_type(agent_id, shared_info)
+    }
+
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        record_teams(&mut self.routes.teams, initial_state);
+        for obs in self.routes.iter_mut() {
+            obs.reset(initial_state, shared_info);
+        }
+    }
+
+    fn build_obs(&mut self, ctx: &StepContext, shared_info: &mut SI) -> FullObs {
+        record_teams(&mut self.routes.teams, ctx.state);
+        let routes = self.routes.routes(ctx.state);
+
+        // every builder that a car uses builds the observations of all cars, only the rows of its cars are kept
+        let mut obs = vec![Vec::new(); routes.len()];
+        let mut built = Vec::new();
+        for route in &routes {
+            if built.contains(route) {
+                continue;
+            }
+            built.push(*route);
+
+            let rows = self.routes.get_mut(*route).build_obs(ctx, shared_info);
+            for ((row, obs), car_route) in rows.into_iter().zip(&mut obs).zip(&routes) {
+                if car_route == route {
+                    *obs = row;
+                }
+            }
+        }
+
+        obs
+    }
+
+    fn validate(&self, agents: &AgentMap, shared_info: &SI) -> Result<()> {
+        for obs in self.routes.iter() {
+            obs.validate(agents, shared_info)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An `Action` whose input is one agent's raw values, so parsers with different inputs can be boxed together
+trait DynAction<SI> {
+    fn get_action_space(&self, agent_id: AgentId, shared_info: &SI) -> usize;
+    fn get_action_space_type(&self, agent_id: AgentId, shared_info: &SI) -> SpaceType;
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI);
+    fn get_action_mask(&self, agent_id: AgentId, state: &GameStateA, shared_info: &SI)
+        -> Vec<bool>;
+    fn parse_actions(
+        &mut self,
+        actions: Vec<Vec<f32>>,
+        state: &GameStateA,
+        shared_info: &mut SI,
+    ) -> Vec<CarControls>;
+    fn validate(&self, agents: &AgentMap, shared_info: &SI) -> Result<()>;
+}
+
+impl<SI, A> DynAction<SI> for A
+where
+    A: Action<SI>,
+    A::Input: FromRemoteActions,
+{
+    fn get_action_space(&self, agent_id: AgentId, shared_info: &SI) -> usize {
+        Action::get_action_space(self, agent_id, shared_info)
+    }
+
+    fn get_action_space_type(&self, agent_id: AgentId, shared_info: &SI) -> SpaceType {
+        Action::get_action_space_type(self, agent_id, shared_info)
+    }
+
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        Action::reset(self, initial_state, shared_info);
+    }
+
+    fn get_action_mask(
+        &self,
+        agent_id: AgentId,
+        state: &GameStateA,
+        shared_info: &SI,
+    ) -> Vec<bool> {
+        Action::get_action_mask(self, agent_id, state, shared_info)
+    }
+
+    fn parse_actions(
+        &mut self,
+        actions: Vec<Vec<f32>>,
+        state: &GameStateA,
+        shared_info: &mut SI,
+    ) -> Vec<CarControls> {
+        Action::parse_actions(self, A::Input::from_remote(actions), state, shared_info)
+    }
+
+    fn validate(&self, agents: &AgentMap, shared_info: &SI) -> Result<()> {
+        Action::validate(self, agents, shared_info)
+    }
+}
+
+/// Parses each agent's actions with the parser registered for it, then the one for its team,
+/// and otherwise `default`, e.g. continuous actions for the learner and a lookup table for a frozen opponent.
+///
+/// The input is each agent's raw values keyed by its id, in the format of `remote::FromRemoteActions`,
+/// so cars driven by a policy or on a team driven by `Env::set_team_policy` are simply left out.
+/// The parsed controls come out in car order.
+/// Every parser must step `TICK_SKIP` ticks, which is checked when it's registered
+pub struct MultiAction<SI, const TICK_SKIP: u32 = 8> {
+    routes: Routes<Box<dyn DynAction<SI>>>,
+}
+
+impl<SI, const TICK_SKIP: u32> MultiAction<SI, TICK_SKIP> {
+    pub fn new<A>(default: A) -> Result<Self>
+    where
+        A: Action<SI> + 'static,
+        A::Input: FromRemoteActions,
+    {
+        Ok(Self {
+            routes: Routes::new(Self::boxed(default)?),
+        })
+    }
+
+    /// Parse the actions of blue cars with `blue` and of orange cars with `orange`
+    pub fn from_teams<B, O>(blue: B, orange: O) -> Result<Self>
+    where
+        B: Action<SI> + 'static,
+        B::Input: FromRemoteActions,
+        O: Action<SI> + 'static,
+        O::Input: FromRemoteActions,
+    {
+        Self::new(blue)?.with_team(Team::Orange, orange)
+    }
+
+    /// Parse the actions of `agent_id` with `action`, replacing any parser registered for it before
+    pub fn with_agent<A>(mut self, agent_id: AgentId, action: A) -> Result<Self>
+    where
+        A: Action<SI> + 'static,
+        A::Input: FromRemoteActions,
+    {
+        self.routes.set_agent(agent_id, Self::boxed(action)?);
+        Ok(self)
+    }
+
+    /// Parse the actions of every car on `team` with `action`, unless one is registered for the agent itself
+    pub fn with_team<A>(mut self, team: Team, action: A) -> Result<Self>
+    where
+        A: Action<SI> + 'static,
+        A::Input: FromRemoteActions,
+    {
+        self.routes.set_team(team, Self::boxed(action)?);
+        Ok(self)
+    }
+
+    fn boxed<A>(action: A) -> Result<Box<dyn DynAction<SI>>>
+    where
+        A: Action<SI> + 'static,
+        A::Input: FromRemoteActions,
+    {
+        if A::get_tick_skip() != TICK_SKIP {
+            return Err(Error::Invalid(format!(
+                "An action parser with a tick skip of {} can't be mixed with ones of {TICK_SKIP}",
+                A::get_tick_skip()
+            )));
+        }
+
+        Ok(Box::new(action))
+    }
+}
+
+impl<SI, const TICK_SKIP: u32> Action<SI> for MultiAction<SI, TICK_SKIP> {
+    type Input = Vec<(AgentId, Vec<f32>)>;
+
+    fn get_tick_skip() -> u32 {
+        TICK_SKIP
+    }
+
+    fn get_action_space(&self, agent_id: AgentId, shared_info: &SI) -> usize {
+        self.routes
+            .of(agent_id)
+            .get_action_space(agent_id, shared_info)
+    }
+
+    fn get_action_space_type(&self, agent_id: AgentId, shared_info: &SI) -> SpaceType {
+        self.routes
+            .of(agent_id)
+            .get_action_space_type(agent_id, shared_info)
+    }
+
+    fn reset(&mut self, initial_state: &GameStateA, shared_info: &mut SI) {
+        record_teams(&mut self.routes.teams, initial_state);
+        for action in self.routes.iter_mut() {
+            action.reset(initial_state, shared_info);
+        }
+    }
+
+    fn get_action_mask(
+        &self,
+        agent_id: AgentId,
+        state: &GameStateA,
+        shared_info: &SI,
+    ) -> Vec<bool> {
+        self.routes
+            .of(agent_id)
+            .get_action_mask(agent_id, state, shared_info)
+    }
+
+    fn parse_actions(
+        &mut self,
+        mut actions: Self::Input,
+        state: &GameStateA,
+        shared_info: &mut SI,
+    ) -> Vec<CarControls> {
+        let index_of = |agent_id: AgentId| {
+            state
+                .cars
+                .iter()
+                .position(|car| AgentId::of(car) == agent_id)
+        };
+        actions.retain(|(agent_id, _)| {
+            let known = index_of(*agent_id).is_some();
+            if !known {
+                log::warn!("Agent {agent_id} got an action, but it isn't in the arena");
+            }
+            known
+        });
+        actions.sort_by_key(|(agent_id, _)| index_of(*agent_id));
+
+        // each parser parses the actions of its agents at once, then they're put back in car order
+        let routes = actions
+            .iter()
+            .map(|(agent_id, _)| {
+                let team = index_of(*agent_id).map(|index| state.cars[index].team);
+                self.routes.route(*agent_id, team)
+            })
+            .collect::<Vec<_>>();
+        let mut controls = vec![CarControls::default(); actions.len()];
+        let mut parsed = Vec::new();
+        for route in &routes {
+            if parsed.contains(route) {
+                continue;
+            }
+            parsed.push(*route);
+
+            let (indices, rows): (Vec<_>, Vec<_>) = actions
+                .iter()
+                .zip(&routes)
+                .enumerate()
+                .filter(|(_, (_, action_route))| *action_route == route)
+                .map(|(index, ((_, row), _))| (index, row.clone()))
+                .unzip();
+            let route_controls =
+                self.routes
+                    .get_mut(*route)
+                    .parse_actions(rows, state, shared_info);
+            for (index, car_controls) in indices.into_iter().zip(route_controls) {
+                controls[index] = car_controls;
+            }
+        }
+
+        controls
+    }
+
+    fn validate(&self, agents: &AgentMap, shared_info: &SI) -> Result<()> {
+        for action in self.routes.iter() {
+            action.validate(agents, shared_info)?;
+        }
+
+        Ok(())
+    }
+}
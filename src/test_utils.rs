@@ -0,0 +1,167 @@
+use crate::{AgentId, FullObs, Obs, Reward, SharedInfoProvider, StepContext, Terminal, Truncate};
+use glam::Vec3A;
+use rocketsim_rs::{
+    glam_ext::{CarInfoA, GameStateA},
+    sim::{CarConfig, Team},
+};
+
+/// Builds a `GameStateA` by hand, for feeding to components without a RocketSim arena.
+///
+/// Cars get the ids 1, 2, 3... in the order they're added, like they would in an arena
+#[derive(Clone, Default)]
+pub struct StateBuilder {
+    state: GameStateA,
+}
+
+impl StateBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tick_count(mut self, tick_count: u64) -> Self {
+        self.state.tick_count = tick_count;
+        self
+    }
+
+    pub fn with_tick_rate(mut self, tick_rate: f32) -> Self {
+        self.state.tick_rate = tick_rate;
+        self
+    }
+
+    pub fn with_ball(mut self, pos: Vec3A, vel: Vec3A) -> Self {
+        self.state.ball.pos = pos;
+        self.state.ball.vel = vel;
+        self
+    }
+
+    /// Add an octane at `pos` that's standing still
+    pub fn with_car(self, team: Team, pos: Vec3A) -> Self {
+        self.with_car_info(team, |car| car.state.pos = pos)
+    }
+
+    /// Add an octane and change anything about it in `edit`, the id and team are set before it runs
+    pub fn with_car_info(mut self, team: Team, edit: impl FnOnce(&mut CarInfoA)) -> Self {
+        let mut car = CarInfoA {
+            id: self.state.cars.len() as u32 + 1,
+            team,
+            config: *CarConfig::octane(),
+            ..Default::default()
+        };
+        edit(&mut car);
+
+        self.state.cars.push(car);
+        self
+    }
+
+    pub fn build(self) -> GameStateA {
+        self.state
+    }
+}
+
+/// A shared info provider that leaves the shared info alone
+pub struct NoSharedInfo;
+
+impl<SI> SharedInfoProvider<SI> for NoSharedInfo {
+    fn reset(&mut self, _initial_state: &GameStateA, _shared_info: &mut SI) {}
+    fn apply(&mut self, _game_state: &GameStateA, _shared_info: &mut SI) {}
+}
+
+/// Runs components over a fixed sequence of states in the same order `Env` would,
+/// so they can be tested in microseconds without initializing RocketSim.
+///
+/// The first state is the one the episode starts from, every state after it is the result of a step
+pub struct ScriptedEpisode<SIP, SI> {
+    states: Vec<GameStateA>,
+    shared_info_provider: SIP,
+    pub shared_info: SI,
+}
+
+impl<SIP: SharedInfoProvider<SI>, SI> ScriptedEpisode<SIP, SI> {
+    /// Panics if `states` is empty
+    pub fn new(states: Vec<GameStateA>, shared_info_provider: SIP, shared_info: SI) -> Self {
+        assert!(
+            !states.is_empty(),
+            "A scripted episode needs at least an initial state"
+        );
+
+        Self {
+            states,
+            shared_info_provider,
+            shared_info,
+        }
+    }
+
+    pub fn states(&self) -> &[GameStateA] {
+        &self.states
+    }
+
+    /// Reset the shared info and `component` on the initial state,
+    /// then call `f` with the context of every step to a following state
+    fn run<C: ?Sized, T>(
+        &mut self,
+        component: &mut C,
+        reset: impl FnOnce(&mut C, &GameStateA, &mut SI),
+        mut f: impl FnMut(&mut C, &StepContext, &mut SI) -> T,
+    ) -> Vec<T> {
+        let Self {
+            states,
+            shared_info_provider,
+            shared_info,
+        } = self;
+
+        shared_info_provider.reset(&states[0], shared_info);
+        reset(component, &states[0], shared_info);
+        let start_tick = states[0].tick_count;
+
+        states
+            .windows(2)
+            .map(|states| {
+                let (previous, state) = (&states[0], &states[1]);
+                // the controls each car stepped with are all a script has
+                let actions = state
+                    .cars
+                    .iter()
+                    .map(|car| (AgentId::of(car), car.state.last_controls))
+                    .collect::<Vec<_>>();
+                shared_info_provider.pre_step(&actions, previous, shared_info);
+                shared_info_provider.apply(state, shared_info);
+
+                let ctx = StepContext::new(state)
+                    .with_previous_state(previous)
+                    .with_actions(&actions)
+                    .with_episode_ticks(state.tick_count.saturating_sub(start_tick));
+                f(component, &ctx, shared_info)
+            })
+            .collect()
+    }
+
+    /// The observations of every state, including the initial one
+    pub fn obs<OBS: Obs<SI>>(&mut self, obs: &mut OBS) -> Vec<FullObs> {
+        let mut initial_obs = None;
+        let steps = self.run(
+            obs,
+            |obs, state, shared_info| {
+                obs.reset(state, shared_info);
+                initial_obs = Some(obs.build_obs(&StepContext::new(state), shared_info));
+            },
+            OBS::build_obs,
+        );
+
+        initial_obs.into_iter().chain(steps).collect()
+    }
+
+    /// The rewards of every state after the initial one
+    pub fn rewards<R, REW: Reward<SI, R>>(&mut self, reward: &mut REW) -> Vec<Vec<R>> {
+        self.run(reward, REW::reset, REW::get_rewards)
+    }
+
+    /// Whether every state after the initial one is terminal
+    pub fn terminals<TERM: Terminal<SI>>(&mut self, terminal: &mut TERM) -> Vec<bool> {
+        self.run(terminal, TERM::reset, TERM::is_terminal)
+    }
+
+    /// Whether every state after the initial one should truncate the episode
+    pub fn truncations<TRUNC: Truncate<SI>>(&mut self, truncate: &mut TRUNC) -> Vec<bool> {
+        self.run(truncate, TRUNC::reset, TRUNC::should_truncate)
+    }
+}
@@ -0,0 +1,272 @@
+use crate::{
+    game_modes::scoring_team,
+    rating::{MatchOutcome, RatingSystem},
+    share_policy, Action, Env, Obs, Policy, Result, Reward, SharedInfoProvider, SharedPolicy,
+    StateSetter, Terminal, Truncate,
+};
+use rocketsim_rs::sim::{GameMode, Team};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Schedule {
+    /// Every entrant plays every other entrant `rounds` times, switching sides each time
+    RoundRobin { rounds: usize },
+    /// Each round, entrants with similar scores are paired up, avoiding rematches where possible
+    Swiss { rounds: usize },
+}
+
+struct Entrant<SI, R> {
+    name: String,
+    policy: SharedPolicy<dyn Policy<SI>>,
+    rating: R,
+    wins: u32,
+    losses: u32,
+    draws: u32,
+    opponents: Vec<usize>,
+}
+
+impl<SI, R> Entrant<SI, R> {
+    fn points(&self) -> f32 {
+        self.wins as f32 + self.draws as f32 * 0.5
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Standing<R> {
+    pub name: String,
+    pub rating: R,
+    /// The rating as a single number, as given by `RatingSystem::skill`
+    pub skill: f32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+/// Plays matches between a set of policies and rates them from the results,
+/// e.g. to pick the best checkpoint or compare ablations
+pub struct Tournament<SI, RS: RatingSystem> {
+    rating_system: RS,
+    entrants: Vec<Entrant<SI, RS::Rating>>,
+}
+
+impl<SI: 'static, RS: RatingSystem> Tournament<SI, RS> {
+    pub fn new(rating_system: RS) -> Self {
+        Self {
+            rating_system,
+            entrants: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, policy: impl Policy<SI> + 'static) {
+        self.entrants.push(Entrant {
+            name: name.into(),
+            policy: share_policy(policy),
+            rating: self.rating_system.initial_rating(),
+            wins: 0,
+            losses: 0,
+            draws: 0,
+            opponents: Vec::new(),
+        });
+    }
+
+    /// Play every match in `schedule`.
+    ///
+    /// `play` is given the blue and orange policies and returns the outcome from blue's point of view,
+    /// `play_match` can be used to play it out in an `Env`
+    pub fn run(
+        &mut self,
+        schedule: Schedule,
+        mut play: impl FnMut(Box<dyn Policy<SI>>, Box<dyn Policy<SI>>) -> MatchOutcome,
+    ) {
+        match schedule {
+            Schedule::RoundRobin { rounds } => {
+                for round in 0..rounds {
+                    for i in 0..self.entrants.len() {
+                        for j in i + 1..self.entrants.len() {
+                            let (blue, orange) = if round % 2 == 0 { (i, j) } else { (j, i) };
+                            self.play_one(blue, orange, &mut play);
+                        }
+                    }
+                }
+            }
+            Schedule::Swiss { rounds } => {
+                for round in 0..rounds {
+                    for (i, j) in self.swiss_pairings() {
+                        let (blue, orange) = if round % 2 == 0 { (i, j) } else { (j, i) };
+                        self.play_one(blue, orange, &mut play);
+                    }
+                }
+            }
+        }
+    }
+
+    fn play_one(
+        &mut self,
+        blue: usize,
+        orange: usize,
+        play: &mut impl FnMut(Box<dyn Policy<SI>>, Box<dyn Policy<SI>>) -> MatchOutcome,
+    ) {
+        let outcome = play(
+            Box::new(self.entrants[blue].policy.clone()),
+            Box::new(self.entrants[orange].policy.clone()),
+        );
+
+        let (low, high) = self.entrants.split_at_mut(blue.max(orange));
+        let (blue_entrant, orange_entrant) = if blue < orange {
+            (&mut low[blue], &mut high[0])
+        } else {
+            (&mut high[0], &mut low[orange])
+        };
+
+        self.rating_system.update(
+            &mut blue_entrant.rating,
+            &mut orange_entrant.rating,
+            outcome,
+        );
+
+        for (entrant, outcome) in [
+            (&mut *blue_entrant, outcome),
+            (&mut *orange_entrant, outcome.reversed()),
+        ] {
+            match outcome {
+                MatchOutcome::Win => entrant.wins += 1,
+                MatchOutcome::Loss => entrant.losses += 1,
+                MatchOutcome::Draw => entrant.draws += 1,
+            }
+        }
+
+        blue_entrant.opponents.push(orange);
+        orange_entrant.opponents.push(blue);
+    }
+
+    /// Pair entrants with similar points, the lowest ranked entrant sits out if there's an odd number
+    fn swiss_pairings(&self) -> Vec<(usize, usize)> {
+        let mut ranked = (0..self.entrants.len()).collect::<Vec<_>>();
+        ranked.sort_by(|&a, &b| {
+            let (a, b) = (&self.entrants[a], &self.entrants[b]);
+            b.points().total_cmp(&a.points()).then_with(|| {
+                self.rating_system
+                    .skill(&b.rating)
+                    .total_cmp(&self.rating_system.skill(&a.rating))
+            })
+        });
+
+        let mut pairs = Vec::with_capacity(ranked.len() / 2);
+        while ranked.len() > 1 {
+            let first = ranked.remove(0);
+            let opponent = ranked
+                .iter()
+                .position(|other| !self.entrants[first].opponents.contains(other))
+                .unwrap_or(0);
+            pairs.push((first, ranked.remove(opponent)));
+        }
+
+        pairs
+    }
+
+    /// Every entrant sorted from the highest to the lowest skill
+    pub fn leaderboard(&self) -> Vec<Standing<RS::Rating>> {
+        let mut standings = self
+            .entrants
+            .iter()
+            .map(|entrant| Standing {
+                name: entrant.name.clone(),
+                rating: entrant.rating.clone(),
+                skill: self.rating_system.skill(&entrant.rating),
+                wins: entrant.wins,
+                losses: entrant.losses,
+                draws: entrant.draws,
+            })
+            .collect::<Vec<_>>();
+
+        standings.sort_by(|a, b| b.skill.total_cmp(&a.skill));
+        standings
+    }
+}
+
+/// Play one episode in `env` with `blue` driving every blue car and `orange` every orange car.
+///
+/// The episode ends when the env's terminal or truncate condition triggers or after `max_steps`,
+/// whichever team scored more goals wins.
+/// Goals are detected from the ball's position in `game_mode`, so the terminal condition should end the episode
+/// on a goal instead of the arena resetting to kickoff itself.
+///
+/// Returns the outcome from blue's point of view
+pub fn play_match<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI>(
+    env: &mut Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI>,
+    blue: Box<dyn Policy<SI>>,
+    orange: Box<dyn Policy<SI>>,
+    game_mode: GameMode,
+    max_steps: usize,
+) -> Result<MatchOutcome>
+where
+    SS: StateSetter<SI>,
+    SIP: SharedInfoProvider<SI>,
+    OBS: Obs<SI>,
+    ACT: Action<SI>,
+    ACT::Input: Default,
+    REW: Reward<SI>,
+    TERM: Terminal<SI>,
+    TRUNC: Truncate<SI>,
+{
+    let old_blue = env.remove_team_policy(Team::Blue);
+    let old_orange = env.remove_team_policy(Team::Orange);
+    env.set_team_policy(Team::Blue, blue);
+    env.set_team_policy(Team::Orange, orange);
+
+    let goals = play_episode(env, game_mode, max_steps);
+
+    env.remove_team_policy(Team::Blue);
+    env.remove_team_policy(Team::Orange);
+    if let Some(policy) = old_blue {
+        env.set_team_policy(Team::Blue, policy);
+    }
+    if let Some(policy) = old_orange {
+        env.set_team_policy(Team::Orange, policy);
+    }
+
+    let (blue_goals, orange_goals) = goals?;
+    Ok(MatchOutcome::from_goals(blue_goals, orange_goals))
+}
+
+/// Returns the blue and orange goals
+fn play_episode<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI>(
+    env: &mut Env<SS, SIP, OBS, ACT, REW, TERM, TRUNC, SI>,
+    game_mode: GameMode,
+    max_steps: usize,
+) -> Result<(u32, u32)>
+where
+    SS: StateSetter<SI>,
+    SIP: SharedInfoProvider<SI>,
+    OBS: Obs<SI>,
+    ACT: Action<SI>,
+    ACT::Input: Default,
+    REW: Reward<SI>,
+    TERM: Terminal<SI>,
+    TRUNC: Truncate<SI>,
+{
+    env.reset()?;
+
+    let mut blue_goals = 0;
+    let mut orange_goals = 0;
+    let mut was_scored = false;
+
+    for _ in 0..max_steps {
+        let result = env.step(ACT::Input::default())?;
+
+        let scorer = scoring_team(game_mode, &result.state);
+        if let (Some(team), false) = (scorer, was_scored) {
+            if team == Team::Blue {
+                blue_goals += 1;
+            } else {
+                orange_goals += 1;
+            }
+        }
+        was_scored = scorer.is_some();
+
+        if result.is_terminal || result.truncated {
+            break;
+        }
+    }
+
+    Ok((blue_goals, orange_goals))
+}
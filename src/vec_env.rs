@@ -0,0 +1,559 @@
+use crate::{
+    remote::{RemoteEnv, RemoteStep},
+    Error, FullObs, Result,
+};
+use std::{
+    sync::{
+        mpsc::{channel, Receiver, Sender, TryRecvError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+enum Command {
+    Reset,
+    Step(Vec<Vec<f32>>),
+}
+
+enum Reply {
+    Ready {
+        obs_spaces: Vec<usize>,
+        action_spaces: Vec<usize>,
+    },
+    Env(EnvReply),
+}
+
+/// What an env on its own thread sends back for each command
+#[derive(Clone, Debug)]
+pub enum EnvReply {
+    Reset(FullObs),
+    /// Envs whose episode ended are reset right away, so `step.obs` is the first obs of the next episode
+    /// and `final_obs` the last one of the episode that ended
+    Step {
+        step: RemoteStep,
+        final_obs: Option<FullObs>,
+    },
+}
+
+struct Worker {
+    commands: Option<Sender<Command>>,
+    replies: Receiver<Result<Reply>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    fn spawn<E, F>(make_env: F) -> Self
+    where
+        E: RemoteEnv,
+        F: FnOnce() -> Result<E> + Send + 'static,
+    {
+        let (commands, command_rx) = channel();
+        let (reply_tx, replies) = channel();
+
+        let thread = thread::spawn(move || {
+            let mut env = match make_env() {
+                Ok(env) => env,
+                Err(e) => {
+                    let _ = reply_tx.send(Err(e));
+                    return;
+                }
+            };
+
+            let ready = Reply::Ready {
+                obs_spaces: env.obs_spaces(),
+                action_spaces: env.action_spaces(),
+            };
+            if reply_tx.send(Ok(ready)).is_err() {
+                return;
+            }
+
+            for command in command_rx {
+                let reply = match command {
+                    Command::Reset => env.reset().map(EnvReply::Reset),
+                    Command::Step(actions) => step_and_reset(&mut env, actions),
+                };
+
+                if reply_tx.send(reply.map(Reply::Env)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self {
+            commands: Some(commands),
+            replies,
+            thread: Some(thread),
+        }
+    }
+
+    fn send(&self, index: usize, command: Command) -> Result<()> {
+        self.commands
+            .as_ref()
+            .and_then(|commands| commands.send(command).ok())
+            .ok_or_else(|| stopped(index))
+    }
+
+    fn recv(&self, index: usize) -> Result<Reply> {
+        self.replies.recv().map_err(|_| stopped(index))?
+    }
+
+    fn try_recv(&self, index: usize) -> Option<Result<Reply>> {
+        match self.replies.try_recv() {
+            Ok(reply) => Some(reply),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(stopped(index))),
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // closing the channel stops the thread
+        self.commands.take();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn stopped(index: usize) -> Error {
+    Error::Protocol(format!("The thread of env {index} stopped"))
+}
+
+fn step_and_reset(env: &mut impl RemoteEnv, actions: Vec<Vec<f32>>) -> Result<EnvReply> {
+    let mut step = env.step(actions)?;
+    let mut final_obs = None;
+
+    if step.is_terminal || step.truncated {
+        let obs = env.reset()?;
+        final_obs = Some(std::mem::replace(&mut step.obs, obs));
+    }
+
+    Ok(EnvReply::Step { step, final_obs })
+}
+
+/// One env running on its own thread, taking commands over a channel and answering them in order.
+///
+/// Sending doesn't wait for the env, so a learner can keep working while it steps
+/// and pick up the reply later with `recv` or `try_recv`
+pub struct EnvWorker {
+    index: usize,
+    worker: Worker,
+    in_flight: usize,
+    obs_spaces: Vec<usize>,
+    action_spaces: Vec<usize>,
+}
+
+impl EnvWorker {
+    /// Start an env built by `make_env` on a new thread, waiting until it's built.
+    ///
+    /// `index` only shows up in errors, to tell workers apart
+    pub fn spawn<E, F>(index: usize, make_env: F) -> Result<Self>
+    where
+        E: RemoteEnv,
+        F: FnOnce() -> Result<E> + Send + 'static,
+    {
+        Self::ready(index, Worker::spawn(make_env))
+    }
+
+    fn ready(index: usize, worker: Worker) -> Result<Self> {
+        let Reply::Ready {
+            obs_spaces,
+            action_spaces,
+        } = worker.recv(index)?
+        else {
+            unreachable!("Workers are ready before they get any commands");
+        };
+
+        Ok(Self {
+            index,
+            worker,
+            in_flight: 0,
+            obs_spaces,
+            action_spaces,
+        })
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Observation sizes of the env's agents, as of when it was built
+    pub fn obs_spaces(&self) -> &[usize] {
+        &self.obs_spaces
+    }
+
+    /// Action sizes of the env's agents, as of when it was built
+    pub fn action_spaces(&self) -> &[usize] {
+        &self.action_spaces
+    }
+
+    /// How many commands the env hasn't replied to yet
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    pub fn send_reset(&mut self) -> Result<()> {
+        self.send(Command::Reset)
+    }
+
+    pub fn send_step(&mut self, actions: Vec<Vec<f32>>) -> Result<()> {
+        self.send(Command::Step(actions))
+    }
+
+    fn send(&mut self, command: Command) -> Result<()> {
+        self.worker.send(self.index, command)?;
+        self.in_flight += 1;
+        Ok(())
+    }
+
+    /// Wait for the reply to the oldest command that wasn't answered yet
+    pub fn recv(&mut self) -> Result<EnvReply> {
+        let reply = self.worker.recv(self.index);
+        self.in_flight = self.in_flight.saturating_sub(1);
+        Self::env_reply(reply)
+    }
+
+    /// The reply to the oldest command that wasn't answered yet if it's there, without waiting
+    pub fn try_recv(&mut self) -> Option<Result<EnvReply>> {
+        let reply = self.worker.try_recv(self.index)?;
+        self.in_flight = self.in_flight.saturating_sub(1);
+        Some(Self::env_reply(reply))
+    }
+
+    fn env_reply(reply: Result<Reply>) -> Result<EnvReply> {
+        match reply? {
+            Reply::Env(reply) => Ok(reply),
+            Reply::Ready { .. } => unreachable!("Workers are only ready once"),
+        }
+    }
+}
+
+/// Several `EnvWorker`s, to step every env at once with `step_all`
+/// or hand out actions as they're ready and collect the results with `poll`
+pub struct EnvPool {
+    workers: Vec<EnvWorker>,
+}
+
+impl EnvPool {
+    /// Build `num_envs` envs, each on its own thread, failing with the first error from `make_env`.
+    ///
+    /// `make_env` is given the env's index
+    pub fn new<E, F>(num_envs: usize, make_env: F) -> Result<Self>
+    where
+        E: RemoteEnv,
+        F: Fn(usize) -> Result<E> + Send + Sync + 'static,
+    {
+        let make_env = Arc::new(make_env);
+        let workers = (0..num_envs)
+            .map(|index| {
+                let make_env = make_env.clone();
+                Worker::spawn(move || make_env(index))
+            })
+            .collect::<Vec<_>>();
+
+        // wait for every env to be ready only after starting them all, so they're built at the same time
+        let workers = workers
+            .into_iter()
+            .enumerate()
+            .map(|(index, worker)| EnvWorker::ready(index, worker))
+            .collect::<Vec<_>>();
+
+        Ok(Self {
+            workers: workers.into_iter().collect::<Result<_>>()?,
+        })
+    }
+
+    pub fn num_envs(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn workers(&self) -> &[EnvWorker] {
+        &self.workers
+    }
+
+    pub fn worker_mut(&mut self, index: usize) -> Option<&mut EnvWorker> {
+        self.workers.get_mut(index)
+    }
+
+    /// Step env `index` without waiting for it, its reply comes out of `poll`
+    pub fn send_step(&mut self, index: usize, actions: Vec<Vec<f32>>) -> Result<()> {
+        self.workers
+            .get_mut(index)
+            .ok_or_else(|| Error::Invalid(format!("There's no env {index}")))?
+            .send_step(actions)
+    }
+
+    /// Every reply that's ready, without waiting, along with the index of the env that sent it
+    pub fn poll(&mut self) -> Vec<(usize, Result<EnvReply>)> {
+        self.workers
+            .iter_mut()
+            .filter(|worker| worker.in_flight() > 0)
+            .filter_map(|worker| Some((worker.index(), worker.try_recv()?)))
+            .collect()
+    }
+
+    /// Wait for a reply from every env before returning the first error,
+    /// so no reply is left over for the next command
+    fn gather(&mut self) -> Result<Vec<EnvReply>> {
+        let replies = self
+            .workers
+            .iter_mut()
+            .map(EnvWorker::recv)
+            .collect::<Vec<_>>();
+
+        replies.into_iter().collect()
+    }
+
+    /// Reset every env and return their observations
+    pub fn reset_all(&mut self) -> Result<Vec<FullObs>> {
+        self.check_idle()?;
+        for worker in &mut self.workers {
+            worker.send_reset()?;
+        }
+
+        Ok(self
+            .gather()?
+            .into_iter()
+            .map(|reply| match reply {
+                EnvReply::Reset(obs) => obs,
+                EnvReply::Step { .. } => unreachable!("Workers reply to commands in order"),
+            })
+            .collect())
+    }
+
+    /// Step every env with its own actions and wait for all of them, `actions[i]` goes to env `i`.
+    ///
+    /// Returns each env's step and, if its episode ended, the last obs of the episode
+    pub fn step_all(
+        &mut self,
+        actions: Vec<Vec<Vec<f32>>>,
+    ) -> Result<Vec<(RemoteStep, Option<FullObs>)>> {
+        if actions.len() != self.num_envs() {
+            return Err(Error::ActionCount {
+                expected: self.num_envs(),
+                actual: actions.len(),
+            });
+        }
+
+        self.check_idle()?;
+        for (worker, actions) in self.workers.iter_mut().zip(actions) {
+            worker.send_step(actions)?;
+        }
+
+        Ok(self
+            .gather()?
+            .into_iter()
+            .map(|reply| match reply {
+                EnvReply::Step { step, final_obs } => (step, final_obs),
+                EnvReply::Reset(_) => unreachable!("Workers reply to commands in order"),
+            })
+            .collect())
+    }
+
+    /// Replies of earlier `send_step`s would otherwise be mistaken for the ones of every env at once
+    fn check_idle(&self) -> Result<()> {
+        match self.workers.iter().find(|worker| worker.in_flight() > 0) {
+            Some(worker) => Err(Error::Invalid(format!(
+                "Env {} still has replies to collect with poll",
+                worker.index()
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for EnvPool {
+    fn drop(&mut self) {
+        // stop every thread before waiting for any of them
+        for worker in &mut self.workers {
+            worker.worker.commands.take();
+        }
+    }
+}
+
+/// The results of stepping every env once, indexed by env
+#[derive(Clone, Debug, Default)]
+pub struct VecStep {
+    /// Envs whose episode ended are reset right away, so their obs is the first of the next episode
+    pub obs: Vec<FullObs>,
+    pub rewards: Vec<Vec<f32>>,
+    pub is_terminal: Vec<bool>,
+    pub truncated: Vec<bool>,
+    /// The last obs of the episodes that ended, e.g. to bootstrap the value of truncated episodes
+    pub final_obs: Vec<Option<FullObs>>,
+}
+
+/// Every agent of every env flattened into one batch, e.g. for one forward pass of a policy
+/// over envs with different team sizes
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AgentBatch {
+    /// One row per agent, env by env, zero padded or cut to `obs_size`
+    pub obs: Vec<Vec<f32>>,
+    /// How many values of each row are real, the rest is padding
+    pub obs_lens: Vec<usize>,
+    /// The env each row comes from
+    pub env_indices: Vec<usize>,
+    pub obs_size: usize,
+    /// Envs in the batch, including any without agents
+    pub num_envs: usize,
+}
+
+impl AgentBatch {
+    pub fn new(obs: &[FullObs], obs_size: usize) -> Self {
+        let mut batch = Self {
+            obs_size,
+            num_envs: obs.len(),
+            ..Default::default()
+        };
+
+        for (env_index, env_obs) in obs.iter().enumerate() {
+            for row in env_obs {
+                let mut padded = Vec::with_capacity(obs_size);
+                padded.extend(row.iter().take(obs_size));
+                padded.resize(obs_size, 0.);
+
+                batch.obs.push(padded);
+                batch.obs_lens.push(row.len().min(obs_size));
+                batch.env_indices.push(env_index);
+            }
+        }
+
+        batch
+    }
+
+    pub fn len(&self) -> usize {
+        self.obs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.obs.is_empty()
+    }
+
+    /// Split one value per row, e.g. the actions picked from the batch, back into one list per env
+    pub fn split<T>(&self, values: Vec<T>) -> Result<Vec<Vec<T>>> {
+        if values.len() != self.len() {
+            return Err(Error::Invalid(format!(
+                "Expected a value for each of the {} rows, got {}",
+                self.len(),
+                values.len()
+            )));
+        }
+
+        let mut split = (0..self.num_envs).map(|_| Vec::new()).collect::<Vec<_>>();
+        for (env_index, value) in self.env_indices.iter().zip(values) {
+            split[*env_index].push(value);
+        }
+
+        Ok(split)
+    }
+}
+
+/// Owns several independent envs and steps them all at once, each on its own thread.
+///
+/// Envs are only `Send` with the `sync` feature, so to work without it
+/// every env is built on its thread by `make_env`, which is given the env's index.
+/// Envs can differ in team size or state setter, `batch` pads their observations into one batch.
+/// Use an `EnvPool` directly to step the envs without waiting for all of them
+pub struct VecEnv {
+    pool: EnvPool,
+    obs_spaces: Vec<Vec<usize>>,
+    action_spaces: Vec<Vec<usize>>,
+    labels: Vec<String>,
+}
+
+impl VecEnv {
+    /// Build `num_envs` envs, failing with the first error from `make_env`
+    pub fn new<E, F>(num_envs: usize, make_env: F) -> Result<Self>
+    where
+        E: RemoteEnv,
+        F: Fn(usize) -> Result<E> + Send + Sync + 'static,
+    {
+        let pool = EnvPool::new(num_envs, make_env)?;
+        let obs_spaces = pool
+            .workers()
+            .iter()
+            .map(|worker| worker.obs_spaces().to_vec())
+            .collect();
+        let action_spaces = pool
+            .workers()
+            .iter()
+            .map(|worker| worker.action_spaces().to_vec())
+            .collect();
+
+        Ok(Self {
+            labels: vec![String::new(); pool.num_envs()],
+            pool,
+            obs_spaces,
+            action_spaces,
+        })
+    }
+
+    /// Name each env, e.g. `"1v1"` or `"3v3"`, to tell them apart in logs and metrics
+    pub fn with_labels<S: Into<String>>(mut self, labels: impl IntoIterator<Item = S>) -> Self {
+        for (label, new_label) in self.labels.iter_mut().zip(labels) {
+            *label = new_label.into();
+        }
+        self
+    }
+
+    pub fn num_envs(&self) -> usize {
+        self.pool.num_envs()
+    }
+
+    /// The label of env `index`, empty if it wasn't given one
+    pub fn label(&self, index: usize) -> Option<&str> {
+        self.labels.get(index).map(String::as_str)
+    }
+
+    /// How many agents env `index` had when it was built
+    pub fn num_agents(&self, index: usize) -> Option<usize> {
+        self.obs_spaces.get(index).map(Vec::len)
+    }
+
+    /// The biggest observation of any agent in any env, what `batch` pads to
+    pub fn max_obs_size(&self) -> usize {
+        self.obs_spaces
+            .iter()
+            .flatten()
+            .copied()
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Every agent's observation padded to `max_obs_size` in one batch,
+    /// split the actions picked from it with `AgentBatch::split` to step the envs
+    pub fn batch(&self, obs: &[FullObs]) -> AgentBatch {
+        AgentBatch::new(obs, self.max_obs_size())
+    }
+
+    /// Observation sizes of the agents in each env, as of when the env was built
+    pub fn obs_spaces(&self) -> &[Vec<usize>] {
+        &self.obs_spaces
+    }
+
+    /// Action sizes of the agents in each env, as of when the env was built
+    pub fn action_spaces(&self) -> &[Vec<usize>] {
+        &self.action_spaces
+    }
+
+    /// Reset every env and return their observations
+    pub fn reset(&mut self) -> Result<Vec<FullObs>> {
+        self.pool.reset_all()
+    }
+
+    /// Step every env with its own actions, `actions[i]` goes to env `i`
+    pub fn step(&mut self, actions: Vec<Vec<Vec<f32>>>) -> Result<VecStep> {
+        let mut result = VecStep::default();
+        for (step, final_obs) in self.pool.step_all(actions)? {
+            result.obs.push(step.obs);
+            result.rewards.push(step.rewards);
+            result.is_terminal.push(step.is_terminal);
+            result.truncated.push(step.truncated);
+            result.final_obs.push(final_obs);
+        }
+
+        Ok(result)
+    }
+}
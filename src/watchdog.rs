@@ -0,0 +1,253 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// The parts of a step that the watchdog times
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Component {
+    StateSetter,
+    SharedInfoProvider,
+    Obs,
+    Action,
+    Reward,
+    Terminal,
+    Truncate,
+    /// RocketSim stepping the arena
+    Physics,
+}
+
+impl Component {
+    pub const ALL: [Self; 8] = [
+        Self::StateSetter,
+        Self::SharedInfoProvider,
+        Self::Obs,
+        Self::Action,
+        Self::Reward,
+        Self::Terminal,
+        Self::Truncate,
+        Self::Physics,
+    ];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Call durations of one component over the recent window
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ComponentStats {
+    pub calls: u64,
+    /// Calls that took longer than the budget since the watchdog was created
+    pub overruns: u64,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+#[derive(Default)]
+struct Timings {
+    recent: VecDeque<Duration>,
+    calls: u64,
+    overruns: u64,
+    consecutive_overruns: u32,
+    flagged: bool,
+}
+
+/// Times every component call of an `Env` against a budget,
+/// and flags components that go over it several calls in a row.
+///
+/// Register one with `Env::with_watchdog`
+pub struct Watchdog {
+    budget: Duration,
+    max_consecutive_overruns: u32,
+    window: usize,
+    timings: [Timings; Component::ALL.len()],
+}
+
+impl Watchdog {
+    /// `budget` is how long a single call to any component may take
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            max_consecutive_overruns: 10,
+            window: 1000,
+            timings: Default::default(),
+        }
+    }
+
+    /// How many calls in a row have to go over budget before a component is flagged, 10 by default
+    pub fn with_max_consecutive_overruns(mut self, max_consecutive_overruns: u32) -> Self {
+        self.max_consecutive_overruns = max_consecutive_overruns.max(1);
+        self
+    }
+
+    /// How many of the most recent calls the percentiles are computed over, 1000 by default
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window.max(1);
+        self
+    }
+
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    pub fn time<T>(&mut self, component: Component, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(component, start.elapsed());
+        result
+    }
+
+    pub fn record(&mut self, component: Component, duration: Duration) {
+        let timings = &mut self.timings[component.index()];
+
+        if timings.recent.len() == self.window {
+            timings.recent.pop_front();
+        }
+        timings.recent.push_back(duration);
+        timings.calls += 1;
+
+        if duration <= self.budget {
+            timings.consecutive_overruns = 0;
+            return;
+        }
+
+        timings.overruns += 1;
+        timings.consecutive_overruns += 1;
+
+        if timings.consecutive_overruns == self.max_consecutive_overruns {
+            timings.flagged = true;
+            log::warn!(
+                "{component} went over its budget of {:?} {} calls in a row, the last took {duration:?}",
+                self.budget,
+                timings.consecutive_overruns
+            );
+        }
+    }
+
+    /// `None` if the component hasn't been called yet
+    pub fn stats(&self, component: Component) -> Option<ComponentStats> {
+        let timings = &self.timings[component.index()];
+        if timings.recent.is_empty() {
+            return None;
+        }
+
+        let mut recent = Vec::from(timings.recent.clone());
+        recent.sort_unstable();
+        let percentile = |p: usize| recent[(recent.len() - 1) * p / 100];
+
+        Some(ComponentStats {
+            calls: timings.calls,
+            overruns: timings.overruns,
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: percentile(100),
+        })
+    }
+
+    /// Components that went over budget too many calls in a row at some point
+    pub fn flagged(&self) -> impl Iterator<Item = Component> + '_ {
+        Component::ALL
+            .into_iter()
+            .filter(|component| self.timings[component.index()].flagged)
+    }
+
+    /// Log the stats of every component that was called, slowest first
+    pub fn log_report(&self) {
+        let mut stats = Component::ALL
+            .into_iter()
+            .filter_map(|component| Some((component, self.stats(component)?)))
+            .collect::<Vec<_>>();
+        stats.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.p90));
+
+        for (component, stats) in stats {
+            log::info!(
+                "{component}: {} calls, {} over budget, p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+                stats.calls,
+                stats.overruns,
+                stats.p50,
+                stats.p90,
+                stats.p99,
+                stats.max
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn percentiles() {
+        let mut watchdog = Watchdog::new(ms(50));
+        assert_eq!(watchdog.stats(Component::Obs), None);
+
+        watchdog.record(Component::Obs, ms(7));
+        let stats = watchdog.stats(Component::Obs).unwrap();
+        assert_eq!((stats.p50, stats.p99, stats.max), (ms(7), ms(7), ms(7)));
+
+        let mut watchdog = Watchdog::new(ms(50));
+        for millis in (1..=100).rev() {
+            watchdog.record(Component::Obs, ms(millis));
+        }
+        assert_eq!(
+            watchdog.stats(Component::Obs),
+            Some(ComponentStats {
+                calls: 100,
+                overruns: 50,
+                p50: ms(50),
+                p90: ms(90),
+                p99: ms(99),
+                max: ms(100),
+            })
+        );
+        assert_eq!(watchdog.stats(Component::Reward), None);
+    }
+
+    #[test]
+    fn percentiles_of_the_window() {
+        let mut watchdog = Watchdog::new(ms(50)).with_window(10);
+        for millis in 1..=100 {
+            watchdog.record(Component::Physics, ms(millis));
+        }
+
+        let stats = watchdog.stats(Component::Physics).unwrap();
+        assert_eq!(stats.calls, 100);
+        assert_eq!((stats.p50, stats.max), (ms(95), ms(100)));
+    }
+
+    #[test]
+    fn flags_consecutive_overruns() {
+        let mut watchdog = Watchdog::new(ms(10)).with_max_consecutive_overruns(3);
+
+        // exactly on budget is fine, and a call under it starts the count over
+        for millis in [20, 20, 10, 20, 20, 5, 20, 20] {
+            watchdog.record(Component::Reward, ms(millis));
+        }
+        assert_eq!(watchdog.flagged().count(), 0);
+
+        watchdog.record(Component::Reward, ms(20));
+        watchdog.record(Component::Obs, ms(20));
+        assert_eq!(watchdog.flagged().collect::<Vec<_>>(), [Component::Reward]);
+
+        // once flagged, it stays flagged
+        watchdog.record(Component::Reward, ms(1));
+        assert_eq!(watchdog.flagged().collect::<Vec<_>>(), [Component::Reward]);
+        assert_eq!(watchdog.stats(Component::Reward).unwrap().overruns, 7);
+    }
+}
@@ -0,0 +1,408 @@
+use crate::{
+    handshake::{config_hash, Handshake},
+    remote::{read_transition, write_transition, RemoteEnv, Transition},
+    wire::{Reader, Writer},
+    Error, FullObs, Result,
+};
+use std::{
+    io, thread,
+    time::{Duration, Instant},
+};
+
+/// Version of the message layout below, sent in `ControlRequest::Hello`
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// Sent by a worker's REQ socket to the learner's REP socket.
+///
+/// Every message is a single frame: a `u8` tag followed by the fields in order, little-endian.
+/// Strings and lists are prefixed with their length as a `u32`
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControlRequest {
+    /// Tag 0, sent on startup and after every reconnect.
+    /// The learner should `Handshake::check` the handshake and reply with `ControlReply::Stop` if it's rejected
+    Hello {
+        worker_id: String,
+        handshake: Handshake,
+        obs_spaces: Vec<u32>,
+        action_spaces: Vec<u32>,
+    },
+    /// Tag 1
+    Heartbeat { worker_id: String, steps: u64 },
+    /// Tag 2, the learner must reply with `ControlReply::Actions` or `ControlReply::Stop`
+    Act { worker_id: String, obs: FullObs },
+}
+
+impl ControlRequest {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        match self {
+            Self::Hello {
+                worker_id,
+                handshake,
+                obs_spaces,
+                action_spaces,
+            } => {
+                writer.u8(0).str(worker_id);
+                handshake.write(&mut writer);
+                for spaces in [obs_spaces, action_spaces] {
+                    writer.u32(spaces.len() as u32);
+                    for space in spaces {
+                        writer.u32(*space);
+                    }
+                }
+            }
+            Self::Heartbeat { worker_id, steps } => {
+                writer.u8(1).str(worker_id).u64(*steps);
+            }
+            Self::Act { worker_id, obs } => {
+                writer.u8(2).str(worker_id).float_lists(obs);
+            }
+        }
+
+        writer.finish()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        match reader.u8()? {
+            0 => {
+                let worker_id = reader.str()?;
+                let handshake = Handshake::read(&mut reader)?;
+                let mut spaces = || {
+                    let len = reader.u32()?;
+                    (0..len)
+                        .map(|_| reader.u32())
+                        .collect::<io::Result<Vec<_>>>()
+                };
+                let obs_spaces = spaces()?;
+                let action_spaces = spaces()?;
+
+                Ok(Self::Hello {
+                    worker_id,
+                    handshake,
+                    obs_spaces,
+                    action_spaces,
+                })
+            }
+            1 => Ok(Self::Heartbeat {
+                worker_id: reader.str()?,
+                steps: reader.u64()?,
+            }),
+            2 => Ok(Self::Act {
+                worker_id: reader.str()?,
+                obs: reader.float_lists()?,
+            }),
+            tag => Err(Error::Protocol(format!(
+                "Unknown control request tag {tag}"
+            ))),
+        }
+    }
+}
+
+/// Sent by the learner in reply to a `ControlRequest`, encoded the same way
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControlReply {
+    /// Tag 0
+    Ack,
+    /// Tag 1, one action per agent in the same order as the observations
+    Actions(Vec<Vec<f32>>),
+    /// Tag 2, the worker pushes what it has collected and returns from `ZmqWorker::run`
+    Stop,
+}
+
+impl ControlReply {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        match self {
+            Self::Ack => writer.u8(0),
+            Self::Actions(actions) => writer.u8(1).float_lists(actions),
+            Self::Stop => writer.u8(2),
+        };
+
+        writer.finish()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        match reader.u8()? {
+            0 => Ok(Self::Ack),
+            1 => Ok(Self::Actions(reader.float_lists()?)),
+            2 => Ok(Self::Stop),
+            tag => Err(Error::Protocol(format!("Unknown control reply tag {tag}"))),
+        }
+    }
+}
+
+/// Sent by a worker's PUSH socket to the learner's PULL socket.
+///
+/// Encoded as the worker id, the number of transitions, then each transition
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trajectory {
+    pub worker_id: String,
+    pub transitions: Vec<Transition>,
+}
+
+impl Trajectory {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::default();
+        writer
+            .str(&self.worker_id)
+            .u32(self.transitions.len() as u32);
+
+        for transition in &self.transitions {
+            write_transition(&mut writer, transition);
+        }
+
+        writer.finish()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        let worker_id = reader.str()?;
+        let len = reader.u32()?;
+        let transitions = (0..len)
+            .map(|_| read_transition(&mut reader))
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self {
+            worker_id,
+            transitions,
+        })
+    }
+}
+
+fn zmq_error(error: zmq::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, error)
+}
+
+fn connect(
+    context: &zmq::Context,
+    kind: zmq::SocketType,
+    endpoint: &str,
+) -> io::Result<zmq::Socket> {
+    let socket = context.socket(kind).map_err(zmq_error)?;
+    // don't hang on close waiting for a learner that's gone
+    socket.set_linger(0).map_err(zmq_error)?;
+    socket.connect(endpoint).map_err(zmq_error)?;
+    Ok(socket)
+}
+
+/// Steps an env for a learner over ZeroMQ.
+///
+/// The worker asks the learner for actions over REQ/REP and pushes finished trajectories over PUSH/PULL,
+/// so the learner binds a REP and a PULL socket and any number of workers connect to them.
+///
+/// If the learner doesn't reply within the timeout, the worker reconnects, says hello again and retries
+/// with an increasing delay, so the learner can be restarted without restarting its workers
+pub struct ZmqWorker {
+    worker_id: String,
+    context: zmq::Context,
+    control_endpoint: String,
+    control: zmq::Socket,
+    trajectories: zmq::Socket,
+    timeout: Duration,
+    max_retries: u32,
+    heartbeat_interval: Duration,
+    trajectory_len: usize,
+    hello: Vec<u8>,
+    steps: u64,
+}
+
+impl ZmqWorker {
+    /// Connect to the learner's REP socket at `control_endpoint` and its PULL socket at `trajectory_endpoint`,
+    /// e.g. `tcp://learner:5555` and `tcp://learner:5556`
+    pub fn connect(
+        worker_id: impl Into<String>,
+        control_endpoint: &str,
+        trajectory_endpoint: &str,
+    ) -> Result<Self> {
+        let context = zmq::Context::new();
+        let control = connect(&context, zmq::REQ, control_endpoint)?;
+        let trajectories = connect(&context, zmq::PUSH, trajectory_endpoint)?;
+        // give the last trajectories a chance to reach the learner when the worker is dropped
+        trajectories.set_linger(5000).map_err(zmq_error)?;
+
+        Ok(Self {
+            worker_id: worker_id.into(),
+            context,
+            control_endpoint: control_endpoint.to_string(),
+            control,
+            trajectories,
+            timeout: Duration::from_secs(5),
+            max_retries: 10,
+            heartbeat_interval: Duration::from_secs(1),
+            trajectory_len: 1024,
+            hello: Vec::new(),
+            steps: 0,
+        })
+    }
+
+    /// How long to wait for each reply from the learner, defaults to 5 seconds
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How many times to reconnect before giving up on the learner, defaults to 10
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Defaults to 1 second
+    pub fn with_heartbeat_interval(mut self, heartbeat_interval: Duration) -> Self {
+        self.heartbeat_interval = heartbeat_interval;
+        self
+    }
+
+    /// The maximum number of transitions in a pushed trajectory, defaults to 1024.
+    /// Trajectories are also pushed at the end of every episode
+    pub fn with_trajectory_len(mut self, trajectory_len: usize) -> Self {
+        self.trajectory_len = trajectory_len.max(1);
+        self
+    }
+
+    pub fn worker_id(&self) -> &str {
+        &self.worker_id
+    }
+
+    /// Total number of env steps taken by this worker
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Send one message and wait for the reply, `None` if the learner didn't reply in time
+    fn exchange(&mut self, message: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        self.control.send(message, 0).map_err(zmq_error)?;
+
+        let timeout = self.timeout.as_millis() as i64;
+        if self.control.poll(zmq::POLLIN, timeout).map_err(zmq_error)? > 0 {
+            return self.control.recv_bytes(0).map(Some).map_err(zmq_error);
+        }
+
+        // a REQ socket can't send again until it gets a reply, so start over with a new one
+        self.control = connect(&self.context, zmq::REQ, &self.control_endpoint)?;
+        Ok(None)
+    }
+
+    fn request(&mut self, request: &ControlRequest) -> io::Result<ControlReply> {
+        let message = request.encode();
+
+        for attempt in 0..=self.max_retries {
+            if attempt > 0 {
+                thread::sleep(Duration::from_millis(100 << attempt.min(6)));
+
+                // the learner might have restarted and forgotten about us
+                let hello = std::mem::take(&mut self.hello);
+                let reply = self.exchange(&hello);
+                self.hello = hello;
+                if reply?.is_none() {
+                    continue;
+                }
+            }
+
+            if let Some(reply) = self.exchange(&message)? {
+                return Ok(ControlReply::decode(&reply)?);
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "The learner stopped responding",
+        ))
+    }
+
+    fn push(&mut self, transitions: &mut Vec<Transition>) -> io::Result<()> {
+        let trajectory = Trajectory {
+            worker_id: self.worker_id.clone(),
+            transitions: std::mem::take(transitions),
+        };
+
+        self.trajectories
+            .send(trajectory.encode(), 0)
+            .map_err(zmq_error)
+    }
+
+    /// Step `env` with the learner's actions until the learner replies with `ControlReply::Stop`,
+    /// or an error occurs or the learner can't be reached
+    pub fn run(&mut self, env: &mut dyn RemoteEnv) -> Result<()> {
+        let mut obs = env.reset()?;
+
+        let obs_spaces = env.obs_spaces();
+        let action_spaces = env.action_spaces();
+        let to_u32s = |spaces: &[usize]| spaces.iter().map(|space| *space as u32).collect();
+        let hello = ControlRequest::Hello {
+            worker_id: self.worker_id.clone(),
+            handshake: Handshake::new(PROTOCOL_VERSION)
+                .with_config_hash(config_hash(&obs_spaces, &action_spaces)),
+            obs_spaces: to_u32s(&obs_spaces),
+            action_spaces: to_u32s(&action_spaces),
+        };
+        self.hello = hello.encode();
+        if self.request(&hello)? == ControlReply::Stop {
+            return Ok(());
+        }
+
+        let mut transitions = Vec::with_capacity(self.trajectory_len);
+        let mut last_heartbeat = Instant::now();
+
+        loop {
+            let reply = self.request(&ControlRequest::Act {
+                worker_id: self.worker_id.clone(),
+                obs: obs.clone(),
+            })?;
+
+            let actions = match reply {
+                ControlReply::Actions(actions) => actions,
+                ControlReply::Stop => break,
+                ControlReply::Ack => {
+                    return Err(Error::Protocol(
+                        "Expected actions from the learner".to_string(),
+                    ))
+                }
+            };
+
+            let step = env.step(actions.clone())?;
+            self.steps += 1;
+
+            // the env is reset right away, but the last obs of the episode is kept for bootstrapping
+            let done = step.is_terminal || step.truncated;
+            let (next_obs, final_obs) = if done {
+                (env.reset()?, Some(step.obs))
+            } else {
+                (step.obs, None)
+            };
+            transitions.push(Transition {
+                obs,
+                actions,
+                rewards: step.rewards,
+                is_terminal: step.is_terminal,
+                truncated: step.truncated,
+                final_obs,
+            });
+            obs = next_obs;
+
+            if done || transitions.len() >= self.trajectory_len {
+                self.push(&mut transitions)?;
+            }
+
+            if last_heartbeat.elapsed() >= self.heartbeat_interval {
+                last_heartbeat = Instant::now();
+
+                let heartbeat = ControlRequest::Heartbeat {
+                    worker_id: self.worker_id.clone(),
+                    steps: self.steps,
+                };
+                if self.request(&heartbeat)? == ControlReply::Stop {
+                    break;
+                }
+            }
+        }
+
+        if !transitions.is_empty() {
+            self.push(&mut transitions)?;
+        }
+
+        Ok(())
+    }
+}